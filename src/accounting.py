@@ -0,0 +1,84 @@
+"""以 principal 為單位的用量統計與每日配額 (MSSP 多租戶計費用)。
+
+每次 Indexer 查詢記錄「掃描文件數」(符合查詢的文件總數) 與「回傳位元組數」，
+依 principal + 日期 (UTC) 累計存放在狀態儲存的 usage namespace。
+"""
+
+import json
+import threading
+from datetime import datetime, timedelta, timezone
+
+USAGE_NAMESPACE = "usage"
+
+
+def _today():
+    return datetime.now(timezone.utc).strftime("%Y-%m-%d")
+
+
+def parse_quotas(default_docs, default_bytes, overrides_json):
+    """overrides_json 例如 {"tenant-a": {"docs": 1000000, "bytes": 50000000}}；0 代表不限制"""
+    overrides = json.loads(overrides_json) if overrides_json else {}
+    if not isinstance(overrides, dict):
+        raise ValueError("MCP_PRINCIPAL_QUOTAS 必須是以 principal 為鍵的 JSON 物件")
+    return {"default": {"docs": default_docs, "bytes": default_bytes}, **overrides}
+
+
+class QuotaExceeded(Exception):
+    pass
+
+
+class UsageMeter:
+    def __init__(self, store, quotas):
+        self.store = store
+        self.quotas = quotas
+        self.lock = threading.Lock()
+
+    def quota_for(self, principal):
+        quota = dict(self.quotas["default"])
+        quota.update(self.quotas.get(principal, {}))
+        return quota
+
+    def usage(self, principal, day=None):
+        key = f"{principal}:{day or _today()}"
+        return self.store.get(USAGE_NAMESPACE, key) or {"queries": 0, "docs": 0, "bytes": 0}
+
+    def check(self, principal):
+        """查詢前檢查今日配額，超過時拋出 QuotaExceeded"""
+        quota = self.quota_for(principal)
+        used = self.usage(principal)
+        for metric, label in (("docs", "掃描文件數"), ("bytes", "回傳資料量")):
+            if quota.get(metric) and used[metric] >= quota[metric]:
+                raise QuotaExceeded(
+                    f"principal '{principal}' 今日{label}已達配額上限 ({used[metric]}/{quota[metric]})，明日 (UTC) 重置"
+                )
+
+    def record(self, principal, docs, size):
+        with self.lock:
+            day = _today()
+            used = self.usage(principal, day)
+            used["queries"] += 1
+            used["docs"] += docs
+            used["bytes"] += size
+            self.store.put(USAGE_NAMESPACE, f"{principal}:{day}", used)
+
+    def report(self, days, principal=None):
+        """最近 days 天的用量，依 principal 彙總並附上今日配額使用率"""
+        start = (datetime.now(timezone.utc) - timedelta(days=days - 1)).strftime("%Y-%m-%d")
+        rows = {}
+        for key, used in self.store.list(USAGE_NAMESPACE):
+            who, _, day = key.rpartition(":")
+            if day < start or (principal and who != principal):
+                continue
+            rows.setdefault(who, {"daily": {}, "total": {"queries": 0, "docs": 0, "bytes": 0}})
+            rows[who]["daily"][day] = used
+            for metric in ("queries", "docs", "bytes"):
+                rows[who]["total"][metric] += used.get(metric, 0)
+        for who, row in rows.items():
+            quota = self.quota_for(who)
+            today = self.usage(who)
+            row["quota"] = quota
+            row["today_percent"] = {
+                m: round(100 * today[m] / quota[m], 1) if quota.get(m) else None
+                for m in ("docs", "bytes")
+            }
+        return rows
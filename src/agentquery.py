@@ -0,0 +1,94 @@
+"""list_agents 的篩選條件: 結構化參數 -> Wazuh API /agents 的查詢參數 (q 語法) 與伺服器端的補充比對。
+
+能在 manager 端篩選的條件都轉成 API 參數，只取回符合的 agent:
+- status / group / older_than (lastKeepAlive 早於多久以前) 是 /agents 的原生參數
+- 平台、作業系統、名稱、版本、IP 轉成 q 語法 (";" 為 AND，"=" 完全相符，"~" 包含)
+q 語法無法表達的條件在取回後比對:
+- version 的大小比較 (<4.8.0、>=4.7) 依版本號數值比較，API 的字串比較會把 4.10 排在 4.9 前面
+- ip 為 CIDR 時，q 只能以 "~" 先縮小到相同開頭的位址，再精確判斷是否在網段內
+"""
+
+import ipaddress
+import re
+
+from alert_utils import parse_duration
+
+STATUSES = ("active", "disconnected", "never_connected", "pending")
+_VERSION = re.compile(r"^\s*(<=|>=|<|>|=)?\s*(?:wazuh\s*)?v?(\d+(?:\.\d+)*)\s*$", re.I)
+# q 語法的保留字元，值中不能出現
+_RESERVED = re.compile(r"[;,()]")
+
+
+def version_tuple(value):
+    """"Wazuh v4.7.2" / "4.7" -> (4, 7, 2) / (4, 7)；無法解析時為 None"""
+    m = re.search(r"(\d+(?:\.\d+)*)", value or "")
+    return tuple(int(part) for part in m.group(1).split(".")) if m else None
+
+
+def _compare(actual, operator, wanted):
+    width = max(len(actual), len(wanted))
+    actual, wanted = actual + (0,) * (width - len(actual)), wanted + (0,) * (width - len(wanted))
+    return {"<": actual < wanted, "<=": actual <= wanted, ">": actual > wanted,
+            ">=": actual >= wanted}[operator]
+
+
+def _value(name, value):
+    value = str(value).strip()
+    if _RESERVED.search(value):
+        raise ValueError(f"{name} 不能包含 ; , ( ) 字元: '{value}'")
+    return value
+
+
+def compile_filters(status=None, platform=None, os_name=None, version=None, group=None, name=None, ip=None,
+                    older_than=None):
+    """結構化篩選條件 -> (API 查詢參數, [(條件說明, fn(agent) -> bool)] 取回後的比對)；格式錯誤拋出 ValueError"""
+    params, q, checks = {}, [], []
+    if status:
+        statuses = [s.strip().lower() for s in status.split(",") if s.strip()]
+        unknown = [s for s in statuses if s not in STATUSES]
+        if unknown:
+            raise ValueError(f"未知的 status: {', '.join(unknown)} (可用: {', '.join(STATUSES)})")
+        params["status"] = ",".join(statuses)
+    if group:
+        params["group"] = _value("group", group)
+    if older_than:
+        params["older_than"] = f"{int(parse_duration(older_than).total_seconds())}s"
+    if platform:
+        q.append(f"os.platform={_value('platform', platform).lower()}")
+    if os_name:
+        q.append(f"os.name~{_value('os_name', os_name)}")
+    if name:
+        q.append(f"name~{_value('name', name)}")
+    if version:
+        m = _VERSION.match(version)
+        if not m:
+            raise ValueError(f"無法解析 version '{version}'，請使用如 4.7.2、4.7 或 <4.8.0 的格式")
+        operator, number = m.group(1), m.group(2)
+        if operator in (None, "="):
+            # 完整版本號完全相符，只給 major.minor 時比對開頭
+            q.append(f"version=Wazuh v{number}" if number.count(".") >= 2 else f"version~v{number}.")
+        else:
+            wanted = version_tuple(number)
+            checks.append((f"version{operator}{number}", lambda agent: (
+                (actual := version_tuple(agent.get("version"))) is not None and _compare(actual, operator, wanted))))
+    if ip:
+        try:
+            network = ipaddress.ip_network(ip.strip(), strict=False)
+        except ValueError as e:
+            raise ValueError(f"無法解析 ip '{ip}'，請使用 IP 或 CIDR (例如 10.0.0.0/8): {e}") from e
+        if network.num_addresses == 1:
+            q.append(f"ip={network.network_address}")
+        else:
+            if network.version == 4 and network.prefixlen >= 8:
+                octets = str(network.network_address).split(".")[:network.prefixlen // 8]
+                q.append(f"ip~{'.'.join(octets)}.")
+
+            def in_network(agent):
+                try:
+                    return ipaddress.ip_address(agent.get("ip") or "") in network
+                except ValueError:
+                    return False
+            checks.append((f"ip in {network}", in_network))
+    if q:
+        params["q"] = ";".join(q)
+    return params, checks
@@ -0,0 +1,58 @@
+"""告警資料處理的共用小工具 (欄位取值、時間解析)。"""
+
+import re
+from datetime import datetime, timedelta
+
+
+def get_field(doc, path, default=None):
+    """以點號路徑讀取巢狀欄位，例如 get_field(alert, "rule.level")"""
+    current = doc
+    for part in path.split('.'):
+        if isinstance(current, dict) and part in current:
+            current = current[part]
+        else:
+            return default
+    return current
+
+
+def first_field(doc, *paths):
+    """依序讀取多個候選欄位 (不同日誌來源的同義欄位)，回傳第一個有值的"""
+    for path in paths:
+        value = get_field(doc, path)
+        if value not in (None, ""):
+            return value
+    return None
+
+
+_DURATION_RE = re.compile(r"^\s*(\d+)\s*(ms|s|m|h|d|w)\s*$")
+_DURATION_UNITS = {
+    "ms": timedelta(milliseconds=1),
+    "s": timedelta(seconds=1),
+    "m": timedelta(minutes=1),
+    "h": timedelta(hours=1),
+    "d": timedelta(days=1),
+    "w": timedelta(weeks=1),
+}
+
+
+def parse_duration(text):
+    """將 "30s"、"2m"、"1h" 這類字串轉成 timedelta，格式錯誤時拋出 ValueError"""
+    m = _DURATION_RE.match(text or "")
+    if not m:
+        raise ValueError(f"無法解析時間長度 '{text}'，請使用如 30s、2m、1h、7d 的格式")
+    return int(m.group(1)) * _DURATION_UNITS[m.group(2)]
+
+
+def parse_timestamp(value):
+    """解析 Wazuh 告警的 timestamp (例如 2024-01-01T12:00:00.000+0000)"""
+    if not value:
+        return None
+    for fmt in ("%Y-%m-%dT%H:%M:%S.%f%z", "%Y-%m-%dT%H:%M:%S%z"):
+        try:
+            return datetime.strptime(value, fmt)
+        except ValueError:
+            continue
+    try:
+        return datetime.fromisoformat(value.replace("Z", "+00:00"))
+    except ValueError:
+        return None
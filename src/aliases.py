@@ -0,0 +1,114 @@
+"""工具別名: 讓工具改名或改版 (v2) 後，舊名稱仍可呼叫。
+
+呼叫舊名稱時，middleware 會把參數轉換成新工具的格式 (translate)，改呼叫新工具，
+並在回應中加上 "_deprecation" 說明 (改用哪個工具、預計移除的版本、被忽略的參數)，
+既有的 prompt 範本不會因為升級而失效，也能看到該改用什麼。
+
+新增別名的方式: 在 TOOL_ALIASES 加一筆 ToolAlias；舊參數與新參數不同時提供 translate
+(回傳 (新參數, 被忽略的參數名稱)) 以及舊的 parameters (JSON schema，列在工具清單中)。
+"""
+
+import logging
+
+from fastmcp.server.middleware import Middleware
+
+from output import annotate_result
+
+logger = logging.getLogger("wazuh_mcp")
+
+
+def keep(*names, rename=None):
+    """產生 translate: 只保留 names 內的參數 (可用 rename 改名)，其餘列為忽略"""
+    rename = rename or {}
+
+    def translate(args):
+        kept = {rename.get(k, k): v for k, v in args.items() if k in names}
+        return kept, sorted(k for k in args if k not in names)
+    return translate
+
+
+class ToolAlias:
+    def __init__(self, name, target, since, removal=None, translate=None, parameters=None, note=None):
+        self.name = name
+        self.target = target
+        self.since = since
+        self.removal = removal
+        self.translate = translate or (lambda args: (dict(args), []))
+        self.parameters = parameters
+        self.note = note
+
+    def notice(self, ignored):
+        notice = {"tool": self.name, "use_instead": self.target, "deprecated_since": self.since}
+        if self.removal:
+            notice["removal"] = self.removal
+        if ignored:
+            notice["ignored_arguments"] = ignored
+        if self.note:
+            notice["note"] = self.note
+        return notice
+
+    def description(self, target_description):
+        removal = f"，預計於 {self.removal} 移除" if self.removal else ""
+        return f"[已淘汰，請改用 {self.target}{removal}] {target_description or ''}".strip()
+
+
+# 舊版 (Rust 實作的 mcp-server-wazuh) 的工具名稱，沿用該版本的 prompt 範本仍可運作
+TOOL_ALIASES = [
+    ToolAlias(
+        "get_wazuh_alert_summary", "search_alerts", since="0.3.0", removal="0.5.0",
+        translate=keep("limit"),
+        parameters={"type": "object", "properties": {
+            "limit": {"type": "integer", "description": "最多回傳幾筆告警"}}},
+    ),
+    ToolAlias(
+        "get_wazuh_agents", "list_agents", since="0.3.0", removal="0.5.0",
+        translate=keep("status", "name", "ip", "group", "os_platform", "version", "limit",
+                       rename={"os_platform": "platform"}),
+        parameters={"type": "object", "properties": {
+            name: {"type": "string"} for name in ("status", "name", "ip", "group", "os_platform", "version")
+        } | {"limit": {"type": "integer"}}},
+    ),
+    ToolAlias(
+        "get_wazuh_cluster_health", "get_infrastructure_status", since="0.3.0", removal="0.5.0",
+        translate=keep(),
+        parameters={"type": "object", "properties": {}},
+    ),
+]
+
+
+class AliasMiddleware(Middleware):
+    """把舊工具名稱的呼叫轉給新工具；list_aliases=False 時舊名稱仍可呼叫，但不列在工具清單中"""
+
+    def __init__(self, aliases, list_aliases=True):
+        self.aliases = {alias.name: alias for alias in aliases}
+        self.list_aliases = list_aliases
+        self._warned = set()
+
+    async def on_list_tools(self, context, call_next):
+        tools = await call_next(context)
+        if not self.list_aliases:
+            return tools
+        by_name = {tool.name: tool for tool in tools}
+        listed = list(tools)
+        for alias in self.aliases.values():
+            target = by_name.get(alias.target)
+            # 目標工具被停用 / 過濾時別名也不列出；同名的正式工具優先
+            if target is None or alias.name in by_name:
+                continue
+            update = {"name": alias.name, "description": alias.description(target.description)}
+            if alias.parameters is not None:
+                update["parameters"] = alias.parameters
+            listed.append(target.model_copy(update=update))
+        return listed
+
+    async def on_call_tool(self, context, call_next):
+        alias = self.aliases.get(context.message.name)
+        if alias is None:
+            return await call_next(context)
+        args, ignored = alias.translate(dict(context.message.arguments or {}))
+        if alias.name not in self._warned:
+            self._warned.add(alias.name)
+            logger.warning("收到已淘汰的工具名稱 %s，已轉為呼叫 %s", alias.name, alias.target)
+        message = context.message.model_copy(update={"name": alias.target, "arguments": args})
+        result = await call_next(context.copy(message=message))
+        return annotate_result(result, "_deprecation", alias.notice(ignored), "已淘汰")
@@ -0,0 +1,199 @@
+"""HTTP / WebSocket 傳輸的 API key 驗證: MCP 端點 (/mcp) 的請求必須帶 Authorization: Bearer <key>。
+
+金鑰來源 (可同時使用):
+- MCP_API_KEYS: 逗號分隔，"名稱:金鑰" 或只有金鑰 (名稱預設為 key-1、key-2...)
+- MCP_API_KEYS_FILE: 每行一把 (格式同上，# 開頭為註解)；檔案修改後下一個請求就會重新載入，換金鑰不必重啟
+
+沒有帶或帶錯金鑰的請求在進入 MCP 處理之前就回 401 (WebSocket 則拒絕握手)；/healthz、/metrics 等不受影響。
+驗證成功時金鑰名稱即為該請求的 principal (見 principal.py)，優先於 MCP_PRINCIPAL_HEADER；
+保留的名稱 (RESERVED_PRINCIPALS) 不能當金鑰名稱。
+BearerAuth 也接受其他驗證方式 (例如 oidc.py 的 JWT)，依序嘗試，任一個通過即可；
+MCP session 綁定建立它的身分，其他身分帶同一個 Mcp-Session-Id 會被拒絕 (403)。
+"""
+
+import asyncio
+import hashlib
+import hmac
+import json
+import logging
+import os
+import threading
+from collections import OrderedDict
+
+from scoping import RESERVED_PRINCIPALS
+
+logger = logging.getLogger("wazuh_mcp.auth")
+
+# 驗證失敗時的 WebSocket 關閉代碼 (1008 = Policy Violation)
+CLOSE_POLICY_VIOLATION = 1008
+
+
+def _digest(key):
+    return hashlib.sha256(key.encode()).digest()
+
+
+def parse_keys(text, source="MCP_API_KEYS"):
+    """"名稱:金鑰" 清單 (逗號或換行分隔) -> [(名稱, 金鑰)]"""
+    entries = []
+    for line in text.replace(",", "\n").splitlines():
+        line = line.strip()
+        if not line or line.startswith("#"):
+            continue
+        name, sep, key = line.partition(":")
+        if not sep:
+            name, key = f"key-{len(entries) + 1}", name
+        name, key = name.strip(), key.strip()
+        if not name or not key:
+            raise ValueError(f"{source} 的項目格式錯誤: '{line}' (應為 名稱:金鑰)")
+        if name.lower() in RESERVED_PRINCIPALS:
+            raise ValueError(f"{source} 的金鑰名稱不能是保留的身分 '{name}' ({', '.join(RESERVED_PRINCIPALS)})")
+        entries.append((name, key))
+    return entries
+
+
+class APIKeys:
+    """設定的金鑰 (只保存 SHA-256 雜湊)；keys file 依修改時間自動重新載入"""
+
+    def __init__(self, static="", path=None):
+        self.static = {_digest(key): name for name, key in parse_keys(static or "")}
+        self.path = path
+        self.from_file = {}
+        self.mtime = None
+        self.lock = threading.Lock()
+        if path:
+            self._reload()
+
+    @property
+    def enabled(self):
+        return bool(self.static or self.path)
+
+    def _reload(self):
+        try:
+            mtime = os.stat(self.path).st_mtime
+        except OSError as e:
+            if self.mtime is None:
+                raise ValueError(f"無法讀取 MCP_API_KEYS_FILE {self.path}: {e}") from e
+            logger.warning("無法讀取 API key 檔 %s，沿用上次載入的金鑰: %s", self.path, e)
+            return
+        if mtime == self.mtime:
+            return
+        with open(self.path, encoding="utf-8") as f:
+            entries = parse_keys(f.read(), self.path)
+        self.from_file = {_digest(key): name for name, key in entries}
+        self.mtime = mtime
+        logger.info("已載入 %d 把 API key (%s)", len(entries), self.path)
+
+    def authenticate(self, key):
+        """金鑰正確時回傳身分 {"principal": 名稱, "method": "api_key"}，否則 None"""
+        if not key:
+            return None
+        if self.path:
+            with self.lock:
+                try:
+                    self._reload()
+                except ValueError as e:  # 檔案內容格式錯誤時沿用舊的金鑰
+                    logger.warning("%s", e)
+        digest = _digest(key)
+        name = None
+        # 逐一以固定時間比較，避免以回應時間猜測金鑰
+        for known, known_name in (*self.static.items(), *self.from_file.items()):
+            if hmac.compare_digest(known, digest):
+                name = known_name
+        return {"principal": name, "method": "api_key"} if name else None
+
+
+def bearer_token(headers):
+    for name, value in headers:
+        if name.lower() == b"authorization":
+            scheme, _, token = value.decode("latin-1").partition(" ")
+            if scheme.lower() == "bearer":
+                return token.strip()
+    return None
+
+
+def authenticated_principal(scope):
+    """目前請求驗證通過的身分名稱；沒有啟用 Bearer 驗證時為 None"""
+    return (scope.get("extensions", {}).get("auth") or {}).get("principal")
+
+
+def _header(headers, wanted):
+    for name, value in headers:
+        if name.lower() == wanted:
+            return value.decode("latin-1")
+    return None
+
+
+class BearerAuth:
+    """ASGI middleware: path (MCP 端點) 底下的請求必須帶任一 authenticator 接受的 Bearer 權杖"""
+
+    MAX_SESSIONS = 10000
+
+    def __init__(self, app, path, *authenticators):
+        self.app = app
+        self.path = path.rstrip("/")
+        self.authenticators = authenticators
+        self.sessions = OrderedDict()  # Mcp-Session-Id -> 建立 session 的 principal
+
+    def _authenticate(self, token):
+        if not token:
+            return None
+        for authenticator in self.authenticators:
+            identity = authenticator.authenticate(token)
+            if identity:
+                return identity
+        return None
+
+    def _bind(self, session_id, principal):
+        self.sessions[session_id] = principal
+        self.sessions.move_to_end(session_id)
+        while len(self.sessions) > self.MAX_SESSIONS:
+            self.sessions.popitem(last=False)
+
+    async def _reject(self, scope, send, status, error, challenge):
+        if scope["type"] == "websocket":
+            await send({"type": "websocket.close", "code": CLOSE_POLICY_VIOLATION})
+            return
+        body = json.dumps({"error": error}).encode()
+        await send({"type": "http.response.start", "status": status,
+                    "headers": [(b"content-type", b"application/json"),
+                                (b"content-length", str(len(body)).encode()),
+                                (b"www-authenticate", challenge.encode())]})
+        await send({"type": "http.response.body", "body": body})
+
+    def _protected(self, path):
+        return path == self.path or path.startswith(self.path + "/")
+
+    async def __call__(self, scope, receive, send):
+        if scope["type"] not in ("http", "websocket") or not self._protected(scope["path"]):
+            await self.app(scope, receive, send)
+            return
+        headers = scope.get("headers", [])
+        client = (scope.get("client") or ("?",))[0]
+        # JWKS 下載等可能阻塞，不在事件迴圈上執行
+        token = bearer_token(headers)
+        identity = await asyncio.to_thread(self._authenticate, token)
+        if identity is None:
+            logger.warning("拒絕未通過驗證的請求: %s %s (client %s)",
+                           scope.get("method", "WEBSOCKET"), scope["path"], client)
+            # 沒有帶權杖時依 RFC 6750 不附 error
+            await self._reject(scope, send, 401, "unauthorized",
+                               'Bearer realm="wazuh-mcp"' + (', error="invalid_token"' if token else ""))
+            return
+        principal = identity["principal"]
+        session_id = _header(headers, b"mcp-session-id")
+        owner = self.sessions.get(session_id) if session_id else None
+        if owner is not None and owner != principal:
+            logger.warning("拒絕 %s 使用 %s 建立的 MCP session (client %s)", principal, owner, client)
+            await self._reject(scope, send, 403, "session belongs to another identity",
+                               'Bearer realm="wazuh-mcp", error="insufficient_scope"')
+            return
+        scope["extensions"] = {**scope.get("extensions", {}), "auth": identity}
+
+        async def bind_session(message):
+            if message["type"] == "http.response.start":
+                created = _header(message.get("headers", []), b"mcp-session-id")
+                if created and created not in self.sessions:
+                    self._bind(created, principal)
+            await send(message)
+
+        await self.app(scope, receive, bind_session if scope["type"] == "http" else send)
@@ -0,0 +1,55 @@
+"""從 Wazuh Manager 分段取回小型設定檔 (規則檔、decoder、CDB 清單、group 共用檔)。
+
+告警常引用這些檔案 (例如 rule.id 所在的規則檔、CDB 清單比對)，讓助理可以直接檢視內容。
+檔案內容以 base64 分段回傳，單段最多 MAX_CHUNK_BYTES；超過 MCP_FILE_RETRIEVAL_MAX_BYTES 的檔案一律拒絕。
+"""
+
+import base64
+import hashlib
+import re
+
+MAX_CHUNK_BYTES = 64 * 1024
+DEFAULT_CHUNK_BYTES = 32 * 1024
+
+# 種類 -> Manager API 路徑 (raw=true 取得原始內容)
+KINDS = {
+    "rule": "/rules/files/{name}",
+    "decoder": "/decoders/files/{name}",
+    "list": "/lists/files/{name}",
+    "group": "/groups/{group}/files/{name}",
+}
+
+_NAME = re.compile(r"^[A-Za-z0-9_.\-]+$")
+
+
+def api_path(kind, name, group=None):
+    """驗證參數並組出 API 路徑；不合法時拋出 ValueError"""
+    if kind not in KINDS:
+        raise ValueError(f"不支援的檔案種類 '{kind}'，可用: {', '.join(KINDS)}")
+    if not _NAME.match(name or "") or name.startswith("."):
+        raise ValueError(f"檔名格式錯誤: '{name}' (只能是單一檔名，不可包含路徑)")
+    if kind == "group":
+        if not _NAME.match(group or "") or group.startswith("."):
+            raise ValueError("kind=group 需要合法的 group 名稱")
+    elif group:
+        raise ValueError("只有 kind=group 需要指定 group")
+    return KINDS[kind].format(name=name, group=group)
+
+
+def chunk(content, offset, chunk_size):
+    """回傳從 offset 開始的一段 (base64)，以及讓 client 接續下一段所需的資訊"""
+    if offset < 0 or offset > len(content):
+        raise ValueError(f"offset 超出檔案範圍 (檔案大小 {len(content)} bytes)")
+    if not 1 <= chunk_size <= MAX_CHUNK_BYTES:
+        raise ValueError(f"chunk_size 必須介於 1 到 {MAX_CHUNK_BYTES}")
+    piece = content[offset:offset + chunk_size]
+    end = offset + len(piece)
+    return {
+        "size": len(content),
+        "sha256": hashlib.sha256(content).hexdigest(),
+        "offset": offset,
+        "length": len(piece),
+        "next_offset": end if end < len(content) else None,
+        "encoding": "base64",
+        "data": base64.b64encode(piece).decode("ascii"),
+    }
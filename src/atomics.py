@@ -0,0 +1,59 @@
+"""Atomic Red Team 測試對照與偵測驗證 (purple team)。
+
+data/atomic_red_team.json 內建 MITRE 技術 -> Atomic 測試名稱的對照，
+每個測試附上「預期會留下的遙測」(evidence, KQL)。執行測試後比對:
+- 有 rule.mitre.id 符合該技術的告警            -> detected
+- 只有遙測 (evidence 有結果) 但沒有對應告警    -> telemetry_only (有資料、缺規則)
+- 兩者皆無                                     -> not_detected (可能缺日誌來源)
+可用 ATOMIC_TESTS_FILE 指定自訂的對照檔 (格式相同)。
+"""
+
+import json
+import os
+
+DEFAULT_FILE = os.path.join(os.path.dirname(__file__), "data", "atomic_red_team.json")
+
+
+def load_catalog(path=None):
+    with open(path or DEFAULT_FILE, encoding="utf-8") as f:
+        return json.load(f)["techniques"]
+
+
+def parent_technique(technique):
+    return technique.split(".")[0]
+
+
+def find_tests(catalog, technique, platform=None):
+    """回傳符合的測試 (附執行指令)；給父技術時列出底下所有子技術的測試"""
+    technique = technique.strip().upper()
+    if technique in catalog:
+        entries = {technique: catalog[technique]}
+    else:
+        entries = {t: e for t, e in catalog.items() if parent_technique(t) == technique}
+    tests = []
+    for tid, entry in sorted(entries.items()):
+        for test in entry["tests"]:
+            if platform and platform not in test["platforms"]:
+                continue
+            tests.append({
+                "technique": tid,
+                "technique_name": entry["name"],
+                **test,
+                "invoke": f'Invoke-AtomicTest {tid} -TestNames "{test["name"]}"',
+            })
+    return tests
+
+
+def verdict(alerts, evidence_hits):
+    if alerts:
+        return "detected"
+    if evidence_hits:
+        return "telemetry_only"
+    return "not_detected"
+
+
+VERDICT_ADVICE = {
+    "detected": "已產生對應 MITRE 技術的告警，偵測有效",
+    "telemetry_only": "有相關日誌但沒有觸發對應的規則，建議新增或調整規則 (可用 evidence 當作規則條件的起點)",
+    "not_detected": "沒有任何相關日誌，請確認測試確實執行、agent 有收集對應的日誌來源 (Sysmon、auditd、FIM)",
+}
@@ -0,0 +1,80 @@
+"""工具呼叫的稽核 log: 每次呼叫輸出一筆結構化事件 (誰、哪個工具、結果)，供 Wazuh 監控伺服器本身。
+
+事件寫到 "wazuh_mcp.audit" logger (不經過 stderr)，由 MCP_AUDIT_LOG (JSON lines 檔案，
+讓 Wazuh agent 以 log_format json 收集) 與 MCP_LOG_SYSLOG 送出。格式:
+    {"timestamp": ..., "level": "info", "logger": "wazuh_mcp.audit", "message": "tool_call inject_test_events denied",
+     "mcp": {"event": "tool_call", "tool": ..., "principal": ..., "outcome": "ok|error|denied",
+             "write": true, "duration_ms": 12, "reason": "...", "client_cert": "CN=..."}}
+client_cert 只在 mutual TLS (MCP_TLS_CLIENT_CA) 時出現。
+python src/main.py rules 會依這個格式產生對應的 Wazuh 規則 (selfmonitor.py)。
+"""
+
+import logging
+import time
+
+from fastmcp.server.middleware import Middleware
+
+from principal import client_certificate, current_principal
+
+audit_logger = logging.getLogger("wazuh_mcp.audit")
+
+# 回應以「錯誤」開頭且包含這些字串時視為權限 / 政策拒絕，而不是一般錯誤
+DENIAL_MARKERS = ("MCP_ALLOW_WRITES", "MCP_ALLOW_FILE_RETRIEVAL", "MCP_CONFIRM_WRITES", "使用者未確認",
+                  "租戶範圍受限", "越權", "配額上限", "只能讀取 DETECTION_RULES_ROOT",
+                  "MCP_TOOL_POLICY_FILE", "MCP_READ_ONLY")
+ERROR_PREFIXES = ("錯誤", "API 回傳錯誤", "Indexer 回傳錯誤", "無法連線", "無法解析", "發生例外錯誤", "發生錯誤", "查詢失敗",
+                  "查詢語法錯誤", "基準期間查詢失敗", "首次出現資料庫更新失敗")
+
+
+def classify(text):
+    """工具回應文字 -> (outcome, reason)"""
+    if not text.startswith(ERROR_PREFIXES):
+        return "ok", None
+    reason = text.splitlines()[0][:200]
+    if any(marker in text for marker in DENIAL_MARKERS):
+        return "denied", reason
+    return "error", reason
+
+
+def configure(handlers):
+    """稽核事件只送到指定的 handler (檔案 / syslog)，不混進 stderr 的運作 log"""
+    audit_logger.setLevel(logging.INFO)
+    audit_logger.propagate = False
+    for handler in handlers:
+        if handler is not None:
+            audit_logger.addHandler(handler)
+    audit_logger.disabled = not audit_logger.handlers
+
+
+class AuditMiddleware(Middleware):
+    def __init__(self, write_calls):
+        """write_calls: {工具名稱: fn(arguments) -> 是否為寫入 / 破壞性呼叫}"""
+        self.write_calls = write_calls
+
+    async def on_call_tool(self, context, call_next):
+        if audit_logger.disabled:
+            return await call_next(context)
+        tool = context.message.name
+        arguments = context.message.arguments or {}
+        is_write = self.write_calls.get(tool)
+        event = {"event": "tool_call", "tool": tool, "principal": current_principal(),
+                 "write": bool(is_write and is_write(arguments))}
+        subject = client_certificate()
+        if subject:
+            event["client_cert"] = subject
+        started = time.monotonic()
+        try:
+            result = await call_next(context)
+        except Exception as e:
+            event.update(outcome="error", reason=f"{type(e).__name__}: {e}"[:200])
+            raise
+        else:
+            text = result.content[0].text if result.content and hasattr(result.content[0], "text") else ""
+            outcome, reason = classify(text)
+            event["outcome"] = outcome
+            if reason:
+                event["reason"] = reason
+            return result
+        finally:
+            event["duration_ms"] = round((time.monotonic() - started) * 1000)
+            audit_logger.info("tool_call %s %s", tool, event["outcome"], extra={"mcp": event})
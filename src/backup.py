@@ -0,0 +1,63 @@
+"""伺服器狀態的匯出 / 匯入 (可攜式 JSON bundle)。
+
+用於換主機、換儲存後端或做版本化備份:
+    python src/main.py export --output state-backup.json
+    python src/main.py import --input state-backup.json
+"""
+
+from datetime import datetime, timezone
+
+from migrations import META_NAMESPACE, MIGRATIONS_NAMESPACE, LATEST_VERSION, current_version, run_migrations
+from resumption import SESSION_NAMESPACE
+from rollups import ROLLUP_NAMESPACE
+
+BUNDLE_FORMAT = "wazuh-mcp-state"
+BUNDLE_FORMAT_VERSION = 1
+
+# 遷移紀錄屬於各自的資料庫、告警彙總隨時可以重算、HTTP session 只對原本的伺服器有意義，不隨 bundle 搬移
+_INTERNAL_NAMESPACES = {META_NAMESPACE, MIGRATIONS_NAMESPACE, ROLLUP_NAMESPACE, SESSION_NAMESPACE}
+
+
+def export_state(store):
+    """將所有 namespace 的資料輸出成 bundle (dict)"""
+    namespaces = {
+        ns: {key: value for key, value in store.list(ns)}
+        for ns in store.namespaces() if ns not in _INTERNAL_NAMESPACES
+    }
+    return {
+        "format": BUNDLE_FORMAT,
+        "format_version": BUNDLE_FORMAT_VERSION,
+        "schema_version": current_version(store),
+        "exported_at": datetime.now(timezone.utc).isoformat(),
+        "namespaces": namespaces,
+    }
+
+
+def import_state(store, bundle, replace=False):
+    """匯入 bundle。replace=True 會先清空 bundle 內出現的 namespace；回傳各 namespace 匯入筆數"""
+    if not isinstance(bundle, dict) or bundle.get("format") != BUNDLE_FORMAT:
+        raise ValueError("檔案不是 wazuh-mcp-state 格式的匯出檔")
+    if bundle.get("format_version", 0) > BUNDLE_FORMAT_VERSION:
+        raise ValueError("匯出檔格式比此版本伺服器新，請升級後再匯入")
+    schema_version = bundle.get("schema_version", 0)
+    if schema_version > LATEST_VERSION:
+        raise ValueError(f"匯出檔的資料版本為 {schema_version}，此版本伺服器只支援到 {LATEST_VERSION}")
+    if schema_version > current_version(store):
+        raise ValueError("目標資料庫版本較舊，請先以新版伺服器啟動一次完成遷移後再匯入")
+
+    counts = {}
+    for ns, items in bundle.get("namespaces", {}).items():
+        if ns in _INTERNAL_NAMESPACES:
+            continue
+        if replace:
+            for key, _ in store.list(ns):
+                store.delete(ns, key)
+        for key, value in items.items():
+            store.put(ns, key, value)
+        counts[ns] = len(items)
+
+    # 匯入較舊版本的資料時，補跑資料轉換讓格式跟上目前版本
+    if schema_version < current_version(store):
+        store.put(META_NAMESPACE, "schema_version", {"version": schema_version})
+        run_migrations(store)
+    return counts
@@ -0,0 +1,200 @@
+"""Streamable HTTP 的 JSON-RPC 批次請求: 一個 POST 帶 JSON 陣列，一次完成 initialize + tools/list + 查詢。
+
+MCP SDK 的 transport 一次只接受一個 JSON-RPC 訊息 (陣列會被當成格式錯誤)。BatchRequests 在 SDK 之前
+把陣列拆開，依序以同一組標頭逐一送進 SDK，再把所有回應合併回傳:
+- 依陣列順序處理；initialize 只能是第一個訊息，它建立的 Mcp-Session-Id 會帶給後面的訊息並放在回應標頭
+- SDK 以 SSE 回應時 (預設)，整批以一個 SSE stream 回傳，每個訊息的進度通知、log 與回應依序即時送出；
+  SDK 設定為 JSON 回應時，回傳 JSON-RPC 回應陣列；全部是 notification / response 時回 202
+- 個別訊息的錯誤 (格式錯誤、session 不存在等) 以對應 id 的 JSON-RPC 錯誤放在結果中，不影響其他訊息
+- 每批最多 max_messages 個訊息
+驗證、Origin 檢查在外層對整批做一次；session 過期、接續、SSE 事件重送等 middleware 放在內層，逐一套用到每個訊息。
+"""
+
+import json
+import logging
+
+from resumption import SESSION_HEADER
+
+logger = logging.getLogger("wazuh_mcp")
+
+INVALID_REQUEST = -32600
+
+
+def _error(message_id, message, code=INVALID_REQUEST):
+    return {"jsonrpc": "2.0", "id": message_id, "error": {"code": code, "message": message}}
+
+
+def _content_type(headers):
+    value = next((v.decode("latin-1") for k, v in headers if k.lower() == b"content-type"), "")
+    return value.split(";")[0].strip().lower()
+
+
+def sse_events(buffer):
+    """SSE 內容 -> (完整事件的 data 清單, 尚未結束的部分)；略過註解 (ping) 與沒有 data 的 priming 事件"""
+    *complete, rest = buffer.replace("\r\n", "\n").split("\n\n")
+    events = []
+    for block in complete:
+        data = [line[5:].removeprefix(" ") for line in block.split("\n") if line.startswith("data:")]
+        if data and any(data):
+            events.append("\n".join(data))
+    return events, rest
+
+
+class BatchOutput:
+    """整批的回應: 第一個 SSE 回應出現時開始以 SSE 串流，否則最後以 JSON 陣列回傳"""
+
+    def __init__(self, send):
+        self.send = send
+        self.streaming = False
+        self.pending = []  # 開始串流前收集的 JSON-RPC 訊息
+        self.session_id = None
+
+    def _headers(self, content_type):
+        headers = [(b"content-type", content_type)]
+        if self.session_id:
+            headers.append((SESSION_HEADER.encode(), self.session_id.encode("latin-1")))
+        return headers
+
+    async def stream(self):
+        if self.streaming:
+            return
+        self.streaming = True
+        await self.send({"type": "http.response.start", "status": 200,
+                         "headers": self._headers(b"text/event-stream") + [(b"cache-control", b"no-cache")]})
+        pending, self.pending = self.pending, []
+        for message in pending:
+            await self.emit(message)
+
+    async def emit(self, message):
+        """message: JSON-RPC 訊息 (dict) 或已序列化的 JSON 字串"""
+        if not self.streaming:
+            self.pending.append(message if isinstance(message, dict) else json.loads(message))
+            return
+        data = message if isinstance(message, str) else json.dumps(message, ensure_ascii=False)
+        await self.send({"type": "http.response.body", "body": f"event: message\ndata: {data}\n\n".encode(),
+                         "more_body": True})
+
+    async def close(self):
+        if self.streaming:
+            await self.send({"type": "http.response.body", "body": b""})
+            return
+        if not self.pending:
+            await self.send({"type": "http.response.start", "status": 202, "headers": self._headers(b"text/plain")})
+            await self.send({"type": "http.response.body", "body": b""})
+            return
+        body = json.dumps(self.pending, ensure_ascii=False).encode()
+        await self.send({"type": "http.response.start", "status": 200,
+                         "headers": self._headers(b"application/json") + [
+                             (b"content-length", str(len(body)).encode())]})
+        await self.send({"type": "http.response.body", "body": body})
+
+
+class BatchRequests:
+    """starlette middleware (放在 FastMCP 的 HTTP app 內最外層): 把 MCP 端點的 JSON 陣列 POST 拆成單一訊息依序處理"""
+
+    def __init__(self, app, max_messages):
+        self.app = app
+        self.max_messages = max_messages
+
+    @staticmethod
+    def _is_endpoint(scope):
+        return scope["type"] == "http" and scope["method"] == "POST" and scope["path"].rstrip("/").endswith("/mcp")
+
+    async def __call__(self, scope, receive, send):
+        if not self._is_endpoint(scope):
+            return await self.app(scope, receive, send)
+        chunks, more = [], True
+        while more:
+            message = await receive()
+            if message["type"] == "http.disconnect":
+                return
+            chunks.append(message.get("body", b""))
+            more = message.get("more_body", False)
+        body = b"".join(chunks)
+        batch = None
+        if body.lstrip()[:1] == b"[":
+            try:
+                batch = json.loads(body)
+            except ValueError:
+                pass  # 交給 SDK 回 parse error
+        if batch is None:
+            return await self.app(scope, self._replay(body, receive), send)
+        if not batch or len(batch) > self.max_messages:
+            reason = (f"batch of {len(batch)} messages exceeds the limit of {self.max_messages}" if batch
+                      else "empty batch")
+            payload = json.dumps(_error(None, f"Invalid Request: {reason}")).encode()
+            await send({"type": "http.response.start", "status": 400,
+                        "headers": [(b"content-type", b"application/json"),
+                                    (b"content-length", str(len(payload)).encode())]})
+            return await send({"type": "http.response.body", "body": payload})
+        await self.run_batch(scope, receive, send, batch)
+
+    @staticmethod
+    def _replay(body, receive):
+        """已讀出的 body 先交給下游，之後的 receive (等待斷線) 照常轉給 server"""
+        delivered = False
+
+        async def replay():
+            nonlocal delivered
+            if not delivered:
+                delivered = True
+                return {"type": "http.request", "body": body, "more_body": False}
+            return await receive()
+        return replay
+
+    async def run_batch(self, scope, receive, send, batch):
+        output = BatchOutput(send)
+        headers = [(k, v) for k, v in scope["headers"] if k.lower() not in (b"content-length", SESSION_HEADER.encode())]
+        output.session_id = next((v.decode("latin-1") for k, v in scope["headers"]
+                                  if k.lower() == SESSION_HEADER.encode()), None)
+        logger.info("JSON-RPC 批次請求: %d 個訊息", len(batch))
+        for i, message in enumerate(batch):
+            if not isinstance(message, dict):
+                await output.emit(_error(None, "Invalid Request: batch entries must be JSON-RPC objects"))
+                continue
+            message_id = message.get("id")
+            if message.get("method") == "initialize" and i > 0:
+                await output.emit(_error(message_id, "Invalid Request: initialize must be the first message "
+                                                     "of a batch"))
+                continue
+            body = json.dumps(message).encode()
+            sub_headers = headers + [(b"content-length", str(len(body)).encode())]
+            if output.session_id:
+                sub_headers.append((SESSION_HEADER.encode(), output.session_id.encode("latin-1")))
+            await self.dispatch({**scope, "headers": sub_headers}, self._replay(body, receive), output, message_id)
+        await output.close()
+
+    async def dispatch(self, scope, receive, output, message_id):
+        """把單一訊息送進 SDK，回應轉成整批的輸出"""
+        state = {"status": None, "type": "", "buffer": "", "body": b""}
+
+        async def capture(message):
+            if message["type"] == "http.response.start":
+                state["status"], state["type"] = message["status"], _content_type(message.get("headers", []))
+                created = next((v.decode("latin-1") for k, v in message.get("headers", [])
+                                if k.lower() == SESSION_HEADER.encode()), None)
+                if created and not output.session_id:
+                    output.session_id = created
+                if state["type"] == "text/event-stream" and state["status"] < 400:
+                    await output.stream()
+                return
+            chunk = message.get("body", b"")
+            if state["type"] == "text/event-stream" and state["status"] < 400:
+                events, state["buffer"] = sse_events(state["buffer"] + chunk.decode("utf-8"))
+                for data in events:
+                    await output.emit(data)
+            else:
+                state["body"] += chunk
+
+        await self.app(scope, receive, capture)
+        if (state["type"] == "text/event-stream" and state["status"] < 400) or not state["body"]:
+            return
+        try:
+            response = json.loads(state["body"])
+        except ValueError:
+            response = _error(message_id, f"HTTP {state['status']}: {state['body'][:200].decode('utf-8', 'replace')}",
+                              code=-32603)
+        if isinstance(response, dict) and "error" in response and state["status"] >= 400:
+            # SDK 的傳輸層錯誤 (session 不存在等) 沒有對應的請求 id
+            response["id"] = message_id
+        await output.emit(response)
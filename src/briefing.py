@@ -0,0 +1,133 @@
+"""環境簡報 (context pack): 把受監控環境的基本事實整理成一份精簡摘要，放在對話開頭，
+讓模型不必猜測「有幾台 Windows」「有沒有開 vulnerability-detector」「資料保留多久」。
+
+各段資料分別取得，單一段落失敗只會列在 errors，不影響其他段落。
+"""
+
+import re
+from collections import Counter
+
+# 沒有 disabled / enabled 開關、不算「功能模組」的 ossec.conf 區段
+NON_MODULE_SECTIONS = {"global", "alerts", "logging", "remote", "auth", "ruleset", "localfile",
+                       "command", "labels", "reports", "integration", "active-response"}
+
+# wazuh-alerts-4.x-2024.05.01 / wazuh-monitoring-2024.20w -> 依日期切分前的索引系列
+_INDEX_DATE_SUFFIX = re.compile(r"-\d{4}\.\d{2}(\.\d{2}|w)?$|-\d{4}\.\d{1,2}w$")
+
+
+def _top(counter, limit):
+    return dict(counter.most_common(limit))
+
+
+def agent_breakdown(agents, limit=10):
+    """agent 清單 -> 總數與依狀態 / 作業系統 / group 的分布 (manager 本身 000 不計)"""
+    agents = [a for a in agents if a.get("id") != "000"]
+    by_os = Counter((a.get("os") or {}).get("platform") or "unknown" for a in agents)
+    by_group = Counter(g for a in agents for g in (a.get("group") or ["(none)"]))
+    return {
+        "total": len(agents),
+        "by_status": dict(Counter(a.get("status") or "unknown" for a in agents)),
+        "by_os": _top(by_os, limit),
+        "by_group": _top(by_group, limit),
+    }
+
+
+def _enabled(section):
+    if "disabled" in section:
+        return section["disabled"] == "no"
+    if "enabled" in section:
+        return section["enabled"] == "yes"
+    return None
+
+
+def enabled_modules(config):
+    """/manager/configuration 的結果 -> 啟用中的模組名稱 (wodle 以 wodle:<name> 表示)"""
+    modules = []
+    for name, section in config.items():
+        if name in NON_MODULE_SECTIONS:
+            continue
+        entries = section if isinstance(section, list) else [section]
+        for entry in entries:
+            if not isinstance(entry, dict):
+                continue
+            label = f"{name}:{entry['name']}" if name == "wodle" and entry.get("name") else name
+            if _enabled(entry):
+                modules.append(label)
+    return sorted(set(modules))
+
+
+def log_sources(config):
+    """localfile 設定 -> 依 log_format 統計的收集來源數"""
+    entries = config.get("localfile") or []
+    if isinstance(entries, dict):
+        entries = [entries]
+    return dict(Counter(e.get("log_format", "unknown") for e in entries))
+
+
+def index_families(indices):
+    """_cat/indices 的結果 -> 依索引系列 (去掉日期) 彙整的索引數、文件數與日期範圍"""
+    families = {}
+    for idx in indices:
+        name = idx.get("index", "")
+        if name.startswith("."):
+            continue
+        pattern = _INDEX_DATE_SUFFIX.sub("-*", name)
+        family = families.setdefault(pattern, {"pattern": pattern, "indices": 0, "docs": 0, "names": []})
+        family["indices"] += 1
+        family["docs"] += int(idx.get("docs.count") or 0)
+        family["names"].append(name)
+    result = []
+    for family in sorted(families.values(), key=lambda f: f["pattern"]):
+        names = sorted(family.pop("names"))
+        if family["indices"] > 1:
+            family["oldest"], family["newest"] = names[0], names[-1]
+        result.append(family)
+    return result
+
+
+def retention(oldest, newest, now):
+    """最舊 / 最新告警時間 (datetime) -> 實際可查詢的資料範圍"""
+    if oldest is None:
+        return {"oldest_alert": None, "newest_alert": None, "days": 0}
+    return {
+        "oldest_alert": oldest.isoformat(),
+        "newest_alert": newest.isoformat() if newest else None,
+        "days": (now - oldest).days,
+    }
+
+
+def format_brief(brief):
+    """簡報的精簡文字版 (Markdown 條列)，適合直接放進 system prompt"""
+    lines = ["# Wazuh 環境簡報", f"產生時間: {brief['generated_at']}"]
+    agents = brief.get("agents")
+    if agents:
+        lines.append(f"- Agents: {agents['total']} 台 ({_pairs(agents['by_status'])})")
+        lines.append(f"- 作業系統: {_pairs(agents['by_os'])}")
+        lines.append(f"- Agent groups: {_pairs(agents['by_group'])}")
+    if brief.get("wazuh_version"):
+        lines.append(f"- Wazuh 版本: {brief['wazuh_version']}")
+    if brief.get("modules") is not None:
+        lines.append(f"- 啟用的模組: {', '.join(brief['modules']) or '(無)'}")
+    if brief.get("log_sources"):
+        lines.append(f"- 日誌收集來源 (log_format): {_pairs(brief['log_sources'])}")
+    if brief.get("retention"):
+        r = brief["retention"]
+        lines.append(f"- 可查詢的告警範圍: {r['oldest_alert']} ~ {r['newest_alert']} (約 {r['days']} 天)")
+    if brief.get("indices"):
+        lines.append("- 索引: " + "; ".join(f"{f['pattern']} ({f['indices']} 個, {f['docs']} 筆)"
+                                           for f in brief["indices"]))
+    if brief.get("top_rules"):
+        lines.append(f"- 最常觸發的規則 ({brief['time_range']} 起):")
+        lines.extend(f"  - {r['rule_id']} L{r['level']} {r['description']} ({r['alerts']} 筆)"
+                     for r in brief["top_rules"])
+    if brief.get("features"):
+        lines.append(f"- MCP 功能模組: {', '.join(brief['features'])}")
+    for note in brief.get("notes", []):
+        lines.append(f"- 註: {note}")
+    for error in brief.get("errors", []):
+        lines.append(f"- 無法取得: {error}")
+    return "\n".join(lines)
+
+
+def _pairs(counts):
+    return ", ".join(f"{k} {v}" for k, v in counts.items()) or "(無)"
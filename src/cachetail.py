@@ -0,0 +1,93 @@
+"""查詢快取的晚到告警監看 (live tail): 歷史時間範圍出現新告警時主動清除相關快取。
+
+查詢快取假設「現在 - 匯入延遲」之前的資料不會再變，但 agent 離線後補送、filebeat 積壓時，
+舊時段仍可能出現新的告警。CacheTail 每隔 interval 以一個 date_histogram 統計 lookback 內
+(到「現在 - 匯入延遲」為止) 每個時段的告警數，與上一次比較；數量有變動 (新增或被刪除) 的時段
+會清除時間範圍涵蓋該時段的快取，讓「最近的告警」類問題維持新鮮，又不需要每次都重查 Indexer。
+"""
+
+import logging
+import threading
+from datetime import datetime, timedelta, timezone
+
+logger = logging.getLogger("wazuh_mcp")
+
+BUCKET = timedelta(hours=1)
+BUCKET_MS = int(BUCKET.total_seconds() * 1000)
+
+
+def _epoch_ms(ts):
+    return int(ts.timestamp() * 1000)
+
+
+def changed_windows(previous, current):
+    """比較兩次統計 ({時段起點 epoch ms: 告警數})，回傳數量有變動的時段 [(start, end), ...] (相鄰時段合併)"""
+    changed = sorted(k for k in set(previous) | set(current) if previous.get(k, 0) != current.get(k, 0))
+    windows = []
+    for key in changed:
+        start = datetime.fromtimestamp(key / 1000, tz=timezone.utc)
+        if windows and windows[-1][1] == start:
+            windows[-1] = (windows[-1][0], start + BUCKET)
+        else:
+            windows.append((start, start + BUCKET))
+    return windows
+
+
+class CacheTail:
+    def __init__(self, search, cache, interval, lookback):
+        """search(body) -> (結果, 錯誤訊息)，應以系統身分、不套用全域篩選查詢"""
+        self.search = search
+        self.cache = cache
+        self.interval = interval
+        self.lookback = lookback
+        self.counts = None
+        self.settled = None
+        self.invalidated = 0
+        self._stop = threading.Event()
+
+    def body(self, start, settled):
+        return {
+            "size": 0,
+            "query": {"range": {"timestamp": {"gte": start.isoformat(), "lt": settled.isoformat()}}},
+            "aggs": {"per_hour": {"date_histogram": {"field": "timestamp", "fixed_interval": "1h",
+                                                     "min_doc_count": 1}}},
+        }
+
+    def tick(self):
+        settled = datetime.now(timezone.utc) - self.cache.ingest_delay
+        start = settled - self.lookback
+        result, error = self.search(self.body(start, settled))
+        if error or "_incomplete" in result:
+            # 統計不完整時不能拿來比較，否則缺少的時段會被誤判為告警被刪除
+            logger.debug("快取監看略過本次統計: %s", error or result["_incomplete"])
+            return []
+        buckets = result.get("aggregations", {}).get("per_hour", {}).get("buckets", [])
+        current = {b["key"]: b["doc_count"] for b in buckets}
+        previous, self.counts = self.counts, current
+        previous_settled, self.settled = self.settled, settled
+        if previous is None:
+            return []
+        # 時間窗往前移動時，頭尾兩個不完整的時段數量本來就會變，只比較兩次都完整統計到的時段
+        first = _epoch_ms(start)
+        last = _epoch_ms(previous_settled) - BUCKET_MS
+        windows = changed_windows(*({k: v for k, v in counts.items() if first <= k <= last}
+                                    for counts in (previous, current)))
+        if windows:
+            removed = self.cache.invalidate(windows)
+            self.invalidated += removed
+            logger.info("歷史時段出現晚到的告警 %s，已清除 %d 筆查詢快取",
+                        [(s.isoformat(), e.isoformat()) for s, e in windows], removed)
+        return windows
+
+    def _loop(self):
+        while not self._stop.wait(self.interval):
+            try:
+                self.tick()
+            except Exception:  # 監看執行緒不能因為單次統計出錯而結束
+                logger.exception("查詢快取監看發生例外")
+
+    def start(self):
+        threading.Thread(target=self._loop, name="query-cache-tail", daemon=True).start()
+
+    def stop(self):
+        self._stop.set()
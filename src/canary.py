@@ -0,0 +1,133 @@
+"""後端延遲金絲雀 (canary): 背景定期對每個後端執行一個便宜、一定會成功的查詢並記錄延遲。
+
+與 supervisor 的連線檢查不同，這裡關心的是「變慢」: 保留最近 24 小時的延遲紀錄，
+近期中位數明顯高於基準時標示 degrading，讓 Indexer 變慢在使用者抱怨之前就被發現。
+結果可由 wazuh_backend_latency 工具與 /metrics (Prometheus 格式) 查詢。
+"""
+
+import logging
+import math
+import threading
+import time
+from collections import deque
+from datetime import datetime, timezone
+
+logger = logging.getLogger("wazuh_mcp")
+
+HISTORY_SECONDS = 24 * 3600
+RECENT_SAMPLES = 5
+# 近期中位數超過基準中位數的倍數 (且至少多出 MIN_DEGRADATION_MS) 視為變慢
+DEGRADATION_FACTOR = 2.0
+MIN_DEGRADATION_MS = 100
+
+
+class Probe:
+    """fn() 成功時回傳 None，失敗時回傳錯誤訊息字串"""
+
+    def __init__(self, name, fn, description):
+        self.name = name
+        self.fn = fn
+        self.description = description
+
+
+def percentile(values, q):
+    if not values:
+        return None
+    ordered = sorted(values)
+    return ordered[min(len(ordered) - 1, max(0, math.ceil(q * len(ordered)) - 1))]
+
+
+class Canary:
+    def __init__(self, probes, interval):
+        self.probes = probes
+        self.interval = interval
+        size = max(1, int(HISTORY_SECONDS / interval)) if interval > 0 else 1
+        self.history = {p.name: deque(maxlen=size) for p in probes}
+        self.lock = threading.Lock()
+        self._stop = threading.Event()
+
+    def tick(self):
+        for probe in self.probes:
+            started = time.monotonic()
+            try:
+                error = probe.fn()
+            except Exception as e:  # 探測本身出錯也記為失敗
+                error = f"{type(e).__name__}: {e}"
+            latency = round((time.monotonic() - started) * 1000)
+            with self.lock:
+                self.history[probe.name].append((time.time(), latency, error))
+            if error:
+                logger.info("後端探測 %s 失敗 (%d ms): %s", probe.name, latency, error)
+
+    def _loop(self):
+        while not self._stop.wait(self.interval):
+            try:
+                self.tick()
+            except Exception:
+                logger.exception("後端延遲探測發生例外")
+
+    def start(self):
+        threading.Thread(target=self._loop, name="backend-canary", daemon=True).start()
+
+    def stop(self):
+        self._stop.set()
+
+    def stats(self, window_seconds=HISTORY_SECONDS):
+        """每個後端在 window 內的延遲統計 (成功的探測才計入延遲)"""
+        cutoff = time.time() - window_seconds
+        with self.lock:
+            samples = {name: [s for s in history if s[0] >= cutoff] for name, history in self.history.items()}
+        report = {}
+        for probe in self.probes:
+            entries = samples[probe.name]
+            ok = [latency for _, latency, error in entries if error is None]
+            failures = [(ts, error) for ts, _, error in entries if error]
+            stats = {
+                "description": probe.description,
+                "samples": len(entries),
+                "success_rate": round(len(ok) / len(entries), 3) if entries else None,
+                "latency_ms": {"last": ok[-1] if ok else None,
+                               "p50": percentile(ok, 0.5), "p95": percentile(ok, 0.95),
+                               "max": max(ok) if ok else None},
+            }
+            if failures:
+                ts, error = failures[-1]
+                stats["last_error"] = {"at": datetime.fromtimestamp(ts, timezone.utc).isoformat(), "detail": error}
+            stats["status"] = status(ok, entries)
+            report[probe.name] = stats
+        return report
+
+
+def status(ok, entries):
+    """healthy / degrading (近期明顯變慢) / failing (最近一次失敗) / unknown (還沒有紀錄)"""
+    if not entries:
+        return "unknown"
+    if entries[-1][2]:
+        return "failing"
+    recent, baseline = ok[-RECENT_SAMPLES:], ok[:-RECENT_SAMPLES]
+    if len(baseline) >= RECENT_SAMPLES:
+        now, before = percentile(recent, 0.5), percentile(baseline, 0.5)
+        if now > before * DEGRADATION_FACTOR and now - before >= MIN_DEGRADATION_MS:
+            return "degrading"
+    return "healthy"
+
+
+def prometheus_text(report):
+    """stats() -> Prometheus text exposition format"""
+    lines = [
+        "# HELP wazuh_mcp_backend_latency_ms Canary query latency per backend (successful probes).",
+        "# TYPE wazuh_mcp_backend_latency_ms gauge",
+    ]
+    for name, stats in report.items():
+        for key, value in stats["latency_ms"].items():
+            if value is not None:
+                lines.append(f'wazuh_mcp_backend_latency_ms{{backend="{name}",stat="{key}"}} {value}')
+    lines += ["# HELP wazuh_mcp_backend_success_ratio Share of successful canary probes in the window.",
+              "# TYPE wazuh_mcp_backend_success_ratio gauge"]
+    lines += [f'wazuh_mcp_backend_success_ratio{{backend="{name}"}} {stats["success_rate"]}'
+              for name, stats in report.items() if stats["success_rate"] is not None]
+    lines += ["# HELP wazuh_mcp_backend_up 1 when the last canary probe succeeded.",
+              "# TYPE wazuh_mcp_backend_up gauge"]
+    lines += [f'wazuh_mcp_backend_up{{backend="{name}"}} {0 if stats["status"] == "failing" else 1}'
+              for name, stats in report.items() if stats["samples"]]
+    return "\n".join(lines) + "\n"
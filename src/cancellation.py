@@ -0,0 +1,73 @@
+"""工具呼叫的取消 (MCP notifications/cancelled)。
+
+工具都是同步函式，直接在 event loop 上執行時 client 的取消通知要等工具結束才會被處理。
+CancellationMiddleware 把每次工具呼叫移到獨立執行緒，event loop 收到取消時立刻回應 client，
+並觸發該次呼叫的 CancelToken:
+- 之後的 Wazuh API / Indexer 請求在送出前就以 cancelled() 中止 (切段查詢剩下的片段也不會再送出)
+- 進行中的 Indexer 搜尋以 X-Opaque-Id 找到對應的 task 並呼叫 _tasks/_cancel，
+  讓 Indexer 停止運算，等待回應的執行緒也會因此很快結束
+"""
+
+import asyncio
+import contextvars
+import logging
+import threading
+import uuid
+
+from fastmcp.server.middleware import Middleware
+
+logger = logging.getLogger("wazuh_mcp")
+
+_current = contextvars.ContextVar("cancel_token", default=None)
+
+CANCELLED_MESSAGE = "錯誤: 呼叫已被 client 取消"
+
+
+class CancelToken:
+    def __init__(self, tool):
+        self.tool = tool
+        self.id = f"wazuh-mcp-{uuid.uuid4().hex[:12]}"
+        self.event = threading.Event()
+
+    @property
+    def cancelled(self):
+        return self.event.is_set()
+
+
+def cancelled():
+    """目前的工具呼叫是否已被 client 取消 (不在工具呼叫中時為 False)"""
+    token = _current.get()
+    return token is not None and token.cancelled
+
+
+def opaque_id():
+    """帶在 Indexer 請求的 X-Opaque-Id，取消時用來找出對應的搜尋 task"""
+    token = _current.get()
+    return token.id if token is not None else None
+
+
+class CancellationMiddleware(Middleware):
+    def __init__(self, cancel_backend):
+        """cancel_backend(opaque_id): 取消 Indexer 上進行中的搜尋"""
+        self.cancel_backend = cancel_backend
+
+    def _abort(self, token):
+        token.event.set()
+        try:
+            self.cancel_backend(token.id)
+        except Exception as e:
+            logger.warning("取消 Indexer 搜尋失敗 (%s): %s", token.tool, e)
+
+    async def on_call_tool(self, context, call_next):
+        token = CancelToken(context.message.name)
+        reset = _current.set(token)
+        try:
+            # 執行緒帶著目前的 context (principal、timing、取消 token)，在自己的 event loop 執行工具
+            return await asyncio.to_thread(asyncio.run, call_next(context))
+        except asyncio.CancelledError:
+            logger.info("工具 %s 已被 client 取消，中止後端查詢", token.tool)
+            # 目前的 task 已被取消，不能再 await；在背景執行緒通知後端
+            threading.Thread(target=self._abort, args=(token,), daemon=True).start()
+            raise
+        finally:
+            _current.reset(reset)
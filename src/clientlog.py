@@ -0,0 +1,101 @@
+"""MCP logging: 把伺服器 log 以 notifications/message 轉送給 client。
+
+client 以 logging/setLevel 設定等級後，"wazuh_mcp" logger 的紀錄 (Wazuh API 失敗、token 重新驗證、
+慢查詢警告等) 會直接出現在 MCP client 裡，不必翻 stderr。MCP_CLIENT_LOG_LEVEL 設定後，
+沒有呼叫 setLevel 的 session 也會以該等級收到 log。
+log 內容可能涉及其他租戶，只轉送給不受租戶範圍限制的呼叫者。
+"""
+
+import asyncio
+import logging
+import threading
+
+from fastmcp.server.middleware import Middleware
+
+# MCP 的 8 個等級 -> Python logging 門檻
+MCP_LEVELS = {
+    "debug": logging.DEBUG, "info": logging.INFO, "notice": logging.INFO + 5, "warning": logging.WARNING,
+    "error": logging.ERROR, "critical": logging.CRITICAL, "alert": logging.CRITICAL + 5,
+    "emergency": logging.CRITICAL + 10,
+}
+
+
+def mcp_level(levelno):
+    """Python logging 等級 -> MCP 等級名稱"""
+    if levelno >= logging.CRITICAL:
+        return "critical"
+    if levelno >= logging.ERROR:
+        return "error"
+    if levelno >= logging.WARNING:
+        return "warning"
+    if levelno >= logging.INFO:
+        return "info"
+    return "debug"
+
+
+class ClientLogForwarder(logging.Handler):
+    def __init__(self, default_level=None):
+        super().__init__(logging.DEBUG)
+        if default_level is not None and default_level not in MCP_LEVELS:
+            raise ValueError(f"MCP_CLIENT_LOG_LEVEL 必須是 {' / '.join(MCP_LEVELS)}")
+        self.default_level = default_level
+        self.sessions_lock = threading.Lock()
+        self.sessions = {}  # session -> (event loop, 門檻)
+        self._sending = threading.local()
+
+    def set_level(self, session, level):
+        with self.sessions_lock:
+            self.sessions[session] = (asyncio.get_running_loop(), MCP_LEVELS[level])
+
+    def register(self, session):
+        """有設定 MCP_CLIENT_LOG_LEVEL 時，新 session 以預設等級開始接收"""
+        if self.default_level is None:
+            return
+        with self.sessions_lock:
+            if session not in self.sessions:
+                self.sessions[session] = (asyncio.get_running_loop(), MCP_LEVELS[self.default_level])
+
+    def drop(self, session):
+        with self.sessions_lock:
+            self.sessions.pop(session, None)
+
+    def emit(self, record):
+        if getattr(self._sending, "active", False):  # 送出失敗時寫的 log 不再轉送，避免遞迴
+            return
+        with self.sessions_lock:
+            targets = [(s, loop) for s, (loop, threshold) in self.sessions.items() if record.levelno >= threshold]
+        if not targets:
+            return
+        data = {"message": record.getMessage()}
+        if record.exc_info:
+            data["exception"] = logging.Formatter().formatException(record.exc_info)
+        self._sending.active = True
+        try:
+            for session, loop in targets:
+                try:
+                    future = asyncio.run_coroutine_threadsafe(
+                        session.send_log_message(mcp_level(record.levelno), data, logger=record.name), loop)
+                    # 送不出去 (連線已斷) 就不再轉送給這個 session
+                    future.add_done_callback(lambda f, s=session: f.cancelled() or f.exception() is None or self.drop(s))
+                except RuntimeError:  # event loop 已關閉 = session 已結束
+                    self.drop(session)
+        finally:
+            self._sending.active = False
+
+
+class ClientLogSessions(Middleware):
+    """把發出請求的 session 登記為預設等級的 log 接收者 (MCP_CLIENT_LOG_LEVEL)"""
+
+    def __init__(self, forwarder, allowed):
+        """allowed() -> 目前呼叫者是否可以接收伺服器 log"""
+        self.forwarder = forwarder
+        self.allowed = allowed
+
+    async def on_request(self, context, call_next):
+        ctx = context.fastmcp_context
+        if self.forwarder.default_level is not None and ctx is not None and self.allowed():
+            try:
+                self.forwarder.register(ctx.session)
+            except (AttributeError, RuntimeError):
+                pass
+        return await call_next(context)
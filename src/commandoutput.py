@@ -0,0 +1,83 @@
+"""定期執行的指令輸出 (command wodle 與 logcollector 的 command / full_command)。
+
+有些環境不裝額外的 agent 模組，而是讓 Wazuh 定期執行 netstat、自訂腳本等指令收集狀態:
+- logcollector <localfile> 的 command / full_command: 事件內容為 "ossec: output: '<alias>': <輸出>"，
+  location 是 alias (沒設定時為指令本身)；full_command 整份輸出是一筆事件，command 每行一筆
+- <wodle name="command">: location 為 command_<tag>，每行輸出一筆事件
+這些事件多半只觸發等級 0 的規則 (530)，所以只存在 archives；netstat / df 等內建規則偵測到輸出改變時才會產生告警。
+"""
+
+import re
+
+from alert_utils import get_field, first_field
+from provenance import event_ref
+
+WODLE_PREFIX = "command_"
+OUTPUT_HEADER = re.compile(r"^ossec: output: '([^']*)':?[ \t]*\n?")
+# ossec 解碼器的指令輸出規則 (530 為父規則；531 df、533 netstat 等在輸出改變時告警)
+OUTPUT_RULES = ["530", "531", "532", "533", "534", "535"]
+SOURCE_FIELDS = ["timestamp", "agent.id", "agent.name", "location", "full_log", "rule.id", "rule.description",
+                 "previous_output"]
+
+
+def output_query(command=None):
+    """指令輸出事件；command 比對 alias / tag (完全相符或包含) 與事件開頭的 alias"""
+    query = {"bool": {"should": [
+        {"terms": {"rule.id": OUTPUT_RULES}},
+        {"prefix": {"location": WODLE_PREFIX}},
+        {"match_phrase": {"full_log": "ossec: output"}},
+    ], "minimum_should_match": 1}}
+    if not command:
+        return query
+    return {"bool": {"filter": [query, {"bool": {"should": [
+        {"terms": {"location": [command, WODLE_PREFIX + command]}},
+        {"wildcard": {"location": {"value": f"*{command}*", "case_insensitive": True}}},
+        {"match_phrase": {"full_log": f"ossec: output: '{command}'"}},
+    ], "minimum_should_match": 1}}]}}
+
+
+def summary_aggregations(limit):
+    """各指令 (location) 的事件數、agent 數與最近一次輸出的時間"""
+    return {"commands": {"terms": {"field": "location", "size": limit}, "aggs": {
+        "agents": {"cardinality": {"field": "agent.id"}},
+        "last_seen": {"max": {"field": "timestamp"}},
+    }}}
+
+
+def command_name(location):
+    location = location or ""
+    return location[len(WODLE_PREFIX):] if location.startswith(WODLE_PREFIX) else location
+
+
+def summarize_commands(aggregations):
+    return [{"command": command_name(b["key"]), "location": b["key"], "events": b["doc_count"],
+             "agents": b.get("agents", {}).get("value"),
+             "last_seen": b.get("last_seen", {}).get("value_as_string")}
+            for b in aggregations.get("commands", {}).get("buckets", [])]
+
+
+def output_event(hit):
+    """單筆指令輸出: 去掉 "ossec: output: '<alias>':" 開頭，保留指令名稱與輸出內容"""
+    alert = hit.get("_source", {})
+    text = str(get_field(alert, "full_log") or "")
+    m = OUTPUT_HEADER.match(text)
+    event = {
+        "timestamp": alert.get("timestamp"),
+        "agent": first_field(alert, "agent.name", "agent.id"),
+        "command": m.group(1) if m else command_name(get_field(alert, "location")),
+        "output": text[m.end():] if m else text,
+        "rule": get_field(alert, "rule.id"),
+    }
+    if get_field(alert, "previous_output"):
+        event["previous_output"] = get_field(alert, "previous_output")
+    event["_ref"] = event_ref(hit)
+    return event
+
+
+def latest_runs(events):
+    """每個 (agent, 指令) 只保留最近一次執行的輸出 (同一秒內的所有輸出行)"""
+    newest = {}
+    for event in events:
+        key = (event["agent"], event["command"])
+        newest[key] = max(newest.get(key, ""), (event["timestamp"] or "")[:19])
+    return [e for e in events if (e["timestamp"] or "")[:19] == newest[(e["agent"], e["command"])]]
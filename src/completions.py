@@ -0,0 +1,101 @@
+"""參數自動完成 (MCP completion/complete): client 輸入 prompt 或資源範本的參數時提供候選值。
+
+依參數名稱決定候選來源 (agent 與規則清單都有快取，不會每打一個字就查一次 Manager API):
+    agent_id     agent ID (也比對名稱)          agent        agent 名稱
+    rule_id      規則 ID                        group        規則群組
+    level        規則等級 0-15                  min_severity 標準化嚴重度
+    time_range   常用的相對時間範圍
+MCP 的自動完成只涵蓋 prompt 與資源範本的參數，工具參數不在協定範圍內。
+"""
+
+import threading
+import time
+
+from severity import SEVERITY_ORDER
+
+MAX_VALUES = 100  # MCP 規定每次最多回傳 100 個候選值
+AGENT_CACHE_TTL = 60
+TIME_RANGES = ("now-15m", "now-1h", "now-6h", "now-24h", "now-7d", "now-30d")
+
+
+class CachedList:
+    """fetch() -> 清單；查詢失敗時沿用上一次的結果 (沒有時回傳空清單)"""
+
+    def __init__(self, fetch, ttl):
+        self.fetch = fetch
+        self.ttl = ttl
+        self.lock = threading.Lock()
+        self._items = None
+        self._loaded_at = 0
+
+    def get(self):
+        with self.lock:
+            if self._items is not None and time.time() - self._loaded_at < self.ttl:
+                return self._items
+        try:
+            items = self.fetch()
+        except Exception:
+            return self._items or []
+        with self.lock:
+            self._items, self._loaded_at = items, time.time()
+        return items
+
+
+def _numeric_key(value):
+    return (0, int(value)) if str(value).isdigit() else (1, str(value))
+
+
+class Completer:
+    def __init__(self, agents, rules, visible_agents):
+        """agents: CachedList (Manager API 的 agent 清單)；rules: RuleCatalog；
+        visible_agents() -> 目前呼叫者可存取的 agent id 集合 (None = 不限)"""
+        self.agents = agents
+        self.rules = rules
+        self.visible_agents = visible_agents
+        self.providers = {
+            "agent_id": self._agent_ids,
+            "agent": self._agent_names,
+            "rule_id": self._rule_ids,
+            "group": self._rule_groups,
+            "level": lambda value: [str(level) for level in range(16) if str(level).startswith(value)],
+            "min_severity": lambda value: [s for s in SEVERITY_ORDER if s.startswith(value.lower())],
+            "time_range": lambda value: [t for t in TIME_RANGES if t.startswith(value)],
+        }
+
+    def complete(self, argument, value):
+        """回傳 (候選值 (最多 MAX_VALUES 個), 符合的總數)；不認得的參數回傳空清單"""
+        provider = self.providers.get(argument)
+        values = provider((value or "").strip()) if provider else []
+        return values[:MAX_VALUES], len(values)
+
+    def _visible(self):
+        allowed = self.visible_agents()
+        return [a for a in self.agents.get() if allowed is None or a.get("id") in allowed]
+
+    def _agent_ids(self, value):
+        needle = value.lower()
+        matches = [a["id"] for a in self._visible()
+                   if a.get("id", "").startswith(value) or needle and needle in (a.get("name") or "").lower()]
+        return sorted(matches, key=_numeric_key)
+
+    def _agent_names(self, value):
+        needle = value.lower()
+        names = {a["name"] for a in self._visible() if a.get("name") and needle in a["name"].lower()}
+        # 開頭相符的排前面
+        return sorted(names, key=lambda n: (not n.lower().startswith(needle), n))
+
+    def _rule_ids(self, value):
+        try:
+            rules = self.rules.rules()
+        except RuntimeError:
+            return []
+        return sorted((rule_id for rule_id in rules if rule_id.startswith(value)), key=_numeric_key)
+
+    def _rule_groups(self, value):
+        try:
+            rules = self.rules.rules()
+        except RuntimeError:
+            return []
+        needle = value.lower()
+        groups = {g for rule in rules.values() for g in rule["groups"] if needle in g.lower()}
+        return sorted(groups, key=lambda g: (not g.lower().startswith(needle), g))
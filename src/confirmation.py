@@ -0,0 +1,59 @@
+"""寫入 / 破壞性工具執行前向使用者確認 (MCP elicitation)。
+
+client 支援 elicitation 時，WRITE_CALLS 判定為寫入的呼叫會先送出確認請求，內容包含
+要做的事與受影響的範圍 (agent / 索引 / manager)，使用者接受後才執行。MCP_CONFIRM_WRITES:
+    when-supported  client 支援才詢問，不支援時照舊執行 (仍受 MCP_ALLOW_WRITES 限制) (預設)
+    required        一律要確認，client 不支援 elicitation 時拒絕執行
+    off             不詢問
+"""
+
+import logging
+
+from fastmcp.server.middleware import Middleware
+from fastmcp.tools.tool import ToolResult
+from mcp.types import TextContent
+
+logger = logging.getLogger("wazuh_mcp")
+
+CONFIRM_MODES = ("when-supported", "required", "off")
+
+
+def supports_elicitation(ctx):
+    try:
+        capabilities = ctx.session.client_params.capabilities
+    except AttributeError:
+        return False
+    return capabilities is not None and capabilities.elicitation is not None
+
+
+def _refuse(text):
+    return ToolResult(content=[TextContent(type="text", text=text)])
+
+
+class ConfirmWrites(Middleware):
+    def __init__(self, write_calls, summaries, mode):
+        """summaries: {工具名稱: fn(arguments) -> (要執行的動作說明, [受影響的範圍])}"""
+        if mode not in CONFIRM_MODES:
+            raise ValueError(f"MCP_CONFIRM_WRITES 必須是 {' / '.join(CONFIRM_MODES)}")
+        self.write_calls = write_calls
+        self.summaries = summaries
+        self.mode = mode
+
+    async def on_call_tool(self, context, call_next):
+        tool = context.message.name
+        arguments = context.message.arguments or {}
+        is_write = self.write_calls.get(tool)
+        if self.mode == "off" or not (is_write and is_write(arguments)):
+            return await call_next(context)
+        ctx = context.fastmcp_context
+        if ctx is None or not supports_elicitation(ctx):
+            if self.mode == "required":
+                return _refuse(f"錯誤: {tool} 需要使用者確認，但 client 不支援 elicitation (MCP_CONFIRM_WRITES=required)")
+            return await call_next(context)
+        action, affected = self.summaries[tool](arguments)
+        message = f"{tool}: {action}\n影響範圍: {'、'.join(affected)}\n確定要執行嗎？"
+        answer = await ctx.elicit(message, response_type=None)
+        if answer.action != "accept":
+            logger.info("使用者未確認 %s (%s)，已取消", tool, answer.action)
+            return _refuse(f"錯誤: 使用者未確認 ({answer.action})，已取消 {tool}")
+        return await call_next(context)
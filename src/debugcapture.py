@@ -0,0 +1,133 @@
+"""工具呼叫的後端請求擷取 (debug: true)，回答「這個工具為什麼什麼都沒回傳」。
+
+MCP_ALLOW_DEBUG=true 時每個工具多一個 debug 參數；帶 debug: true 的呼叫會在回應最後附上 _debug 區塊，
+列出這次呼叫對 Wazuh API / Indexer 發出的每個請求: 方法、網址、標頭 (Authorization、Cookie 等已遮蔽)、
+請求內容 (例如 Indexer DSL) 與回應狀態、耗時、回應內容的開頭 (MCP_DEBUG_SNIPPET_BYTES)。
+向 Wazuh API 取得 token 的回應一律遮蔽；查詢快取命中的查詢沒有實際請求，會另外註記。
+"""
+
+import contextvars
+import json
+import re
+import threading
+from urllib.parse import parse_qsl, quote, urlencode, urlsplit, urlunsplit
+
+from fastmcp.server.middleware import Middleware
+
+from output import annotate_result
+
+_current = contextvars.ContextVar("debug_capture", default=None)
+
+DEBUG_PARAMETER = {"type": "boolean", "default": False,
+                   "description": "附上這次呼叫的後端請求與回應 (除錯用)"}
+SENSITIVE_HEADERS = {"authorization", "proxy-authorization", "cookie", "set-cookie", "x-api-key"}
+_SENSITIVE_PARAM = re.compile(r"pass|token|secret|key", re.I)
+AUTH_PATH = "/security/user/authenticate"
+MAX_EXCHANGES = 50
+REDACTED = "[redacted]"
+
+
+def sanitize_url(url):
+    """去掉網址中的帳號密碼，遮蔽看起來像秘密的查詢參數"""
+    parts = urlsplit(url)
+    netloc = parts.netloc.rsplit("@", 1)[-1]
+    params = [(k, REDACTED if _SENSITIVE_PARAM.search(k) else v)
+              for k, v in parse_qsl(parts.query, keep_blank_values=True)]
+    query = urlencode(params, safe=",:*[]", quote_via=quote)
+    return urlunsplit((parts.scheme, netloc, parts.path, query, parts.fragment))
+
+
+def sanitize_headers(headers):
+    """標準的認證標頭與名稱像秘密的標頭 (例如閘道要求的 X-Gateway-Key) 都遮蔽"""
+    return {k: REDACTED if k.lower() in SENSITIVE_HEADERS or _SENSITIVE_PARAM.search(k) else v
+            for k, v in headers.items()}
+
+
+def snippet(content, limit):
+    if content is None:
+        return None
+    if isinstance(content, bytes):
+        content = content.decode("utf-8", errors="replace")
+    return content if len(content) <= limit else content[:limit] + f"... ({len(content)} 字元，已截斷)"
+
+
+class Capture:
+    def __init__(self, snippet_bytes):
+        self.snippet_bytes = snippet_bytes
+        self.exchanges = []
+        self.dropped = 0
+        self.lock = threading.Lock()
+
+    def add(self, entry):
+        with self.lock:
+            if len(self.exchanges) < MAX_EXCHANGES:
+                self.exchanges.append(entry)
+            else:
+                self.dropped += 1
+
+    def summary(self):
+        with self.lock:
+            report = {"backend_requests": list(self.exchanges)}
+            if self.dropped:
+                report["dropped"] = self.dropped
+            return report
+
+
+def record_exchange(resp, *args, **kwargs):
+    """requests 的 response hook: debug 呼叫中的每個後端請求"""
+    capture = _current.get()
+    if capture is None:
+        return
+    request = resp.request
+    limit = capture.snippet_bytes
+    auth = AUTH_PATH in resp.url
+    capture.add({
+        "method": request.method,
+        "url": sanitize_url(request.url),
+        "request_headers": sanitize_headers(request.headers),
+        "request_body": snippet(request.body, limit),
+        "status": resp.status_code,
+        "elapsed_ms": round(resp.elapsed.total_seconds() * 1000),
+        "response_content_type": resp.headers.get("Content-Type"),
+        "response_snippet": f"{REDACTED} (認證回應含 token)" if auth else snippet(resp.content, limit),
+    })
+
+
+def note(kind, **details):
+    """沒有實際發出請求的查詢 (例如快取命中) 也記一筆，避免誤以為沒有查詢"""
+    capture = _current.get()
+    if capture is not None:
+        capture.add({"note": kind, **{k: snippet(json.dumps(v, ensure_ascii=False), capture.snippet_bytes)
+                                      if isinstance(v, (dict, list)) else v for k, v in details.items()}})
+
+
+class DebugCapture(Middleware):
+    """每個工具加上 debug 參數；debug: true 的呼叫在回應最後附上 _debug 區塊"""
+
+    def __init__(self, snippet_bytes=2000):
+        self.snippet_bytes = snippet_bytes
+
+    async def on_list_tools(self, context, call_next):
+        tools = await call_next(context)
+        listed = []
+        for tool in tools:
+            parameters = dict(tool.parameters or {"type": "object"})
+            parameters["properties"] = {**parameters.get("properties", {}), "debug": DEBUG_PARAMETER}
+            listed.append(tool.model_copy(update={"parameters": parameters}))
+        return listed
+
+    async def on_call_tool(self, context, call_next):
+        arguments = dict(context.message.arguments or {})
+        if "debug" not in arguments:
+            return await call_next(context)
+        enabled = arguments.pop("debug") is True
+        context = context.copy(message=context.message.model_copy(update={"arguments": arguments}))
+        if not enabled:
+            return await call_next(context)
+        capture = Capture(self.snippet_bytes)
+        token = _current.set(capture)
+        try:
+            result = await call_next(context)
+        finally:
+            _current.reset(token)
+        return annotate_result(result, "_debug", capture.summary(), "debug", first=False)
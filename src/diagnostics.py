@@ -0,0 +1,149 @@
+"""執行期診斷: 動態調整 log 等級、追蹤進行中的工具呼叫、輸出診斷快照。
+
+不需重新啟動即可操作:
+- kill -USR1 <pid>   提高 log 詳細程度 (WARNING -> INFO -> DEBUG)，並輸出診斷快照
+- kill -USR2 <pid>   降低 log 詳細程度，並輸出診斷快照
+- HTTP 模式下的 /admin/diagnostics 與 /admin/log-level (需設定 MCP_ADMIN_TOKEN)
+"""
+
+import logging
+import os
+import signal
+import threading
+import time
+from datetime import datetime, timezone
+
+from fastmcp.server.middleware import Middleware
+
+logger = logging.getLogger("wazuh_mcp")
+
+LEVELS = [logging.ERROR, logging.WARNING, logging.INFO, logging.DEBUG]
+STARTED_AT = time.time()
+
+
+def set_log_level(level):
+    """level 可以是名稱 ("debug") 或數值；回傳實際套用的名稱"""
+    if isinstance(level, str):
+        level = logging.getLevelName(level.upper())
+        if not isinstance(level, int):
+            raise ValueError("未知的 log 等級，可用: error、warning、info、debug")
+    logging.getLogger().setLevel(level)
+    logger.setLevel(level)
+    return logging.getLevelName(level)
+
+
+def shift_log_level(step):
+    """step > 0 更詳細、step < 0 更精簡"""
+    current = logger.getEffectiveLevel()
+    index = LEVELS.index(current) if current in LEVELS else 1
+    index = max(0, min(len(LEVELS) - 1, index + step))
+    return set_log_level(LEVELS[index])
+
+
+class CallTracker(Middleware):
+    """記錄目前進行中的工具呼叫與各 session 的活動時間"""
+
+    def __init__(self):
+        self.lock = threading.Lock()
+        self.in_flight = {}
+        self.sessions = {}
+        self.completed = 0
+        self.failed = 0
+        self._next_id = 0
+
+    async def on_call_tool(self, context, call_next):
+        session_id = _session_id(context)
+        with self.lock:
+            self._next_id += 1
+            call_id = self._next_id
+            self.in_flight[call_id] = {
+                "tool": context.message.name,
+                "session": session_id,
+                "started_at": time.time(),
+            }
+            self.sessions[session_id] = time.time()
+        try:
+            result = await call_next(context)
+            with self.lock:
+                self.completed += 1
+            return result
+        except Exception:
+            with self.lock:
+                self.failed += 1
+            raise
+        finally:
+            with self.lock:
+                self.in_flight.pop(call_id, None)
+
+    def forget(self, session_id):
+        with self.lock:
+            self.sessions.pop(session_id, None)
+
+    def snapshot(self):
+        now = time.time()
+        with self.lock:
+            return {
+                "sessions": [
+                    {"session": sid, "idle_seconds": round(now - last, 1)}
+                    for sid, last in self.sessions.items()
+                ],
+                "in_flight": [
+                    {"tool": c["tool"], "session": c["session"],
+                     "running_seconds": round(now - c["started_at"], 1)}
+                    for c in self.in_flight.values()
+                ],
+                "completed_calls": self.completed,
+                "failed_calls": self.failed,
+            }
+
+
+def _session_id(context):
+    ctx = getattr(context, "fastmcp_context", None)
+    try:
+        return ctx.session_id if ctx else "unknown"
+    except Exception:
+        return "unknown"
+
+
+def pool_stats(http_session):
+    """requests.Session 底下各連線池的使用狀況"""
+    stats = []
+    for prefix, adapter in http_session.adapters.items():
+        manager = getattr(adapter, "poolmanager", None)
+        if manager is None:
+            continue
+        for key in list(manager.pools.keys()):
+            pool = manager.pools.get(key)
+            if pool is None:
+                continue
+            stats.append({
+                "host": f"{pool.scheme}://{pool.host}:{pool.port}",
+                "connections_opened": pool.num_connections,
+                "requests": pool.num_requests,
+                "idle_connections": pool.pool.qsize() if pool.pool else 0,
+            })
+    return stats
+
+
+def build_snapshot(tracker, http_session):
+    return {
+        "time": datetime.now(timezone.utc).isoformat(),
+        "pid": os.getpid(),
+        "uptime_seconds": round(time.time() - STARTED_AT, 1),
+        "log_level": logging.getLevelName(logger.getEffectiveLevel()),
+        **tracker.snapshot(),
+        "connection_pools": pool_stats(http_session),
+    }
+
+
+def install_signal_handlers(tracker, http_session):
+    """註冊 SIGUSR1 / SIGUSR2 (Windows 沒有這兩個訊號，直接略過)"""
+    if not hasattr(signal, "SIGUSR1"):
+        return
+
+    def handler(signum, _frame):
+        level = shift_log_level(1 if signum == signal.SIGUSR1 else -1)
+        logger.warning("log 等級已調整為 %s，診斷快照: %s", level, build_snapshot(tracker, http_session))
+
+    signal.signal(signal.SIGUSR1, handler)
+    signal.signal(signal.SIGUSR2, handler)
@@ -0,0 +1,171 @@
+"""DNS 查詢分析: NXDOMAIN 比例、新出現的網域、DGA 可能性評分與罕見網域。
+
+DGA (Domain Generation Algorithm) 惡意程式會大量查詢隨機產生的網域，
+特徵是短時間內大量 NXDOMAIN，以及網域名稱看起來像亂碼 (高熵、少母音、多數字)。
+Sysmon Event 22、Suricata eve.json 與 Zeek dns.log 的欄位不同，這裡統一處理。
+"""
+
+import math
+import re
+from collections import Counter
+
+# 查詢的網域名稱
+QUERY_FIELDS = [
+    "data.win.eventdata.queryName", "data.dns.question.name",
+    "data.dns.rrname", "data.query",
+]
+# 回應代碼 (Sysmon 的 QueryStatus 9003 = DNS_ERROR_RCODE_NAME_ERROR)
+NXDOMAIN_MATCHES = [
+    {"term": {"data.win.eventdata.queryStatus": "9003"}},
+    {"term": {"data.dns.rcode": "NXDOMAIN"}},
+    {"term": {"data.rcode_name": "NXDOMAIN"}},
+]
+DOMAIN_TERMS = 5000
+AGENT_TERMS = 500
+DOMAINS_PER_AGENT = 1000
+
+# 常見的二層公開尾碼，取可註冊網域時要多保留一層
+TWO_LEVEL_SUFFIXES = {
+    "co.uk", "org.uk", "ac.uk", "com.tw", "org.tw", "net.tw", "edu.tw", "gov.tw",
+    "com.cn", "net.cn", "com.hk", "co.jp", "ne.jp", "co.kr", "com.au", "net.au", "com.br",
+}
+_VOWELS = set("aeiou")
+_CONSONANT_RUN_RE = re.compile(r"[bcdfghjklmnpqrstvwxz]{4,}")
+
+
+def registered_domain(name):
+    """www.evil.example.co.uk -> example.co.uk"""
+    labels = name.strip(".").lower().split(".")
+    keep = 3 if ".".join(labels[-2:]) in TWO_LEVEL_SUFFIXES else 2
+    return ".".join(labels[-keep:])
+
+
+def entropy(text):
+    if not text:
+        return 0.0
+    counts = Counter(text)
+    return -sum(c / len(text) * math.log2(c / len(text)) for c in counts.values())
+
+
+def dga_score(name):
+    """0~1 的 DGA 可能性與理由；只看可註冊網域最左邊那一層 (子網域常是合法的雜湊或 CDN 名稱)"""
+    label = registered_domain(name).split(".")[0]
+    if len(label) < 6:
+        return 0.0, []
+    factors = []
+    score = 0.0
+    h = entropy(label)
+    if h >= 3.5:
+        score += 0.4
+        factors.append(f"字元熵 {h:.2f}")
+    elif h >= 3.0:
+        score += 0.2
+        factors.append(f"字元熵 {h:.2f}")
+    letters = [c for c in label if c.isalpha()]
+    vowel_ratio = sum(c in _VOWELS for c in letters) / len(letters) if letters else 0
+    if vowel_ratio < 0.25:
+        score += 0.2
+        factors.append(f"母音比例 {vowel_ratio:.0%}")
+    digit_ratio = sum(c.isdigit() for c in label) / len(label)
+    if digit_ratio > 0.3:
+        score += 0.2
+        factors.append(f"數字比例 {digit_ratio:.0%}")
+    if _CONSONANT_RUN_RE.search(label):
+        score += 0.1
+        factors.append("連續 4 個以上子音")
+    if len(label) >= 15:
+        score += 0.1
+        factors.append(f"長度 {len(label)}")
+    return round(min(score, 1.0), 2), factors
+
+
+def dns_query(extra=None):
+    should = [{"exists": {"field": field}} for field in QUERY_FIELDS]
+    node = {"bool": {"should": should, "minimum_should_match": 1}}
+    return {"bool": {"filter": [node, *extra]}} if extra else node
+
+
+def window_aggregations():
+    domain_aggs = {
+        f"domains__{i}": {"terms": {"field": field, "size": DOMAIN_TERMS},
+                          "aggs": {"agents": {"terms": {"field": "agent.name", "size": 10}}}}
+        for i, field in enumerate(QUERY_FIELDS)
+    }
+    per_agent = {"terms": {"field": "agent.name", "size": AGENT_TERMS}, "aggs": {
+        "nxdomain": {"filter": {"bool": {"should": NXDOMAIN_MATCHES, "minimum_should_match": 1}}},
+        **{f"domains__{i}": {"terms": {"field": field, "size": DOMAINS_PER_AGENT}}
+           for i, field in enumerate(QUERY_FIELDS)},
+    }}
+    return {"per_agent": per_agent, **domain_aggs}
+
+
+def baseline_aggregations(domains):
+    """只統計 domains 在基準期間內是否出現過"""
+    values = sorted(domains)
+    return {
+        f"seen__{i}": {"filter": {"terms": {field: values}},
+                       "aggs": {"values": {"terms": {"field": field, "size": len(values) or 1}}}}
+        for i, field in enumerate(QUERY_FIELDS)
+    }
+
+
+def collect_domains(aggregations):
+    """{網域 (小寫): {"queries": 次數, "agents": [主機]}}"""
+    domains = {}
+    for i in range(len(QUERY_FIELDS)):
+        for b in aggregations.get(f"domains__{i}", {}).get("buckets", []):
+            entry = domains.setdefault(str(b["key"]).strip(".").lower(), {"queries": 0, "agents": set()})
+            entry["queries"] += b["doc_count"]
+            entry["agents"].update(a["key"] for a in b.get("agents", {}).get("buckets", []))
+    return domains
+
+
+def collect_seen(aggregations):
+    return {str(b["key"]).strip(".").lower()
+            for i in range(len(QUERY_FIELDS))
+            for b in aggregations.get(f"seen__{i}", {}).get("values", {}).get("buckets", [])}
+
+
+def summarize(aggregations, seen_before, limit, min_dga_score):
+    domains = collect_domains(aggregations)
+    new_domains = set(domains) - seen_before
+
+    agents = []
+    for b in aggregations.get("per_agent", {}).get("buckets", []):
+        queried = {str(d["key"]).strip(".").lower()
+                   for i in range(len(QUERY_FIELDS))
+                   for d in b.get(f"domains__{i}", {}).get("buckets", [])}
+        nx = b["nxdomain"]["doc_count"]
+        agents.append({
+            "agent": b["key"],
+            "queries": b["doc_count"],
+            "nxdomain": nx,
+            "nxdomain_rate": round(nx / b["doc_count"], 3) if b["doc_count"] else 0,
+            "new_domains": len(queried & new_domains),
+        })
+    agents.sort(key=lambda a: (a["nxdomain_rate"], a["new_domains"]), reverse=True)
+
+    suspicious = []
+    for name, entry in domains.items():
+        score, factors = dga_score(name)
+        if score >= min_dga_score:
+            suspicious.append({"domain": name, "dga_score": score, "factors": factors,
+                               "queries": entry["queries"], "agents": sorted(entry["agents"]),
+                               "new": name in new_domains})
+    suspicious.sort(key=lambda d: (d["dga_score"], d["new"]), reverse=True)
+
+    # 罕見網域: 只有一台主機查詢過、次數最少的網域
+    rare = sorted(
+        ({"domain": name, "queries": e["queries"], "agents": sorted(e["agents"]), "new": name in new_domains}
+         for name, e in domains.items() if len(e["agents"]) <= 1),
+        key=lambda d: (d["queries"], not d["new"])
+    )
+    return {
+        "domains_queried": len(domains),
+        "new_domains": len(new_domains),
+        "agents": agents[:limit],
+        "dga_candidates": suspicious[:limit],
+        "rare_domains": rare[:limit],
+        "truncated": any(aggregations.get(f"domains__{i}", {}).get("sum_other_doc_count", 0)
+                         for i in range(len(QUERY_FIELDS))),
+    }
@@ -0,0 +1,115 @@
+"""Drain 式的日誌模板探勘: 把大量日誌訊息逐筆歸類成模板 (變動的位置以 <*> 表示)。
+
+參考 Drain (He et al., ICWS 2017) 的固定深度解析樹:
+1. 訊息先經 logtokens.tokenize 斷詞 (IP、路徑、數字等已換成佔位符)
+2. 依 token 數分組，再依前 depth-2 個 token 往下走 (含數字的 token 視為萬用字元，避免樹爆炸)
+3. 葉節點內找相似度最高的模板 (同位置相同的 token 比例)，達到 similarity 就併入並把不同的位置改成 <*>，
+   否則建立新模板
+逐筆處理，不需要先看完所有訊息，記憶體只跟模板數有關。
+"""
+
+from collections import Counter
+
+from logtokens import tokenize
+
+WILDCARD = "<*>"
+DEFAULT_DEPTH = 4
+DEFAULT_SIMILARITY = 0.5
+MAX_CHILDREN = 100
+
+
+class Cluster:
+    def __init__(self, tokens, max_examples):
+        self.tokens = list(tokens)
+        self.count = 0
+        self.examples = []
+        self.max_examples = max_examples
+        self.rules = Counter()
+        self.agents = Counter()
+        self.first_seen = None
+        self.last_seen = None
+
+    @property
+    def template(self):
+        return " ".join(self.tokens)
+
+    def similarity(self, tokens):
+        """同位置相同 token 的比例；模板中已是 <*> 的位置不計分"""
+        same = sum(1 for a, b in zip(self.tokens, tokens) if a == b and a != WILDCARD)
+        return same / len(tokens) if tokens else 1.0
+
+    def merge(self, tokens):
+        self.tokens = [a if a == b else WILDCARD for a, b in zip(self.tokens, tokens)]
+
+    def record(self, message, timestamp=None, rule=None, agent=None):
+        self.count += 1
+        if len(self.examples) < self.max_examples:
+            self.examples.append(message)
+        if rule:
+            self.rules[str(rule)] += 1
+        if agent:
+            self.agents[str(agent)] += 1
+        if timestamp:
+            self.first_seen = min(self.first_seen or timestamp, timestamp)
+            self.last_seen = max(self.last_seen or timestamp, timestamp)
+
+    def summary(self, top=5):
+        return {
+            "template": self.template,
+            "count": self.count,
+            "first_seen": self.first_seen,
+            "last_seen": self.last_seen,
+            "rules": dict(self.rules.most_common(top)),
+            "agents": dict(self.agents.most_common(top)),
+            "examples": self.examples,
+        }
+
+
+def _key(token):
+    return WILDCARD if any(c.isdigit() for c in token) else token
+
+
+class Drain:
+    def __init__(self, depth=DEFAULT_DEPTH, similarity=DEFAULT_SIMILARITY, max_examples=3,
+                 max_children=MAX_CHILDREN):
+        if depth < 3:
+            raise ValueError("depth 至少要 3")
+        if not 0 < similarity <= 1:
+            raise ValueError("similarity 必須介於 0 (不含) 與 1 之間")
+        self.depth = depth
+        self.similarity = similarity
+        self.max_examples = max_examples
+        self.max_children = max_children
+        self.root = {}
+        self.clusters = []
+
+    def _leaf(self, tokens):
+        node = self.root.setdefault(len(tokens), {})
+        for token in tokens[:self.depth - 2]:
+            key = _key(token)
+            if key not in node:
+                # 子節點太多時其餘的 token 一律走萬用字元分支
+                key = key if len(node) < self.max_children else WILDCARD
+            node = node.setdefault(key, {})
+        return node.setdefault(None, [])
+
+    def add(self, message, **info):
+        """加入一筆訊息 (info: timestamp / rule / agent)，回傳所屬的 Cluster"""
+        tokens = tokenize(message)
+        leaf = self._leaf(tokens)
+        best, score = None, -1.0
+        for cluster in leaf:
+            candidate = cluster.similarity(tokens)
+            if candidate > score:
+                best, score = cluster, candidate
+        if best is not None and score >= self.similarity:
+            best.merge(tokens)
+        else:
+            best = Cluster(tokens, self.max_examples)
+            leaf.append(best)
+            self.clusters.append(best)
+        best.record(message, **info)
+        return best
+
+    def top(self, limit):
+        return [c.summary() for c in sorted(self.clusters, key=lambda c: c.count, reverse=True)[:limit]]
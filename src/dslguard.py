@@ -0,0 +1,91 @@
+"""search_alerts_dsl 的防護: 檢查使用者直接提供的 OpenSearch 查詢本文。
+
+熟悉 DSL 的分析師可以直接下查詢，但本文仍要經過和其他工具相同的租戶範圍與全域篩選，且不能拖垮 Indexer:
+- 頂層只接受搜尋相關的欄位 (query、aggs、sort、size、from、_source 等)
+- size / from、每個聚合的 size 有上限；timeout 不能超過伺服器的查詢逾時
+- 拒絕會跳出查詢範圍或讀取其他索引的寫法: global 聚合 (忽略 query，會繞過租戶範圍)、
+  terms lookup、more_like_this / percolate 引用的文件、indexed_shape
+- 拒絕 script (script query / script_score / scripted_metric / script 排序或欄位)，避免昂貴或任意的 painless 執行
+"""
+
+from alert_utils import parse_duration
+
+ALLOWED_KEYS = {"query", "aggs", "aggregations", "sort", "size", "from", "_source", "track_total_hits",
+                "search_after", "collapse", "post_filter", "highlight", "timeout", "terminate_after", "fields",
+                "docvalue_fields", "min_score", "seq_no_primary_term", "version"}
+# 出現在任何位置都拒絕的鍵 -> 原因
+FORBIDDEN = {
+    "script": "不允許 script",
+    "script_score": "不允許 script",
+    "scripted_metric": "不允許 script",
+    "script_fields": "不允許 script",
+    "runtime_mappings": "不允許 script",
+    "global": "global 聚合會忽略 query (繞過租戶範圍)",
+    "percolate": "percolate 會讀取其他文件",
+    "more_like_this": "more_like_this 可引用其他索引的文件",
+    "indexed_shape": "indexed_shape 會讀取其他索引",
+}
+MAX_FROM = 10000
+
+
+def _walk(node, path, problems):
+    if isinstance(node, list):
+        for i, item in enumerate(node):
+            _walk(item, f"{path}[{i}]", problems)
+        return
+    if not isinstance(node, dict):
+        return
+    for key, value in node.items():
+        where = f"{path}.{key}" if path else key
+        if key in FORBIDDEN:
+            problems.append(f"{where}: {FORBIDDEN[key]}")
+            continue
+        if key == "terms" and isinstance(value, dict) and any(
+                isinstance(v, dict) and "index" in v for v in value.values()):
+            problems.append(f"{where}: 不允許 terms lookup (會讀取其他索引)")
+            continue
+        _walk(value, where, problems)
+
+
+def _aggregation_sizes(aggs, path, max_buckets, problems):
+    for name, agg in (aggs or {}).items():
+        if not isinstance(agg, dict):
+            problems.append(f"{path}.{name}: 聚合必須是物件")
+            continue
+        for kind, spec in agg.items():
+            if kind in ("aggs", "aggregations"):
+                _aggregation_sizes(spec, f"{path}.{name}.{kind}", max_buckets, problems)
+            elif isinstance(spec, dict):
+                for size_key in ("size", "shard_size"):
+                    size = spec.get(size_key)
+                    if isinstance(size, int) and size > max_buckets:
+                        problems.append(f"{path}.{name}.{kind}.{size_key}={size} 超過上限 {max_buckets}")
+
+
+def check(body, max_size, max_buckets, max_timeout):
+    """檢查查詢本文，回傳整理後的本文 (補上 size / timeout)；不符合時拋出 ValueError 並列出所有問題"""
+    if not isinstance(body, dict):
+        raise ValueError("查詢本文必須是 JSON 物件")
+    problems = [f"{key}: 不支援的頂層欄位 (可用: {', '.join(sorted(ALLOWED_KEYS))})"
+                for key in body if key not in ALLOWED_KEYS]
+    _walk(body, "", problems)
+    size, offset = body.get("size", 10), body.get("from", 0)
+    if not isinstance(size, int) or not 0 <= size <= max_size:
+        problems.append(f"size 必須介於 0 到 {max_size}")
+    if not isinstance(offset, int) or offset < 0 or offset + (size if isinstance(size, int) else 0) > MAX_FROM:
+        problems.append(f"from + size 不能超過 {MAX_FROM}，深層分頁請改用 search_after")
+    for key in ("aggs", "aggregations"):
+        if key in body:
+            _aggregation_sizes(body[key], key, max_buckets, problems)
+    timeout = body.get("timeout", max_timeout)
+    if not isinstance(timeout, str):
+        problems.append(f"timeout 必須是時間長度字串 (例如 30s)，不能超過 {max_timeout}")
+    else:
+        try:
+            if parse_duration(timeout) > parse_duration(max_timeout):
+                problems.append(f"timeout 不能超過 {max_timeout}")
+        except ValueError as e:
+            problems.append(f"timeout: {e}")
+    if problems:
+        raise ValueError("查詢本文不符合限制: " + "; ".join(problems))
+    return {**body, "size": size, "timeout": timeout}
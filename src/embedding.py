@@ -0,0 +1,47 @@
+"""把 MCP Server 嵌入其他 Python 程式 (例如更大的 agent runtime)，在同一個 process 內執行。
+
+transport 只需要提供 connect(): 一個 async context manager，產出 MCP SDK 的
+(read_stream, write_stream) (SessionMessage 串流)。內建兩種:
+    StdioTransport()              與 python src/main.py 相同的 stdio
+    StreamTransport(read, write)  呼叫端自行建立的 anyio 串流，例如 memory_transport()
+用法:
+    import main
+    transport, (read, write) = memory_transport()
+    await main.serve(transport)   # 另一端以 mcp.ClientSession(read, write) 連線
+"""
+
+import contextlib
+
+import anyio
+from mcp.server.stdio import stdio_server
+
+
+class StdioTransport:
+    def connect(self):
+        return stdio_server()
+
+
+class StreamTransport:
+    def __init__(self, read_stream, write_stream):
+        self.read_stream = read_stream
+        self.write_stream = write_stream
+
+    @contextlib.asynccontextmanager
+    async def connect(self):
+        async with self.read_stream, self.write_stream:
+            yield self.read_stream, self.write_stream
+
+
+def memory_transport(buffer_size=16):
+    """建立一對記憶體串流: (server 端 transport, client 端 (read_stream, write_stream))"""
+    client_send, server_receive = anyio.create_memory_object_stream(buffer_size)
+    server_send, client_receive = anyio.create_memory_object_stream(buffer_size)
+    return StreamTransport(server_receive, server_send), (client_receive, client_send)
+
+
+async def serve_transport(server, transport):
+    """在 transport 上執行 FastMCP server 直到對方關閉連線 (含 server 的 lifespan)"""
+    async with transport.connect() as (read_stream, write_stream):
+        async with server._lifespan_manager():
+            low = server._mcp_server
+            await low.run(read_stream, write_stream, low.create_initialization_options())
@@ -0,0 +1,152 @@
+"""伺服器端狀態的靜態加密 (encryption at rest)。
+
+案件、註記與查詢快取可能包含調查細節，部署在共用主機時不應以明文寫入磁碟。
+設定金鑰後，EncryptedStore 會以 AES-256-GCM 加密每一筆資料的內容再交給底層儲存後端
+(sqlite / dbm / postgresql 皆適用)；(namespace, key) 作為附加驗證資料，資料被搬到別的鍵時無法解密。
+namespace 與 key 本身不加密，不要把敏感內容放在 key 裡。
+
+金鑰 (32 bytes，base64 編碼) 的來源，依序取第一個有設定的:
+- WAZUH_MCP_STORE_KEY          直接設定
+- WAZUH_MCP_STORE_KEY_FILE     檔案內容 (例如 Kubernetes / Docker secret)
+- WAZUH_MCP_STORE_KEY_COMMAND  執行指令取得 (例如向 KMS / Vault 解密資料金鑰)，標準輸出即為金鑰
+更換金鑰時把舊金鑰放在 WAZUH_MCP_STORE_OLD_KEYS (逗號分隔)，啟動時會以新金鑰重新加密。
+"""
+
+import base64
+import hashlib
+import json
+import os
+import shlex
+import subprocess
+
+from cryptography.exceptions import InvalidTag
+from cryptography.hazmat.primitives.ciphers.aead import AESGCM
+
+from storage import StateStore
+
+ENVELOPE = "_encrypted"
+KEY_COMMAND_TIMEOUT = 30
+
+
+def generate_key():
+    return base64.b64encode(AESGCM.generate_key(bit_length=256)).decode("ascii")
+
+
+def decode_key(value, source="金鑰"):
+    try:
+        key = base64.b64decode(value.strip(), validate=True)
+    except ValueError:
+        raise ValueError(f"{source} 不是有效的 base64") from None
+    if len(key) != 32:
+        raise ValueError(f"{source} 必須是 32 bytes (AES-256)，目前為 {len(key)} bytes；"
+                         f"可用 python src/encryption.py 產生新金鑰")
+    return key
+
+
+def load_keys(env=os.environ):
+    """依設定取得 (目前金鑰, [舊金鑰])；沒有設定任何金鑰時回傳 (None, [])"""
+    current = None
+    if env.get("WAZUH_MCP_STORE_KEY"):
+        current = decode_key(env["WAZUH_MCP_STORE_KEY"], "WAZUH_MCP_STORE_KEY")
+    elif env.get("WAZUH_MCP_STORE_KEY_FILE"):
+        with open(env["WAZUH_MCP_STORE_KEY_FILE"], encoding="utf-8") as f:
+            current = decode_key(f.read(), "WAZUH_MCP_STORE_KEY_FILE")
+    elif env.get("WAZUH_MCP_STORE_KEY_COMMAND"):
+        try:
+            output = subprocess.run(shlex.split(env["WAZUH_MCP_STORE_KEY_COMMAND"]), capture_output=True,
+                                    text=True, check=True, timeout=KEY_COMMAND_TIMEOUT).stdout
+        except (OSError, subprocess.SubprocessError) as e:
+            raise ValueError(f"WAZUH_MCP_STORE_KEY_COMMAND 執行失敗: {e}") from None
+        current = decode_key(output, "WAZUH_MCP_STORE_KEY_COMMAND 的輸出")
+    old = [decode_key(k, "WAZUH_MCP_STORE_OLD_KEYS") for k in env.get("WAZUH_MCP_STORE_OLD_KEYS", "").split(",")
+           if k.strip()]
+    if old and current is None:
+        raise ValueError("設定了 WAZUH_MCP_STORE_OLD_KEYS 但沒有設定新的金鑰")
+    return current, old
+
+
+def key_id(key):
+    return hashlib.sha256(key).hexdigest()[:12]
+
+
+def is_encrypted(value):
+    return isinstance(value, dict) and ENVELOPE in value
+
+
+class EncryptedStore(StateStore):
+    """包裝任一儲存後端，寫入時加密、讀取時解密；尚未加密的舊資料照常讀取，由 reencrypt() 轉換"""
+
+    def __init__(self, inner, key, old_keys=()):
+        self.inner = inner
+        self.kid = key_id(key)
+        self.ciphers = {key_id(k): AESGCM(k) for k in (key, *old_keys)}
+
+    @staticmethod
+    def _aad(namespace, key):
+        return f"{namespace}\x00{key}".encode("utf-8")
+
+    def _seal(self, namespace, key, value):
+        nonce = os.urandom(12)
+        plaintext = json.dumps(value, ensure_ascii=False).encode("utf-8")
+        ciphertext = self.ciphers[self.kid].encrypt(nonce, plaintext, self._aad(namespace, key))
+        return {ENVELOPE: 1, "kid": self.kid,
+                "nonce": base64.b64encode(nonce).decode("ascii"),
+                "data": base64.b64encode(ciphertext).decode("ascii")}
+
+    def _open(self, namespace, key, value):
+        if not is_encrypted(value):
+            return value
+        cipher = self.ciphers.get(value.get("kid"))
+        if cipher is None:
+            raise ValueError(f"狀態資料 {namespace}/{key} 以未知的金鑰 ({value.get('kid')}) 加密，"
+                             f"請確認 WAZUH_MCP_STORE_KEY / WAZUH_MCP_STORE_OLD_KEYS")
+        try:
+            plaintext = cipher.decrypt(base64.b64decode(value["nonce"]), base64.b64decode(value["data"]),
+                                       self._aad(namespace, key))
+        except InvalidTag:
+            raise ValueError(f"狀態資料 {namespace}/{key} 驗證失敗 (資料損毀或被竄改)") from None
+        return json.loads(plaintext)
+
+    def get(self, namespace, key):
+        value = self.inner.get(namespace, key)
+        return None if value is None else self._open(namespace, key, value)
+
+    def put(self, namespace, key, value):
+        self.inner.put(namespace, key, self._seal(namespace, key, value))
+
+    def delete(self, namespace, key):
+        return self.inner.delete(namespace, key)
+
+    def list(self, namespace):
+        return [(key, self._open(namespace, key, value)) for key, value in self.inner.list(namespace)]
+
+    def namespaces(self):
+        return self.inner.namespaces()
+
+    def close(self):
+        self.inner.close()
+
+    def reencrypt(self):
+        """把明文資料與舊金鑰加密的資料改以目前金鑰加密，回傳轉換筆數"""
+        converted = 0
+        for namespace in self.inner.namespaces():
+            for key, value in self.inner.list(namespace):
+                if is_encrypted(value) and value.get("kid") == self.kid:
+                    continue
+                self.put(namespace, key, self._open(namespace, key, value))
+                converted += 1
+        return converted
+
+
+def find_encrypted(store):
+    """未設定金鑰時檢查既有資料是否已加密 (每個 namespace 抽查)，回傳已加密的 namespace"""
+    found = []
+    for namespace in store.namespaces():
+        entries = store.list(namespace)
+        if entries and is_encrypted(entries[0][1]):
+            found.append(namespace)
+    return found
+
+
+if __name__ == "__main__":
+    print(generate_key())
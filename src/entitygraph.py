@@ -0,0 +1,176 @@
+"""調查範圍內實體關係圖 (link analysis)，輸出 JSON Graph Format 或 Graphviz DOT。
+
+從告警抽出實體 (主機、使用者、IP、雜湊、程序) 作為節點，
+同一筆告警中的實體以三種關係相連:
+- connected-to: 來源 IP -> 目的 IP；沒有目的 IP 時為來源 IP -> 主機 (例如 SSH 登入嘗試)
+- executed: 使用者 (沒有使用者時為主機) -> 程序 / 程序雜湊
+- observed-with: 同一筆告警中其他沒有更具體關係的實體 (無方向)
+相同的關係合併成一條邊，附上次數、首次 / 最後出現時間與觸發的規則。
+"""
+
+import re
+from itertools import combinations
+
+from alert_utils import get_field
+
+# 執行動作的帳號在前；目標帳號 (sudo 切換成的身分、被登入的帳號) 不算是執行者
+ACTOR_USER_FIELDS = ["data.srcuser", "data.win.eventdata.subjectUserName", "data.win.eventdata.user", "data.audit.acct"]
+TARGET_USER_FIELDS = ["data.dstuser", "data.win.eventdata.targetUserName"]
+SOURCE_IP_FIELDS = ["data.srcip", "data.win.eventdata.ipAddress", "data.win.eventdata.sourceAddress"]
+DEST_IP_FIELDS = ["data.dstip", "data.dest_ip", "data.win.eventdata.destAddress"]
+FILE_HASH_FIELDS = ["syscheck.sha256_after", "syscheck.md5_after"]
+PROCESS_FIELDS = ["data.win.eventdata.image", "data.audit.exe", "data.command"]
+PROCESS_HASH_FIELD = "data.win.eventdata.hashes"
+SOURCE_FIELDS = [
+    "timestamp", "agent.id", "agent.name", "rule.id", "rule.level", *ACTOR_USER_FIELDS, *TARGET_USER_FIELDS,
+    *SOURCE_IP_FIELDS, *DEST_IP_FIELDS, *FILE_HASH_FIELDS, *PROCESS_FIELDS, PROCESS_HASH_FIELD,
+]
+
+KINDS = ("agent", "user", "ip", "hash", "process")
+RULES_PER_EDGE = 5
+# Sysmon 的 Hashes 欄位: "SHA256=...,MD5=...,IMPHASH=..."，只取最可靠的一種
+_SYSMON_HASH = re.compile(r"(SHA256|SHA1|MD5)=([0-9A-Fa-f]+)")
+_HASH_PREFERENCE = ("SHA256", "SHA1", "MD5")
+DOT_SHAPES = {"agent": "box3d", "user": "ellipse", "ip": "diamond", "hash": "note", "process": "component"}
+
+
+def _values(alert, fields):
+    values = []
+    for field in fields:
+        value = get_field(alert, field)
+        if value not in (None, "") and str(value) not in values:
+            values.append(str(value))
+    return values
+
+
+def process_hash(value):
+    found = dict((algo.upper(), digest.lower()) for algo, digest in _SYSMON_HASH.findall(value or ""))
+    for algo in _HASH_PREFERENCE:
+        if algo in found:
+            return found[algo]
+    return None
+
+
+def alert_entities(alert):
+    """單筆告警 -> (實體 [(種類, 值)], 有方向的關係 [(來源, 目的, 關係)])"""
+    agent = get_field(alert, "agent.name") or get_field(alert, "agent.id")
+    agents = [("agent", str(agent))] if agent else []
+    actors = [("user", v) for v in _values(alert, ACTOR_USER_FIELDS)]
+    users = actors + [("user", v) for v in _values(alert, TARGET_USER_FIELDS) if ("user", v) not in actors]
+    sources = [("ip", v) for v in _values(alert, SOURCE_IP_FIELDS)]
+    dests = [("ip", v) for v in _values(alert, DEST_IP_FIELDS) if ("ip", v) not in sources]
+    processes = [("process", v) for v in _values(alert, PROCESS_FIELDS)]
+    hashes = [("hash", v.lower()) for v in _values(alert, FILE_HASH_FIELDS)]
+    executed_hash = process_hash(get_field(alert, PROCESS_HASH_FIELD))
+    if executed_hash:
+        processes.append(("hash", executed_hash))
+
+    relations = []
+    for src in sources:
+        for dst in dests or agents:
+            relations.append((src, dst, "connected-to"))
+    for actor in actors or users or agents:
+        for target in processes:
+            relations.append((actor, target, "executed"))
+    entities = list(dict.fromkeys(agents + users + sources + dests + processes + hashes))
+    return entities, relations
+
+
+def _node_id(entity):
+    return f"{entity[0]}:{entity[1]}"
+
+
+def _touch(item, alert):
+    timestamp = get_field(alert, "timestamp")
+    item["count"] += 1
+    if timestamp:
+        item["first_seen"] = min(item["first_seen"] or timestamp, timestamp)
+        item["last_seen"] = max(item["last_seen"] or timestamp, timestamp)
+
+
+def build_graph(alerts):
+    """告警 -> (nodes {id: 節點}, edges {(來源, 目的, 關係): 邊})"""
+    nodes, edges = {}, {}
+    for alert in alerts:
+        entities, relations = alert_entities(alert)
+        if not entities:
+            continue
+        level = int(get_field(alert, "rule.level") or 0)
+        rule = get_field(alert, "rule.id")
+        for entity in entities:
+            node = nodes.setdefault(_node_id(entity), {
+                "kind": entity[0], "label": entity[1], "count": 0,
+                "first_seen": None, "last_seen": None, "max_level": 0})
+            _touch(node, alert)
+            node["max_level"] = max(node["max_level"], level)
+        linked = set()
+        for src, dst, relation in relations:
+            linked.add(frozenset((src, dst)))
+            _add_edge(edges, (_node_id(src), _node_id(dst), relation), alert, rule)
+        for a, b in combinations(entities, 2):
+            if frozenset((a, b)) not in linked:
+                # 無方向的關係以排序後的節點順序合併，A-B 與 B-A 是同一條邊
+                source, target = sorted((_node_id(a), _node_id(b)))
+                _add_edge(edges, (source, target, "observed-with"), alert, rule)
+    return nodes, edges
+
+
+def _add_edge(edges, key, alert, rule):
+    edge = edges.setdefault(key, {"count": 0, "first_seen": None, "last_seen": None, "rules": []})
+    _touch(edge, alert)
+    if rule and rule not in edge["rules"] and len(edge["rules"]) < RULES_PER_EDGE:
+        edge["rules"].append(rule)
+
+
+def prune(nodes, edges, max_nodes, kinds=None):
+    """只留下指定種類中出現次數最多的 max_nodes 個節點 (主機優先)，以及兩端都留下的邊"""
+    candidates = [(nid, node) for nid, node in nodes.items() if kinds is None or node["kind"] in kinds]
+    candidates.sort(key=lambda item: (item[1]["kind"] != "agent", -item[1]["count"], item[0]))
+    kept = dict(candidates[:max_nodes])
+    kept_edges = {key: edge for key, edge in edges.items() if key[0] in kept and key[1] in kept}
+    return kept, kept_edges
+
+
+def to_json_graph(nodes, edges, label, metadata):
+    """JSON Graph Format (https://jsongraphformat.info/) v2"""
+    return {"graph": {
+        "directed": True,
+        "type": "wazuh-entity-relationships",
+        "label": label,
+        "metadata": metadata,
+        "nodes": {nid: {"label": node["label"], "metadata": {k: v for k, v in node.items() if k != "label"}}
+                  for nid, node in nodes.items()},
+        "edges": [{"source": source, "target": target, "relation": relation,
+                   "directed": relation != "observed-with", "metadata": edge}
+                  for (source, target, relation), edge in sorted(edges.items(), key=lambda e: -e[1]["count"])],
+    }}
+
+
+def _dot_quote(text):
+    return '"' + str(text).replace("\\", "\\\\").replace('"', '\\"').replace("\n", "\\n") + '"'
+
+
+def to_dot(nodes, edges, label):
+    """Graphviz DOT；節點形狀區分種類，observed-with 畫成無箭頭的虛線"""
+    lines = [f"digraph {_dot_quote('wazuh_entities')} {{",
+             f"  label={_dot_quote(label)};",
+             "  rankdir=LR;",
+             "  node [fontsize=10];"]
+    for nid, node in nodes.items():
+        title = f"{node['label']}\n{node['kind']} ({node['count']})"
+        lines.append(f"  {_dot_quote(nid)} [label={_dot_quote(title)}, shape={DOT_SHAPES[node['kind']]}];")
+    for (source, target, relation), edge in edges.items():
+        title = f"{relation} ({edge['count']})"
+        style = ", dir=none, style=dashed" if relation == "observed-with" else ""
+        lines.append(f"  {_dot_quote(source)} -> {_dot_quote(target)} [label={_dot_quote(title)}{style}];")
+    lines.append("}")
+    return "\n".join(lines)
+
+
+def summarize(nodes, edges):
+    kinds = {kind: sum(1 for node in nodes.values() if node["kind"] == kind) for kind in KINDS}
+    relations = {}
+    for _, _, relation in edges:
+        relations[relation] = relations.get(relation, 0) + 1
+    return {"nodes": len(nodes), "edges": len(edges), "node_kinds": kinds, "relations": relations}
+
@@ -0,0 +1,84 @@
+"""主機環境標籤 (production / honeypot / lab …)。
+
+蜜罐與實驗室主機的告警量和正式環境完全不同，混在一起統計會扭曲結果。
+透過 MCP_AGENT_ENVIRONMENTS 以 agent group 或 agent id 指定環境，例如:
+    {"honeypot": {"groups": ["honeypot"]}, "lab": {"agents": ["012", "013"]}}
+沒有被指定的主機一律歸類為 DEFAULT_ENVIRONMENT (production)。
+"""
+
+import json
+import threading
+import time
+
+DEFAULT_ENVIRONMENT = "production"
+CACHE_TTL = 300
+
+
+def parse_environments(text):
+    envs = json.loads(text) if text else {}
+    if not isinstance(envs, dict):
+        raise ValueError("MCP_AGENT_ENVIRONMENTS 必須是 {環境名稱: {groups: [...], agents: [...]}} 格式的 JSON")
+    for name, spec in envs.items():
+        if name == DEFAULT_ENVIRONMENT:
+            raise ValueError(f"'{DEFAULT_ENVIRONMENT}' 是預設環境，不需要 (也不能) 另外指定")
+        if not isinstance(spec, dict):
+            raise ValueError(f"環境 '{name}' 的設定必須是物件")
+    return envs
+
+
+class EnvironmentMap:
+    def __init__(self, envs, fetch_group_agents):
+        self.envs = envs
+        self.fetch_group_agents = fetch_group_agents
+        self.lock = threading.Lock()
+        self.cached = None
+        self.cached_at = 0
+
+    @property
+    def enabled(self):
+        return bool(self.envs)
+
+    def agent_sets(self):
+        """{環境名稱: agent id 集合}，不含預設環境；group 成員查詢結果會快取"""
+        with self.lock:
+            if self.cached is not None and time.time() - self.cached_at < CACHE_TTL:
+                return self.cached
+        sets = {}
+        for name, spec in self.envs.items():
+            agents = set(spec.get("agents", []))
+            for group in spec.get("groups", []):
+                agents.update(self.fetch_group_agents(group))
+            sets[name] = agents
+        with self.lock:
+            self.cached, self.cached_at = sets, time.time()
+        return sets
+
+    def label(self, agent_id):
+        if not self.enabled:
+            return DEFAULT_ENVIRONMENT
+        for name, agents in self.agent_sets().items():
+            if agent_id in agents:
+                return name
+        return DEFAULT_ENVIRONMENT
+
+    def annotate(self, alert):
+        """在告警的 agent 區塊加上 environment 標籤"""
+        agent = alert.get("agent")
+        if isinstance(agent, dict):
+            agent["environment"] = self.label(agent.get("id"))
+        return alert
+
+    def split_aggs(self, aggs):
+        """把聚合包在 filters aggregation 裡，讓每個環境各自統計"""
+        filters = {name: {"terms": {"agent.id": sorted(agents)}} if agents else {"match_none": {}}
+                   for name, agents in self.agent_sets().items()}
+        return {"by_environment": {
+            "filters": {"filters": filters, "other_bucket_key": DEFAULT_ENVIRONMENT},
+            "aggs": aggs
+        }}
+
+    @staticmethod
+    def environment_buckets(result):
+        """將 split_aggs 的結果拆回 {環境名稱: 該環境的 aggregations}"""
+        buckets = result.get("aggregations", {}).get("by_environment", {}).get("buckets", {})
+        return {name: {"doc_count": b.get("doc_count", 0), "aggregations": b} for name, b in buckets.items()}
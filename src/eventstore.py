@@ -0,0 +1,152 @@
+"""Streamable HTTP 的 SSE 事件重送 (Last-Event-ID)。
+
+沒有 event store 時，SSE 連線中斷 (網路切換、proxy 逾時) 期間送出的通知就此遺失，
+client 重新連上 GET stream 也拿不回進度通知、tail_live_alerts / 資源訂閱的更新。
+啟用後 (MCP_SSE_REPLAY_EVENTS > 0) 每個 SSE 事件都帶 id，client 以 Last-Event-ID 重新連線時，
+伺服器重送同一個 stream 在該事件之後的所有事件，再接著送新的事件。
+
+MCP SDK 的 session manager 讓所有 session 共用一個 event store，而每個 session 的 GET stream
+id 都相同 (_GET_stream)，不能只以 stream id 區分。因此事件以 (session, stream) 分開保存，
+ReplayableStreams 在 session 建立後換上綁定該 session 的 event store: 只能重送自己 session 的事件，
+拿到其他 session 的事件 id 也不會洩漏內容。
+每個 stream 保留最近 max_events 個事件，超過 ttl 沒有新事件的 stream 整個丟棄；session 結束 (DELETE) 時一併清除。
+事件只存在記憶體，重啟後無法重送 (session 本身的接續見 resumption.py)。
+"""
+
+import logging
+import time
+import uuid
+from collections import deque
+
+from mcp.server.streamable_http import EventMessage, EventStore
+
+from resumption import SESSION_HEADER, find_session_manager
+
+logger = logging.getLogger("wazuh_mcp")
+
+# 過期 stream 最多每分鐘清一次
+SWEEP_INTERVAL = 60
+
+
+class ReplayBuffer:
+    """所有 session 的 SSE 事件；view(session_id) 取得綁定某個 session 的 event store"""
+
+    def __init__(self, max_events, ttl):
+        self.max_events = max_events
+        self.ttl = ttl
+        self.streams = {}  # (session, stream) -> deque[(事件 id, 訊息, 時間)]
+        self.index = {}  # 事件 id -> (session, stream)
+        self.swept = time.monotonic()
+
+    def view(self, session_id):
+        return SessionEventStore(self, session_id)
+
+    def add(self, session_id, stream_id, message):
+        now = time.monotonic()
+        if now - self.swept > SWEEP_INTERVAL:
+            self.sweep(now)
+        event_id = uuid.uuid4().hex
+        key = (session_id, stream_id)
+        events = self.streams.setdefault(key, deque())
+        events.append((event_id, message, now))
+        self.index[event_id] = key
+        while len(events) > self.max_events:
+            self.index.pop(events.popleft()[0], None)
+        return event_id
+
+    def after(self, session_id, last_event_id):
+        """last_event_id 之後同一個 stream 的事件 -> (stream id, [(事件 id, 訊息)])；不認得的 id 回傳 None"""
+        key = self.index.get(last_event_id)
+        if key is None or key[0] != session_id:
+            return None
+        events = list(self.streams.get(key, ()))
+        position = next(i for i, event in enumerate(events) if event[0] == last_event_id)
+        return key[1], [(event_id, message) for event_id, message, _ in events[position + 1:]]
+
+    def forget(self, session_id):
+        for key in [key for key in self.streams if key[0] == session_id]:
+            self._drop(key)
+
+    def sweep(self, now=None):
+        """丟棄超過 ttl 沒有新事件的 stream，回傳丟棄的數量"""
+        now = now or time.monotonic()
+        self.swept = now
+        expired = [key for key, events in self.streams.items() if now - events[-1][2] > self.ttl]
+        for key in expired:
+            self._drop(key)
+        return len(expired)
+
+    def _drop(self, key):
+        for event_id, _, _ in self.streams.pop(key, ()):
+            self.index.pop(event_id, None)
+
+    def stats(self):
+        return {"sessions": len({session for session, _ in self.streams}), "streams": len(self.streams),
+                "events": len(self.index)}
+
+
+class SessionEventStore(EventStore):
+    """MCP SDK 的 EventStore 介面；session_id 為 None 時 (session 建立前) 只配發 id、不保存也不重送"""
+
+    def __init__(self, buffer, session_id):
+        self.buffer = buffer
+        self.session_id = session_id
+
+    async def store_event(self, stream_id, message):
+        if self.session_id is None:
+            return uuid.uuid4().hex
+        return self.buffer.add(self.session_id, stream_id, message)
+
+    async def replay_events_after(self, last_event_id, send_callback):
+        found = self.buffer.after(self.session_id, last_event_id) if self.session_id else None
+        if found is None:
+            logger.warning("無法重送 Last-Event-ID %s 之後的事件 (已過期或不屬於這個 session)", last_event_id)
+            return None
+        stream_id, events = found
+        for event_id, message in events:
+            # message 為 None 的是 SDK 的 priming 事件，只用來讓 client 取得 id
+            if message is not None:
+                await send_callback(EventMessage(message, event_id))
+        logger.info("已重送 %d 個 SSE 事件 (stream %s)", len(events), stream_id)
+        return stream_id
+
+
+class ReplayableStreams:
+    """starlette middleware (放在 FastMCP 的 HTTP app 內): 設定 session manager 的 event store，
+    並讓每個 session 的 transport 使用綁定該 session 的 event store"""
+
+    def __init__(self, app, events):
+        self.app = app
+        self.events = events
+        self.manager = None
+
+    def bind(self, session_id):
+        transport = self.manager._server_instances.get(session_id)
+        store = getattr(transport, "_event_store", None)
+        if isinstance(store, SessionEventStore) and store.session_id != session_id:
+            transport._event_store = self.events.view(session_id)
+
+    async def __call__(self, scope, receive, send):
+        if scope["type"] != "http":
+            return await self.app(scope, receive, send)
+        if self.manager is None:
+            self.manager = find_session_manager(scope["app"])
+            if self.manager.event_store is None:
+                # 必須在第一個 session 建立前設定；session manager 只在建立 transport 時讀取
+                self.manager.event_store = self.events.view(None)
+        session_id = next((value.decode("latin-1") for name, value in scope["headers"]
+                           if name.lower() == SESSION_HEADER.encode()), None)
+        if session_id:
+            self.bind(session_id)
+
+        async def bind_new_session(message):
+            if message["type"] == "http.response.start" and not session_id:
+                created = next((value.decode("latin-1") for name, value in message.get("headers", [])
+                                if name.lower() == SESSION_HEADER.encode()), None)
+                if created:
+                    self.bind(created)
+            await send(message)
+
+        await self.app(scope, receive, bind_new_session)
+        if session_id and scope["method"] == "DELETE":
+            self.events.forget(session_id)
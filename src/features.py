@@ -0,0 +1,28 @@
+"""功能模組開關: 精簡部署 (edge / 嵌入式環境) 時只註冊核心查詢工具。
+
+MCP_FEATURES 以逗號列出要啟用的功能 (預設 all)；core 一律啟用，不必列出:
+    MCP_FEATURES=core                  只有核心查詢工具
+    MCP_FEATURES=hunting,reporting     核心 + 獵捕工具 + Arrow 輸出
+停用的功能在啟動時就不會註冊對應的工具，工具清單與 prompt 的 token 也跟著縮小。
+"""
+
+FEATURES = {
+    "core": "agent 狀態、告警搜尋、序列獵捕、規則群組、資料一致性檢查",
+    "state": "嵌入式狀態資料庫 (用量統計 / 配額、查詢快取、首次出現資料庫、獵捕假說)；停用時改用記憶體，不寫入磁碟",
+    "enrichment": "搜尋結果的下一步建議與首次出現標註",
+    "reporting": "Arrow IPC 輸出 (需要 pyarrow)、告警趨勢、環境簡報",
+    "hunting": "專題獵捕工具 (TLS 指紋、DNS、特權指令、持久化、登錄檔、自動執行項目、抽取式媒體、Office 365 / GitHub 稽核、osquery 結果、定期指令輸出、防火牆、日誌模板分群、實體關係圖、帳密外洩掃描、數值欄位統計、跨資料來源 join、原始 DSL 查詢、即時告警串流)",
+    "fleet": "agent group 清單與共用設定 (agent.conf) 變更的影響模擬與分批上線",
+    "detection_engineering": "測試事件注入、Atomic Red Team 對照與驗證、規則審查、從發現草擬規則、規則噪音模擬、規則庫差異比較、manager 設定檔取回",
+}
+
+
+def parse_features(value):
+    """解析 MCP_FEATURES，回傳啟用的功能集合 (一定包含 core)"""
+    if not value or not value.strip() or value.strip().lower() == "all":
+        return set(FEATURES)
+    names = {name.strip().lower() for name in value.split(",") if name.strip()}
+    unknown = names - set(FEATURES)
+    if unknown:
+        raise ValueError(f"MCP_FEATURES 有未知的功能: {', '.join(sorted(unknown))}，可用: {', '.join(FEATURES)}")
+    return names | {"core"}
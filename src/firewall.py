@@ -0,0 +1,105 @@
+"""防火牆 / NAT 日誌 (pfSense、iptables、Windows Firewall) 的摘要與突增偵測。
+
+三種來源的欄位各不相同，這裡把來源、目的、連接埠與政策統一成四種維度，
+對每個維度分別聚合後再合併，讓網路分流 (triage) 時不必手寫 DSL。
+"""
+
+from statistics import median
+
+FIREWALL_QUERY = {"bool": {"should": [
+    {"terms": {"rule.groups": ["firewall", "pfsense", "iptables", "firewall_drop"]}},
+    # Windows Filtering Platform: 5152 封包被擋、5156 允許連線、5157 擋下連線
+    {"terms": {"data.win.system.eventID": ["5152", "5156", "5157"]}},
+], "minimum_should_match": 1}}
+
+ACTIONS = {
+    "deny": {"bool": {"should": [
+        {"terms": {"data.action": ["block", "drop", "deny", "reject", "BLOCK", "DROP", "DENY", "REJECT"]}},
+        {"terms": {"data.win.system.eventID": ["5152", "5157"]}},
+    ], "minimum_should_match": 1}},
+    "allow": {"bool": {"should": [
+        {"terms": {"data.action": ["pass", "allow", "accept", "PASS", "ALLOW", "ACCEPT"]}},
+        {"term": {"data.win.system.eventID": "5156"}},
+    ], "minimum_should_match": 1}},
+}
+
+DIMENSIONS = {
+    "src": ["data.srcip", "data.win.eventdata.sourceAddress"],
+    "dst": ["data.dstip", "data.win.eventdata.destAddress"],
+    "port": ["data.dstport", "data.win.eventdata.destPort"],
+    "policy": ["data.policy", "data.win.eventdata.filterRTID", "rule.description"],
+}
+
+SPIKE_MIN_EVENTS = 10
+
+
+def firewall_query(action=None):
+    if action is None:
+        return FIREWALL_QUERY
+    if action not in ACTIONS:
+        raise ValueError(f"未知的動作 '{action}'，可用: {', '.join(ACTIONS)}")
+    return {"bool": {"filter": [FIREWALL_QUERY, ACTIONS[action]]}}
+
+
+def summary_aggregations(dimension, limit, interval, time_range):
+    if dimension not in DIMENSIONS:
+        raise ValueError(f"未知的分組維度 '{dimension}'，可用: {', '.join(DIMENSIONS)}")
+    histogram = {"date_histogram": {
+        "field": "timestamp", "fixed_interval": interval, "min_doc_count": 0,
+        "extended_bounds": {"min": time_range, "max": "now"},
+    }}
+    aggs = {f"by__{i}": {"terms": {"field": field, "size": limit}, "aggs": {
+                "over_time": histogram,
+                "actions": {"filters": {"filters": ACTIONS}},
+                "agents": {"cardinality": {"field": "agent.id"}},
+            }}
+            for i, field in enumerate(DIMENSIONS[dimension])}
+    aggs["over_time"] = histogram
+    aggs["actions"] = {"filters": {"filters": ACTIONS}}
+    return aggs
+
+
+def find_spikes(buckets, factor):
+    """時間序列中超過中位數 factor 倍 (且至少 SPIKE_MIN_EVENTS 筆) 的時段"""
+    counts = [b.get("doc_count", 0) for b in buckets]
+    if len(counts) < 3:
+        return []
+    base = max(median(counts), 1)
+    return [
+        {"time": b.get("key_as_string"), "events": b["doc_count"], "baseline": base,
+         "ratio": round(b["doc_count"] / base, 1)}
+        for b in buckets
+        if b.get("doc_count", 0) >= SPIKE_MIN_EVENTS and b["doc_count"] >= base * factor
+    ]
+
+
+def summarize(aggregations, dimension, limit, spike_factor):
+    """合併同一維度在不同來源欄位的結果，每個值附上允許 / 拒絕數量與突增時段"""
+    rows = {}
+    for i, field in enumerate(DIMENSIONS[dimension]):
+        for b in aggregations.get(f"by__{i}", {}).get("buckets", []):
+            key = str(b["key"])
+            actions = b.get("actions", {}).get("buckets", {})
+            if key in rows:
+                # 同一個值出現在多個來源欄位時累加數量，突增時段沿用第一個來源欄位的結果
+                rows[key]["events"] += b["doc_count"]
+                rows[key]["deny"] += actions.get("deny", {}).get("doc_count", 0)
+                rows[key]["allow"] += actions.get("allow", {}).get("doc_count", 0)
+                continue
+            rows[key] = {
+                "value": key,
+                "field": field,
+                "events": b["doc_count"],
+                "deny": actions.get("deny", {}).get("doc_count", 0),
+                "allow": actions.get("allow", {}).get("doc_count", 0),
+                "agents": b.get("agents", {}).get("value", 0),
+                "spikes": find_spikes(b.get("over_time", {}).get("buckets", []), spike_factor),
+            }
+    ranked = sorted(rows.values(), key=lambda r: r["events"], reverse=True)[:limit]
+    actions = aggregations.get("actions", {}).get("buckets", {})
+    return {
+        "totals": {name: actions.get(name, {}).get("doc_count", 0) for name in ACTIONS},
+        "overall_spikes": find_spikes(aggregations.get("over_time", {}).get("buckets", []), spike_factor),
+        "top": ranked,
+        "spiking": [r["value"] for r in ranked if r["spikes"]],
+    }
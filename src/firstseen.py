@@ -0,0 +1,148 @@
+"""可觀察值 (雜湊、網域、IP、User-Agent) 的首次出現資料庫。
+
+「這個雜湊在整個環境裡從來沒出現過」是最有價值的獵捕訊號之一。
+伺服器會以聚合查詢增量掃描新進的告警，把每個值第一次 / 最後一次出現的時間
+存進狀態儲存 (first_seen namespace)，之後查詢結果可以直接標註「首次出現」。
+"""
+
+import threading
+from datetime import datetime, timezone
+
+from alert_utils import get_field, parse_timestamp
+
+FIRST_SEEN_NAMESPACE = "first_seen"
+META_KEY = "first_seen_refreshed_at"
+TERMS_SIZE = 10000
+
+OBSERVABLE_FIELDS = {
+    "hash": [
+        "syscheck.sha256_after", "syscheck.md5_after", "syscheck.sha1_after",
+        "data.win.eventdata.hashes", "data.virustotal.source.sha1",
+    ],
+    "domain": ["data.win.eventdata.queryName", "data.dns.question.name"],
+    "ip": ["data.srcip", "data.dstip", "data.win.eventdata.destinationIp"],
+    "user_agent": ["data.http.http_user_agent", "data.user_agent"],
+}
+
+
+def _iso(value):
+    """統一成 UTC、毫秒精度的 ISO 字串，確保可以直接用字串比較先後"""
+    ts = parse_timestamp(value) if isinstance(value, str) else value
+    if ts is None:
+        return None
+    if ts.tzinfo is None:
+        ts = ts.replace(tzinfo=timezone.utc)
+    return ts.astimezone(timezone.utc).isoformat(timespec="milliseconds")
+
+
+def split_values(kind, raw):
+    """Sysmon 的 hashes 欄位是 "SHA256=...,MD5=..." 的組合字串，要拆成個別雜湊"""
+    raw = str(raw)
+    if kind == "hash" and "=" in raw:
+        return [part.split("=", 1)[1].lower() for part in raw.split(",") if "=" in part]
+    return [raw.lower() if kind in ("hash", "domain") else raw]
+
+
+def extract_observables(alert):
+    """從單筆告警取出 [(類型, 值)]"""
+    found = []
+    for kind, fields in OBSERVABLE_FIELDS.items():
+        for field in fields:
+            value = get_field(alert, field)
+            if value not in (None, ""):
+                found.extend((kind, v) for v in split_values(kind, value))
+    return found
+
+
+class FirstSeenTracker:
+    def __init__(self, store, search, backfill, refresh_interval):
+        """search(body) -> (result, error)；backfill 為第一次建立資料庫時往回掃描的時間長度"""
+        self.store = store
+        self.search = search
+        self.backfill = backfill
+        self.refresh_interval = refresh_interval
+        self.lock = threading.Lock()
+
+    def _last_refresh(self):
+        meta = self.store.get("_meta", META_KEY)
+        return datetime.fromisoformat(meta["at"]) if meta else None
+
+    def refresh(self, force=False):
+        """增量掃描上次更新之後的告警；回傳新加入的可觀察值數量"""
+        with self.lock:
+            now = datetime.now(timezone.utc)
+            last = self._last_refresh()
+            if not force and last and now - last < self.refresh_interval:
+                return 0
+            since = last or now - self.backfill
+            aggs = {
+                field.replace(".", "__"): {
+                    "terms": {"field": field, "size": TERMS_SIZE},
+                    "aggs": {
+                        "first": {"min": {"field": "timestamp"}},
+                        "last": {"max": {"field": "timestamp"}}
+                    }
+                }
+                for fields in OBSERVABLE_FIELDS.values() for field in fields
+            }
+            body = {
+                "size": 0,
+                "query": {"range": {"timestamp": {"gte": since.isoformat(), "lt": now.isoformat()}}},
+                "aggs": aggs
+            }
+            result, error = self.search(body)
+            if error:
+                raise RuntimeError(error)
+
+            added = 0
+            for kind, fields in OBSERVABLE_FIELDS.items():
+                for field in fields:
+                    buckets = result.get("aggregations", {}).get(field.replace(".", "__"), {}).get("buckets", [])
+                    for b in buckets:
+                        first = _iso(b["first"].get("value_as_string"))
+                        last_seen = _iso(b["last"].get("value_as_string"))
+                        for value in split_values(kind, b["key"]):
+                            added += self._upsert(kind, value, field, first, last_seen, b["doc_count"])
+            self.store.put("_meta", META_KEY, {"at": _iso(now)})
+            return added
+
+    def _upsert(self, kind, value, field, first, last, count):
+        key = f"{kind}:{value}"
+        entry = self.store.get(FIRST_SEEN_NAMESPACE, key)
+        if entry is None:
+            self.store.put(FIRST_SEEN_NAMESPACE, key, {
+                "kind": kind, "value": value, "field": field,
+                "first_seen": first, "last_seen": last, "count": count
+            })
+            return 1
+        if first and (not entry["first_seen"] or first < entry["first_seen"]):
+            entry["first_seen"] = first
+        if last and (not entry["last_seen"] or last > entry["last_seen"]):
+            entry["last_seen"] = last
+        entry["count"] += count
+        self.store.put(FIRST_SEEN_NAMESPACE, key, entry)
+        return 0
+
+    def lookup(self, kind, value):
+        return self.store.get(FIRST_SEEN_NAMESPACE, f"{kind}:{split_values(kind, value)[0]}")
+
+    def new_since(self, since, kind=None):
+        """列出 since 之後才第一次出現的可觀察值"""
+        since = _iso(since)
+        rows = [entry for _, entry in self.store.list(FIRST_SEEN_NAMESPACE)
+                if entry.get("first_seen") and entry["first_seen"] >= since
+                and (kind is None or entry["kind"] == kind)]
+        return sorted(rows, key=lambda e: e["first_seen"], reverse=True)
+
+    def annotate(self, alert, window):
+        """告警中有在 window 內首次出現的值時，加上 _first_seen 標註"""
+        threshold = _iso(datetime.now(timezone.utc) - window)
+        flags = []
+        for kind, value in extract_observables(alert):
+            entry = self.lookup(kind, value)
+            if entry and entry.get("first_seen") and entry["first_seen"] >= threshold:
+                flags.append({"kind": kind, "value": value, "first_seen": entry["first_seen"]})
+        if flags:
+            alert["_first_seen"] = flags
+        return alert
+
@@ -0,0 +1,49 @@
+"""Agent group 成員查詢與快取，讓「所有網域控制站」這類以群組為單位的獵捕只需要一個參數。
+
+group 成員透過 Manager API 查詢後快取 GROUP_CACHE_TTL 秒，轉成 agent.id 的 terms 篩選；
+租戶範圍 (scoping)、主機環境 (environments) 也共用同一份快取。
+"""
+
+import threading
+import time
+
+GROUP_CACHE_TTL = 300
+
+
+class GroupMembership:
+    def __init__(self, fetch_group_agents, ttl=GROUP_CACHE_TTL):
+        """fetch_group_agents(group) -> agent id 清單 (查詢失敗時拋出例外)"""
+        self.fetch_group_agents = fetch_group_agents
+        self.ttl = ttl
+        self.cache = {}
+        self.lock = threading.Lock()
+
+    def agents(self, group):
+        now = time.time()
+        with self.lock:
+            cached = self.cache.get(group)
+            if cached and now - cached[0] < self.ttl:
+                return cached[1]
+        agents = set(self.fetch_group_agents(group))
+        with self.lock:
+            self.cache[group] = (now, agents)
+        return agents
+
+    def resolve(self, groups):
+        """多個 group 的 agent id 聯集，以及每個 group 的成員數；查詢失敗時拋出 ValueError"""
+        members, counts = set(), {}
+        for group in groups:
+            try:
+                agents = self.agents(group)
+            except RuntimeError as e:
+                raise ValueError(f"無法取得 agent group '{group}' 的成員: {e}") from e
+            members |= agents
+            counts[group] = len(agents)
+        return members, counts
+
+    def invalidate(self, group=None):
+        with self.lock:
+            if group is None:
+                self.cache.clear()
+            else:
+                self.cache.pop(group, None)
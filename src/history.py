@@ -0,0 +1,97 @@
+"""各 MCP session 最近的 JSON-RPC 往來紀錄，協助排查行為異常的 client。
+
+每個 session 保留最近 MCP_SESSION_HISTORY 筆 (request / notification 與回應、耗時)，
+最多追蹤 MAX_SESSIONS 個 session (最久沒活動的先淘汰)。內容可能包含工具參數與查詢結果，
+只透過需要管理權杖的 GET /admin/sessions/{session_id}/history 提供。
+"""
+
+import json
+import threading
+import time
+from collections import OrderedDict, deque
+from datetime import datetime, timezone
+
+from fastmcp.server.middleware import Middleware
+
+from diagnostics import _session_id
+
+MAX_SESSIONS = 50
+MAX_ENTRY_CHARS = 4000
+
+
+def jsonable(value):
+    if hasattr(value, "model_dump"):
+        return value.model_dump(mode="json", by_alias=True, exclude_none=True)
+    if hasattr(value, "content") and hasattr(value, "structured_content"):  # fastmcp ToolResult
+        return {"content": jsonable(value.content), "structuredContent": value.structured_content}
+    if isinstance(value, dict):
+        return {str(k): jsonable(v) for k, v in value.items()}
+    if isinstance(value, (list, tuple)):
+        return [jsonable(v) for v in value]
+    if value is None or isinstance(value, (str, int, float, bool)):
+        return value
+    return repr(value)
+
+
+def clip(value):
+    """過長的內容改存前 MAX_ENTRY_CHARS 個字元的 JSON 字串"""
+    text = json.dumps(jsonable(value), ensure_ascii=False, default=str)
+    if len(text) <= MAX_ENTRY_CHARS:
+        return json.loads(text)
+    return {"truncated": True, "chars": len(text), "preview": text[:MAX_ENTRY_CHARS]}
+
+
+class SessionHistory(Middleware):
+    def __init__(self, size):
+        self.size = size
+        self.lock = threading.Lock()
+        self.sessions = OrderedDict()
+
+    def _record(self, session_id, entry):
+        with self.lock:
+            history = self.sessions.pop(session_id, None) or deque(maxlen=self.size)
+            history.append(entry)
+            self.sessions[session_id] = history
+            while len(self.sessions) > MAX_SESSIONS:
+                self.sessions.popitem(last=False)
+
+    async def on_message(self, context, call_next):
+        if self.size <= 0:
+            return await call_next(context)
+        entry = {
+            "at": datetime.now(timezone.utc).isoformat(),
+            "type": context.type,
+            "method": context.method,
+            "params": clip(context.message),
+        }
+        started = time.monotonic()
+        try:
+            result = await call_next(context)
+        except Exception as e:
+            entry["error"] = f"{type(e).__name__}: {e}"[:MAX_ENTRY_CHARS]
+            raise
+        else:
+            if context.type == "request":
+                entry["result"] = clip(result)
+            return result
+        finally:
+            entry["duration_ms"] = round((time.monotonic() - started) * 1000)
+            self._record(_session_id(context), entry)
+
+    def forget(self, session_id):
+        with self.lock:
+            self.sessions.pop(session_id, None)
+
+    def sessions_summary(self):
+        with self.lock:
+            return [{"session": sid, "entries": len(h), "last_at": h[-1]["at"]}
+                    for sid, h in reversed(self.sessions.items())]
+
+    def history(self, session_id, limit=None):
+        """最近的紀錄 (舊到新)；不存在的 session 回傳 None"""
+        with self.lock:
+            entries = self.sessions.get(session_id)
+            if entries is None:
+                return None
+            entries = list(entries)
+        return entries[-limit:] if limit else entries
@@ -0,0 +1,49 @@
+"""對 Wazuh Manager API 與 Indexer 的 HTTP 設定: 路徑前綴、額外標頭與 User-Agent。
+
+部署在反向代理 / 企業閘道後方時常需要:
+- 路徑前綴 (WAZUH_API_BASE_PATH=/wazuh-api)，請求變成 https://host:port/wazuh-api/agents
+- 閘道要求的額外標頭 (WAZUH_API_HEADERS / WAZUH_INDEXER_HEADERS，JSON 物件)
+- 自訂 User-Agent (WAZUH_MCP_USER_AGENT)，讓閘道可以辨識或放行
+兩個後端共用同一個 requests.Session，額外標頭以依網址前綴掛載的 adapter 加上，
+不會送到另一個後端；工具本身指定的標頭 (例如 Authorization) 優先。
+"""
+
+import json
+
+from requests.adapters import HTTPAdapter
+
+
+def base_path(value):
+    """"/"、"" 或 None -> ""；其餘正規化為 "/prefix" (不含結尾斜線)"""
+    value = (value or "").strip().strip("/")
+    return f"/{value}" if value else ""
+
+
+def parse_headers(text, name):
+    headers = json.loads(text) if text else {}
+    if not isinstance(headers, dict) or not all(isinstance(v, str) for v in headers.values()):
+        raise ValueError(f"{name} 必須是 {{標頭名稱: 字串值}} 格式的 JSON")
+    return headers
+
+
+class HeaderAdapter(HTTPAdapter):
+    def __init__(self, headers):
+        super().__init__()
+        self.extra_headers = headers
+
+    def add_headers(self, request, **kwargs):
+        for name, value in self.extra_headers.items():
+            if name not in request.headers:
+                request.headers[name] = value
+
+
+def configure(session, user_agent, targets):
+    """targets: [(後端網址, 額外標頭)]；兩個後端網址相同時標頭合併"""
+    if user_agent:
+        session.headers["User-Agent"] = user_agent
+    merged = {}
+    for url, headers in targets:
+        if headers:
+            merged.setdefault(url.rstrip("/") + "/", {}).update(headers)
+    for prefix, headers in merged.items():
+        session.mount(prefix, HeaderAdapter(headers))
@@ -0,0 +1,86 @@
+"""預先寫好的威脅獵捕 prompt (MCP prompts)。
+
+每個 prompt 在回傳前會先向 Wazuh 取得即時資料 (告警、agent、統計)，把資料和分析步驟一起交給模型，
+模型不必先花幾輪工具呼叫才知道要看什麼。取得資料失敗時仍會回傳 prompt，並註明缺少哪些資料。
+"""
+
+import json
+
+# 與身分驗證 / 橫向移動相關的規則群組
+AUTH_GROUPS = ["authentication_success", "authentication_failed", "authentication_failures",
+               "win_authentication_failed", "sshd", "pam"]
+SOURCE_IP_FIELDS = ["data.srcip", "data.win.eventdata.ipAddress"]
+USER_FIELDS = ["data.dstuser", "data.win.eventdata.targetUserName"]
+
+
+def alert_query(alert_id):
+    """以 Indexer 的 _id 或告警本身的 id 欄位查詢單筆告警"""
+    return {"bool": {"should": [{"ids": {"values": [alert_id]}}, {"term": {"id": alert_id}}],
+                     "minimum_should_match": 1}}
+
+
+def lateral_movement_query(agent):
+    return {"bool": {
+        "filter": [{"term": {"agent.name": agent}}],
+        "should": [{"terms": {"rule.groups": AUTH_GROUPS}},
+                   {"term": {"rule.mitre.tactic": "Lateral Movement"}}],
+        "minimum_should_match": 1,
+    }}
+
+
+def lateral_movement_aggregations(limit=10):
+    aggs = {f"src:{f}": {"terms": {"field": f, "size": limit}} for f in SOURCE_IP_FIELDS}
+    aggs.update({f"user:{f}": {"terms": {"field": f, "size": limit}} for f in USER_FIELDS})
+    aggs["rules"] = {"terms": {"field": "rule.description", "size": limit}}
+    aggs["outcome"] = {"filters": {"filters": {
+        "success": {"term": {"rule.groups": "authentication_success"}},
+        "failure": {"terms": {"rule.groups": ["authentication_failed", "authentication_failures",
+                                              "win_authentication_failed"]}},
+    }}}
+    return aggs
+
+
+def source_reach_aggregations(limit=20):
+    """同一批來源 IP 還出現在哪些 agent 上 (擴散範圍)"""
+    return {"agents": {"terms": {"field": "agent.name", "size": limit},
+                       "aggs": {"rules": {"terms": {"field": "rule.description", "size": 3}}}}}
+
+
+def critical_summary_aggregations(limit=10):
+    return {
+        "rules": {"terms": {"field": "rule.id", "size": limit},
+                  "aggs": {"description": {"terms": {"field": "rule.description", "size": 1}},
+                           "agents": {"terms": {"field": "agent.name", "size": 5}},
+                           "last_seen": {"max": {"field": "timestamp"}}}},
+        "agents": {"terms": {"field": "agent.name", "size": limit}},
+        "tactics": {"terms": {"field": "rule.mitre.tactic", "size": limit}},
+    }
+
+
+def terms(aggregations, prefix):
+    """合併多個同類欄位 (例如 Linux / Windows 的來源 IP) 的 terms 結果"""
+    merged = {}
+    for name, agg in aggregations.items():
+        if name.startswith(prefix):
+            for b in agg.get("buckets", []):
+                merged[b["key"]] = merged.get(b["key"], 0) + b["doc_count"]
+    return dict(sorted(merged.items(), key=lambda kv: -kv[1]))
+
+
+def context_block(title, data):
+    return f"## {title}\n```json\n{json.dumps(data, indent=2, ensure_ascii=False)}\n```"
+
+
+def missing_block(errors):
+    if not errors:
+        return ""
+    return "## 無法取得的資料\n" + "\n".join(f"- {e}" for e in errors) + \
+        "\n(請在分析中註明這些資料缺漏，必要時改用對應的工具重新查詢)"
+
+
+def compose(task, sections, steps, errors):
+    parts = [task, *sections, "## 分析步驟\n" + "\n".join(f"{i}. {s}" for i, s in enumerate(steps, 1))]
+    missing = missing_block(errors)
+    if missing:
+        parts.append(missing)
+    return "\n\n".join(parts)
@@ -0,0 +1,189 @@
+"""獵捕假說 (hypothesis) 與佐證 (evidence) 的追蹤，把「提出假說 -> 查證 -> 確認 / 推翻」的獵捕循環留下紀錄。
+
+每個假說有陳述、狀態 (STATUSES) 與信心分數 (0~1)，可歸到某個案件 (case，例如事件單號) 底下。
+佐證是其他工具的查詢結果: 摘要、原始事件的 _ref (index + id) 以及可重現查詢的工具名稱與參數，
+並註明是支持 (supports=True) 還是反駁 (False) 假說。狀態與信心的每次變更都會留下歷程。
+資料存放在狀態儲存的 hypotheses namespace，會隨 export / import 一起搬移。
+"""
+
+import re
+import threading
+from datetime import datetime, timezone
+
+HYPOTHESIS_NAMESPACE = "hypotheses"
+STATUSES = ("open", "investigating", "confirmed", "refuted", "inconclusive")
+CLOSED_STATUSES = ("confirmed", "refuted", "inconclusive")
+MAX_EVIDENCE = 200
+MAX_REFS = 50
+_ID = re.compile(r"H(\d+)$")
+
+
+def _now():
+    return datetime.now(timezone.utc).isoformat()
+
+
+def check_confidence(value):
+    if value is None:
+        return None
+    if not 0 <= value <= 1:
+        raise ValueError("confidence 必須介於 0 到 1")
+    return round(float(value), 2)
+
+
+def check_status(value):
+    if value is not None and value not in STATUSES:
+        raise ValueError(f"未知的狀態 '{value}'，可用: {', '.join(STATUSES)}")
+    return value
+
+
+def _refs(refs):
+    cleaned = []
+    for ref in refs or []:
+        if not isinstance(ref, dict) or not ref.get("id"):
+            raise ValueError("refs 的每一項都必須是工具結果中的 _ref 物件 ({\"index\": ..., \"id\": ...})")
+        cleaned.append({"index": ref.get("index"), "id": str(ref["id"])})
+    if len(cleaned) > MAX_REFS:
+        raise ValueError(f"一筆佐證最多 {MAX_REFS} 個 refs，請改附上可重現查詢的 tool / arguments")
+    return cleaned
+
+
+class Hypotheses:
+    def __init__(self, store):
+        self.store = store
+        self.lock = threading.Lock()
+
+    def get(self, hypothesis_id):
+        return self.store.get(HYPOTHESIS_NAMESPACE, hypothesis_id)
+
+    def _next_id(self):
+        numbers = [int(m.group(1)) for key, _ in self.store.list(HYPOTHESIS_NAMESPACE) if (m := _ID.match(key))]
+        return f"H{max(numbers, default=0) + 1}"
+
+    def create(self, statement, principal, case=None, confidence=None):
+        if not statement or not statement.strip():
+            raise ValueError("statement 不可為空")
+        with self.lock:
+            now = _now()
+            record = {
+                "id": self._next_id(),
+                "case": case,
+                "statement": statement.strip(),
+                "status": "open",
+                "confidence": check_confidence(confidence),
+                "created_by": principal,
+                "created_at": now,
+                "updated_at": now,
+                "evidence": [],
+                "history": [{"at": now, "by": principal, "status": "open", "confidence": check_confidence(confidence)}],
+            }
+            self.store.put(HYPOTHESIS_NAMESPACE, record["id"], record)
+            return record
+
+    def update(self, hypothesis_id, principal, status=None, confidence=None, note=None):
+        check_status(status)
+        confidence = check_confidence(confidence)
+        with self.lock:
+            record = self._require(hypothesis_id)
+            change = {"at": _now(), "by": principal}
+            if status is not None and status != record["status"]:
+                change["status"] = record["status"] = status
+            if confidence is not None and confidence != record["confidence"]:
+                change["confidence"] = record["confidence"] = confidence
+            if note:
+                change["note"] = note
+            if len(change) > 2:
+                record["history"].append(change)
+                record["updated_at"] = change["at"]
+                self.store.put(HYPOTHESIS_NAMESPACE, hypothesis_id, record)
+            return record
+
+    def attach(self, hypothesis_id, principal, summary, supports=None, refs=None, tool=None, arguments=None):
+        if not summary or not summary.strip():
+            raise ValueError("summary 不可為空，請簡述這筆佐證說明了什麼")
+        if arguments is not None and not tool:
+            raise ValueError("附上 arguments 時必須同時指定 tool")
+        evidence = {
+            "summary": summary.strip(),
+            "supports": supports,
+            "refs": _refs(refs),
+            "tool": tool,
+            "arguments": arguments,
+            "added_by": principal,
+            "added_at": _now(),
+        }
+        with self.lock:
+            record = self._require(hypothesis_id)
+            if len(record["evidence"]) >= MAX_EVIDENCE:
+                raise ValueError(f"假說 {hypothesis_id} 已有 {MAX_EVIDENCE} 筆佐證，請整理後再新增")
+            evidence["seq"] = len(record["evidence"]) + 1
+            record["evidence"].append(evidence)
+            record["updated_at"] = evidence["added_at"]
+            if record["status"] == "open":
+                # 開始蒐集佐證代表假說已進入查證階段
+                record["status"] = "investigating"
+                record["history"].append({"at": evidence["added_at"], "by": principal, "status": "investigating"})
+            self.store.put(HYPOTHESIS_NAMESPACE, hypothesis_id, record)
+            return record, evidence
+
+    def list(self, case=None, status=None, visible=None):
+        """visible(record) 為假時略過 (租戶範圍)；依建立順序排列"""
+        records = [record for _, record in self.store.list(HYPOTHESIS_NAMESPACE)
+                   if (case is None or record.get("case") == case)
+                   and (status is None or record["status"] == status)
+                   and (visible is None or visible(record))]
+        return sorted(records, key=lambda r: int(_ID.match(r["id"]).group(1)) if _ID.match(r["id"]) else 0)
+
+    def _require(self, hypothesis_id):
+        record = self.get(hypothesis_id)
+        if record is None:
+            raise KeyError(hypothesis_id)
+        return record
+
+
+def evidence_balance(record):
+    supporting = sum(1 for e in record["evidence"] if e["supports"] is True)
+    contradicting = sum(1 for e in record["evidence"] if e["supports"] is False)
+    return {"supporting": supporting, "contradicting": contradicting,
+            "context": len(record["evidence"]) - supporting - contradicting}
+
+
+def brief(record, include_evidence=False):
+    item = {
+        "id": record["id"],
+        "statement": record["statement"],
+        "status": record["status"],
+        "confidence": record["confidence"],
+        "case": record["case"],
+        "evidence": evidence_balance(record),
+        "updated_at": record["updated_at"],
+    }
+    if record["status"] in CLOSED_STATUSES:
+        closed = [h for h in record["history"] if h.get("status") == record["status"]]
+        if closed:
+            item["closed_at"] = closed[-1]["at"]
+            item["closed_by"] = closed[-1]["by"]
+            if closed[-1].get("note"):
+                item["conclusion"] = closed[-1]["note"]
+    if include_evidence:
+        # 確認的假說列出支持的佐證，推翻的列出反駁的佐證，其他列出全部
+        wanted = {"confirmed": True, "refuted": False}.get(record["status"])
+        item["key_evidence"] = [
+            {k: v for k, v in e.items() if v not in (None, []) and k not in ("added_by",)}
+            for e in record["evidence"] if wanted is None or e["supports"] is wanted
+        ]
+    return item
+
+
+def build_report(records, include_evidence=True):
+    """依狀態分組的假說報告: 確認 / 推翻 / 無定論 / 仍在查證"""
+    counts = {status: 0 for status in STATUSES}
+    for record in records:
+        counts[record["status"]] += 1
+    return {
+        "hypotheses": len(records),
+        "by_status": counts,
+        "confirmed": [brief(r, include_evidence) for r in records if r["status"] == "confirmed"],
+        "refuted": [brief(r, include_evidence) for r in records if r["status"] == "refuted"],
+        "inconclusive": [brief(r, include_evidence) for r in records if r["status"] == "inconclusive"],
+        "open": [brief(r) for r in records if r["status"] in ("open", "investigating")],
+    }
@@ -0,0 +1,106 @@
+"""Wazuh integrator 推送告警的接收端 (POST /ingest/alerts)，取代輪詢 Indexer 的延遲。
+
+Manager 的 integrator 以自訂整合 (integrations/custom-wazuh-mcp) 把符合條件 (等級、群組、規則) 的告警
+即時 POST 過來，請求需帶 Authorization: Bearer <MCP_INGEST_TOKEN>。內容可以是單一告警 JSON、
+告警陣列或 NDJSON。收到的告警放在記憶體的環形緩衝區 (MCP_INGEST_BUFFER 筆)，用途:
+- live tail: tail_live_alerts 工具以 cursor 讀取新告警；訂閱 wazuh://alerts/live 的 session 會收到更新通知
+- 查詢快取: 時間落在已快取時段的晚到告警會立即清除相關快取，不必等 CacheTail 下一次比對
+"""
+
+import json
+import threading
+from collections import deque
+from datetime import datetime, timezone
+
+from alert_utils import get_field, parse_timestamp
+
+MAX_BODY_BYTES = 5 * 1024 * 1024
+
+
+def parse_payload(body):
+    """請求內容 -> 告警清單；接受 JSON 物件、JSON 陣列或 NDJSON，每筆都必須有 rule 欄位"""
+    text = body.decode("utf-8").strip()
+    if not text:
+        raise ValueError("內容是空的")
+    try:
+        data = json.loads(text)
+        alerts = data if isinstance(data, list) else [data]
+    except json.JSONDecodeError:
+        try:
+            alerts = [json.loads(line) for line in text.splitlines() if line.strip()]
+        except json.JSONDecodeError as e:
+            raise ValueError(f"不是有效的 JSON / NDJSON: {e}") from None
+    for alert in alerts:
+        if not isinstance(alert, dict) or not isinstance(alert.get("rule"), dict):
+            raise ValueError("每筆告警都必須是帶有 rule 欄位的 JSON 物件")
+    return alerts
+
+
+class AlertBuffer:
+    """最近推送的告警 (依收到順序編號，cursor 為最後讀到的編號)"""
+
+    def __init__(self, capacity):
+        self.entries = deque(maxlen=capacity)  # (序號, 收到時間, 告警)
+        self.capacity = capacity
+        self.seq = 0
+        self.received = 0
+        self.last_push = None
+        self.lock = threading.Lock()
+
+    def push(self, alerts):
+        now = datetime.now(timezone.utc)
+        with self.lock:
+            for alert in alerts:
+                self.seq += 1
+                self.entries.append((self.seq, now, alert))
+            self.received += len(alerts)
+            self.last_push = now
+            return self.seq
+
+    def since(self, cursor, visible=None, limit=100):
+        """cursor 之後、visible(alert) 為真的告警，回傳 (告警, 下一個 cursor, 已被擠出緩衝區而漏掉的筆數)"""
+        with self.lock:
+            entries = list(self.entries)
+            latest = self.seq
+        oldest = entries[0][0] if entries else latest + 1
+        missed = max(0, oldest - cursor - 1) if cursor < latest else 0
+        alerts, next_cursor = [], cursor
+        for seq, received_at, alert in entries:
+            if seq <= cursor:
+                continue
+            next_cursor = seq
+            if visible is None or visible(alert):
+                alerts.append({"_cursor": seq, "_received_at": received_at.isoformat(), **alert})
+                if len(alerts) >= limit:
+                    break
+        else:
+            next_cursor = max(next_cursor, latest)
+        return alerts, next_cursor, missed
+
+    def stats(self):
+        with self.lock:
+            return {
+                "buffered": len(self.entries),
+                "capacity": self.capacity,
+                "received_total": self.received,
+                "cursor": self.seq,
+                "last_push": self.last_push.isoformat() if self.last_push else None,
+            }
+
+
+def late_windows(alerts, settled):
+    """時間早於 settled (現在 - 匯入延遲) 的告警 -> 需要清除的快取時段 [(時間, 時間)]"""
+    stamps = {parse_timestamp(get_field(alert, "timestamp")) for alert in alerts} - {None}
+    stamps = {ts if ts.tzinfo else ts.replace(tzinfo=timezone.utc) for ts in stamps}
+    return sorted((ts, ts) for ts in stamps if ts < settled)
+
+
+def alert_filter(allowed=None, min_level=0, agent=None):
+    """租戶範圍 (allowed agent id) 與工具參數的篩選條件"""
+    def visible(alert):
+        if allowed is not None and get_field(alert, "agent.id") not in allowed:
+            return False
+        if int(get_field(alert, "rule.level") or 0) < min_level:
+            return False
+        return agent is None or agent in (get_field(alert, "agent.name"), get_field(alert, "agent.id"))
+    return visible
@@ -0,0 +1,63 @@
+"""注入合成事件，用已知資料驗證獵捕查詢、規則與儀表板。
+
+兩種方式:
+- manager: 透過 Manager API 的 POST /events 把原始日誌送進 analysisd，走完整的解碼 / 規則流程
+- index:   直接把告警文件寫進 Indexer 的測試索引 (TEST_INDEX_PREFIX)，不經過規則引擎，
+           適合驗證查詢與儀表板；測試索引仍符合 wazuh-alerts-*，所以所有工具都查得到
+每一批注入都有 batch id，寫進告警的 data.mcp_injection_id，方便查詢與清除。
+"""
+
+import copy
+import json
+import uuid
+from datetime import datetime, timezone
+
+TEST_INDEX_PREFIX = "wazuh-alerts-mcptest-"
+MANAGER_EVENTS_PER_REQUEST = 100
+TEST_RULE_GROUP = "mcp_injected"
+
+
+def new_batch_id():
+    return uuid.uuid4().hex[:12]
+
+
+def test_index(now=None):
+    now = now or datetime.now(timezone.utc)
+    return f"{TEST_INDEX_PREFIX}{now:%Y.%m.%d}"
+
+
+def chunks(events, size=MANAGER_EVENTS_PER_REQUEST):
+    for i in range(0, len(events), size):
+        yield events[i:i + size]
+
+
+def prepare_alerts(alerts, batch_id, now=None):
+    """補上時間戳記、測試標記與 batch id；不修改呼叫者傳入的物件"""
+    stamp = (now or datetime.now(timezone.utc)).strftime("%Y-%m-%dT%H:%M:%S.%f")[:-3] + "+0000"
+    prepared = []
+    for alert in alerts:
+        if not isinstance(alert, dict):
+            raise ValueError("每筆告警都必須是 JSON 物件")
+        doc = copy.deepcopy(alert)
+        doc.setdefault("timestamp", stamp)
+        doc.setdefault("agent", {"id": "000", "name": "mcp-injection"})
+        rule = doc.setdefault("rule", {"id": "100000", "level": 3, "description": "MCP injected test alert"})
+        groups = rule.setdefault("groups", [])
+        if TEST_RULE_GROUP not in groups:
+            groups.append(TEST_RULE_GROUP)
+        doc.setdefault("data", {})["mcp_injection_id"] = batch_id
+        prepared.append(doc)
+    return prepared
+
+
+def bulk_body(index, docs):
+    lines = []
+    for doc in docs:
+        lines.append(json.dumps({"index": {"_index": index}}))
+        lines.append(json.dumps(doc, ensure_ascii=False))
+    return "\n".join(lines) + "\n"
+
+
+def bulk_errors(result):
+    """bulk API 回應中失敗項目的錯誤內容"""
+    return [item["index"]["error"] for item in result.get("items", []) if item.get("index", {}).get("error")]
@@ -0,0 +1,109 @@
+"""瀏覽器擴充功能與開機自動執行項目的清冊，以及全環境的普及度排名。
+
+Wazuh syscollector 不會收集瀏覽器擴充功能，需要靠 agent 的 command monitoring
+(<localfile><log_format>command</log_format>) 定期執行盤點腳本，輸出每行一個項目:
+    {"type": "browser_extension", "browser": "chrome", "id": "...", "name": "...", "version": "...", "user": "..."}
+或以 | 分隔: 類型|瀏覽器或位置|id|名稱|版本|使用者
+盤點指令的 alias 由 INVENTORY_COMMAND_ALIASES 指定 (預設 browser-extensions,autoruns)。
+Windows 的 Run 機碼另外從登錄檔 FIM 取得，不需要額外的盤點腳本。
+
+只出現在一兩台主機上的擴充功能 / 自動執行項目最可疑 (流氓擴充功能、單點植入)，
+所以結果依普及度由低到高排列。
+"""
+
+import json
+
+from alert_utils import get_field
+
+DEFAULT_ALIASES = "browser-extensions,autoruns"
+KINDS = ("browser_extension", "startup")
+FIELDS = ("type", "location", "id", "name", "version", "user")
+
+
+def parse_aliases(text):
+    return [a.strip() for a in (text or DEFAULT_ALIASES).split(",") if a.strip()]
+
+
+def parse_line(line):
+    """解析盤點腳本輸出的一行；無法解析時回傳 None"""
+    line = (line or "").strip()
+    if not line:
+        return None
+    if line.startswith("{"):
+        try:
+            item = json.loads(line)
+        except ValueError:
+            return None
+        if not isinstance(item, dict):
+            return None
+        item.setdefault("location", item.pop("browser", None))
+    else:
+        parts = [p.strip() for p in line.split("|")]
+        if len(parts) < 3:
+            return None
+        item = dict(zip(FIELDS, parts))
+    if item.get("type") not in KINDS or not (item.get("id") or item.get("name")):
+        return None
+    return item
+
+
+def collect_entries(command_hits, run_key_hits):
+    """{(類型, 位置, id): {"item": …, "agents": {主機: 最新時間}}}"""
+    entries = {}
+
+    def add(item, agent, ts):
+        key = (item["type"], item.get("location"), item.get("id") or item.get("name"))
+        entry = entries.setdefault(key, {"item": item, "agents": {}, "users": set()})
+        if agent not in entry["agents"] or (ts and ts > entry["agents"][agent]):
+            entry["agents"][agent] = ts
+        if item.get("user"):
+            entry["users"].add(item["user"])
+
+    for hit in command_hits:
+        alert = hit.get("_source", {})
+        for line in str(alert.get("full_log", "")).splitlines():
+            item = parse_line(line)
+            if item:
+                add(item, get_field(alert, "agent.name"), alert.get("timestamp"))
+
+    # run_key_hits 依時間由新到舊；最新一筆是 deleted 的值已經不存在，較舊的事件也要略過
+    seen = set()
+    for hit in run_key_hits:
+        alert = hit.get("_source", {})
+        value_key = (get_field(alert, "agent.name"), get_field(alert, "syscheck.path"),
+                     get_field(alert, "syscheck.value_name"))
+        if value_key in seen:
+            continue
+        seen.add(value_key)
+        if get_field(alert, "syscheck.event") == "deleted" or not value_key[2]:
+            continue
+        item = {"type": "startup", "location": get_field(alert, "syscheck.path"),
+                "id": get_field(alert, "syscheck.value_name"), "name": get_field(alert, "syscheck.value_name")}
+        add(item, get_field(alert, "agent.name"), alert.get("timestamp"))
+    return entries
+
+
+def rank_by_prevalence(entries, fleet_size, kind=None, max_agents=None):
+    """依出現的主機數由少到多排列；max_agents 可只保留出現在不超過這麼多台主機的項目"""
+    rows = []
+    for entry in entries.values():
+        item = entry["item"]
+        if kind and item["type"] != kind:
+            continue
+        count = len(entry["agents"])
+        if max_agents is not None and count > max_agents:
+            continue
+        rows.append({
+            "type": item["type"],
+            "location": item.get("location"),
+            "id": item.get("id"),
+            "name": item.get("name"),
+            "version": item.get("version"),
+            "agents": count,
+            "prevalence": round(count / fleet_size, 3) if fleet_size else None,
+            "agent_names": sorted(a for a in entry["agents"] if a)[:20],
+            "users": sorted(entry["users"])[:20],
+            "last_seen": max((ts for ts in entry["agents"].values() if ts), default=None),
+        })
+    rows.sort(key=lambda r: (r["agents"], r["type"], str(r["name"])))
+    return rows
@@ -0,0 +1,68 @@
+"""兩組查詢結果依共同欄位合併 (join)，在伺服器端完成，LLM 不必自己比對兩份資料。
+
+例如告警 join 弱點狀態 (agent.id)、DNS 查詢 join 程序事件 (同一台主機的 pid)。
+鍵值是陣列時每個元素都算一個鍵；within 設定時只合併時間相差在範圍內的事件 (避免 pid 重複使用造成誤配)。
+"""
+
+from alert_utils import get_field, parse_timestamp
+
+MAX_MATCHES = 20  # 每筆左側資料最多附上幾筆右側資料
+
+
+def project(doc, fields):
+    """只保留指定欄位 (以點號路徑為鍵)；fields 為空時保留整份文件"""
+    if not fields:
+        return doc
+    return {field: get_field(doc, field) for field in fields if get_field(doc, field) is not None}
+
+
+def keys_of(doc, path):
+    value = get_field(doc, path)
+    if value is None:
+        return []
+    values = value if isinstance(value, list) else [value]
+    return [str(v) for v in values if v is not None and str(v) != ""]
+
+
+def _close_enough(left, right, within):
+    a, b = parse_timestamp(left.get("timestamp")), parse_timestamp(right.get("timestamp"))
+    return a is not None and b is not None and abs(a - b) <= within
+
+
+def join(left, right, left_key, right_key, how="inner", within=None, left_fields=None, right_fields=None,
+         max_rows=200):
+    """回傳 (合併後的列, 統計)；how=left 時沒有對應的左側資料也會列出 (matches=0)"""
+    index = {}
+    for doc in right:
+        for key in keys_of(doc, right_key):
+            index.setdefault(key, []).append(doc)
+    rows, matched_left, unmatched_left = [], 0, 0
+    matched_right = set()
+    for doc in left:
+        for key in keys_of(doc, left_key) or [None]:
+            candidates = index.get(key, []) if key is not None else []
+            if within is not None:
+                candidates = [r for r in candidates if _close_enough(doc, r, within)]
+            if candidates:
+                matched_left += 1
+                matched_right.update(id(r) for r in candidates)
+            else:
+                unmatched_left += 1
+                if how != "left":
+                    continue
+            if len(rows) < max_rows:
+                rows.append({
+                    "key": key,
+                    "left": project(doc, left_fields),
+                    "matches": len(candidates),
+                    "right": [project(r, right_fields) for r in candidates[:MAX_MATCHES]],
+                })
+    stats = {
+        "left_rows": len(left),
+        "right_rows": len(right),
+        "left_matched": matched_left,
+        "left_unmatched": unmatched_left,
+        "right_matched": len(matched_right),
+        "distinct_keys": len(index),
+    }
+    return rows, stats
@@ -0,0 +1,273 @@
+"""KQL / Lucene 查詢字串轉換為 OpenSearch DSL。
+
+支援的語法 (涵蓋分析師最常用的部分):
+- 欄位比對: rule.level:10、agent.name:"web-01"、data.srcip:10.0.*
+- 多值: rule.id:(5710 or 5712)
+- 存在判斷: data.dstuser:*
+- 範圍: rule.level >= 10、rule.level:[10 TO 15]、timestamp:{now-1d TO now}
+- 布林運算: and / or / not (不分大小寫)、&& / || / !、括號
+- 無欄位的自由文字: "failed password" (對所有欄位做片語比對)
+"""
+
+import re
+
+
+class KQLSyntaxError(ValueError):
+    """查詢字串語法錯誤，附帶發生錯誤的字元位置。"""
+
+    def __init__(self, message, position):
+        super().__init__(f"{message} (位置 {position})")
+        self.position = position
+
+
+# --- 1. 斷詞 (Tokenizer) ---
+
+_TOKEN_RE = re.compile(
+    r"""
+    (?P<ws>\s+)
+  | (?P<string>"(?:[^"\\]|\\.)*")
+  | (?P<op>>=|<=|>|<|&&|\|\||:|\(|\)|\[|\]|\{|\}|!)
+  | (?P<word>(?:[^\s"():<>=\[\]{}!\\]|\\.)+)
+    """,
+    re.VERBOSE,
+)
+
+_KEYWORDS = {"and": "AND", "or": "OR", "not": "NOT", "to": "TO"}
+
+
+def _tokenize(text):
+    tokens = []
+    pos = 0
+    while pos < len(text):
+        m = _TOKEN_RE.match(text, pos)
+        if not m:
+            if text[pos] == '"':
+                raise KQLSyntaxError("引號沒有成對關閉", pos)
+            raise KQLSyntaxError(f"無法辨識的字元 '{text[pos]}'", pos)
+        kind = m.lastgroup
+        value = m.group(kind)
+        if kind == "string":
+            tokens.append(("STRING", re.sub(r"\\(.)", r"\1", value[1:-1]), pos, m.end()))
+        elif kind == "op":
+            mapped = {"&&": "AND", "||": "OR", "!": "NOT"}.get(value, value)
+            tokens.append((mapped, value, pos, m.end()))
+        elif kind == "word":
+            keyword = _KEYWORDS.get(value.lower())
+            if keyword:
+                tokens.append((keyword, value, pos, m.end()))
+            else:
+                tokens.append(("WORD", re.sub(r"\\(.)", r"\1", value), pos, m.end()))
+        pos = m.end()
+    tokens.append(("EOF", "", len(text), len(text)))
+    return tokens
+
+
+# --- 2. 語法分析 (遞迴下降) ---
+
+class _Parser:
+    def __init__(self, text):
+        self.text = text
+        self.tokens = _tokenize(text)
+        self.index = 0
+
+    def peek(self):
+        return self.tokens[self.index]
+
+    def advance(self):
+        tok = self.tokens[self.index]
+        self.index += 1
+        return tok
+
+    def expect(self, kind, what):
+        tok = self.peek()
+        if tok[0] != kind:
+            found = tok[1] or "查詢結尾"
+            raise KQLSyntaxError(f"預期 {what}，但遇到 '{found}'", tok[2])
+        return self.advance()
+
+    def parse(self):
+        if self.peek()[0] == "EOF":
+            return {"match_all": {}}
+        node = self.parse_or()
+        tok = self.peek()
+        if tok[0] != "EOF":
+            raise KQLSyntaxError(f"多餘的內容 '{tok[1]}'", tok[2])
+        return node
+
+    def parse_or(self):
+        clauses = [self.parse_and()]
+        while self.peek()[0] == "OR":
+            self.advance()
+            clauses.append(self.parse_and())
+        if len(clauses) == 1:
+            return clauses[0]
+        return {"bool": {"should": clauses, "minimum_should_match": 1}}
+
+    def parse_and(self):
+        clauses = [self.parse_not()]
+        while True:
+            kind = self.peek()[0]
+            if kind == "AND":
+                self.advance()
+            elif kind in ("WORD", "STRING", "NOT", "("):
+                # Lucene 風格: 相鄰的條件視為 AND
+                pass
+            else:
+                break
+            clauses.append(self.parse_not())
+        if len(clauses) == 1:
+            return clauses[0]
+        return {"bool": {"filter": clauses}}
+
+    def parse_not(self):
+        if self.peek()[0] == "NOT":
+            self.advance()
+            return {"bool": {"must_not": [self.parse_not()]}}
+        return self.parse_primary()
+
+    def parse_primary(self):
+        tok = self.peek()
+        if tok[0] == "(":
+            self.advance()
+            node = self.parse_or()
+            self.expect(")", "')'")
+            return node
+        if tok[0] == "STRING":
+            self.advance()
+            return _free_text(tok[1])
+        if tok[0] == "WORD":
+            self.advance()
+            nxt = self.peek()[0]
+            if nxt == ":":
+                self.advance()
+                return self.parse_field_value(tok[1])
+            if nxt in (">", ">=", "<", "<="):
+                op = self.advance()[0]
+                value = self.parse_scalar()
+                return _range(tok[1], {_RANGE_OPS[op]: value})
+            return _free_text(tok[1])
+        found = tok[1] or "查詢結尾"
+        raise KQLSyntaxError(f"預期欄位或搜尋字詞，但遇到 '{found}'", tok[2])
+
+    def parse_scalar(self):
+        tok = self.peek()
+        if tok[0] == "WORD":
+            self.advance()
+            # 範圍值中未加引號的時間 (2024-01-01T00:00:00Z) 會被冒號切開，緊鄰的片段要接回來
+            value, end = tok[1], tok[3]
+            while (self.peek()[0] == ":" and self.peek()[2] == end
+                   and self.tokens[self.index + 1][0] == "WORD" and self.tokens[self.index + 1][2] == end + 1):
+                self.advance()
+                word = self.advance()
+                value, end = f"{value}:{word[1]}", word[3]
+            return value
+        if tok[0] == "STRING":
+            self.advance()
+            return tok[1]
+        found = tok[1] or "查詢結尾"
+        raise KQLSyntaxError(f"預期數值，但遇到 '{found}'", tok[2])
+
+    def parse_field_value(self, field):
+        tok = self.peek()
+        if tok[0] in ("[", "{"):
+            return self.parse_lucene_range(field)
+        if tok[0] in (">", ">=", "<", "<="):
+            op = self.advance()[0]
+            return _range(field, {_RANGE_OPS[op]: self.parse_scalar()})
+        if tok[0] == "(":
+            self.advance()
+            node = self.parse_value_or(field)
+            self.expect(")", "')'")
+            return node
+        if tok[0] == "STRING":
+            self.advance()
+            return {"match_phrase": {field: tok[1]}}
+        if tok[0] == "WORD":
+            self.advance()
+            return _field_term(field, tok[1])
+        found = tok[1] or "查詢結尾"
+        raise KQLSyntaxError(f"欄位 '{field}' 缺少比對值，遇到 '{found}'", tok[2])
+
+    def parse_value_or(self, field):
+        clauses = [self.parse_value_and(field)]
+        while self.peek()[0] == "OR":
+            self.advance()
+            clauses.append(self.parse_value_and(field))
+        if len(clauses) == 1:
+            return clauses[0]
+        return {"bool": {"should": clauses, "minimum_should_match": 1}}
+
+    def parse_value_and(self, field):
+        clauses = [self.parse_value_not(field)]
+        while self.peek()[0] == "AND":
+            self.advance()
+            clauses.append(self.parse_value_not(field))
+        if len(clauses) == 1:
+            return clauses[0]
+        return {"bool": {"filter": clauses}}
+
+    def parse_value_not(self, field):
+        if self.peek()[0] == "NOT":
+            self.advance()
+            return {"bool": {"must_not": [self.parse_value_not(field)]}}
+        tok = self.peek()
+        if tok[0] == "(":
+            self.advance()
+            node = self.parse_value_or(field)
+            self.expect(")", "')'")
+            return node
+        if tok[0] == "STRING":
+            self.advance()
+            return {"match_phrase": {field: tok[1]}}
+        if tok[0] == "WORD":
+            self.advance()
+            return _field_term(field, tok[1])
+        found = tok[1] or "查詢結尾"
+        raise KQLSyntaxError(f"欄位 '{field}' 的值清單不完整，遇到 '{found}'", tok[2])
+
+    def parse_lucene_range(self, field):
+        open_tok = self.advance()
+        low = self.parse_scalar()
+        self.expect("TO", "'TO'")
+        high = self.parse_scalar()
+        close_tok = self.peek()
+        if close_tok[0] not in ("]", "}"):
+            raise KQLSyntaxError("範圍缺少結尾的 ']' 或 '}'", close_tok[2])
+        self.advance()
+        bounds = {}
+        if low != "*":
+            bounds["gte" if open_tok[0] == "[" else "gt"] = low
+        if high != "*":
+            bounds["lte" if close_tok[0] == "]" else "lt"] = high
+        return _range(field, bounds)
+
+
+_RANGE_OPS = {">": "gt", ">=": "gte", "<": "lt", "<=": "lte"}
+
+
+def _range(field, bounds):
+    return {"range": {field: bounds}}
+
+
+def _field_term(field, value):
+    if value == "*":
+        return {"exists": {"field": field}}
+    if "*" in value or "?" in value:
+        return {"wildcard": {field: {"value": value, "case_insensitive": True}}}
+    return {"match_phrase": {field: value}}
+
+
+def _free_text(value):
+    if "*" in value or "?" in value:
+        return {"query_string": {"query": value, "lenient": True}}
+    return {"multi_match": {"query": value, "type": "phrase", "lenient": True}}
+
+
+# --- 3. 對外介面 ---
+
+def kql_to_dsl(text):
+    """將 KQL / Lucene 查詢字串轉為 OpenSearch DSL 的 query 物件。
+
+    語法錯誤時拋出 KQLSyntaxError，訊息中包含錯誤位置，方便 AI 自行修正查詢。
+    """
+    return _Parser(text or "").parse()
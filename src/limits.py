@@ -0,0 +1,167 @@
+"""請求本文與工具回應的大小上限 (MCP_MAX_REQUEST_BYTES / MCP_MAX_RESULT_BYTES)。
+
+- LimitRequestBody: HTTP 請求本文超過上限時回 413，不讓過大的 POST (例如誤貼的整份 log) 佔用記憶體；
+  Content-Length 超過時直接拒絕，分段傳送的本文則在累計超過時停止讀取
+- ResultLimit: 工具回應的文字 (不含 timing 等附加標註) 超過上限時只回傳第一段，並附上 continuation token；
+  client 以 continue_result(token) 依序取回其餘部分，避免一次塞爆 client 的 context window。
+  剩餘內容只存在記憶體，ttl 後過期，且只有原本的呼叫者能取回
+"""
+
+import json
+import logging
+import threading
+import time
+import uuid
+from collections import OrderedDict
+
+from fastmcp.server.middleware import Middleware
+from fastmcp.tools.tool import ToolResult
+from mcp.types import TextContent
+
+from output import annotate_result
+
+logger = logging.getLogger("wazuh_mcp")
+
+# 同時保留的截斷結果數，超過時丟棄最舊的
+MAX_PENDING_RESULTS = 200
+
+
+class LimitRequestBody:
+    """ASGI middleware: 請求本文超過 max_bytes 時回 413 (0 = 不限制)"""
+
+    def __init__(self, app, max_bytes):
+        self.app = app
+        self.max_bytes = max_bytes
+
+    async def _reject(self, send, size):
+        body = json.dumps({"error": "request body too large",
+                           "detail": f"{size} bytes exceeds the limit of {self.max_bytes} bytes"}).encode()
+        await send({"type": "http.response.start", "status": 413,
+                    "headers": [(b"content-type", b"application/json"), (b"connection", b"close"),
+                                (b"content-length", str(len(body)).encode())]})
+        await send({"type": "http.response.body", "body": body})
+
+    async def __call__(self, scope, receive, send):
+        if scope["type"] != "http" or not self.max_bytes:
+            return await self.app(scope, receive, send)
+        declared = next((value for name, value in scope["headers"] if name.lower() == b"content-length"), None)
+        if declared is not None and declared.isdigit() and int(declared) > self.max_bytes:
+            logger.warning("拒絕過大的請求本文: %s %s (%s bytes)", scope["method"], scope["path"], declared.decode())
+            return await self._reject(send, int(declared))
+        state = {"received": 0, "exceeded": False, "started": False}
+
+        async def limited_receive():
+            if state["exceeded"]:
+                return {"type": "http.disconnect"}
+            message = await receive()
+            if message["type"] == "http.request":
+                state["received"] += len(message.get("body", b""))
+                if state["received"] > self.max_bytes:
+                    # 讓下游當作 client 已斷線，之後由這裡回 413
+                    state["exceeded"] = True
+                    return {"type": "http.disconnect"}
+            return message
+
+        async def guarded_send(message):
+            if state["exceeded"]:
+                return
+            if message["type"] == "http.response.start":
+                state["started"] = True
+            await send(message)
+
+        await self.app(scope, limited_receive, guarded_send)
+        if state["exceeded"] and not state["started"]:
+            logger.warning("拒絕過大的請求本文: %s %s (超過 %d bytes)", scope["method"], scope["path"], self.max_bytes)
+            await self._reject(send, state["received"])
+
+
+def _cut(data, max_bytes):
+    """在 max_bytes 以內、不切斷 UTF-8 字元的位置切開"""
+    end = min(max_bytes, len(data))
+    while 0 < end < len(data) and data[end] & 0xC0 == 0x80:
+        end -= 1
+    if end == 0 and data:
+        # 上限比一個字元還小時至少回傳一個字元，避免永遠取不完
+        end = 1
+        while end < len(data) and data[end] & 0xC0 == 0x80:
+            end += 1
+    return end
+
+
+class ResultPages:
+    """被截斷的工具回應: token -> 剩餘內容 (所屬的呼叫者、建立時間)"""
+
+    def __init__(self, max_bytes, ttl):
+        self.max_bytes = max_bytes
+        self.ttl = ttl
+        self.pending = OrderedDict()  # 結果 id -> {"data", "principal", "tool", "created"}
+        self.lock = threading.Lock()
+
+    def _sweep(self, now):
+        for result_id in [k for k, v in self.pending.items() if now - v["created"] > self.ttl]:
+            del self.pending[result_id]
+        while len(self.pending) > MAX_PENDING_RESULTS:
+            self.pending.popitem(last=False)
+
+    def split(self, text, principal, tool):
+        """超過上限的文字 -> (第一段, 截斷資訊)；沒有超過時回傳 (text, None)"""
+        data = text.encode("utf-8")
+        if not self.max_bytes or len(data) <= self.max_bytes:
+            return text, None
+        end = _cut(data, self.max_bytes)
+        result_id = uuid.uuid4().hex
+        with self.lock:
+            now = time.monotonic()
+            self._sweep(now)
+            self.pending[result_id] = {"data": data, "principal": principal, "tool": tool, "created": now}
+        return data[:end].decode("utf-8"), self._info(result_id, end, len(data))
+
+    def _info(self, result_id, offset, total):
+        return {"total_bytes": total, "returned_bytes": offset, "continuation": f"{result_id}.{offset}",
+                "next": "以 continue_result(token=continuation) 取得下一段"}
+
+    def next_page(self, token, principal):
+        """continuation token -> {"tool", "text", 截斷資訊 (最後一段時 continuation 為 None)}；無效時拋出 ValueError"""
+        result_id, _, offset = (token or "").partition(".")
+        with self.lock:
+            self._sweep(time.monotonic())
+            entry = self.pending.get(result_id)
+        if entry is None or entry["principal"] != principal or not offset.isdigit():
+            raise ValueError("continuation token 無效或已過期，請重新呼叫原本的工具")
+        data, start = entry["data"], int(offset)
+        end = start + _cut(data[start:], self.max_bytes)
+        page = {"tool": entry["tool"], "offset": start, **self._info(result_id, end, len(data)),
+                "text": data[start:end].decode("utf-8")}
+        if end >= len(data):
+            page.update(continuation=None, next=None)
+            with self.lock:
+                self.pending.pop(result_id, None)
+        return page
+
+    def stats(self):
+        return {"max_bytes": self.max_bytes, "pending": len(self.pending)}
+
+
+class ResultLimit(Middleware):
+    """工具回應超過上限時截斷並附上 _truncated (含 continuation token)；principal() 回傳目前的呼叫者"""
+
+    def __init__(self, pages, principal, skip=("continue_result",)):
+        self.pages = pages
+        self.principal = principal
+        self.skip = skip
+
+    async def on_call_tool(self, context, call_next):
+        result = await call_next(context)
+        name = context.message.name
+        if name in self.skip or not self.pages.max_bytes:
+            return result
+        if not result.content or not isinstance(result.content[0], TextContent):
+            return result
+        # 只分段工具本身的回應 (第一個區塊)，其他 middleware 附在後面的標註區塊保持不變
+        first, info = self.pages.split(result.content[0].text, self.principal(), name)
+        if info is None:
+            return result
+        logger.info("%s 的回應 %d bytes 超過上限，已截斷", name, info["total_bytes"])
+        truncated = ToolResult(content=[TextContent(type="text", text=first), *result.content[1:]],
+                               structured_content={"ok": True, "text": first, "_truncated": info})
+        return annotate_result(truncated, "_truncated", info, "結果已截斷", first=False)
@@ -0,0 +1,54 @@
+"""full_log 的斷詞與正規化: 把路徑、GUID、IP、數字等會變動的部分換成佔位符，讓相似的日誌訊息歸成同一個模式。
+
+    Failed password for root from 203.0.113.7 port 52144 ssh2
+    Failed password for root from 198.51.100.9 port 40022 ssh2
+        -> Failed password for root from <IP> port <NUM> ssh2
+
+多語系: 先做 NFKC 正規化 (全形英數 / 符號轉半形)；中日韓文字沒有空白分隔，連續的中日韓字元算一個 token，
+與相鄰的英數字分開 (例如 "使用者admin登入失敗" -> 使用者 / admin / 登入失敗)。
+"""
+
+import re
+import unicodedata
+
+_MONTHS = "jan|feb|mar|apr|may|jun|jul|aug|sep|oct|nov|dec"
+_CJK = "\u3040-\u30ff\u3400-\u4dbf\u4e00-\u9fff\uac00-\ud7af\uf900-\ufaff"
+
+# 依序套用 (先比對較長、較特定的格式，避免被後面的規則切碎)
+MASKS = [
+    ("<URL>", r"\b[a-z][a-z0-9+.\-]*://[^\s\"'<>]+"),
+    ("<EMAIL>", r"\b[\w.+\-]+@[\w\-]+(?:\.[\w\-]+)+"),
+    ("<GUID>", r"\{?\b[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}\b\}?"),
+    ("<PATH>", r"(?:\b[a-z]:\\|\\\\|(?<![\w/.<])(?:~|\.{1,2})?/)[^\s\"'<>|,;]*[^\s\"'<>|,;:.)\]]"),
+    ("<TS>", r"\b\d{4}-\d{2}-\d{2}[t ]\d{2}:\d{2}:\d{2}(?:[.,]\d+)?(?:z|[+\-]\d{2}:?\d{2})?"
+             rf"|\b(?:{_MONTHS})\s+\d{{1,2}}\s+\d{{2}}:\d{{2}}:\d{{2}}\b"
+             r"|\b\d{2}:\d{2}:\d{2}(?:[.,]\d+)?\b"),
+    ("<MAC>", r"\b(?:[0-9a-f]{2}[:\-]){5}[0-9a-f]{2}\b"),
+    ("<IP>", r"\b\d{1,3}(?:\.\d{1,3}){3}(?:/\d{1,2})?(?::\d{1,5})?\b"
+             r"|(?<![\w:])(?=[0-9a-f:]*[0-9a-f])(?:[0-9a-f]{1,4}(?::[0-9a-f]{1,4}){7}"
+             r"|(?:[0-9a-f]{1,4}(?::[0-9a-f]{1,4})*)?::(?:[0-9a-f]{1,4}(?::[0-9a-f]{1,4})*)?)(?![\w:])"),
+    ("<HEX>", r"\b0x[0-9a-f]+\b|\b(?=[0-9a-f]*\d)(?=[0-9a-f]*[a-f])[0-9a-f]{8,}\b"),
+    ("<NUM>", r"(?<![\w.])[+\-]?\d+(?:\.\d+)?\b"),
+]
+_MASKS = [(placeholder, re.compile(pattern, re.IGNORECASE)) for placeholder, pattern in MASKS]
+PLACEHOLDERS = [placeholder for placeholder, _ in MASKS]
+
+_TOKEN = re.compile(rf"<[A-Z]+>|[{_CJK}]+|[^\W{_CJK}]+(?:['.\-][^\W{_CJK}]+)*")
+
+
+def mask(text):
+    """正規化空白與全形字元後，把會變動的部分換成佔位符 (保留標點，方便閱讀)"""
+    text = " ".join(unicodedata.normalize("NFKC", str(text or "")).split())
+    for placeholder, regex in _MASKS:
+        text = regex.sub(placeholder, text)
+    return text
+
+
+def tokenize(text):
+    """mask 之後切成 token (佔位符、中日韓字元串、英數單字)，標點不算 token"""
+    return _TOKEN.findall(mask(text))
+
+
+def pattern(text):
+    """同一模式的訊息會得到相同的字串 (不分大小寫)"""
+    return " ".join(tokenize(text)).casefold()
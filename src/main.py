@@ -3,7 +3,132 @@ import requests
 import os
 import urllib3
 import json
+import random
+import sys
+import argparse
+import asyncio
+import threading
+import hmac
+import uvicorn
+import time
+import logging
+import tarfile
+import zipfile
 from dotenv import load_dotenv
+from kql import kql_to_dsl, KQLSyntaxError
+from dslguard import check as check_dsl
+from alert_utils import parse_duration, parse_timestamp, get_field
+from datetime import datetime, timezone
+from sequence import match_sequences
+from output import render_rows
+from severity import SeverityMapper, DEFAULT_SEVERITY_MAP
+from suggestions import collect_pivots, build_suggestions
+from provenance import event_ref, provenance, sequence_confidence
+from storage import open_store
+from encryption import EncryptedStore, load_keys, find_encrypted
+from migrations import run_migrations
+from backup import export_state, import_state
+from winservice import install_service, uninstall_service
+from aliases import AliasMiddleware, TOOL_ALIASES
+from features import parse_features
+from diagnostics import CallTracker, build_snapshot, install_signal_handlers, set_log_level
+from principal import current_principal
+from limits import LimitRequestBody, ResultLimit, ResultPages
+from preflight import Check, Preflight, STARTUP_MODES, public_view, format_report
+from supervisor import Supervisor, DegradedNotice
+from timing import TimingMiddleware, measure, record, record_response, record_cache
+from debugcapture import DebugCapture, note as debug_note, record_exchange
+from accounting import UsageMeter, QuotaExceeded, parse_quotas
+from partial import incomplete_info, narrow
+from querycache import QueryCache
+from cachetail import CacheTail
+from splitting import parse_time_bound, slice_range, run_slices, merge_results
+from environments import EnvironmentMap, parse_environments
+from firstseen import FirstSeenTracker, OBSERVABLE_FIELDS
+from hypotheses import STATUSES as HYPOTHESIS_STATUSES, Hypotheses, brief as brief_hypothesis, build_report
+from commandoutput import (SOURCE_FIELDS as COMMAND_SOURCE_FIELDS, latest_runs, output_event,
+                           summarize_commands)
+from dnsanalytics import collect_domains, collect_seen
+from dnsanalytics import summarize as summarize_dns
+from privileged import (SOURCE_FIELDS as PRIVILEGED_SOURCE_FIELDS, parse_command_baseline, collect_users,
+                        summarize as summarize_privileged)
+from osquery import SOURCE_FIELDS as OSQUERY_SOURCE_FIELDS, result_row as osquery_row, summarize_queries
+from persistence import MECHANISMS as PERSISTENCE_MECHANISMS, group_by_agent
+from inventory import parse_aliases, collect_entries, rank_by_prevalence, KINDS as INVENTORY_KINDS
+from removable import ATTACH_SOURCE, FILE_SOURCE, attach_event, file_event, correlate
+from removable import summarize as summarize_removable
+from saasaudit import (INTEGRATIONS as SAAS_INTEGRATIONS, SOURCE_FIELDS as SAAS_SOURCE_FIELDS,
+                       VOLUME_ACTIVITIES, audit_event, check_activities, summarize_actors)
+from firewall import DIMENSIONS as FIREWALL_DIMENSIONS
+from firewall import summarize as summarize_firewall
+from entitygraph import SOURCE_FIELDS as GRAPH_SOURCE_FIELDS, KINDS as GRAPH_KINDS, build_graph, prune
+from entitygraph import to_dot, to_json_graph
+from entitygraph import summarize as summarize_graph
+from injection import (TEST_INDEX_PREFIX, new_batch_id, test_index, chunks, prepare_alerts,
+                       bulk_body, bulk_errors)
+from atomics import load_catalog, find_tests, verdict, VERDICT_ADVICE
+from ruletests import load_bundle, validate_rules, parse_tests, evaluate, coverage
+from noisebudget import (SOURCE_FIELDS as NOISE_SOURCE_FIELDS, matches, parent_query, parse_rules, simulate_frequency,
+                         summarize as summarize_noise)
+from agentquery import compile_filters as compile_agent_filters
+from rollout import (AGENT_FIELDS as ROLLOUT_AGENT_FIELDS, DEFAULT_WAVES, Rollouts, batches, breakdown,
+                     config_diff, matching_blocks, next_wave, parse_agent_config, plan_waves, progress)
+from ruledraft import (SOURCE_FIELDS as DRAFT_SOURCE_FIELDS, DRAFT_ID_RANGE, chosen_fields, common_fields,
+                       draft_rules, free_rule_id, test_cases, to_ini)
+from registry import REGISTRY_PRESETS, SOURCE_FIELDS as REGISTRY_SOURCE_FIELDS, render_change
+from tlsfingerprint import parse_fingerprint_list, fields_for, summarize as summarize_fingerprints
+from querylib import QueryLibrary
+from scoping import ScopeResolver, parse_scopes
+from groups import GroupMembership
+from starlette.middleware import Middleware as StarletteMiddleware
+from starlette.requests import Request
+from starlette.responses import JSONResponse, PlainTextResponse
+from canary import Canary, Probe, prometheus_text
+from sparkline import sparkline as render_sparkline, interval_for
+from ruleset import RuleCatalog, RuleResourceList
+from fastmcp.exceptions import ResourceError
+from syslogsink import syslog_handler, json_file_handler
+from audit import AuditMiddleware, configure as configure_audit
+from selfmonitor import DEFAULT_BASE_ID, rules_xml, decoders_xml
+from embedding import StdioTransport, StreamTransport, memory_transport, serve_transport
+from subscriptions import (AGENTS_URI, ALERTS_LIVE_URI, HYPOTHESES_URI, ROLLOUTS_URI, AgentWatcher,
+                           SubscriptionRegistry, document_collection)
+from ingest import MAX_BODY_BYTES as INGEST_MAX_BYTES, AlertBuffer, alert_filter, late_windows, parse_payload
+from progress import ProgressMiddleware, expect as expect_progress, step as progress_step
+from cancellation import CANCELLED_MESSAGE, CancellationMiddleware, cancelled, opaque_id
+from httpclient import base_path, configure as configure_http, parse_headers
+from resumption import PersistentSessions, ResumableSessions, SessionPersistence, request_session_id
+from eventstore import ReplayableStreams, ReplayBuffer
+from batching import BatchRequests
+from sessionexpiry import ExpireSessions, SessionLifetimes, SessionTracking
+from proxy import TrustedProxies, behind_proxy, build_http_app, path_prefix
+from structured import StructuredOutput
+from completions import AGENT_CACHE_TTL, CachedList, Completer
+from history import SessionHistory
+from confirmation import ConfirmWrites
+from readonly import ReadOnlyMode
+from toolpolicy import PolicyFile, ToolPolicyMiddleware
+from clientlog import ClientLogForwarder, ClientLogSessions
+from rulediff import catalog_ruleset, compare as compare_rules, parse_ruleset
+from wstransport import WebSocketSessions, build_websocket_app
+from drain import Drain, DEFAULT_SIMILARITY
+from credscan import (KINDS as CREDENTIAL_KINDS, scan_event as scan_credentials,
+                      summarize as summarize_credentials)
+from unixsocket import DEFAULT_SOCKET_PATH, bind as bind_unix, close as close_unix, parse_mode as parse_socket_mode
+from tls import server_options as tls_options
+from numstats import DEFAULT_PERCENTS, METRICS as NUMERIC_METRICS, summarize as summarize_numeric
+from joins import join as join_rows
+from rollups import Rollups, merge as merge_rollups, split_days
+from apikeys import APIKeys, BearerAuth
+from origins import OriginCheck, OriginPolicy
+from oidc import DEFAULT_ALGORITHMS as OIDC_ALGORITHMS, OIDCValidator
+from mtls import ClientCertH11Protocol, ClientCertWebSocketProtocol, RequireClientCert
+from artifacts import DEFAULT_CHUNK_BYTES, api_path as artifact_path, chunk as artifact_chunk
+from mcp.types import Completion
+from vulnerabilities import VULNERABILITY_INDEX, from_api, from_index, summarize as summarize_vulnerabilities
+from huntprompts import SOURCE_IP_FIELDS, terms, context_block, compose
+from briefing import agent_breakdown, enabled_modules, log_sources, index_families, retention, format_brief
+from shaping import dedupe_alerts, parse_groups, apply_sampling, collect_stratified
 
 # --- 1. 設定與初始化區 ---
 # 載入上一層資料夾的 .env 設定
@@ -15,51 +140,689 @@ urllib3.disable_warnings(urllib3.exceptions.InsecureRequestWarning)
 # 初始化 MCP Server，名稱改為 Threat Hunter 比較帥氣
 mcp = FastMCP("Wazuh-Threat-Hunter")
 
+# Log 一律輸出到 stderr (stdout 是 stdio 模式的 MCP 通道)；執行中可用 SIGUSR1/SIGUSR2 調整
+logging.basicConfig(level=os.getenv("MCP_LOG_LEVEL", "WARNING").upper(),
+                    format="%(asctime)s %(levelname)s %(name)s: %(message)s")
+logger = logging.getLogger("wazuh_mcp")
+# 另外把 log 送到 syslog / journald / Wazuh Manager，讓伺服器本身也受 SIEM 監控
+SYSLOG_HANDLER = syslog_handler(os.getenv("MCP_LOG_SYSLOG"), wazuh_host=os.getenv("WAZUH_API_HOST"),
+                                facility=os.getenv("MCP_LOG_SYSLOG_FACILITY", "local0"),
+                                level=os.getenv("MCP_LOG_SYSLOG_LEVEL"))
+if SYSLOG_HANDLER:
+    logging.getLogger().addHandler(SYSLOG_HANDLER)
+# 工具呼叫的稽核事件 (JSON lines 檔案 / syslog)，Wazuh 規則可用 "main.py rules" 產生
+configure_audit([json_file_handler(os.getenv("MCP_AUDIT_LOG")), SYSLOG_HANDLER])
+# client 以 logging/setLevel 訂閱後，伺服器 log 以 notifications/message 送到 MCP client
+CLIENT_LOG = ClientLogForwarder(os.getenv("MCP_CLIENT_LOG_LEVEL", "").lower() or None)
+logger.addHandler(CLIENT_LOG)
+
+# 共用的 HTTP 連線池，對 Wazuh API 與 Indexer 的連線可重複使用
+HTTP = requests.Session()
+
+# 追蹤進行中的工具呼叫，供診斷快照使用
+TRACKER = CallTracker()
+mcp.add_middleware(TRACKER)
+
+# 各 session 最近的 JSON-RPC 往來 (含回應與耗時)，供 /admin/sessions 排查 client 問題 (0 = 停用)
+HISTORY = SessionHistory(int(os.getenv("MCP_SESSION_HISTORY", "50")))
+mcp.add_middleware(HISTORY)
+
+# 工具回應超過 MCP_MAX_RESULT_BYTES 時截斷並附上 continuation token，以 continue_result 取回其餘部分 (0 = 不限制)；
+# 放在外層，其他 middleware 附加的標註也算在內
+try:
+    RESULT_PAGES = ResultPages(int(os.getenv("MCP_MAX_RESULT_BYTES", "262144")),
+                               parse_duration(os.getenv("MCP_RESULT_PAGE_TTL", "15m")).total_seconds())
+except ValueError as e:
+    sys.exit(f"MCP_MAX_RESULT_BYTES / MCP_RESULT_PAGE_TTL 設定錯誤: {e}")
+mcp.add_middleware(ResultLimit(RESULT_PAGES, current_principal))
+# HTTP / WebSocket 請求本文的上限 (0 = 不限制)
+MAX_REQUEST_BYTES = int(os.getenv("MCP_MAX_REQUEST_BYTES", str(10 * 1024 * 1024)))
+
+# MCP_PERSIST_SESSIONS: HTTP session 存進狀態儲存，重啟後 client 可以沿用原本的 session (SESSIONS 在狀態儲存建立後設定)。
+# 放在前面，後面的 middleware (例如 elicitation 確認) 才看得到還原的 client 能力
+PERSIST_SESSIONS = os.getenv("MCP_PERSIST_SESSIONS", "false").lower() in ("1", "true", "yes")
+SESSIONS = None
+if PERSIST_SESSIONS:
+    mcp.add_middleware(SessionPersistence(lambda: SESSIONS, current_principal))
+
+# HTTP session 閒置超過 MCP_SESSION_IDLE_TIMEOUT 或存在超過 MCP_SESSION_MAX_LIFETIME 時結束並釋放相關狀態 (0s = 不限制)
+try:
+    SESSION_LIFETIMES = SessionLifetimes(parse_duration(os.getenv("MCP_SESSION_IDLE_TIMEOUT", "1h")).total_seconds(),
+                                         parse_duration(os.getenv("MCP_SESSION_MAX_LIFETIME", "0s")).total_seconds(),
+                                         lambda session_id, session: end_session(session_id, session))
+except ValueError as e:
+    sys.exit(f"MCP_SESSION_IDLE_TIMEOUT / MCP_SESSION_MAX_LIFETIME 設定錯誤: {e}")
+if SESSION_LIFETIMES.enabled:
+    mcp.add_middleware(SessionTracking(SESSION_LIFETIMES))
+
+def unscoped_caller():
+    """目前呼叫者不受租戶範圍限制 (伺服器 log 可能涉及所有租戶，只提供給這類呼叫者)"""
+    groups = SCOPES.groups_for(current_principal())
+    return groups is None or "*" in groups
+
+mcp.add_middleware(ClientLogSessions(CLIENT_LOG, unscoped_caller))
+
+# 會寫入 Wazuh 的工具呼叫，在稽核事件中標記為 write
+WRITE_CALLS = {
+    "inject_test_events": lambda args: True,
+    "review_detection_rules": lambda args: bool(args.get("deploy")),
+    "draft_detection_rule": lambda args: bool(args.get("deploy_test")),
+    "apply_config_rollout": lambda args: args.get("action", "status") != "status",
+}
+mcp.add_middleware(AuditMiddleware(WRITE_CALLS))
+
+_alias_targets = {alias.name: alias.target for alias in TOOL_ALIASES}
+
+# 每個身分可呼叫的工具 (RBAC)；放在稽核之後，被拒絕的呼叫也會留下 denied 紀錄
+if os.getenv("MCP_TOOL_POLICY_FILE"):
+    try:
+        TOOL_POLICY = PolicyFile(os.getenv("MCP_TOOL_POLICY_FILE"))
+    except (OSError, ValueError) as e:
+        sys.exit(f"工具權限政策設定錯誤: {e}")
+    mcp.add_middleware(ToolPolicyMiddleware(TOOL_POLICY, lambda name: _alias_targets.get(name, name)))
+
+# 唯讀模式 (MCP_READ_ONLY 或 --read-only): 隱藏寫入類工具並拒絕寫入呼叫，在詢問使用者確認之前就擋下
+READ_ONLY = ReadOnlyMode(WRITE_CALLS, os.getenv("MCP_READ_ONLY", "false").lower() in ("1", "true", "yes"),
+                         lambda name: _alias_targets.get(name, name))
+mcp.add_middleware(READ_ONLY)
+
+def summarize_injection(args):
+    if args.get("purge"):
+        return f"刪除所有測試索引 {TEST_INDEX_PREFIX}* (mode=index 注入的資料)", ["Wazuh Indexer 測試索引"]
+    if args.get("mode") == "manager":
+        return (f"透過 Manager API 送出 {len(args.get('events') or [])} 筆原始日誌給 analysisd，"
+                "會產生真實告警並可能觸發 active response", ["Wazuh manager 與所有規則 / active response 設定"])
+    alerts = args.get("alerts") or []
+    agents = sorted({str((a.get("agent") or {}).get("name")) for a in alerts if (a.get("agent") or {}).get("name")})
+    return f"寫入 {len(alerts)} 筆合成告警到測試索引 {TEST_INDEX_PREFIX}*", agents or ["預設的測試 agent"]
+
+# 寫入呼叫執行前以 elicitation 請使用者確認 (動作說明, 受影響的範圍)
+WRITE_SUMMARIES = {
+    "inject_test_events": summarize_injection,
+    "review_detection_rules": lambda args: (
+        f"暫時把 {args.get('path')} 的候選規則上傳到 manager 規則目錄 (檔名 {RULE_REVIEW_PREFIX}*)，測試後刪除",
+        ["Wazuh manager 規則庫 (測試期間影響所有 agent 的告警)"]),
+    "draft_detection_rule": lambda args: (
+        f"暫時把草擬的規則上傳到 manager 規則目錄 (檔名 {RULE_REVIEW_PREFIX}draft_*) 跑 logtest，測試後刪除",
+        ["Wazuh manager 規則庫 (測試期間影響所有 agent 的告警)"]),
+    "apply_config_rollout": lambda args: (
+        f"執行設定上線計畫 {args.get('plan_id')} 的 {args.get('action')} 步驟 (建立 / 刪除暫存 group、"
+        "指派 agent 或寫入 agent.conf)",
+        ["Wazuh manager 的 agent group 指派與共用設定 (agent 會收到新的設定)"]),
+}
+mcp.add_middleware(ConfirmWrites(WRITE_CALLS, WRITE_SUMMARIES,
+                                 os.getenv("MCP_CONFIRM_WRITES", "when-supported").lower()))
+
+# 已改名 / 改版工具的舊名稱仍可呼叫 (回應中附淘汰說明)；設為 false 時舊名稱不列在工具清單中
+LIST_DEPRECATED_ALIASES = os.getenv("MCP_LIST_DEPRECATED_ALIASES", "true").lower() in ("1", "true", "yes")
+# MCP_ALLOW_DEBUG: 每個工具多一個 debug 參數，附上這次呼叫的後端請求與回應 (放在別名轉換之前，舊名稱也能用)
+if os.getenv("MCP_ALLOW_DEBUG", "false").lower() in ("1", "true", "yes"):
+    mcp.add_middleware(DebugCapture(int(os.getenv("MCP_DEBUG_SNIPPET_BYTES", "2000"))))
+    HTTP.hooks["response"].append(record_exchange)
+
+mcp.add_middleware(AliasMiddleware(TOOL_ALIASES, list_aliases=LIST_DEPRECATED_ALIASES))
+
+# 每個工具回應附上 timing 區塊 (auth / backend / parse / render 毫秒數與快取命中)
+if os.getenv("MCP_RESPONSE_TIMING", "true").lower() in ("1", "true", "yes"):
+    mcp.add_middleware(TimingMiddleware())
+    HTTP.hooks["response"].append(record_response)
+
+# client 帶 progressToken 時，每完成一次 Indexer 查詢送出 notifications/progress
+mcp.add_middleware(ProgressMiddleware())
+
+# 工具在獨立執行緒執行，client 送出 notifications/cancelled 時中止後端請求與 Indexer 搜尋
+# (cancel_indexer_tasks 定義在後面，middleware 要在這個位置註冊才能維持順序，所以呼叫時才查找)
+mcp.add_middleware(CancellationMiddleware(lambda opaque: cancel_indexer_tasks(opaque)))
+
+# 管理端點 (/admin/*) 的存取權杖；未設定時管理端點一律拒絕
+ADMIN_TOKEN = os.getenv("MCP_ADMIN_TOKEN")
+# integrator 推送告警 (POST /ingest/alerts) 用的權杖，未設定時停用
+INGEST_TOKEN = os.getenv("MCP_INGEST_TOKEN")
+
+# 會寫入 Wazuh 的工具 (例如注入測試事件) 預設停用，需明確設定 MCP_ALLOW_WRITES=true
+ALLOW_WRITES = os.getenv("MCP_ALLOW_WRITES", "false").lower() in ("1", "true", "yes")
+
+# 啟用的功能模組 (MCP_FEATURES，預設全部)；停用的模組不註冊工具
+FEATURES = parse_features(os.getenv("MCP_FEATURES"))
+
+def feature_tool(feature):
+    """功能模組啟用時才註冊為 MCP 工具 (函式本身仍可在程式內呼叫)"""
+    if feature in FEATURES:
+        return mcp.tool()
+    return lambda fn: fn
+
 # 讀取環境變數
 HOST = os.getenv("WAZUH_API_HOST")
 PORT = os.getenv("WAZUH_API_PORT", "55000")
 USER = os.getenv("WAZUH_API_USERNAME")
 PASS = os.getenv("WAZUH_API_PASSWORD")
-BASE_URL = f"https://{HOST}:{PORT}"
+# 位於反向代理後方時的路徑前綴，例如 /wazuh-api
+BASE_URL = f"https://{HOST}:{PORT}{base_path(os.getenv('WAZUH_API_BASE_PATH'))}"
+# Manager API 清單分頁: 每頁筆數 (API 建議不超過 500) 與最多取回的總筆數
+API_PAGE_SIZE = 500
+API_MAX_ITEMS = 100000
+
+# Wazuh Indexer (OpenSearch) 設定，告警資料都存放在這裡
+INDEXER_HOST = os.getenv("WAZUH_INDEXER_HOST", HOST)
+INDEXER_PORT = os.getenv("WAZUH_INDEXER_PORT", "9200")
+INDEXER_USER = os.getenv("WAZUH_INDEXER_USERNAME", "admin")
+INDEXER_PASS = os.getenv("WAZUH_INDEXER_PASSWORD")
+INDEXER_URL = f"https://{INDEXER_HOST}:{INDEXER_PORT}{base_path(os.getenv('WAZUH_INDEXER_BASE_PATH'))}"
+
+# 送往後端的 User-Agent 與額外標頭 (例如企業閘道的存取權杖)
+configure_http(HTTP, os.getenv("WAZUH_MCP_USER_AGENT"), [
+    (BASE_URL, parse_headers(os.getenv("WAZUH_API_HEADERS"), "WAZUH_API_HEADERS")),
+    (INDEXER_URL, parse_headers(os.getenv("WAZUH_INDEXER_HEADERS"), "WAZUH_INDEXER_HEADERS")),
+])
+ALERTS_INDEX = "wazuh-alerts-*"
+# 所有事件 (含未觸發規則的)，需在 manager 啟用 <logall_json>
+ARCHIVES_INDEX = os.getenv("WAZUH_ARCHIVES_INDEX", "wazuh-archives-*")
+GROUP_BY_MAX_GROUPS = 500
+# search_alerts_dsl 的上限: 回傳筆數與每個聚合的 bucket 數 (timeout 上限為 INDEXER_QUERY_TIMEOUT)
+DSL_MAX_SIZE = int(os.getenv("MCP_DSL_MAX_SIZE", "500"))
+DSL_MAX_BUCKETS = int(os.getenv("MCP_DSL_MAX_BUCKETS", "1000"))
+# Indexer 端的查詢逾時；逾時時回傳部分結果並標示 timed_out，而不是整個請求失敗
+INDEXER_QUERY_TIMEOUT = os.getenv("INDEXER_QUERY_TIMEOUT", "25s")
+# Indexer 查詢花費超過此毫秒數時寫 warning log (0 = 不記錄)
+SLOW_QUERY_MS = int(os.getenv("MCP_SLOW_QUERY_MS", "5000"))
+PARTIAL_RETRIES = 2
+# 查詢時間範圍超過門檻時，自動切成每段 QUERY_SLICE_SIZE 平行查詢再合併
+QUERY_SPLIT_THRESHOLD = os.getenv("QUERY_SPLIT_THRESHOLD", "14d")
+QUERY_SLICE_SIZE = os.getenv("QUERY_SLICE_SIZE", "7d")
+QUERY_SLICE_WORKERS = int(os.getenv("QUERY_SLICE_WORKERS", "4"))
+
+# 伺服器端狀態 (案件、註記、抑制規則等) 的儲存位置，預設為專案資料夾下的 SQLite
+STORE_URL = os.getenv(
+    "WAZUH_MCP_STORE_URL",
+    "sqlite://" + os.path.abspath(os.path.join(os.path.dirname(__file__), '..', 'state.db'))
+)
+# 停用 state 模組時改用記憶體資料庫 (重新啟動即清空)，適合唯讀檔案系統
+STORE = open_store(STORE_URL if "state" in FEATURES else "sqlite://:memory:")
+# 設定金鑰時狀態資料 (含查詢快取) 以 AES-256-GCM 加密後才寫入磁碟
+STORE_KEY, STORE_OLD_KEYS = load_keys()
+if STORE_KEY:
+    STORE = EncryptedStore(STORE, STORE_KEY, STORE_OLD_KEYS)
+    reencrypted = STORE.reencrypt()
+    if reencrypted:
+        logger.warning("已將 %d 筆狀態資料改以目前的金鑰加密", reencrypted)
+elif find_encrypted(STORE):
+    raise ValueError("狀態資料已加密，但沒有設定 WAZUH_MCP_STORE_KEY (或 _KEY_FILE / _KEY_COMMAND)")
+# 升級後第一次啟動時套用尚未執行的狀態資料遷移
+run_migrations(STORE)
+
+# 每個 principal 的每日查詢配額 (0 = 不限制)，個別租戶可用 MCP_PRINCIPAL_QUOTAS 覆寫
+def restore_session(session, context):
+    """接續的 session 重新套用重啟前的 log 等級與資源訂閱"""
+    if context.get("log_level"):
+        CLIENT_LOG.set_level(session, context["log_level"])
+    for uri in context.get("subscriptions", []):
+        SUBSCRIPTIONS.subscribe(session, uri, scoped_agents(), current_principal())
+
+if PERSIST_SESSIONS:
+    try:
+        SESSIONS = PersistentSessions(STORE, parse_duration(os.getenv("MCP_SESSION_RESUME_TTL", "24h")),
+                                      restore_session)
+    except ValueError as e:
+        sys.exit(f"MCP_SESSION_RESUME_TTL 設定錯誤: {e}")
+    SESSIONS.purge()
+
+USAGE = UsageMeter(STORE, parse_quotas(
+    int(os.getenv("MCP_DAILY_QUOTA_DOCS", "0")),
+    int(os.getenv("MCP_DAILY_QUOTA_BYTES", "0")),
+    os.getenv("MCP_PRINCIPAL_QUOTAS")
+))
+
+# 歷史時間範圍 (早於 現在 - 匯入延遲) 的查詢結果不會再變，長時間快取在狀態儲存中
+QUERY_CACHE = QueryCache(
+    STORE,
+    ttl=parse_duration(os.getenv("QUERY_CACHE_TTL", "7d")),
+    ingest_delay=parse_duration(os.getenv("INGEST_DELAY", "15m"))
+)
+QUERY_CACHE.purge_expired()
+
+# 監看歷史時段的晚到告警，出現時清除涵蓋該時段的查詢快取 (0s = 停用)
+CACHE_TAIL_INTERVAL = parse_duration(os.getenv("QUERY_CACHE_TAIL_INTERVAL", "60s")).total_seconds()
+CACHE_TAIL_LOOKBACK = parse_duration(os.getenv("QUERY_CACHE_TAIL_LOOKBACK", "7d"))
+# 每日告警彙總 (0 = 停用)；長時間範圍的統計整天的部分直接讀彙總
+ROLLUP_INTERVAL = parse_duration(os.getenv("MCP_ROLLUP_INTERVAL", "0s")).total_seconds()
+ROLLUP_DAYS = int(os.getenv("MCP_ROLLUP_DAYS", "90"))
+ROLLUP_REFRESH_DAYS = int(os.getenv("MCP_ROLLUP_REFRESH_DAYS", "2"))
+ROLLUP_TERMS = int(os.getenv("MCP_ROLLUP_TERMS", "1000"))
+
+# 組織層級的全域篩選 (KQL)，自動套用到所有 Indexer 查詢，例如排除實驗室主機與自我測試規則:
+#   MCP_GLOBAL_EXCLUDE='agent.name:lab-* or rule.id:99999'
+GLOBAL_FILTER_KQL = os.getenv("MCP_GLOBAL_FILTER", "")
+GLOBAL_EXCLUDE_KQL = os.getenv("MCP_GLOBAL_EXCLUDE", "")
+GLOBAL_FILTER = kql_to_dsl(GLOBAL_FILTER_KQL) if GLOBAL_FILTER_KQL.strip() else None
+GLOBAL_EXCLUDE = kql_to_dsl(GLOBAL_EXCLUDE_KQL) if GLOBAL_EXCLUDE_KQL.strip() else None
+
+# 可觀察值 (雜湊 / 網域 / IP / User-Agent) 首次出現資料庫；第一次建立時往回掃描 FIRST_SEEN_BACKFILL
+FIRST_SEEN = FirstSeenTracker(
+    STORE,
+    search=lambda body: search_indexer(body, global_filters=False, as_system=True),
+    backfill=parse_duration(os.getenv("FIRST_SEEN_BACKFILL", "30d")),
+    refresh_interval=parse_duration(os.getenv("FIRST_SEEN_REFRESH", "10m"))
+)
+# 首次出現的時間在這段期間內，才會在結果中標註為「新出現」
+FIRST_SEEN_WINDOW = parse_duration(os.getenv("FIRST_SEEN_WINDOW", "24h"))
+
+# 獵捕假說與佐證 (hypotheses namespace)
+HYPOTHESES = Hypotheses(STORE)
+
+# agent group 設定變更的分批上線計畫 (rollouts namespace)
+ROLLOUTS = Rollouts(STORE)
+
+# 已知惡意的 JA3 / JA4 指紋清單 (檔案路徑或以逗號分隔的 "指紋=說明")
+TLS_BAD_FINGERPRINTS = parse_fingerprint_list(os.getenv("TLS_BAD_FINGERPRINTS"))
+
+# 特權指令 (sudo / su / pkexec) 的正常指令基準 (檔案路徑或以逗號分隔的 glob 樣式)
+PRIVILEGED_COMMAND_BASELINE = parse_command_baseline(os.getenv("PRIVILEGED_COMMAND_BASELINE"))
+
+# 盤點瀏覽器擴充功能 / 自動執行項目的 command monitoring alias
+INVENTORY_COMMAND_ALIASES = parse_aliases(os.getenv("INVENTORY_COMMAND_ALIASES"))
+
+# Atomic Red Team 測試對照表 (預設使用 src/data/atomic_red_team.json)
+ATOMIC_CATALOG = load_catalog(os.getenv("ATOMIC_TESTS_FILE"))
+
+# 規則包 (review_detection_rules 等的 path) 只能讀取這個目錄底下的檔案；未設定時不接受任何路徑
+DETECTION_RULES_ROOT = os.getenv("DETECTION_RULES_ROOT")
+# 暫時部署候選規則時使用的檔名前綴，方便辨識與清除
+RULE_REVIEW_PREFIX = "mcp_review_"
+# get_manager_file 預設停用；可取回的檔案大小上限
+ALLOW_FILE_RETRIEVAL = os.getenv("MCP_ALLOW_FILE_RETRIEVAL", "false").lower() in ("1", "true", "yes")
+FILE_RETRIEVAL_MAX_BYTES = int(os.getenv("MCP_FILE_RETRIEVAL_MAX_BYTES", str(1024 * 1024)))
+
+# 所有 Indexer 查詢片段都由範本庫產生，依 Wazuh 版本選擇對應的版本 (例如 4.7.3)
+QUERIES = QueryLibrary(os.getenv("WAZUH_VERSION"))
+
+# 規則等級 -> 標準化嚴重度 (info/low/medium/high/critical) 對照表
+SEVERITY = SeverityMapper(os.getenv("WAZUH_SEVERITY_MAP", DEFAULT_SEVERITY_MAP))
+
+# Arrow 檔案輸出目錄 (output_format="arrow" 且 write_file=True 時使用)
+ARROW_OUTPUT_DIR = os.getenv("ARROW_OUTPUT_DIR", os.path.join(os.path.dirname(__file__), '..', 'exports'))
 
 # --- 2. 輔助函式區 ---
-def get_token():
-    """取得 Wazuh JWT Token"""
-    try:
-        resp = requests.get(
-            f"{BASE_URL}/security/user/authenticate", 
-            auth=(USER, PASS), 
-            verify=False, 
-            timeout=5
+# Wazuh API 的 JWT 預設 900 秒到期，快取到 TOKEN_TTL 後提前重新取得
+TOKEN_TTL = 600
+_token = {"value": None, "expires": 0}
+_token_lock = threading.Lock()
+
+def get_token(force=False):
+    """取得 Wazuh JWT Token (快取重用；force=True 或快取失效時重新驗證)"""
+    with _token_lock:
+        if not force and _token["value"] and time.monotonic() < _token["expires"]:
+            return _token["value"]
+        _token["value"] = None
+        try:
+            resp = HTTP.get(
+                f"{BASE_URL}/security/user/authenticate", 
+                auth=(USER, PASS), 
+                verify=False, 
+                timeout=5
+            )
+            if resp.status_code == 200:
+                _token["value"] = resp.json()['data']['token']
+                _token["expires"] = time.monotonic() + TOKEN_TTL
+                logger.info("已重新取得 Wazuh API token%s", " (強制重新驗證)" if force else "")
+            else:
+                logger.warning("Wazuh API 驗證失敗: %d", resp.status_code)
+            return _token["value"]
+        except Exception as e:
+            logger.warning("無法連線至 Wazuh API 驗證端點: %s", e)
+            return None
+
+def invalidate_token():
+    with _token_lock:
+        _token["value"] = None
+
+def _invalidate_on_unauthorized(resp, *args, **kwargs):
+    """API 回 401 (token 過期 / 被撤銷) 時清除快取，下一次請求會重新驗證"""
+    if resp.status_code == 401 and resp.url.startswith(BASE_URL):
+        invalidate_token()
+
+HTTP.hooks["response"].append(_invalidate_on_unauthorized)
+
+def reset_backend_connections():
+    """後端異常時清除 token 快取並關閉連線池中的舊連線，下一次請求會重新建立"""
+    invalidate_token()
+    HTTP.close()
+
+def check_manager_api():
+    try:
+        resp = HTTP.get(f"{BASE_URL}/security/user/authenticate", auth=(USER, PASS), verify=False, timeout=5)
+    except requests.RequestException as e:
+        return f"無法連線至 {BASE_URL}: {e}"
+    if resp.status_code != 200:
+        return f"驗證失敗 ({resp.status_code})，請檢查 WAZUH_API_USERNAME / WAZUH_API_PASSWORD"
+    return None
+
+def check_indexer():
+    health, error = indexer_get("_cluster/health")
+    if error:
+        return error
+    if health.get("status") == "red":
+        return "叢集狀態為 red，部分索引無法查詢"
+    return None
+
+def check_alerts_index():
+    indices, error = indexer_get(f"_cat/indices/{ALERTS_INDEX}?format=json&h=index")
+    if error:
+        return error
+    if not indices:
+        return f"找不到任何 {ALERTS_INDEX} 索引 (filebeat 尚未送出告警？)"
+    return None
+
+def probe_manager_api():
+    _, error = api_get("/")
+    return error
+
+def probe_indexer_search():
+    # 一定成功、幾乎不花成本的查詢: 最近 5 分鐘的告警數 (不取文件、不算精確總數)
+    body = {"size": 0, "track_total_hits": False, "query": QUERIES.render("common.time_range", gte="now-5m")}
+    _, error = search_indexer(body, global_filters=False, as_system=True)
+    return error
+
+def probe_indexer_cluster():
+    _, error = indexer_get("_cluster/health")
+    return error
+
+def api_get(path, params=None):
+    """對 Wazuh Manager API 發出 GET，回傳 (data 區塊, 錯誤訊息)"""
+    return api_request("GET", path, params=params)
+
+def api_get_all(path, params=None, page_size=API_PAGE_SIZE, max_items=API_MAX_ITEMS):
+    """以 offset 分頁取回 Manager API 清單的所有項目，回傳 ({affected_items, total_affected_items}, 錯誤訊息)"""
+    items, total = [], None
+    while total is None or len(items) < min(total, max_items):
+        data, error = api_get(path, {**(params or {}), "offset": len(items), "limit": page_size})
+        if error:
+            return None, error
+        page = data.get("affected_items", [])
+        total = data.get("total_affected_items", len(items) + len(page))
+        if not page:
+            break
+        items.extend(page)
+    return {"affected_items": items[:max_items], "total_affected_items": total}, None
+
+def api_request(method, path, params=None, body=None, data=None, raw=False, content_type="application/octet-stream"):
+    """對 Wazuh Manager API 發出請求 (data 為原始本文，例如上傳規則檔)，回傳 (data 區塊, 錯誤訊息)
+    content_type 是 data 的類型: /rules/files 等檔案上傳用 application/octet-stream，
+    group 設定 (/groups/{group}/configuration) 只接受 application/xml。
+    raw=True 時回傳回應的原始位元組 (搭配 ?raw=true 取得檔案內容)"""
+    if cancelled():
+        return None, CANCELLED_MESSAGE
+    token = get_token()
+    if not token:
+        return None, "錯誤: 無法連線至 Wazuh API，請檢查帳號密碼或網路連線。"
+    headers = {"Authorization": f"Bearer {token}"}
+    if data is not None:
+        headers["Content-Type"] = content_type
+        data = data.encode("utf-8")
+    try:
+        resp = HTTP.request(method, f"{BASE_URL}{path}", headers=headers, params=params, json=body,
+                            data=data, verify=False, timeout=30)
+        if resp.status_code == 401:
+            # token 在快取期間失效 (manager 重啟或撤銷)，重新驗證後重試一次
+            token = get_token(force=True)
+            if not token:
+                return None, "錯誤: 無法連線至 Wazuh API，請檢查帳號密碼或網路連線。"
+            headers["Authorization"] = f"Bearer {token}"
+            resp = HTTP.request(method, f"{BASE_URL}{path}", headers=headers, params=params, json=body,
+                                data=data, verify=False, timeout=30)
+        if resp.status_code == 200:
+            if raw:
+                return resp.content, None
+            with measure("parse"):
+                return resp.json().get('data', {}), None
+        # 4xx 多半是查無資料 / 參數錯誤，5xx 才是 manager 端的問題
+        logger.log(logging.WARNING if resp.status_code >= 500 else logging.INFO,
+                   "Wazuh API %s %s 回傳 %d", method, path, resp.status_code)
+        return None, f"API 回傳錯誤: {resp.status_code} - {resp.text}"
+    except Exception as e:
+        logger.warning("Wazuh API %s %s 失敗: %s", method, path, e)
+        return None, f"發生例外錯誤: {str(e)}"
+
+def indexer_get(path):
+    """對 Wazuh Indexer 發出 GET (叢集 / 索引管理類 API)，回傳 (結果, 錯誤訊息)"""
+    return indexer_request("GET", path)
+
+def indexer_request(method, path, data=None, content_type="application/json"):
+    """對 Wazuh Indexer 發出請求 (data 為已序列化的本文，例如 bulk 的 NDJSON)，回傳 (結果, 錯誤訊息)"""
+    if cancelled():
+        return None, CANCELLED_MESSAGE
+    try:
+        resp = HTTP.request(method, f"{INDEXER_URL}/{path.lstrip('/')}", auth=(INDEXER_USER, INDEXER_PASS),
+                            data=data.encode("utf-8") if data else None,
+                            headers={"Content-Type": content_type}, verify=False, timeout=30)
+        if resp.status_code == 200:
+            return resp.json(), None
+        return None, f"Indexer 回傳錯誤: {resp.status_code} - {resp.text}"
+    except Exception as e:
+        return None, f"無法連線至 Wazuh Indexer: {str(e)}"
+
+def fetch_group_agents(group):
+    """取得 agent group 內所有 agent 的 id，供租戶範圍限制使用"""
+    data, error = api_get(f"/groups/{group}/agents", {"limit": 100000, "select": "id"})
+    if error:
+        raise RuntimeError(error)
+    return [a.get('id') for a in data.get('affected_items', [])]
+
+# agent group 成員快取 (agent_groups 參數、租戶範圍與主機環境共用)
+GROUPS = GroupMembership(fetch_group_agents)
+
+# principal -> agent group 範圍對照 (租戶隔離)
+SCOPES = ScopeResolver(parse_scopes(os.getenv("MCP_PRINCIPAL_SCOPES")), GROUPS.agents)
+
+# 主機環境標籤 (production / honeypot / lab)，聚合時分開統計
+ENVIRONMENTS = EnvironmentMap(parse_environments(os.getenv("MCP_AGENT_ENVIRONMENTS")), GROUPS.agents)
+
+def scoped_agents():
+    """目前呼叫者可存取的 agent id 集合 (None = 不限制)"""
+    return SCOPES.allowed_agents(current_principal())
+
+def search_indexer(body, index=ALERTS_INDEX, retry_partial=False, global_filters=True, as_system=False):
+    """對 Wazuh Indexer 執行 _search，回傳 (結果, 錯誤訊息)
+    查詢會自動套用呼叫者的租戶範圍，所有工具都應透過此函式查詢 Indexer。
+    結果不完整 (分片失敗 / 逾時) 時會在結果加上 _incomplete；retry_partial=True 會縮小查詢重試。
+    global_filters=False 可略過組織層級的全域篩選 (例如要刻意查看實驗室主機時)。
+    as_system=True 只給伺服器內部的全環境維護工作使用 (不套用租戶範圍、不計入用量)。
+    """
+    if global_filters:
+        body = {**body, "query": with_global_filters(body.get("query", {"match_all": {}}))}
+    result, error = _search_indexer_once(body, index, as_system)
+    attempts = 1
+    while retry_partial and result is not None and incomplete_info(result) and attempts <= PARTIAL_RETRIES:
+        body = narrow(body)
+        retry, retry_error = _search_indexer_once(body, index, as_system)
+        attempts += 1
+        if retry_error:
+            break
+        result = retry
+    if result is not None:
+        info = incomplete_info(result)
+        if info:
+            info["attempts"] = attempts
+            result["_incomplete"] = info
+        progress_step(f"Indexer 查詢完成 ({result.get('took', 0)} ms"
+                      f"{', 結果不完整' if info else ''})")
+    else:
+        progress_step("Indexer 查詢失敗")
+    return result, error
+
+def cancel_indexer_tasks(opaque):
+    """取消 Indexer 上帶有此 X-Opaque-Id 的搜尋 task (client 取消工具呼叫時)"""
+    resp = HTTP.get(f"{INDEXER_URL}/_tasks", params={"actions": "*search*", "detailed": "true"},
+                    auth=(INDEXER_USER, INDEXER_PASS), verify=False, timeout=10)
+    resp.raise_for_status()
+    for node in resp.json().get("nodes", {}).values():
+        for task_id, task in node.get("tasks", {}).items():
+            if task.get("headers", {}).get("X-Opaque-Id") == opaque:
+                HTTP.post(f"{INDEXER_URL}/_tasks/{task_id}/_cancel", auth=(INDEXER_USER, INDEXER_PASS),
+                          verify=False, timeout=10)
+                logger.info("已取消 Indexer 搜尋 task %s", task_id)
+
+def _search_indexer_once(body, index, as_system=False):
+    if cancelled():
+        return None, CANCELLED_MESSAGE
+    principal = None if as_system else current_principal()
+    try:
+        if principal:
+            USAGE.check(principal)
+    except QuotaExceeded as e:
+        return None, f"錯誤: {str(e)}"
+    try:
+        # 一律複製後再改寫: as_system 的呼叫者 (CacheTail、Rollups) 會重複使用同一份 body
+        body = {**body, "timeout": body.get("timeout", INDEXER_QUERY_TIMEOUT)}
+        if principal:
+            body["query"] = SCOPES.scope_query(body.get("query", {"match_all": {}}), principal)
+    except RuntimeError as e:
+        return None, f"無法解析租戶範圍，為避免越權已拒絕查詢: {e}"
+    cacheable = QUERY_CACHE.cacheable(body)
+    if cacheable:
+        cached = QUERY_CACHE.get(index, body)
+        record_cache(cached is not None)
+        if cached is not None:
+            debug_note("query_cache_hit", index=index, body=body)
+            return cached, None
+    try:
+        resp = HTTP.post(
+            f"{INDEXER_URL}/{index}/_search",
+            auth=(INDEXER_USER, INDEXER_PASS),
+            headers={"X-Opaque-Id": opaque_id()} if opaque_id() else None,
+            json=body,
+            verify=False,
+            timeout=30
         )
         if resp.status_code == 200:
-            return resp.json()['data']['token']
-        return None
+            with measure("parse"):
+                result = resp.json()
+            record("indexer_took", result.get("took", 0))
+            if SLOW_QUERY_MS and result.get("took", 0) >= SLOW_QUERY_MS:
+                logger.warning("Indexer 慢查詢: %s 花了 %d ms (逾時上限 %s)", index, result["took"],
+                               body.get("timeout"))
+            total = result.get('hits', {}).get('total', {})
+            if principal:
+                USAGE.record(principal, total.get('value', 0) if isinstance(total, dict) else total,
+                             len(resp.content))
+            if cacheable and not incomplete_info(result):
+                QUERY_CACHE.put(index, body, result)
+            return result, None
+        logger.warning("Indexer 搜尋 %s 回傳 %d", index, resp.status_code)
+        return None, f"Indexer 回傳錯誤: {resp.status_code} - {resp.text}"
     except Exception as e:
+        logger.warning("Indexer 搜尋 %s 失敗: %s", index, e)
+        return None, f"無法連線至 Wazuh Indexer: {str(e)}"
+
+def with_global_filters(query):
+    """套用設定檔定義的全域篩選 (MCP_GLOBAL_FILTER 必須符合、MCP_GLOBAL_EXCLUDE 一律排除)"""
+    if not GLOBAL_FILTER and not GLOBAL_EXCLUDE:
+        return query
+    node = {"bool": {"filter": [query]}}
+    if GLOBAL_FILTER:
+        node["bool"]["filter"].append(GLOBAL_FILTER)
+    if GLOBAL_EXCLUDE:
+        node["bool"]["must_not"] = [GLOBAL_EXCLUDE]
+    return node
+
+def global_filters_summary():
+    summary = {}
+    if GLOBAL_FILTER_KQL:
+        summary["filter"] = GLOBAL_FILTER_KQL
+    if GLOBAL_EXCLUDE_KQL:
+        summary["exclude"] = GLOBAL_EXCLUDE_KQL
+    return summary
+
+def search_indexer_split(body, time_from, time_to, retry_partial=False, global_filters=True):
+    """時間範圍夠長時切段平行查詢並合併 (只適用依時間排序的清單與 group_by 聚合)"""
+    start, end = parse_time_bound(time_from), parse_time_bound(time_to)
+    if end - start <= parse_duration(QUERY_SPLIT_THRESHOLD):
         return None
+    slices = slice_range(start, end, parse_duration(QUERY_SLICE_SIZE))
+    expect_progress(len(slices))
+    done = []
+
+    def progress(piece, error):
+        done.append(piece)
+        logger.info("切段查詢進度 %d/%d (%s ~ %s)%s", len(done), len(slices), piece[0], piece[1],
+                    f" 失敗: {error}" if error else "")
+
+    outcomes = run_slices(lambda b: search_indexer(b, retry_partial=retry_partial, global_filters=global_filters),
+                          body, slices, QUERY_SLICE_WORKERS, progress)
+    if all(error for _, _, error in outcomes):
+        return None, outcomes[0][2]
+    order = body.get("sort", [{"timestamp": {"order": "desc"}}])[0].get("timestamp", {}).get("order", "desc")
+    return merge_results(outcomes, order, body.get("size", 0)), None
+
+def build_query(kql, min_severity=None, agent_groups=None):
+    """KQL 轉 DSL，並套用 min_severity 與 agent group 篩選；錯誤時拋出 KQLSyntaxError / ValueError"""
+    query = kql_to_dsl(kql)
+    if min_severity:
+        level = SEVERITY.min_level(min_severity)
+        query = {"bool": {"filter": [query, QUERIES.render("common.min_level", level=level)]}}
+    if agent_groups:
+        members, _ = GROUPS.resolve(agent_groups)
+        query = {"bool": {"filter": [query, QUERIES.render("common.agent_ids", agent_ids=members)]}}
+    return query
+
+def agent_groups_summary(agent_groups):
+    """回報用: 每個 group 的成員數 (已在 build_query 查過，直接取快取)"""
+    return GROUPS.resolve(agent_groups)[1]
+
+def suggest_pivots(kql, alerts, total, time_range="now-7d"):
+    """以一次聚合查詢計算下一步建議；查詢失敗時只略過 IOC 類建議"""
+    pivots = collect_pivots(alerts)
+    agg_result = None
+    if pivots:
+        agg_result, _ = search_indexer(QUERIES.render("search.pivots", pivots=pivots, time_range=time_range))
+    return build_suggestions(kql, alerts, total, pivots, agg_result, time_range)
+
+def scoped_status_summary(headers, allowed):
+    """只針對 allowed 內的 agent 統計連線狀態"""
+    counts = {"active": 0, "disconnected": 0, "never_connected": 0, "pending": 0, "total": 0}
+    if not allowed:
+        return counts
+    resp = HTTP.get(f"{BASE_URL}/agents", headers=headers, verify=False,
+                    params={"agents_list": ",".join(sorted(allowed)), "select": "status", "limit": 100000})
+    resp.raise_for_status()
+    for agent in resp.json().get('data', {}).get('affected_items', []):
+        status = agent.get('status')
+        counts[status] = counts.get(status, 0) + 1
+        counts["total"] += 1
+    return counts
 
 # --- 3. AI 工具定義區 (Tools) ---
 
 @mcp.tool()
-def list_agents() -> str:
-    """列出所有受監控的主機 (Agents) 及其連線狀態。
-    當使用者問「有哪些電腦受監控？」或是「檢查 Agent 狀態」時使用此工具。
+def list_agents(status: str | None = None, platform: str | None = None, os_name: str | None = None,
+                version: str | None = None, group: str | None = None, name: str | None = None,
+                ip: str | None = None, last_keepalive_older_than: str | None = None, limit: int = 0) -> str:
+    """列出受監控的主機 (Agents) 及其連線狀態，可依條件篩選 (條件之間為 AND)。
+    當使用者問「有哪些電腦受監控？」、「檢查 Agent 狀態」，或主機盤點類的問題
+    (例如「哪些 Ubuntu 主機的 agent 還在 4.8 以前、超過 7 天沒回報？」) 時使用此工具。
+    - status: 連線狀態，可用逗號列出多個 (active / disconnected / never_connected / pending)
+    - platform: 平台 (ubuntu、windows、darwin…)；os_name: 作業系統名稱包含的字串 (例如 "Windows Server")
+    - version: 4.7.2 (完全相符)、4.7 (所有 4.7.x) 或比較式 <4.8.0、>=4.7
+    - group: agent group；name: 名稱包含的字串；ip: IP 或 CIDR 網段 (例如 10.0.0.0/8)
+    - last_keepalive_older_than: 最後一次 keepalive 早於多久以前 (例如 7d、12h)
+    條件盡量在 manager 端以 API 的 q 語法篩選，並自動分頁取回所有符合的 agent；limit 限制回傳台數 (0 = 全部)。
     """
-    token = get_token()
-    if not token: 
-        return "錯誤: 無法連線至 Wazuh API，請檢查帳號密碼或網路連線。"
-    
-    headers = {"Authorization": f"Bearer {token}"}
     try:
-        resp = requests.get(f"{BASE_URL}/agents", headers=headers, verify=False, params={"pretty": "true"})
-        if resp.status_code == 200:
-            data = resp.json().get('data', {}).get('affected_items', [])
-            # 直接回傳 JSON 結構，讓 Claude 展現它的分析能力
-            return json.dumps(data, indent=2, ensure_ascii=False)
-        else:
-            return f"API 回傳錯誤: {resp.status_code} - {resp.text}"
-    except Exception as e:
-        return f"發生例外錯誤: {str(e)}"
+        params, checks = compile_agent_filters(status, platform, os_name, version, group, name, ip,
+                                               last_keepalive_older_than)
+    except ValueError as e:
+        return f"錯誤: {e}"
+    data, error = api_get_all("/agents", params)
+    if error:
+        return error
+    agents = data["affected_items"]
+    allowed = scoped_agents()
+    if allowed is not None:
+        agents = [a for a in agents if a.get('id') in allowed]
+    agents = [a for a in agents if all(check(a) for _, check in checks)]
+    if limit > 0:
+        agents = agents[:limit]
+    # 直接回傳 JSON 結構，讓 Claude 展現它的分析能力
+    return json.dumps(agents, indent=2, ensure_ascii=False)
 
 @mcp.tool()
 def get_infrastructure_status() -> str:
@@ -72,8 +835,13 @@ def get_infrastructure_status() -> str:
     
     headers = {"Authorization": f"Bearer {token}"}
     try:
+        allowed = scoped_agents()
+        if allowed is not None:
+            # 有租戶範圍時只統計自己的主機，不能使用全域的 summary
+            summary = {"connection": scoped_status_summary(headers, allowed)}
+            return f"【資安態勢報告】\n{json.dumps(summary, indent=2, ensure_ascii=False)}"
         # 取得 Agent 的統計數據 (多少個 Active, 多少個 Disconnected)
-        resp = requests.get(f"{BASE_URL}/agents/summary/status", headers=headers, verify=False)
+        resp = HTTP.get(f"{BASE_URL}/agents/summary/status", headers=headers, verify=False)
         if resp.status_code == 200:
             summary = resp.json().get('data', {})
             return f"【資安態勢報告】\n{json.dumps(summary, indent=2, ensure_ascii=False)}"
@@ -82,6 +850,3147 @@ def get_infrastructure_status() -> str:
     except Exception as e:
         return f"發生錯誤: {str(e)}"
 
-# --- 4. 啟動區 ---
+@mcp.tool()
+def continue_result(token: str) -> str:
+    """取得被截斷的工具回應的下一段。
+    工具回應超過大小上限時只會回傳第一段，並在 _truncated.continuation 附上 token；
+    以該 token 呼叫此工具取得下一段，直到回傳的 continuation 為 null。
+    """
+    try:
+        page = RESULT_PAGES.next_page(token, current_principal())
+    except ValueError as e:
+        return f"錯誤: {e}"
+    return json.dumps(page, indent=2, ensure_ascii=False)
+
+@mcp.tool()
+def search_alerts(kql: str = "", limit: int = 20, output_format: str = "json",
+                  write_file: bool = False, dedupe_by: list[str] | None = None,
+                  group_by: list[str] | None = None, sample: str = "last",
+                  sample_seed: int | None = None, min_severity: str | None = None,
+                  suggestions: bool = False, retry_partial: bool = False,
+                  time_range: str | None = None, time_to: str = "now",
+                  apply_global_filters: bool = True, split_environments: bool = True,
+                  flag_first_seen: bool = False, agent_groups: list[str] | None = None,
+                  sparkline: bool = False) -> str:
+    """以 KQL / Lucene 語法搜尋 Wazuh 告警 (Alerts)。
+    當使用者想找特定條件的告警，例如「找出 level 10 以上的 SSH 登入失敗」時使用。
+    kql 範例: rule.level >= 10 and rule.groups:sshd and not agent.name:"honeypot-*"
+    time_range / time_to 指定時間範圍 (例如 time_range="now-90d")；超過兩週的範圍會自動
+    切成每週一段平行查詢再合併，結果中的 slices 會列出每一段的狀態。
+    min_severity 可直接用嚴重度篩選 (info/low/medium/high/critical)，不必記規則等級數字；
+    每筆告警的 rule.severity 會標示標準化後的嚴重度。
+    output_format="arrow" 會回傳 base64 編碼的 Arrow IPC (write_file=True 則寫成 .arrow 檔)，
+    適合大量結果直接載入 pandas DataFrame。
+    告警太多太吵時:
+    - dedupe_by: 將同規則、同主機、且這些欄位相同的告警合併成一列並附上 _count
+      (例如 ["data.srcip"])，可將重複告警壓縮 10~100 倍。
+      欄位加上 ":pattern" 會先把內容的路徑、GUID、IP、數字等換成佔位符再比較，
+      例如 ["full_log:pattern"] 可把只差在來源 IP / PID 的訊息歸成同一個模式。
+    - group_by: 改由 Indexer 對所有符合的告警分組計數 (例如 ["rule.id", "agent.name"])，
+      每組回傳 count、first_seen、last_seen 與一筆代表告警。
+      有設定主機環境 (production / honeypot / lab) 時，分組結果會依環境分開統計，
+      避免蜜罐的大量告警扭曲正式環境的數字；split_environments=False 則混在一起。
+    面對上百萬筆告警時可用 sample 取得具代表性的預覽 (total 仍是精確總數):
+    - "last" (預設，最新的 N 筆)、"first" (最早的 N 筆)
+    - "random" (隨機抽樣，回傳 sample_seed 可重現同一份樣本)
+    - "stratified:agent" / "stratified:rule" (依主機或規則分層，按比例分配名額)
+    suggestions=True 時會額外附上下一步建議 (例如「另有 3 台主機也出現這個雜湊」)
+    與可直接呼叫的工具參數，適合連續追查。
+    若 Indexer 只回傳部分結果 (分片失敗或逾時)，結果最前面會有 incomplete 警告；
+    retry_partial=True 會自動縮小查詢重試。
+    組織設定的全域篩選 (例如排除實驗室主機) 預設會套用，結果的 global_filters 會列出內容；
+    需要刻意查看被排除的資料時設 apply_global_filters=False。
+    flag_first_seen=True 會在含有「環境中首次出現」的雜湊 / 網域 / IP / User-Agent 的告警
+    加上 _first_seen 標註，這是最有價值的獵捕訊號之一。
+    agent_groups 可一次限定整個 agent group (例如 ["domain-controllers"])，不必逐台主機查詢；
+    group 成員由 Manager API 查詢並快取，可用 list_agent_groups 查看有哪些 group。
+    sparkline=True (搭配 group_by 與 time_range) 會在每個分組附上約 24 個點的時間序列、
+    峰值時段與文字趨勢圖，不必再查一次就能看出「什麼時候暴增」。
+    """
+    if output_format != "json" and "reporting" not in FEATURES:
+        return "錯誤: 此伺服器未啟用 reporting 模組，只支援 output_format=\"json\""
+    try:
+        query = build_query(kql, min_severity, agent_groups)
+    except KQLSyntaxError as e:
+        return f"查詢語法錯誤: {str(e)}"
+    except ValueError as e:
+        return f"錯誤: {str(e)}"
+    if time_range:
+        query = {"bool": {"filter": [query, QUERIES.render("common.time_range", gte=time_range, lte=time_to)]}}
+
+    body = {
+        "size": limit,
+        "sort": [{"timestamp": {"order": "desc"}}],
+        "track_total_hits": True,
+        "query": query
+    }
+    trend_interval = None
+    if group_by:
+        body["size"] = 0
+        body["aggs"] = QUERIES.render("search.group_by", group_by=group_by, size=GROUP_BY_MAX_GROUPS)
+        if sparkline:
+            if not time_range:
+                return "錯誤: sparkline 需要指定 time_range (例如 now-24h)"
+            try:
+                trend_interval = interval_for(time_range, time_to)
+            except ValueError as e:
+                return f"錯誤: {str(e)}"
+            body["aggs"]["groups"]["aggs"]["trend"] = QUERIES.render(
+                "common.trend", interval=trend_interval, time_from=time_range, time_to=time_to)
+        if split_environments and ENVIRONMENTS.enabled:
+            body["aggs"] = ENVIRONMENTS.split_aggs(body["aggs"])
+    else:
+        if sample_seed is None:
+            sample_seed = random.randint(0, 2**31 - 1)
+        try:
+            body = apply_sampling(body, sample, limit, sample_seed)
+        except ValueError as e:
+            return f"錯誤: {str(e)}"
+    split = None
+    if time_range and (group_by or sample in ("first", "last")):
+        try:
+            split = search_indexer_split(body, time_range, time_to, retry_partial, apply_global_filters)
+        except ValueError as e:
+            return f"錯誤: {str(e)}"
+    result, error = split if split else search_indexer(body, retry_partial=retry_partial,
+                                                       global_filters=apply_global_filters)
+    if error:
+        return error
+
+    meta = {"incomplete": result["_incomplete"]} if "_incomplete" in result else {}
+    if "_slices" in result:
+        meta["slices"] = result["_slices"]
+    if apply_global_filters and global_filters_summary():
+        meta["global_filters"] = global_filters_summary()
+    if agent_groups:
+        meta["agent_groups"] = agent_groups_summary(agent_groups)
+    hits = result.get('hits', {})
+    total = hits.get('total', {}).get('value', 0)
+    if group_by:
+        def summarize(part):
+            groups = parse_groups(part, trend_interval)
+            for g in groups:
+                if g["sample"]:
+                    SEVERITY.annotate(g["sample"])
+                    ENVIRONMENTS.annotate(g["sample"])
+            summary = {"group_count": len(groups), "groups": groups}
+            if part.get("aggregations", {}).get("groups", {}).get("after_key"):
+                summary["warning"] = f"分組數超過 {GROUP_BY_MAX_GROUPS}，只列出部分分組"
+            return summary
+
+        report = {**meta, "total": total, "group_by": group_by}
+        if "by_environment" in result.get("aggregations", {}):
+            report["environments"] = {
+                env: {"total": part["doc_count"], **summarize(part)}
+                for env, part in ENVIRONMENTS.environment_buckets(result).items()
+            }
+        else:
+            report.update(summarize(result))
+        return json.dumps(report, indent=2, ensure_ascii=False)
+
+    extra = {**meta, "total": total}
+    if sample.startswith("stratified:"):
+        alerts, strata = collect_stratified(result, limit, total)
+        extra["sample"] = {"method": sample, "seed": sample_seed, "strata": strata}
+    else:
+        alerts = [hit.get('_source', {}) for hit in hits.get('hits', [])]
+        if sample == "random":
+            extra["sample"] = {"method": sample, "seed": sample_seed}
+    for alert in alerts:
+        SEVERITY.annotate(alert)
+        if ENVIRONMENTS.enabled:
+            ENVIRONMENTS.annotate(alert)
+    if (flag_first_seen or suggestions) and "enrichment" not in FEATURES:
+        extra["enrichment_warning"] = "此伺服器未啟用 enrichment 模組，已略過首次出現標註與下一步建議"
+        flag_first_seen = suggestions = False
+    if flag_first_seen and scoped_agents() is not None:
+        extra["first_seen_warning"] = "首次出現資料庫涵蓋整個環境，租戶範圍受限的呼叫者無法使用"
+    elif flag_first_seen:
+        try:
+            FIRST_SEEN.refresh()
+            for alert in alerts:
+                FIRST_SEEN.annotate(alert, FIRST_SEEN_WINDOW)
+        except RuntimeError as e:
+            extra["first_seen_warning"] = f"首次出現資料庫更新失敗，未標註: {e}"
+    if suggestions:
+        extra["suggestions"] = suggest_pivots(kql, alerts, total)
+    if dedupe_by is not None:
+        alerts = dedupe_alerts(alerts, dedupe_by)
+    try:
+        return render_rows(alerts, output_format,
+                           output_dir=ARROW_OUTPUT_DIR if write_file else None,
+                           extra=extra)
+    except (ValueError, RuntimeError) as e:
+        return f"錯誤: {str(e)}"
+
+@feature_tool("hunting")
+def search_alerts_dsl(body: dict | str, source: str = "alerts", apply_global_filters: bool = True) -> str:
+    """直接以 OpenSearch 查詢 DSL 搜尋告警，給已經熟悉 DSL、需要 KQL 表達不了的查詢的進階獵捕人員。
+    body 是完整的 _search 本文 (JSON 物件或字串)，可用 query、aggs、sort、size、from、_source、
+    search_after、collapse、post_filter、highlight、track_total_hits、timeout 等欄位，例如:
+    {"query": {"bool": {"filter": [{"range": {"rule.level": {"gte": 12}}}]}},
+     "aggs": {"hosts": {"terms": {"field": "agent.name", "size": 20}}}, "size": 5}
+    source: alerts (wazuh-alerts-*) 或 archives (所有事件，需在 manager 啟用 logall_json)。
+    與其他工具相同會套用租戶範圍與全域篩選 (apply_global_filters=False 可略過全域篩選)，並有防護:
+    size 與聚合 bucket 數有上限、timeout 不能超過伺服器設定，不允許 script、global 聚合與讀取其他索引的查詢。
+    一般查詢請優先使用 search_alerts (KQL)。
+    """
+    if source not in ("alerts", "archives"):
+        return "錯誤: source 只支援 alerts 或 archives"
+    if isinstance(body, str):
+        try:
+            body = json.loads(body)
+        except ValueError as e:
+            return f"錯誤: body 不是有效的 JSON: {e}"
+    try:
+        body = check_dsl(body, DSL_MAX_SIZE, DSL_MAX_BUCKETS, INDEXER_QUERY_TIMEOUT)
+    except ValueError as e:
+        return f"錯誤: {e}"
+    index = ALERTS_INDEX if source == "alerts" else ARCHIVES_INDEX
+    result, error = search_indexer(body, index=index, global_filters=apply_global_filters)
+    if error:
+        return error
+    hits = result.get("hits", {})
+    total = hits.get("total", {})
+    report = {
+        "index": index,
+        "took": result.get("took"),
+        "timed_out": result.get("timed_out", False),
+        "total": total.get("value", 0) if isinstance(total, dict) else total,
+        "hits": [{**hit.get("_source", {}), "_ref": event_ref(hit),
+                  **({"_score": hit["_score"]} if hit.get("_score") is not None else {}),
+                  **({"sort": hit["sort"]} if "sort" in hit else {}),
+                  **({"highlight": hit["highlight"]} if "highlight" in hit else {})}
+                 for hit in hits.get("hits", [])],
+    }
+    if "aggregations" in result:
+        report["aggregations"] = result["aggregations"]
+    if "_incomplete" in result:
+        report = {"incomplete": result["_incomplete"], **report}
+    if apply_global_filters and global_filters_summary():
+        report["global_filters"] = global_filters_summary()
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+@mcp.tool()
+def hunt_sequence(steps: list[str], join_by: str, maxspan: str = "2m",
+                  time_range: str = "now-24h", per_step_limit: int = 1000,
+                  limit: int = 20, min_severity: str | None = None,
+                  retry_partial: bool = False, apply_global_filters: bool = True,
+                  agent_groups: list[str] | None = None) -> str:
+    """EQL 風格的序列獵捕：找出「依序發生」的一連串事件。
+    每個 steps 元素是一個 KQL 條件，事件必須在同一台 Agent 上、join_by 欄位值相同，
+    且從第一個到最後一個事件的時間差不超過 maxspan (例如 30s、2m、1h)。
+    每個序列附上 confidence (信心分數與理由) 與 provenance (原始事件、門檻、判斷邏輯版本) 供稽核。
+    範例: 程序建立後 2 分鐘內由同一 PID 發起網路連線
+      steps=["rule.groups:sysmon_event1", "rule.groups:sysmon_event3"],
+      join_by="data.win.eventdata.processId", maxspan="2m"
+    agent_groups 可把所有步驟限定在特定 agent group (例如 ["domain-controllers"])。
+    """
+    if len(steps) < 2:
+        return "錯誤: 序列至少需要兩個步驟"
+    try:
+        span = parse_duration(maxspan)
+        queries = [build_query(step, min_severity, agent_groups) for step in steps]
+    except KQLSyntaxError as e:
+        return f"查詢語法錯誤: {str(e)}"
+    except ValueError as e:
+        return f"錯誤: {str(e)}"
+
+    step_events = []
+    truncated = []
+    incomplete = {}
+    for i, query in enumerate(queries):
+        body = {
+            "size": per_step_limit,
+            "sort": [{"timestamp": {"order": "asc"}}],
+            "query": {"bool": {"filter": [
+                query,
+                {"exists": {"field": join_by}},
+                QUERIES.render("common.time_range", gte=time_range)
+            ]}}
+        }
+        result, error = search_indexer(body, retry_partial=retry_partial,
+                                       global_filters=apply_global_filters)
+        if error:
+            return f"步驟 {i + 1} 查詢失敗: {error}"
+        if "_incomplete" in result:
+            incomplete[f"step_{i + 1}"] = result["_incomplete"]
+        hits = result.get('hits', {}).get('hits', [])
+        if len(hits) >= per_step_limit:
+            truncated.append(i + 1)
+        step_events.append([
+            {**SEVERITY.annotate(hit.get('_source', {})), "_ref": event_ref(hit)} for hit in hits
+        ])
+
+    sequences = match_sequences(step_events, join_by, span, limit)
+    parameters = {"steps": steps, "join_by": join_by, "maxspan": maxspan,
+                  "time_range": time_range, "per_step_limit": per_step_limit,
+                  "min_severity": min_severity, "agent_groups": agent_groups}
+    for seq in sequences:
+        seq["confidence"] = sequence_confidence(seq["span_seconds"], span.total_seconds(),
+                                                bool(truncated or incomplete))
+        seq["provenance"] = provenance("sequence", parameters, seq["events"])
+    report = {
+        **({"incomplete": incomplete} if incomplete else {}),
+        "matched": len(sequences),
+        "step_hits": [len(events) for events in step_events],
+        "sequences": sequences
+    }
+    if truncated:
+        report["warning"] = f"步驟 {truncated} 的結果達到 per_step_limit 上限，可能漏掉部分序列，請縮小 time_range 或提高上限"
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+@mcp.tool()
+def list_rule_groups(time_range: str = "now-7d", limit: int = 50,
+                     include_unseen: bool = False, apply_global_filters: bool = True,
+                     split_environments: bool = True, sparkline: bool = False) -> str:
+    """列出資料中實際出現的規則群組 (rule.groups) 與告警數量，作為獵捕前的「活動類型目錄」。
+    當使用者問「環境裡有哪些類型的活動？」或 AI 不確定該用哪個群組篩選時，先用此工具。
+    每個群組附上最常見的規則描述與最高嚴重度；include_unseen=True 會一併列出
+    規則庫中有定義、但這段時間沒有出現的群組。
+    有設定主機環境時，各環境 (production / honeypot / lab) 的群組分開列出。
+    sparkline=True 會為每個群組附上時間序列、峰值時段與文字趨勢圖。
+    """
+    group_aggs = {"groups": {
+            "terms": {"field": "rule.groups", "size": limit},
+            "aggs": {
+                "max_level": {"max": {"field": "rule.level"}},
+                "top_rules": {"terms": {"field": "rule.description", "size": 3}},
+                "agents": {"cardinality": {"field": "agent.id"}}
+            }
+    }}
+    trend_interval = None
+    if sparkline:
+        try:
+            trend_interval = interval_for(time_range)
+        except ValueError as e:
+            return f"錯誤: {str(e)}"
+        group_aggs["groups"]["aggs"]["trend"] = QUERIES.render(
+            "common.trend", interval=trend_interval, time_from=time_range)
+    split = split_environments and ENVIRONMENTS.enabled
+    body = {
+        "size": 0,
+        "query": QUERIES.render("common.time_range", gte=time_range),
+        "aggs": ENVIRONMENTS.split_aggs(group_aggs) if split else group_aggs
+    }
+    if include_unseen:
+        # 只取群組名稱，判斷哪些規則庫群組完全沒有出現 (不受 limit 影響)
+        body["aggs"]["all_groups"] = {"terms": {"field": "rule.groups", "size": 10000}}
+    result, error = search_indexer(body, global_filters=apply_global_filters)
+    if error:
+        return error
+
+    def summarize(aggregations):
+        groups = []
+        for b in aggregations.get('groups', {}).get('buckets', []):
+            max_level = b.get('max_level', {}).get('value')
+            groups.append({
+                "group": b.get('key'),
+                "alerts": b.get('doc_count', 0),
+                "agents": b.get('agents', {}).get('value', 0),
+                "max_severity": SEVERITY.severity(max_level),
+                "description": [r.get('key') for r in b.get('top_rules', {}).get('buckets', [])]
+            })
+            if "trend" in b:
+                groups[-1]["sparkline"] = render_sparkline(b["trend"].get("buckets", []), trend_interval)
+        return groups
+
+    report = {"time_range": time_range}
+    if split:
+        report["environments"] = {
+            env: {"alerts": part["doc_count"], "groups": summarize(part["aggregations"])}
+            for env, part in ENVIRONMENTS.environment_buckets(result).items()
+        }
+    else:
+        report["groups"] = summarize(result.get('aggregations', {}))
+    if "_incomplete" in result:
+        report = {"incomplete": result["_incomplete"], **report}
+    if include_unseen:
+        data, error = api_get("/rules/groups", {"limit": 10000})
+        if error:
+            report["warning"] = f"無法取得規則庫群組清單: {error}"
+        else:
+            seen = {b.get('key') for b in result.get('aggregations', {}).get('all_groups', {}).get('buckets', [])}
+            report["unseen_groups"] = sorted(g for g in data.get('affected_items', []) if g not in seen)
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+@feature_tool("state")
+def usage_report(days: int = 7, principal: str | None = None) -> str:
+    """查詢各 principal (租戶 / 使用者) 的查詢用量與每日配額使用率。
+    當使用者問「今天查了多少資料？」「哪個客戶用量最大？」或查詢因配額被拒時使用。
+    用量包含查詢次數、掃描文件數 (docs) 與回傳資料量 (bytes)。
+    """
+    if principal is None and current_principal() != "local":
+        # 非本機呼叫者只能看到自己的用量
+        principal = current_principal()
+    elif principal and current_principal() not in ("local", principal):
+        return "錯誤: 只能查詢自己的用量"
+    report = USAGE.report(days, principal)
+    return json.dumps({"days": days, "principals": report}, indent=2, ensure_ascii=False)
+
+@mcp.tool()
+def check_data_consistency(window: str = "1h", stale_after: str = "15m") -> str:
+    """比對 Wazuh Manager API 與 Indexer 的資料是否一致，找出資料匯入 (ingestion) 斷層。
+    當查詢結果「太安靜」、懷疑 filebeat 停止或索引被設為唯讀時使用；
+    在下結論說「沒有發現威脅」之前，也建議先跑一次確認資料是完整的。
+    """
+    try:
+        stale = parse_duration(stale_after)
+        parse_duration(window)
+    except ValueError as e:
+        return f"錯誤: {str(e)}"
+
+    agents_data, error = api_get("/agents", {"limit": 100000, "select": "id,name,status,lastKeepAlive"})
+    if error:
+        return error
+    allowed = scoped_agents()
+    agents = {a.get('id'): a for a in agents_data.get('affected_items', [])
+              if allowed is None or a.get('id') in allowed}
+
+    body = {
+        "size": 0,
+        "query": QUERIES.render("common.time_range", gte=f"now-{window}"),
+        "aggs": {
+            "per_agent": {"terms": {"field": "agent.id", "size": 100000}},
+            "latest": {"max": {"field": "timestamp"}},
+            "today": {"filter": QUERIES.render("common.time_range", gte="now/d")}
+        }
+    }
+    # 檢查資料匯入時不能排除任何主機，否則被全域篩選排除的主機會被誤判為沒有資料
+    result, error = search_indexer(body, global_filters=False)
+    if error:
+        return error
+    indexed = {b['key']: b['doc_count'] for b in result['aggregations']['per_agent']['buckets']}
+
+    findings = []
+    latest = result['aggregations']['latest'].get('value_as_string')
+    latest_ts = parse_timestamp(latest) if latest else None
+    if latest_ts is None:
+        findings.append(f"Indexer 在最近 {window} 內沒有任何告警，資料匯入可能已中斷")
+    elif datetime.now(timezone.utc) - latest_ts > stale:
+        findings.append(f"Indexer 最新一筆告警時間為 {latest}，已超過 {stale_after} 沒有新資料 (filebeat 可能停止)")
+
+    silent = [f"{a['id']} ({a.get('name')})" for aid, a in agents.items()
+              if a.get('status') == 'active' and aid != '000' and aid not in indexed]
+    if silent:
+        findings.append(f"{len(silent)} 台 active 的 agent 在最近 {window} 內沒有任何告警進入 Indexer: {silent[:20]}")
+    unknown = sorted(set(indexed) - set(agents))
+    if unknown:
+        findings.append(f"Indexer 中有 Manager 已不存在的 agent 告警: {unknown[:20]}")
+
+    # Manager 端 analysisd 今日產生的告警數 vs. 實際寫入 Indexer 的數量 (租戶範圍內無法取得全域統計，略過)
+    indexed_today = None
+    manager_today = None
+    if allowed is None:
+        stats, error = api_get("/manager/stats")
+        if error:
+            findings.append(f"無法取得 Manager 告警統計: {error}")
+        else:
+            manager_today = sum(h.get('totalAlerts', 0) for h in stats.get('affected_items', []))
+            indexed_today = result['aggregations']['today']['doc_count']
+            if manager_today and indexed_today < manager_today * 0.9:
+                findings.append(
+                    f"Manager 今日產生 {manager_today} 筆告警，Indexer 只有 {indexed_today} 筆 "
+                    f"({indexed_today / manager_today:.0%})，部分告警沒有被匯入"
+                )
+
+    blocks, error = indexer_get(f"{ALERTS_INDEX}/_settings/index.blocks.*")
+    if error:
+        findings.append(f"無法檢查索引寫入狀態: {error}")
+    else:
+        blocked = sorted(name for name, cfg in blocks.items()
+                         if cfg.get('settings', {}).get('index', {}).get('blocks', {}).get('write') in (True, "true")
+                         or cfg.get('settings', {}).get('index', {}).get('blocks', {}).get('read_only_allow_delete') in (True, "true"))
+        if blocked:
+            findings.append(f"以下索引被設為禁止寫入 (常見原因: 磁碟空間不足): {blocked}")
+
+    if "_incomplete" in result:
+        findings.append("Indexer 本次查詢只回傳部分結果，以上比對可能不準確")
+    report = {
+        "status": "ok" if not findings else "inconsistent",
+        "window": window,
+        "manager_agents": len(agents),
+        "indexer_agents": len(indexed),
+        "latest_indexed_alert": latest,
+        "alerts_today": {"manager": manager_today, "indexer": indexed_today},
+        "findings": findings
+    }
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+@feature_tool("state")
+def first_seen_observables(values: list[str] | None = None, kind: str | None = None,
+                           since: str = "24h", limit: int = 100) -> str:
+    """查詢雜湊、網域、IP、User-Agent 在這個環境中「第一次出現」的時間。
+    - 給 values: 查這些值何時首次 / 最後出現 (從未出現過也會明確標示)
+    - 不給 values: 列出最近 since (例如 24h、7d) 內才第一次出現的值，可用 kind 限定
+      (hash / domain / ip / user_agent)
+    當使用者問「這個雜湊以前出現過嗎？」或「最近有什麼新出現的網域？」時使用。
+    """
+    if scoped_agents() is not None:
+        return "錯誤: 首次出現資料庫涵蓋整個環境，租戶範圍受限的呼叫者無法使用"
+    if kind and kind not in OBSERVABLE_FIELDS:
+        return f"錯誤: 未知的類型 '{kind}'，可用: {', '.join(OBSERVABLE_FIELDS)}"
+    try:
+        window = parse_duration(since)
+        FIRST_SEEN.refresh()
+    except ValueError as e:
+        return f"錯誤: {str(e)}"
+    except RuntimeError as e:
+        return f"首次出現資料庫更新失敗: {e}"
+
+    if values:
+        kinds = [kind] if kind else list(OBSERVABLE_FIELDS)
+        rows = []
+        for value in values:
+            entries = [e for e in (FIRST_SEEN.lookup(k, value) for k in kinds) if e]
+            rows.append(entries[0] if entries else {"value": value, "first_seen": None,
+                                                    "note": "在資料庫涵蓋的期間內從未出現過"})
+        return json.dumps({"observables": rows}, indent=2, ensure_ascii=False)
+
+    rows = FIRST_SEEN.new_since(datetime.now(timezone.utc) - window, kind)
+    return json.dumps({"since": since, "count": len(rows), "observables": rows[:limit]},
+                      indent=2, ensure_ascii=False)
+
+def visible_hypothesis(record):
+    """租戶範圍受限的呼叫者只看得到自己建立的假說 (佐證可能包含其他租戶看不到的事件)"""
+    return scoped_agents() is None or record.get("created_by") == current_principal()
+
+def find_hypothesis(hypothesis_id):
+    record = HYPOTHESES.get(hypothesis_id)
+    return record if record is not None and visible_hypothesis(record) else None
+
+def hypothesis_changed(record):
+    """通知訂閱 wazuh://hypotheses 或這個假說的 client"""
+    SUBSCRIPTIONS.notify_document(f"{HYPOTHESES_URI}/{record['id']}", record.get("created_by"))
+    return record
+
+@feature_tool("state")
+def record_hypothesis(statement: str | None = None, hypothesis_id: str | None = None, case: str | None = None,
+                      status: str | None = None, confidence: float | None = None, note: str | None = None) -> str:
+    """建立或更新獵捕假說 (hypothesis)，把獵捕循環「提出假說 -> 查證 -> 確認 / 推翻」記錄下來。
+    - 只給 statement: 建立新假說 (狀態 open)，回傳 id (例如 H3)；case 可填事件單號，把同一次調查的假說歸在一起
+    - 給 hypothesis_id: 更新狀態 (open / investigating / confirmed / refuted / inconclusive)、
+      信心分數 confidence (0~1) 或附上 note (例如結論的理由)；每次變更都會留下歷程
+    查證過程中用 attach_evidence 附上佐證，最後用 hypothesis_report 彙整結果。
+    當使用者說「我懷疑 web-01 被植入 webshell」「這個假說可以結案了」時使用。
+    """
+    try:
+        if hypothesis_id is None:
+            if status is not None and status != "open":
+                return "錯誤: 新建立的假說狀態一律為 open，請先附上佐證再更新狀態"
+            record = hypothesis_changed(HYPOTHESES.create(statement, current_principal(), case, confidence))
+            return json.dumps({"created": True, **brief_hypothesis(record)}, indent=2, ensure_ascii=False)
+        if find_hypothesis(hypothesis_id) is None:
+            return f"錯誤: 找不到假說 {hypothesis_id}"
+        if statement is not None or case is not None:
+            return "錯誤: 假說建立後不能修改 statement / case；想法不同時請建立新的假說"
+        record = hypothesis_changed(HYPOTHESES.update(hypothesis_id, current_principal(), status, confidence, note))
+    except ValueError as e:
+        return f"錯誤: {str(e)}"
+    return json.dumps({**brief_hypothesis(record), "history": record["history"]}, indent=2, ensure_ascii=False)
+
+@feature_tool("state")
+def attach_evidence(hypothesis_id: str, summary: str, supports: bool | None = None,
+                    refs: list[dict] | None = None, tool: str | None = None,
+                    arguments: dict | None = None) -> str:
+    """把查詢結果附到獵捕假說上作為佐證。
+    summary 簡述這筆佐證說明了什麼；supports=True 表示支持假說、False 表示反駁、不給則只是背景資訊。
+    refs 放其他工具結果中的 _ref ({"index", "id"}) 以便回查原始事件；
+    tool / arguments 記錄產生這筆結果的工具呼叫，之後可以原樣重跑驗證。
+    開放中 (open) 的假說附上第一筆佐證後會自動轉為 investigating。
+    當找到支持或推翻某個假說的告警、統計結果時使用。
+    """
+    if find_hypothesis(hypothesis_id) is None:
+        return f"錯誤: 找不到假說 {hypothesis_id}"
+    try:
+        record, evidence = HYPOTHESES.attach(hypothesis_id, current_principal(), summary, supports, refs, tool,
+                                             arguments)
+    except ValueError as e:
+        return f"錯誤: {str(e)}"
+    hypothesis_changed(record)
+    return json.dumps({"hypothesis": brief_hypothesis(record), "evidence": evidence}, indent=2, ensure_ascii=False)
+
+@feature_tool("state")
+def hypothesis_report(case: str | None = None, status: str | None = None, include_evidence: bool = True) -> str:
+    """彙整獵捕假說的結果: 已確認 (confirmed)、已推翻 (refuted)、無定論 (inconclusive) 與仍在查證中的假說，
+    各附上信心分數、支持 / 反駁的佐證數量與結論；include_evidence=True 時列出關鍵佐證
+    (確認的假說列支持的佐證，推翻的列反駁的佐證)。case 可只看某次調查。
+    當使用者要「這次獵捕的結論」「寫結案報告」或想知道還有哪些假說沒查完時使用。
+    """
+    if status is not None and status not in HYPOTHESIS_STATUSES:
+        return f"錯誤: 未知的狀態 '{status}'，可用: {', '.join(HYPOTHESIS_STATUSES)}"
+    records = HYPOTHESES.list(case, status, visible_hypothesis)
+    report = {"case": case, **build_report(records, include_evidence)}
+    if not records:
+        report["note"] = "沒有符合條件的假說；用 record_hypothesis 建立"
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+@feature_tool("hunting")
+def hunt_tls_fingerprints(kql: str = "", time_range: str = "now-7d", kind: str | None = None,
+                          fingerprints: list[str] | None = None, limit: int = 20,
+                          rare: bool = False, apply_global_filters: bool = True) -> str:
+    """獵捕網路日誌 (Suricata / Zeek) 中的 JA3 / JA3S / JA4 TLS 指紋。
+    惡意程式的 TLS 指紋換了 C2 網域或 IP 也不會變，適合找出偽裝成正常 HTTPS 的連線。
+    - 預設列出各類型最常見的指紋 (含主機數、首次 / 最後出現時間與一筆代表連線)
+    - rare=True 改列出最少見的指紋，少數主機才有的指紋通常最值得追查
+    - fingerprints: 只看這些指紋 (例如從威脅情資拿到的 JA3)
+    - kind: 限定 ja3 / ja3s / ja4
+    組織設定了已知惡意指紋清單時，結果的 known_bad_matches 會列出所有命中的指紋與主機
+    (不受 limit 影響)，top 清單中命中的指紋也會標上 known_bad。
+    當使用者問「有沒有 Cobalt Strike 的 JA3？」「哪些主機的 TLS 指紋很少見？」時使用。
+    """
+    try:
+        fields = fields_for(kind)
+        query = build_query(kql)
+    except KQLSyntaxError as e:
+        return f"查詢語法錯誤: {str(e)}"
+    except ValueError as e:
+        return f"錯誤: {str(e)}"
+    body = {
+        "size": 0,
+        "query": {"bool": {"filter": [
+            query,
+            QUERIES.render("common.time_range", gte=time_range),
+            QUERIES.render("tls.events", fields=fields, values=fingerprints)
+        ]}},
+        "aggs": QUERIES.render("tls.aggregations", fields=fields, limit=limit, known_bad=TLS_BAD_FINGERPRINTS,
+                               rare=rare)
+    }
+    result, error = search_indexer(body, global_filters=apply_global_filters)
+    if error:
+        return error
+
+    top, matches = summarize_fingerprints(result.get('aggregations', {}), fields, TLS_BAD_FINGERPRINTS, limit, rare)
+    total = result.get('hits', {}).get('total', {}).get('value', 0)
+    report = {
+        "time_range": time_range,
+        "events_with_fingerprints": total,
+        "rare" if rare else "top": top,
+        "known_bad_list_size": len(TLS_BAD_FINGERPRINTS),
+        "known_bad_matches": matches,
+    }
+    if total == 0:
+        report["note"] = "這段時間沒有含 TLS 指紋的事件，請確認 Suricata / Zeek 有輸出 JA3 / JA4 欄位並匯入 Wazuh"
+    if "_incomplete" in result:
+        report = {"incomplete": result["_incomplete"], **report}
+    if apply_global_filters and global_filters_summary():
+        report["global_filters"] = global_filters_summary()
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+@feature_tool("hunting")
+def dns_analytics(kql: str = "", time_range: str = "now-24h", baseline: str = "now-7d",
+                  limit: int = 20, min_dga_score: float = 0.6,
+                  apply_global_filters: bool = True) -> str:
+    """分析 DNS 查詢事件 (Sysmon Event 22、Suricata、Zeek)，找出 DGA 與 C2 跡象。
+    結果包含:
+    - agents: 每台主機的查詢數、NXDOMAIN 比例與新出現網域數 (NXDOMAIN 比例高的主機在前)
+    - dga_candidates: 名稱像亂碼的網域 (字元熵、母音 / 數字比例) 與 DGA 可能性分數
+    - rare_domains: 只有一台主機查詢過、次數最少的網域
+    「新出現」是指在 time_range 內有查詢、但 baseline ~ time_range 期間沒有出現過的網域。
+    當使用者問「有沒有主機在連 DGA 網域？」「哪台主機 NXDOMAIN 特別多？」時使用。
+    """
+    try:
+        query = build_query(kql)
+    except KQLSyntaxError as e:
+        return f"查詢語法錯誤: {str(e)}"
+    except ValueError as e:
+        return f"錯誤: {str(e)}"
+    body = {
+        "size": 0,
+        "query": QUERIES.render("dns.events", filters=[query, QUERIES.render("common.time_range", gte=time_range)]),
+        "aggs": QUERIES.render("dns.window_aggregations")
+    }
+    result, error = search_indexer(body, global_filters=apply_global_filters)
+    if error:
+        return error
+    aggregations = result.get('aggregations', {})
+
+    seen_before = set()
+    domains = collect_domains(aggregations)
+    if domains:
+        baseline_body = {
+            "size": 0,
+            "query": QUERIES.render("dns.events", filters=[query, QUERIES.render("common.time_range", gte=baseline, lt=time_range)]),
+            "aggs": QUERIES.render("dns.baseline_aggregations", domains=domains)
+        }
+        baseline_result, error = search_indexer(baseline_body, global_filters=apply_global_filters)
+        if error:
+            return f"基準期間查詢失敗: {error}"
+        seen_before = collect_seen(baseline_result.get('aggregations', {}))
+
+    report = {"time_range": time_range, "baseline": baseline,
+              **summarize_dns(aggregations, seen_before, limit, min_dga_score)}
+    if report["truncated"]:
+        report["warning"] = "網域數量超過統計上限，罕見網域與新網域的計算可能不完整，請縮小 time_range 或加上 kql 條件"
+    if not domains:
+        report["note"] = "這段時間沒有 DNS 查詢事件，請確認 Sysmon Event 22 / Suricata / Zeek 的 DNS 日誌有匯入 Wazuh"
+    if "_incomplete" in result:
+        report = {"incomplete": result["_incomplete"], **report}
+    if apply_global_filters and global_filters_summary():
+        report["global_filters"] = global_filters_summary()
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+@feature_tool("hunting")
+def privileged_command_summary(kql: str = "", time_range: str = "now-24h", baseline: str = "now-30d",
+                               max_events: int = 5000, limit: int = 20,
+                               apply_global_filters: bool = True) -> str:
+    """彙整 sudo / su / pkexec 的使用情形 (來自 sudo 日誌與 auditd)，依使用者與主機分組。
+    每組列出次數、使用的工具、切換成的目標帳號與最常執行的指令，並標出:
+    - first_time_user: 在 baseline ~ time_range 期間從來沒有用過特權指令的帳號
+    - commands_outside_baseline: 不在組織設定的正常指令清單中的指令 (有設定清單時)
+    有標記的組排在最前面。
+    當使用者問「最近誰用了 sudo？」「有沒有人用 root 跑了奇怪的指令？」時使用。
+    """
+    try:
+        query = build_query(kql)
+    except KQLSyntaxError as e:
+        return f"查詢語法錯誤: {str(e)}"
+    except ValueError as e:
+        return f"錯誤: {str(e)}"
+    body = {
+        "size": max_events,
+        "_source": PRIVILEGED_SOURCE_FIELDS,
+        "sort": [{"timestamp": {"order": "desc"}}],
+        "query": {"bool": {"filter": [query, QUERIES.render("privileged.events"), QUERIES.render("common.time_range", gte=time_range)]}}
+    }
+    result, error = search_indexer(body, global_filters=apply_global_filters)
+    if error:
+        return error
+    alerts = [h['_source'] for h in result.get('hits', {}).get('hits', [])]
+    total = result.get('hits', {}).get('total', {}).get('value', 0)
+
+    baseline_body = {
+        "size": 0,
+        "query": {"bool": {"filter": [QUERIES.render("privileged.events"), QUERIES.render("common.time_range", gte=baseline, lt=time_range)]}},
+        "aggs": QUERIES.render("privileged.users_aggregation")
+    }
+    baseline_result, error = search_indexer(baseline_body, global_filters=apply_global_filters)
+    if error:
+        return f"基準期間查詢失敗: {error}"
+    known_users = collect_users(baseline_result.get('aggregations', {}))
+
+    rows = summarize_privileged(alerts, known_users, PRIVILEGED_COMMAND_BASELINE, limit)
+    report = {
+        "time_range": time_range,
+        "baseline": baseline,
+        "events": total,
+        "command_baseline_size": len(PRIVILEGED_COMMAND_BASELINE),
+        "summary": rows[:limit],
+    }
+    if len(alerts) < total:
+        report["warning"] = f"只分析了最新的 {len(alerts)} / {total} 筆事件，請縮小 time_range 或提高 max_events"
+    if not PRIVILEGED_COMMAND_BASELINE:
+        report["note"] = "未設定 PRIVILEGED_COMMAND_BASELINE，不會標記基準外的指令"
+    if "_incomplete" in result:
+        report = {"incomplete": result["_incomplete"], **report}
+    if apply_global_filters and global_filters_summary():
+        report["global_filters"] = global_filters_summary()
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+@feature_tool("hunting")
+def hunt_persistence(kql: str = "", time_range: str = "now-7d", mechanisms: list[str] | None = None,
+                     max_events: int = 2000, apply_global_filters: bool = True) -> str:
+    """綜合獵捕持久化機制，整理成每台主機的持久化候選清單。
+    涵蓋: 排程工作 (schtasks / at / 事件 4698)、服務安裝 (sc create / 事件 7045、4697)、
+    登錄檔 Run 機碼 (FIM / Sysmon 13)、cron 與 systemd 單元檔的新增或修改。
+    每個候選附上名稱、會被執行的內容與建立脈絡 (使用者、建立程序、父程序、觸發規則)，
+    以及可回查原始事件的 _ref。mechanisms 可限定機制，例如 ["service", "registry_run_key"]。
+    當使用者問「這台主機有沒有被植入持久化？」或入侵後要盤點攻擊者留下的落腳點時使用。
+    """
+    try:
+        persistence = QUERIES.render("persistence.events", mechanisms=mechanisms or list(PERSISTENCE_MECHANISMS))
+        query = build_query(kql)
+    except KQLSyntaxError as e:
+        return f"查詢語法錯誤: {str(e)}"
+    except ValueError as e:
+        return f"錯誤: {str(e)}"
+    body = {
+        "size": max_events,
+        "sort": [{"timestamp": {"order": "desc"}}],
+        "query": {"bool": {"filter": [query, QUERIES.render("common.time_range", gte=time_range)],
+                           "must": [persistence]}}
+    }
+    result, error = search_indexer(body, global_filters=apply_global_filters)
+    if error:
+        return error
+    hits = result.get('hits', {}).get('hits', [])
+    total = result.get('hits', {}).get('total', {}).get('value', 0)
+
+    report = {
+        "time_range": time_range,
+        "events": total,
+        "agents": group_by_agent(hits),
+    }
+    if len(hits) < total:
+        report["warning"] = f"只分析了最新的 {len(hits)} / {total} 筆事件，請縮小 time_range 或提高 max_events"
+    if "_incomplete" in result:
+        report = {"incomplete": result["_incomplete"], **report}
+    if apply_global_filters and global_filters_summary():
+        report["global_filters"] = global_filters_summary()
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+@feature_tool("hunting")
+def hunt_registry_changes(presets: list[str] | None = None, key_pattern: str | None = None,
+                          events: list[str] | None = None, kql: str = "", time_range: str = "now-7d",
+                          limit: int = 50, apply_global_filters: bool = True) -> str:
+    """查詢 Windows 登錄檔 FIM 事件 (需在 agent 啟用 registry monitoring)，列出變更前後的差異。
+    presets 是高價值機碼的預設組合 (不指定則全部套用):
+    - run_keys: Run / RunOnce 自動執行
+    - ifeo: Image File Execution Options 偵錯器劫持
+    - lsa: LSA 驗證 / 安全套件
+    - services: 服務設定 (ImagePath、ServiceDll)
+    - winlogon: Userinit、Shell、AppInit_DLLs
+    key_pattern 可另外指定機碼 glob 樣式，例如 "*\\Environment\\UserInitMprLogonScript"；
+    events 可限定 added / modified / deleted。
+    有設定 report_changes 時附上值的 diff，否則附上前後的雜湊與大小。
+    當使用者問「最近有沒有人改 Run 機碼？」「LSA 有沒有被掛奇怪的 DLL？」時使用。
+    """
+    if not presets and not key_pattern:
+        presets = list(REGISTRY_PRESETS)
+    try:
+        registry = QUERIES.render("registry.events", presets=presets, key_pattern=key_pattern, events=events)
+        query = build_query(kql)
+    except KQLSyntaxError as e:
+        return f"查詢語法錯誤: {str(e)}"
+    except ValueError as e:
+        return f"錯誤: {str(e)}"
+    body = {
+        "size": limit,
+        "_source": REGISTRY_SOURCE_FIELDS,
+        "sort": [{"timestamp": {"order": "desc"}}],
+        "query": {"bool": {"filter": [query, registry, QUERIES.render("common.time_range", gte=time_range)]}},
+        "aggs": {"per_agent": {"terms": {"field": "agent.name", "size": 50}}}
+    }
+    result, error = search_indexer(body, global_filters=apply_global_filters)
+    if error:
+        return error
+    total = result.get('hits', {}).get('total', {}).get('value', 0)
+    report = {
+        "time_range": time_range,
+        "presets": {name: REGISTRY_PRESETS[name]["description"] for name in presets or []},
+        "total": total,
+        "per_agent": {b['key']: b['doc_count'] for b in result.get('aggregations', {}).get('per_agent', {}).get('buckets', [])},
+        "changes": [render_change(h) for h in result.get('hits', {}).get('hits', [])],
+    }
+    if key_pattern:
+        report["key_pattern"] = key_pattern
+    if total == 0:
+        report["note"] = "沒有符合的登錄檔 FIM 事件；若預期應該有，請確認 agent 的 syscheck 有設定 windows_registry 監控"
+    if "_incomplete" in result:
+        report = {"incomplete": result["_incomplete"], **report}
+    if apply_global_filters and global_filters_summary():
+        report["global_filters"] = global_filters_summary()
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+@feature_tool("hunting")
+def autostart_inventory(kind: str | None = None, time_range: str = "now-24h",
+                        max_agents: int | None = None, limit: int = 50,
+                        max_events: int = 10000, apply_global_filters: bool = True) -> str:
+    """盤點全環境的瀏覽器擴充功能與開機自動執行項目，依普及度由低到高排列。
+    資料來自 agent 的 command monitoring 盤點腳本 (alias 由 INVENTORY_COMMAND_ALIASES 指定)
+    與 Windows Run 機碼的登錄檔 FIM；time_range 應涵蓋至少一次盤點週期。
+    只出現在一兩台主機上的項目最可疑 (流氓擴充功能、單點植入)，max_agents=2 可只看這些。
+    kind 可限定 browser_extension 或 startup。
+    當使用者問「有沒有奇怪的瀏覽器擴充功能？」「哪些自動執行項目只有少數主機有？」時使用。
+    """
+    if kind and kind not in INVENTORY_KINDS:
+        return f"錯誤: 未知的類型 '{kind}'，可用: {', '.join(INVENTORY_KINDS)}"
+    time_filter = QUERIES.render("common.time_range", gte=time_range)
+    command_body = {
+        "size": max_events,
+        "_source": ["timestamp", "agent.name", "full_log"],
+        "sort": [{"timestamp": {"order": "desc"}}],
+        "query": {"bool": {"filter": [{"terms": {"location": INVENTORY_COMMAND_ALIASES}}, time_filter]}}
+    }
+    result, error = search_indexer(command_body, global_filters=apply_global_filters)
+    if error:
+        return error
+    command_hits = result.get('hits', {}).get('hits', [])
+    command_total = result.get('hits', {}).get('total', {}).get('value', 0)
+
+    run_key_hits = []
+    incomplete = {"commands": result["_incomplete"]} if "_incomplete" in result else {}
+    if kind in (None, "startup"):
+        run_key_body = {
+            "size": max_events,
+            "_source": ["timestamp", "agent.name", "syscheck.path", "syscheck.value_name", "syscheck.event"],
+            "sort": [{"timestamp": {"order": "desc"}}],
+            "query": {"bool": {"filter": [QUERIES.render("registry.events", presets=["run_keys"]), time_filter]}}
+        }
+        run_keys, error = search_indexer(run_key_body, global_filters=apply_global_filters)
+        if error:
+            return error
+        run_key_hits = run_keys.get('hits', {}).get('hits', [])
+        if "_incomplete" in run_keys:
+            incomplete["run_keys"] = run_keys["_incomplete"]
+
+    entries = collect_entries(command_hits, run_key_hits)
+    reporting = {a for e in entries.values() for a in e["agents"]}
+    rows = rank_by_prevalence(entries, len(reporting), kind, max_agents)
+    report = {
+        "time_range": time_range,
+        "reporting_agents": len(reporting),
+        "items": len(rows),
+        "inventory": rows[:limit],
+    }
+    if len(command_hits) < command_total:
+        report["warning"] = f"盤點事件只讀取了最新的 {len(command_hits)} / {command_total} 筆，普及度可能偏低，請縮小 time_range"
+    if not command_hits:
+        report["note"] = (f"沒有找到 command monitoring 盤點輸出 (location: {INVENTORY_COMMAND_ALIASES})，"
+                          "瀏覽器擴充功能需要在 agent 設定盤點腳本才能取得")
+    if incomplete:
+        report = {"incomplete": incomplete, **report}
+    if apply_global_filters and global_filters_summary():
+        report["global_filters"] = global_filters_summary()
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+@feature_tool("hunting")
+def hunt_removable_media(kql: str = "", time_range: str = "now-7d", follow_window: str = "2h",
+                         max_events: int = 5000, limit: int = 50,
+                         apply_global_filters: bool = True) -> str:
+    """USB / 卸除式媒體的內部威脅調查: 找出裝置插入事件，並把插入後 follow_window 內
+    寫到卸除式媒體路徑 (Windows 非 C: 磁碟機、Linux /media、/run/media、/mnt) 的檔案活動
+    關聯到那次插入。
+    結果包含每次插入的裝置與複製的檔案 (sessions)、找不到對應插入事件的卸除式媒體檔案活動
+    (unmatched_file_activity)，以及依主機 (per_agent) 與使用者 (per_user) 的彙整。
+    檔案活動來自 Sysmon Event 11 與 FIM，需要 agent 有監控這些路徑。
+    kql 可限定主機或使用者，例如 agent.name:"hr-laptop-07"。
+    當使用者問「這個離職員工有沒有把資料複製到隨身碟？」時使用。
+    """
+    try:
+        window = parse_duration(follow_window)
+        query = build_query(kql)
+    except KQLSyntaxError as e:
+        return f"查詢語法錯誤: {str(e)}"
+    except ValueError as e:
+        return f"錯誤: {str(e)}"
+    time_filter = QUERIES.render("common.time_range", gte=time_range)
+
+    def fetch(match, source):
+        body = {
+            "size": max_events,
+            "_source": source,
+            "sort": [{"timestamp": {"order": "desc"}}],
+            "query": {"bool": {"filter": [query, match, time_filter]}}
+        }
+        return search_indexer(body, global_filters=apply_global_filters)
+
+    attach_result, error = fetch(QUERIES.render("removable.attach"), ATTACH_SOURCE)
+    if error:
+        return error
+    file_result, error = fetch(QUERIES.render("removable.file_copies"), FILE_SOURCE)
+    if error:
+        return error
+    attaches = [attach_event(h) for h in attach_result.get('hits', {}).get('hits', [])]
+    files = [file_event(h) for h in file_result.get('hits', {}).get('hits', [])]
+
+    sessions, orphans = correlate(attaches, files, window)
+    per_agent, per_user = summarize_removable(sessions, orphans)
+    sessions.sort(key=lambda s: len(s["files"]), reverse=True)
+    report = {
+        "time_range": time_range,
+        "follow_window": follow_window,
+        "attach_events": len(attaches),
+        "file_events": len(files),
+        "per_agent": per_agent,
+        "per_user": per_user,
+        "sessions": [{**s, "files": s["files"][:limit]} for s in sessions[:limit]],
+        "unmatched_file_activity": orphans[:limit],
+    }
+    warnings = []
+    incomplete = {}
+    for name, key, result in (("插入", "attach", attach_result), ("檔案", "files", file_result)):
+        total = result.get('hits', {}).get('total', {}).get('value', 0)
+        if total > max_events:
+            warnings.append(f"{name}事件只讀取了最新的 {max_events} / {total} 筆")
+        if "_incomplete" in result:
+            incomplete[key] = result["_incomplete"]
+    if incomplete:
+        report = {"incomplete": incomplete, **report}
+    if warnings:
+        report["warning"] = "；".join(warnings) + "，請縮小 time_range 或提高 max_events"
+    if apply_global_filters and global_filters_summary():
+        report["global_filters"] = global_filters_summary()
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+def saas_audit_report(integration, kql, time_range, activities, limit, apply_global_filters,
+                      threshold=0, window="1h"):
+    """office365 / github 稽核事件: 依 actor 聚合的活動統計，加上最新的個別事件 (大量下載類只看聚合)"""
+    try:
+        activities = check_activities(integration, activities)
+        parse_duration(window)
+        query = build_query(kql)
+    except KQLSyntaxError as e:
+        return f"查詢語法錯誤: {str(e)}"
+    except ValueError as e:
+        return f"錯誤: {str(e)}"
+    listed = [name for name in activities if name not in VOLUME_ACTIVITIES]
+    body = {
+        "size": limit if listed else 0,
+        "_source": SAAS_SOURCE_FIELDS,
+        "sort": [{"timestamp": {"order": "desc"}}],
+        "query": {"bool": {"filter": [query, QUERIES.render("saas.events", integration=integration,
+                                                                   activities=activities),
+                                      QUERIES.render("common.time_range", gte=time_range)]}},
+        "aggs": QUERIES.render("saas.actor_aggregations", integration=integration, activities=activities,
+                               limit=limit, window=window),
+    }
+    if listed != activities:
+        # 個別事件只列出每一筆都值得檢視的活動，聚合仍涵蓋全部
+        body["post_filter"] = QUERIES.render("saas.events", integration=integration, activities=listed)
+    result, error = search_indexer(body, global_filters=apply_global_filters)
+    if error:
+        return error
+    totals, actors = summarize_actors(integration, result.get("aggregations", {}), activities, threshold)
+    report = {
+        "integration": integration,
+        "time_range": time_range,
+        "activities": {name: {"description": SAAS_INTEGRATIONS[integration]["activities"][name][0],
+                              "events": totals.get(name, 0)} for name in activities},
+        "actors": actors,
+        "events": [audit_event(integration, hit) for hit in result.get("hits", {}).get("hits", [])],
+    }
+    if not any(totals.values()):
+        report["note"] = (f"沒有符合的 {integration} 稽核事件；確認 manager 已啟用 {integration} 模組 "
+                          f"(ossec.conf 的 <{integration}>) 且 time_range 足夠")
+    if "_incomplete" in result:
+        report = {"incomplete": result["_incomplete"], **report}
+    if apply_global_filters and global_filters_summary():
+        report["global_filters"] = global_filters_summary()
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+@feature_tool("hunting")
+def hunt_office365_activity(kql: str = "", time_range: str = "now-7d", activities: list[str] | None = None,
+                            mass_download_threshold: int = 100, download_window: str = "1h", limit: int = 20,
+                            apply_global_filters: bool = True) -> str:
+    """獵捕 Office 365 稽核日誌 (Wazuh office365 模組) 中帳號被盜後常見的活動，依使用者 (UserId) 彙整。
+    activities (不指定則全部):
+    - mailbox_rule: 建立或修改信箱規則 (New-InboxRule 等)；BEC 常用規則轉寄或藏起往來信件，details 含規則內容
+    - mass_download: SharePoint / OneDrive 檔案下載；某個使用者在 download_window 內的下載數達到
+      mass_download_threshold 才標記
+    - oauth_consent: 同意第三方 OAuth 應用程式的權限 (consent phishing)
+    actors 列出每個使用者各類活動的次數、來源 IP、涉及的物件數與時間範圍，有 flags 的排在前面；
+    events 是最新的信箱規則與 OAuth 同意事件 (含 _ref)。kql 可再縮小範圍，例如 data.office365.UserId:"cfo@corp.com"。
+    當使用者問「這個帳號被盜後做了什麼」「有沒有人設了轉寄規則」「誰下載了大量檔案」時使用。
+    """
+    return saas_audit_report("office365", kql, time_range, activities, limit, apply_global_filters,
+                             mass_download_threshold, download_window)
+
+@feature_tool("hunting")
+def hunt_github_activity(kql: str = "", time_range: str = "now-7d", activities: list[str] | None = None,
+                         limit: int = 20, apply_global_filters: bool = True) -> str:
+    """獵捕 GitHub 稽核日誌 (Wazuh github 模組) 中的高風險操作，依 actor 彙整。
+    activities (不指定則全部):
+    - repo_deletion: 刪除 repository (repo.destroy)
+    - token_creation: 建立 personal access token、OAuth token、SSH key 或 deploy key，攻擊者常藉此保留存取權
+    actors 列出每個 actor 各類操作的次數、來源 IP、涉及的 repository 數與時間範圍；
+    events 是最新的個別操作 (含 repository、org、來源國家與 _ref)。kql 可再縮小範圍，例如 data.github.org:"corp"。
+    當使用者問「誰刪了這個 repo」「最近有誰建立了新的 token」或調查開發者帳號外洩時使用。
+    """
+    return saas_audit_report("github", kql, time_range, activities, limit, apply_global_filters)
+
+@feature_tool("hunting")
+def get_osquery_results(query_name: str | None = None, pack: str | None = None, agent: str | None = None,
+                        action: str | None = None, kql: str = "", time_range: str = "now-24h", limit: int = 100,
+                        apply_global_filters: bool = True) -> str:
+    """取回 Wazuh osquery wodle 收集的排程查詢結果，讓已部署 osquery 的環境直接用既有查詢獵捕。
+    - query_name: 查詢名稱 (例如 listening_ports)，同時比對單獨的查詢與 pack 裡的同名查詢
+    - pack: 只看某個 pack (例如 incident-response)
+    - agent: agent 名稱；action: added / removed (差異模式) 或 snapshot
+    - kql 可比對結果欄位，例如 data.osquery.columns.port:4444
+    queries 列出符合條件的各查詢的結果筆數、agent 數與最新結果時間 (不指定 query_name 時可用來看有哪些查詢)；
+    results 是最新的結果列 (查詢結果的欄位放在 columns，附 _ref)。
+    當使用者問「osquery 有沒有看到哪台主機在聽 4444 port」「列出 crontab pack 最近的新增項目」時使用。
+    """
+    try:
+        filters = [QUERIES.render("osquery.results", query_name=query_name, pack=pack, action=action),
+                   build_query(kql), QUERIES.render("common.time_range", gte=time_range)]
+    except KQLSyntaxError as e:
+        return f"查詢語法錯誤: {str(e)}"
+    except ValueError as e:
+        return f"錯誤: {str(e)}"
+    if agent:
+        filters.append(QUERIES.render("common.agent", name=agent))
+    body = {
+        "size": limit,
+        "_source": OSQUERY_SOURCE_FIELDS,
+        "sort": [{"timestamp": {"order": "desc"}}],
+        "track_total_hits": True,
+        "query": {"bool": {"filter": filters}},
+        "aggs": QUERIES.render("osquery.summary_aggregations", limit=50),
+    }
+    result, error = search_indexer(body, global_filters=apply_global_filters)
+    if error:
+        return error
+    hits = result.get("hits", {}).get("hits", [])
+    total = result.get("hits", {}).get("total", {}).get("value", 0)
+    report = {
+        "time_range": time_range,
+        "total": total,
+        "queries": summarize_queries(result.get("aggregations", {})),
+        "results": [osquery_row(hit) for hit in hits],
+    }
+    if len(hits) < total:
+        report["note"] = f"只列出最新的 {len(hits)} / {total} 筆結果，可指定 query_name / agent 或提高 limit"
+    if not total:
+        report["note"] = "沒有符合的 osquery 結果；確認 agent 已啟用 osquery wodle (<wodle name=\"osquery\">) 且查詢名稱正確"
+    if "_incomplete" in result:
+        report = {"incomplete": result["_incomplete"], **report}
+    if apply_global_filters and global_filters_summary():
+        report["global_filters"] = global_filters_summary()
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+@feature_tool("hunting")
+def get_command_output(command: str | None = None, agent: str | None = None, kql: str = "",
+                       time_range: str = "now-24h", source: str = "archives", latest_only: bool = True,
+                       limit: int = 200, apply_global_filters: bool = True) -> str:
+    """取回 Wazuh 定期執行的指令輸出 (command wodle、logcollector 的 command / full_command)，
+    例如 netstat 的監聽 port、自訂盤點腳本的結果。
+    - command: alias / tag 或指令 (例如 "netstat listening ports")，完全相符或包含都算
+    - agent: agent 名稱；kql 可比對輸出內容，例如 full_log:"4444"
+    - source: archives (預設，指令輸出多半只觸發等級 0 的規則，需在 manager 啟用 logall_json) 或 alerts
+      (只有輸出改變時才告警的內建規則，例如 netstat 533、df 531)
+    - latest_only=True 時每個 agent 的每個指令只回傳最近一次執行的輸出；False 則列出 time_range 內的所有輸出
+    commands 列出有哪些指令、各自的事件數、agent 數與最近一次輸出時間；outputs 是輸出內容 (附 _ref)。
+    當使用者問「web-01 最近一次 netstat 的結果」「這個盤點腳本在哪些主機輸出了什麼」時使用。
+    """
+    if source not in ("alerts", "archives"):
+        return "錯誤: source 只支援 alerts 或 archives"
+    try:
+        filters = [QUERIES.render("commands.output", command=command), build_query(kql),
+                   QUERIES.render("common.time_range", gte=time_range)]
+    except KQLSyntaxError as e:
+        return f"查詢語法錯誤: {str(e)}"
+    except ValueError as e:
+        return f"錯誤: {str(e)}"
+    if agent:
+        filters.append(QUERIES.render("common.agent", name=agent))
+    body = {
+        "size": limit,
+        "_source": COMMAND_SOURCE_FIELDS,
+        "sort": [{"timestamp": {"order": "desc"}}],
+        "track_total_hits": True,
+        "query": {"bool": {"filter": filters}},
+        "aggs": QUERIES.render("commands.summary_aggregations", limit=50),
+    }
+    result, error = search_indexer(body, index=ALERTS_INDEX if source == "alerts" else ARCHIVES_INDEX,
+                                   global_filters=apply_global_filters)
+    if error:
+        return error
+    hits = result.get("hits", {}).get("hits", [])
+    total = result.get("hits", {}).get("total", {}).get("value", 0)
+    outputs = [output_event(hit) for hit in hits]
+    report = {
+        "time_range": time_range,
+        "source": source,
+        "total": total,
+        "commands": summarize_commands(result.get("aggregations", {})),
+        "outputs": latest_runs(outputs) if latest_only else outputs,
+    }
+    if len(hits) < total:
+        report["note"] = f"只讀取了最新的 {len(hits)} / {total} 筆輸出，可指定 command / agent 或提高 limit"
+    if not hits and source == "archives":
+        report["note"] = (f"{ARCHIVES_INDEX} 沒有符合的指令輸出；請確認 manager 已啟用 <logall_json>yes</logall_json>，"
+                          "或以 source=alerts 查詢輸出改變時的告警")
+    if "_incomplete" in result:
+        report = {"incomplete": result["_incomplete"], **report}
+    if apply_global_filters and global_filters_summary():
+        report["global_filters"] = global_filters_summary()
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+@feature_tool("hunting")
+def firewall_summary(group_by: str = "src", action: str | None = "deny", kql: str = "",
+                     time_range: str = "now-24h", interval: str = "1h", limit: int = 20,
+                     spike_factor: float = 3.0, apply_global_filters: bool = True) -> str:
+    """彙整防火牆 / NAT 日誌 (pfSense、iptables、Windows Firewall)，不必手寫 DSL 就能做網路分流。
+    group_by 指定分組維度: src (來源 IP)、dst (目的 IP)、port (目的連接埠)、policy (防火牆政策 / 規則)；
+    action 可為 deny (預設)、allow，或 None 表示全部。
+    每組附上允許 / 拒絕數量、涉及的主機數，以及依 interval 切段後超過中位數 spike_factor 倍的突增時段；
+    overall_spikes 是整體流量的突增時段，spiking 列出有突增的值。
+    當使用者問「誰被防火牆擋最多次？」「哪個連接埠的拒絕量突然暴增？」時使用。
+    """
+    try:
+        parse_duration(interval)
+        firewall = QUERIES.render("firewall.events", action=action)
+        aggs = QUERIES.render("firewall.summary_aggregations", dimension=group_by, limit=limit, interval=interval,
+                              time_range=time_range)
+        query = build_query(kql)
+    except KQLSyntaxError as e:
+        return f"查詢語法錯誤: {str(e)}"
+    except ValueError as e:
+        return f"錯誤: {str(e)}"
+    body = {
+        "size": 0,
+        "query": {"bool": {"filter": [query, firewall, QUERIES.render("common.time_range", gte=time_range)]}},
+        "aggs": aggs
+    }
+    result, error = search_indexer(body, global_filters=apply_global_filters)
+    if error:
+        return error
+    total = result.get('hits', {}).get('total', {}).get('value', 0)
+    report = {
+        "time_range": time_range,
+        "group_by": group_by,
+        "fields": FIREWALL_DIMENSIONS[group_by],
+        "action": action or "all",
+        "events": total,
+        **summarize_firewall(result.get('aggregations', {}), group_by, limit, spike_factor),
+    }
+    if total == 0:
+        report["note"] = "沒有防火牆日誌；請確認 pfSense / iptables / Windows Firewall (事件 5152、5156、5157) 有匯入 Wazuh"
+    if "_incomplete" in result:
+        report = {"incomplete": result["_incomplete"], **report}
+    if apply_global_filters and global_filters_summary():
+        report["global_filters"] = global_filters_summary()
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+@feature_tool("hunting")
+def wazuh_cluster_messages(kql: str = "", time_range: str = "now-24h", source: str = "alerts",
+                           field: str = "full_log", limit: int = 5000, top: int = 40,
+                           similarity: float = DEFAULT_SIMILARITY, examples: int = 3,
+                           apply_global_filters: bool = True) -> str:
+    """把大量日誌訊息歸納成模板 (Drain 演算法)，讓分析師看 40 個模式而不是 4 萬行。
+    取回符合 kql 的最新 limit 筆 (最多 10000)，依 field (預設 full_log) 的內容分群，
+    IP、路徑、GUID、數字等變動部分先換成佔位符，其餘不同的位置以 <*> 表示。
+    source: alerts (告警) 或 archives (所有事件，需在 manager 啟用 logall_json)。
+    similarity 越高分得越細 (0-1，預設 0.5)。每個模板附上筆數、時間範圍、主要規則 / 主機與 examples 筆範例。
+    當使用者說「這幾千筆告警大概是哪幾種訊息」或「幫我歸納一下這台主機的日誌」時使用。
+    """
+    if source not in ("alerts", "archives"):
+        return "錯誤: source 只支援 alerts 或 archives"
+    if not 1 <= limit <= 10000:
+        return "錯誤: limit 必須介於 1 到 10000"
+    try:
+        miner = Drain(similarity=similarity, max_examples=examples)
+        query = build_query(kql)
+    except KQLSyntaxError as e:
+        return f"查詢語法錯誤: {str(e)}"
+    except ValueError as e:
+        return f"錯誤: {str(e)}"
+    body = {
+        "size": limit,
+        "_source": [field, "timestamp", "rule.id", "agent.name"],
+        "query": {"bool": {"filter": [query, QUERIES.render("common.time_range", gte=time_range),
+                                      {"exists": {"field": field}}]}},
+        "sort": [{"timestamp": {"order": "desc"}}],
+    }
+    result, error = search_indexer(body, index=ALERTS_INDEX if source == "alerts" else ARCHIVES_INDEX,
+                                   global_filters=apply_global_filters)
+    if error:
+        return error
+    hits = [hit.get("_source", {}) for hit in result.get("hits", {}).get("hits", [])]
+    with measure("render"):
+        for event in hits:
+            message = get_field(event, field)
+            if message:
+                miner.add(str(message), timestamp=event.get("timestamp"), rule=get_field(event, "rule.id"),
+                          agent=get_field(event, "agent.name"))
+    total = result.get("hits", {}).get("total", {})
+    report = {
+        "time_range": time_range,
+        "source": source,
+        "field": field,
+        "matched": total.get("value", 0) if isinstance(total, dict) else total,
+        "analyzed": len(hits),
+        "templates": len(miner.clusters),
+        "clusters": miner.top(top),
+    }
+    notes = []
+    if report["matched"] > len(hits):
+        notes.append(f"只分析了最新的 {len(hits)} 筆，可縮小 kql / time_range 或提高 limit")
+    if not hits and source == "archives":
+        notes.append(f"{ARCHIVES_INDEX} 沒有資料；請確認 manager 已啟用 <logall_json>yes</logall_json>")
+    if notes:
+        report["notes"] = notes
+    if apply_global_filters and global_filters_summary():
+        report["global_filters"] = global_filters_summary()
+    if "_incomplete" in result:
+        report = {"incomplete": result["_incomplete"], **report}
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+@feature_tool("hunting")
+def scan_credential_exposure(kql: str = "", time_range: str = "now-7d", source: str = "alerts",
+                             field: str = "full_log", kinds: list[str] | None = None, limit: int = 5000,
+                             max_findings: int = 100, apply_global_filters: bool = True) -> str:
+    """掃描最近的日誌內容有沒有明文外洩的帳密 (合規稽核常見的獵捕題目)。
+    取回符合 kql 的最新 limit 筆 (最多 10000)，在 field (預設 full_log) 中找:
+    網址內含帳號密碼 (url_basic_auth)、命令列參數帶密碼 (command_line_password)、
+    password= / api_key: 這類鍵值 (password_assignment)、記錄下來的 Authorization 標頭 (authorization_header)、
+    私鑰 PEM 標頭 (private_key)；kinds 可只掃描其中幾種。
+    每筆發現附上遮蔽後的片段 (秘密只保留前兩個字元與長度)、秘密的雜湊指紋與原始事件的 _ref；
+    reused_secrets 列出在多筆事件 / 多台主機出現的同一組秘密。
+    source: alerts (告警) 或 archives (所有事件，需在 manager 啟用 logall_json)。
+    當使用者問「有沒有密碼被寫進日誌？」「哪些主機的命令列帶著明文密碼？」時使用。
+    """
+    if source not in ("alerts", "archives"):
+        return "錯誤: source 只支援 alerts 或 archives"
+    if not 1 <= limit <= 10000:
+        return "錯誤: limit 必須介於 1 到 10000"
+    unknown = sorted(set(kinds or []) - set(CREDENTIAL_KINDS))
+    if unknown:
+        return f"錯誤: 未知的 kinds: {', '.join(unknown)} (可用: {', '.join(CREDENTIAL_KINDS)})"
+    try:
+        query = build_query(kql)
+    except KQLSyntaxError as e:
+        return f"查詢語法錯誤: {str(e)}"
+    except ValueError as e:
+        return f"錯誤: {str(e)}"
+    body = {
+        "size": limit,
+        "_source": [field, "timestamp", "rule.id", "agent.id", "agent.name"],
+        "query": {"bool": {"filter": [query, QUERIES.render("common.time_range", gte=time_range),
+                                      {"exists": {"field": field}}]}},
+        "sort": [{"timestamp": {"order": "desc"}}],
+    }
+    result, error = search_indexer(body, index=ALERTS_INDEX if source == "alerts" else ARCHIVES_INDEX,
+                                   global_filters=apply_global_filters)
+    if error:
+        return error
+    hits = result.get("hits", {}).get("hits", [])
+    with measure("render"):
+        findings = [finding for hit in hits
+                    for finding in scan_credentials(hit.get("_source", {}), field, event_ref(hit), kinds)]
+    total = result.get("hits", {}).get("total", {})
+    matched = total.get("value", 0) if isinstance(total, dict) else total
+    report = {"time_range": time_range, "source": source, "field": field, "matched": matched,
+              **summarize_credentials(findings, len(hits), max_findings)}
+    notes = []
+    if matched > len(hits):
+        notes.append(f"只掃描了最新的 {len(hits)} 筆，可縮小 kql / time_range 或提高 limit")
+    if not hits and source == "archives":
+        notes.append(f"{ARCHIVES_INDEX} 沒有資料；請確認 manager 已啟用 <logall_json>yes</logall_json>")
+    if notes:
+        report["notes"] = notes
+    if apply_global_filters and global_filters_summary():
+        report["global_filters"] = global_filters_summary()
+    if "_incomplete" in result:
+        report = {"incomplete": result["_incomplete"], **report}
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+@feature_tool("hunting")
+def entity_graph(kql: str = "", time_range: str = "now-24h", output_format: str = "json",
+                 kinds: list[str] | None = None, max_events: int = 2000, max_nodes: int = 150,
+                 apply_global_filters: bool = True) -> str:
+    """把調查範圍 (kql + time_range) 內的告警整理成實體關係圖，供 client 畫成關聯分析 (link analysis) 圖。
+    節點: agent (主機)、user、ip、hash、process；邊:
+    - connected-to: 來源 IP -> 目的 IP (沒有目的 IP 時指向主機)
+    - executed: 使用者 (或主機) -> 執行的程序 / 程序雜湊
+    - observed-with: 出現在同一筆告警的其他實體 (無方向)
+    每個節點與邊都附上次數、首次 / 最後出現時間；邊另附觸發的規則。
+    output_format: json (JSON Graph Format，預設) 或 dot (Graphviz，可直接交給 dot / viz.js 繪圖)。
+    kinds 可只保留部分種類的節點 (例如 ["ip", "agent"])；節點超過 max_nodes 時保留出現最多的。
+    當使用者說「把這次事件牽涉到的主機、帳號、IP 畫成關係圖」或想看實體之間怎麼串起來時使用。
+    """
+    if output_format not in ("json", "dot"):
+        return "錯誤: output_format 只支援 json 或 dot"
+    unknown = [kind for kind in kinds or [] if kind not in GRAPH_KINDS]
+    if unknown:
+        return f"錯誤: 未知的節點種類 {', '.join(unknown)}，可用: {', '.join(GRAPH_KINDS)}"
+    if not 1 <= max_events <= 10000:
+        return "錯誤: max_events 必須介於 1 到 10000"
+    try:
+        query = build_query(kql)
+    except KQLSyntaxError as e:
+        return f"查詢語法錯誤: {str(e)}"
+    except ValueError as e:
+        return f"錯誤: {str(e)}"
+    body = {
+        "size": max_events,
+        "_source": GRAPH_SOURCE_FIELDS,
+        "sort": [{"timestamp": {"order": "desc"}}],
+        "query": {"bool": {"filter": [query, QUERIES.render("common.time_range", gte=time_range)]}}
+    }
+    result, error = search_indexer(body, global_filters=apply_global_filters)
+    if error:
+        return error
+    alerts = [h['_source'] for h in result.get('hits', {}).get('hits', [])]
+    total = result.get('hits', {}).get('total', {}).get('value', 0)
+    with measure("render"):
+        all_nodes, all_edges = build_graph(alerts)
+        nodes, edges = prune(all_nodes, all_edges, max_nodes, set(kinds) if kinds else None)
+    label = f"{kql or '*'} ({time_range})"
+    if output_format == "dot":
+        return to_dot(nodes, edges, label)
+    metadata = {"kql": kql, "time_range": time_range, "events": total, "analyzed": len(alerts),
+                **summarize_graph(nodes, edges)}
+    if len(nodes) < len(all_nodes):
+        metadata["truncated"] = f"只保留 {len(nodes)} / {len(all_nodes)} 個節點，可縮小範圍或提高 max_nodes"
+    if len(alerts) < total:
+        metadata["warning"] = f"只分析了最新的 {len(alerts)} / {total} 筆事件，請縮小 time_range 或提高 max_events"
+    report = to_json_graph(nodes, edges, label, metadata)
+    if "_incomplete" in result:
+        report = {"incomplete": result["_incomplete"], **report}
+    if apply_global_filters and global_filters_summary():
+        report["global_filters"] = global_filters_summary()
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+@feature_tool("detection_engineering")
+def inject_test_events(mode: str = "index", alerts: list[dict] | None = None,
+                       events: list[str] | None = None, purge: bool = False) -> str:
+    """注入合成事件，用已知資料驗證獵捕查詢、規則與儀表板 (需設定 MCP_ALLOW_WRITES=true)。
+    - mode="manager": events 為原始日誌字串，透過 Manager API 送進 analysisd，
+      會經過完整的解碼與規則比對 (可用來確認規則會不會觸發)
+    - mode="index": alerts 為告警 JSON，直接寫入測試索引 (wazuh-alerts-mcptest-*)，不經過規則引擎；
+      缺少的 timestamp / agent / rule 會自動補上，並加上 rule.groups "mcp_injected" 與
+      data.mcp_injection_id (回傳的 batch_id)，之後可用 search_alerts 查詢 data.mcp_injection_id:<batch_id>
+    - purge=True: 刪除所有測試索引 (mode="index" 注入的資料)
+    當使用者想「用測試資料驗證這個獵捕查詢」或「確認新規則會不會觸發」時使用。
+    """
+    if not ALLOW_WRITES:
+        return "錯誤: 寫入類工具已停用，需在伺服器設定 MCP_ALLOW_WRITES=true"
+    if scoped_agents() is not None:
+        return "錯誤: 注入測試事件會影響整個環境，租戶範圍受限的呼叫者無法使用"
+
+    if purge:
+        result, error = indexer_request("DELETE", f"{TEST_INDEX_PREFIX}*")
+        if error:
+            return error
+        logger.warning("已刪除測試索引 %s* (principal=%s)", TEST_INDEX_PREFIX, current_principal())
+        QUERY_CACHE.invalidate()
+        return json.dumps({"purged": f"{TEST_INDEX_PREFIX}*", "acknowledged": result.get("acknowledged")},
+                          indent=2, ensure_ascii=False)
+
+    batch_id = new_batch_id()
+    if mode == "manager":
+        if not events:
+            return "錯誤: mode=\"manager\" 需要提供 events (原始日誌字串)"
+        sent = 0
+        for chunk in chunks(events):
+            _, error = api_request("POST", "/events", body={"events": chunk})
+            if error:
+                return f"已送出 {sent} / {len(events)} 筆後失敗: {error}"
+            sent += len(chunk)
+        report = {"mode": mode, "batch_id": batch_id, "sent": sent,
+                  "note": "事件會經過 analysisd 的解碼與規則比對，只有觸發規則的事件才會出現在告警中 (通常數秒內)"}
+    elif mode == "index":
+        if not alerts:
+            return "錯誤: mode=\"index\" 需要提供 alerts (告警 JSON)"
+        try:
+            docs = prepare_alerts(alerts, batch_id)
+        except ValueError as e:
+            return f"錯誤: {str(e)}"
+        index = test_index()
+        result, error = indexer_request("POST", "_bulk?refresh=wait_for", bulk_body(index, docs),
+                                        content_type="application/x-ndjson")
+        if error:
+            return error
+        failed = bulk_errors(result)
+        # 測試告警可以帶過去的 timestamp，涵蓋這些時間的歷史查詢快取已不正確
+        stamps = [parse_timestamp(str(doc["timestamp"])) for doc in docs]
+        QUERY_CACHE.invalidate([(ts, ts) for ts in stamps if ts is not None and ts.tzinfo is not None])
+        report = {"mode": mode, "batch_id": batch_id, "index": index,
+                  "indexed": len(docs) - len(failed), "query": f"data.mcp_injection_id:{batch_id}"}
+        if failed:
+            report["errors"] = failed[:5]
+    else:
+        return f"錯誤: 未知的 mode '{mode}'，可用: manager、index"
+    logger.warning("已注入測試事件 mode=%s batch=%s (principal=%s)", mode, batch_id, current_principal())
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+@feature_tool("detection_engineering")
+def map_atomic_tests(technique: str, platform: str | None = None) -> str:
+    """列出可以模擬某個 MITRE ATT&CK 技術的 Atomic Red Team 測試 (例如 T1003.001)。
+    每個測試附上支援平台、執行指令 (Invoke-AtomicTest) 與預期會留下的遙測 (evidence KQL)。
+    給父技術 (例如 T1053) 會列出所有內建的子技術測試；platform 可限定 windows / linux / macos。
+    執行測試後，用 verify_atomic_test 確認 Wazuh 是否偵測到。
+    當使用者問「要怎麼測試我們能不能偵測 LSASS dump？」時使用。
+    """
+    tests = find_tests(ATOMIC_CATALOG, technique, platform)
+    if not tests:
+        return json.dumps({"technique": technique, "tests": [],
+                           "note": "內建對照表沒有這個技術的測試，請參考 atomic-red-team 官方清單",
+                           "available_techniques": sorted(ATOMIC_CATALOG)}, indent=2, ensure_ascii=False)
+    return json.dumps({"technique": technique, "tests": tests}, indent=2, ensure_ascii=False)
+
+@feature_tool("detection_engineering")
+def verify_atomic_test(technique: str, agent: str, since: str = "now-1h",
+                       test_name: str | None = None) -> str:
+    """在執行 Atomic Red Team 測試後，確認 Wazuh 是否偵測到 (purple team 驗證)。
+    agent 是執行測試的主機名稱，since 是測試開始的時間 (例如 now-30m 或絕對時間)。
+    回傳每個測試的判定:
+    - detected: 有 rule.mitre.id 符合該技術的告警
+    - telemetry_only: 有相關日誌但沒有對應告警 (缺規則)
+    - not_detected: 連相關日誌都沒有 (缺日誌來源或測試沒有成功執行)
+    test_name 可只驗證單一測試。
+    """
+    tests = find_tests(ATOMIC_CATALOG, technique)
+    if test_name:
+        tests = [t for t in tests if t["name"] == test_name]
+    if not tests:
+        return f"錯誤: 內建對照表中找不到技術 {technique} 的測試{f' {test_name}' if test_name else ''}"
+    scope = [QUERIES.render("common.agent", name=agent), QUERIES.render("common.time_range", gte=since)]
+
+    results = []
+    for test in tests:
+        body = {
+            "size": 10,
+            "_source": ["timestamp", "rule.id", "rule.description", "rule.level", "rule.mitre.id"],
+            "sort": [{"timestamp": {"order": "desc"}}],
+            "query": {"bool": {"filter": [*scope, QUERIES.render("common.mitre", technique=test["technique"])]}}
+        }
+        alerts, error = search_indexer(body, global_filters=False)
+        if error:
+            return error
+        try:
+            evidence_query = kql_to_dsl(test["evidence"])
+        except KQLSyntaxError as e:
+            return f"對照表中 {test['name']} 的 evidence 語法錯誤: {e}"
+        evidence, error = search_indexer({"size": 0, "query": {"bool": {"filter": [*scope, evidence_query]}}},
+                                         global_filters=False)
+        if error:
+            return error
+        hits = [h['_source'] for h in alerts.get('hits', {}).get('hits', [])]
+        evidence_count = evidence.get('hits', {}).get('total', {}).get('value', 0)
+        result = verdict(hits, evidence_count)
+        results.append({
+            "technique": test["technique"],
+            "test": test["name"],
+            "verdict": result,
+            "advice": VERDICT_ADVICE[result],
+            "alerts": hits,
+            "evidence_events": evidence_count,
+            "evidence_query": test["evidence"],
+        })
+    summary = {v: sum(1 for r in results if r["verdict"] == v) for v in VERDICT_ADVICE}
+    return json.dumps({"technique": technique, "agent": agent, "since": since,
+                       "summary": summary, "results": results}, indent=2, ensure_ascii=False)
+
+def run_logtest(cases):
+    """以同一個 logtest session 執行所有測試案例，回傳結果清單；session 結束後刪除"""
+    token, results = None, []
+    try:
+        for case in cases:
+            body = {"event": case["event"], "log_format": case["log_format"], "location": case["location"]}
+            if token:
+                body["token"] = token
+            data, error = api_request("PUT", "/logtest", body=body)
+            if error:
+                return None, error
+            token = data.get("token", token)
+            output = data.get("output", {})
+            passed, problems = evaluate(case, output)
+            rule = output.get("rule", {})
+            results.append({
+                "file": case["file"], "name": case["name"], "log": case["log"], "expect": case["expect"],
+                "passed": passed,
+                "problems": problems,
+                "fired_rule": str(rule["id"]) if rule.get("id") is not None else None,
+                "level": rule.get("level"),
+                "description": rule.get("description"),
+                "decoder": output.get("decoder", {}).get("name"),
+                "messages": [m for m in data.get("messages", []) if not m.startswith("INFO")],
+            })
+        return results, None
+    finally:
+        if token:
+            api_request("DELETE", f"/logtest/sessions/{token}")
+
+def outside_rules_root(path):
+    """規則包不在 DETECTION_RULES_ROOT 底下 (解析 symlink 後) 或沒有設定 DETECTION_RULES_ROOT 時回傳錯誤訊息"""
+    if not DETECTION_RULES_ROOT:
+        return "錯誤: 伺服器沒有設定 DETECTION_RULES_ROOT，不接受伺服器主機上的規則包路徑"
+    root = os.path.realpath(DETECTION_RULES_ROOT)
+    if os.path.commonpath([root, os.path.realpath(path)]) != root:
+        return f"錯誤: 只能讀取 DETECTION_RULES_ROOT ({DETECTION_RULES_ROOT}) 底下的規則包"
+    return None
+
+@feature_tool("detection_engineering")
+def review_detection_rules(path: str, deploy: bool = False) -> str:
+    """Detection-as-code 規則審查: 驗證一包候選規則，並用範例日誌跑 logtest 確認哪些規則會觸發。
+    path 是 MCP 伺服器主機上 DETECTION_RULES_ROOT 底下的目錄或壓縮檔 (.zip / .tar.gz)，內含:
+    - *.xml 候選規則檔
+    - *.ini 測試案例 (Wazuh ruleset 測試格式: [名稱] 區段內寫 log 1 pass = <日誌>、rule = <id>、
+      alert = <等級>、decoder = <名稱>；"fail" 的日誌表示不應觸發該規則)
+    流程: XML / 屬性檢查 -> 與已載入規則的 id 衝突檢查 -> 逐行日誌跑 logtest -> 比對預期。
+    deploy=False 時 logtest 使用 manager 目前載入的規則 (適合已部署的規則回歸測試)；
+    deploy=True 會暫時把候選規則上傳到 manager (檔名 mcp_review_*)，在新的 logtest session 測試後立即刪除，
+    不需重啟 manager (需設定 MCP_ALLOW_WRITES=true)。
+    回傳每個測試的通過與否、候選規則的覆蓋情況 (被哪些測試觸發、哪些沒有任何測試)。
+    當使用者說「幫我 review 這批新規則」或「在 CI 前先確認規則會不會觸發」時使用。
+    """
+    error = outside_rules_root(path)
+    if error:
+        return error
+    if deploy:
+        if not ALLOW_WRITES:
+            return "錯誤: deploy=True 會寫入 manager 的規則目錄，需在伺服器設定 MCP_ALLOW_WRITES=true"
+        if scoped_agents() is not None:
+            return "錯誤: 部署規則會影響整個環境，租戶範圍受限的呼叫者無法使用"
+    try:
+        rules, test_files = load_bundle(path)
+        cases = [case for name, text in sorted(test_files.items()) for case in parse_tests(name, text)]
+    except (OSError, ValueError, tarfile.TarError, zipfile.BadZipFile, UnicodeDecodeError) as e:
+        return f"錯誤: 無法讀取規則包: {str(e)}"
+
+    rule_ids, errors, warnings = validate_rules(rules)
+    report = {
+        "path": path,
+        "rule_files": sorted(rules),
+        "rules": rule_ids,
+        "validation": {"valid": not errors, "errors": errors, "warnings": warnings},
+    }
+    if rule_ids:
+        loaded, error = api_get("/rules", {"rule_ids": ",".join(rule_ids), "limit": len(rule_ids)})
+        if error:
+            return error
+        conflicts = [{"rule": str(r["id"]), "loaded_from": f"{r.get('relative_dirname')}/{r.get('filename')}"}
+                     for r in loaded.get("affected_items", [])
+                     if not r.get("filename", "").startswith(RULE_REVIEW_PREFIX)]
+        if conflicts:
+            report["validation"]["conflicts"] = conflicts
+            if deploy:
+                errors.append({"error": "候選規則 id 與 manager 已載入的規則衝突，無法部署 (已部署的版本請用 deploy=False 測試)"})
+                report["validation"]["valid"] = False
+    if not cases:
+        report["note"] = "規則包內沒有 .ini 測試案例，只做了 XML 驗證"
+        return json.dumps(report, indent=2, ensure_ascii=False)
+    if deploy and errors:
+        report["note"] = "驗證失敗，未部署也未執行 logtest (錯誤的規則檔會讓 manager 無法載入規則)"
+        return json.dumps(report, indent=2, ensure_ascii=False)
+
+    deployed = []
+    try:
+        if deploy:
+            for i, (name, text) in enumerate(sorted(rules.items())):
+                filename = f"{RULE_REVIEW_PREFIX}{i}_{os.path.basename(name)}"
+                _, error = api_request("PUT", f"/rules/files/{filename}", params={"overwrite": "false"}, data=text)
+                if error:
+                    return f"上傳候選規則 {name} 失敗: {error}"
+                deployed.append(filename)
+            logger.warning("已暫時部署候選規則 %s (principal=%s)", deployed, current_principal())
+        results, error = run_logtest(cases)
+        if error:
+            return error
+    finally:
+        for filename in deployed:
+            _, error = api_request("DELETE", f"/rules/files/{filename}")
+            if error:
+                logger.error("無法刪除暫時部署的規則檔 %s: %s", filename, error)
+
+    report["ruleset"] = "candidate (暫時部署)" if deploy else "manager 目前載入的規則"
+    report["tests"] = {"total": len(results), "passed": sum(r["passed"] for r in results),
+                       "failed": sum(not r["passed"] for r in results)}
+    report["coverage"] = coverage(rule_ids, results)
+    report["results"] = results
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+def fetch_finding_events(hypothesis_id, refs, kql, time_range, max_events):
+    """草擬規則的來源事件: 已確認假說的支持佐證、_ref 清單或 kql 查詢 (三擇一)，回傳 (事件, 來源說明, 錯誤)"""
+    if sum(x is not None for x in (hypothesis_id, refs, kql)) != 1:
+        return None, None, "錯誤: hypothesis_id、refs、kql 必須剛好指定一個"
+    source = {}
+    if hypothesis_id is not None:
+        record = find_hypothesis(hypothesis_id)
+        if record is None:
+            return None, None, f"錯誤: 找不到假說 {hypothesis_id}"
+        if record["status"] != "confirmed":
+            return None, None, f"錯誤: 假說 {hypothesis_id} 的狀態為 {record['status']}，只能從已確認 (confirmed) 的假說草擬規則"
+        refs = [ref for e in record["evidence"] if e["supports"] is True for ref in e["refs"]]
+        if not refs:
+            return None, None, f"錯誤: 假說 {hypothesis_id} 沒有附上 refs 的支持佐證，請改用 refs 或 kql 指定事件"
+        source = {"hypothesis": hypothesis_id, "statement": record["statement"]}
+    if refs is not None:
+        ids = sorted({str(ref.get("id")) for ref in refs if isinstance(ref, dict) and ref.get("id")})
+        if not ids:
+            return None, None, "錯誤: refs 必須是工具結果中的 _ref 物件 ({\"index\": ..., \"id\": ...})"
+        query = QUERIES.render("common.event_refs", ids=ids[:max_events])
+        source = {**source, "refs": len(ids)}
+    else:
+        try:
+            query = {"bool": {"filter": [build_query(kql), QUERIES.render("common.time_range", gte=time_range)]}}
+        except KQLSyntaxError as e:
+            return None, None, f"查詢語法錯誤: {str(e)}"
+        except ValueError as e:
+            return None, None, f"錯誤: {str(e)}"
+        source = {"kql": kql, "time_range": time_range}
+    body = {"size": max_events, "_source": DRAFT_SOURCE_FIELDS, "sort": [{"timestamp": {"order": "desc"}}],
+            "query": query}
+    result, error = search_indexer(body)
+    if error:
+        return None, None, error
+    events = [h["_source"] for h in result.get("hits", {}).get("hits", [])]
+    if not events:
+        return None, None, "錯誤: 找不到來源事件 (或不在可存取的範圍內)"
+    return events, source, None
+
+@feature_tool("detection_engineering")
+def draft_detection_rule(hypothesis_id: str | None = None, refs: list[dict] | None = None, kql: str | None = None,
+                         time_range: str = "now-24h", fields: list[str] | None = None, level: int = 10,
+                         description: str | None = None, rule_id: int | None = None,
+                         frequency: int | None = None, timeframe: str = "5m", same_field: str | None = None,
+                         mitre: list[str] | None = None, deploy_test: bool = False, max_events: int = 50) -> str:
+    """從已確認的發現草擬 Wazuh 候選規則 (XML)，並用 logtest 驗證，加速「獵捕 -> 偵測規則」的交接。
+    來源事件三擇一: hypothesis_id (已確認假說中附有 refs 的支持佐證)、refs (工具結果中的 _ref) 或 kql + time_range。
+    規則以事件原本觸發的規則為父規則 (if_sid)，條件為 fields 指定的解碼欄位 (例如 ["data.srcip",
+    "data.win.eventdata.image"])；不指定時自動挑選所有事件共同且固定的欄位。
+    frequency (>= 2) 另外產生關聯規則: timeframe 內 (例如 5m) 同一個 same_field (例如 data.srcip) 出現 frequency 次才告警。
+    回傳 rule_xml、對應的 .ini 測試案例 (tests_ini，可連同規則交給 review_detection_rules)、XML 驗證結果與 logtest:
+    - 預設以 manager 目前的規則跑事件的原始日誌，確認解碼與父規則會觸發
+    - deploy_test=True 暫時上傳草稿規則 (需 MCP_ALLOW_WRITES=true) 確認草稿規則本身會觸發，測試後立即刪除
+    當使用者說「這個發現幫我寫成規則」「把這次獵捕的結果變成偵測」時使用。
+    """
+    if not 0 <= level <= 16:
+        return "錯誤: level 必須介於 0 到 16"
+    if frequency is not None and frequency < 2:
+        return "錯誤: frequency 至少為 2"
+    if not 1 <= max_events <= 500:
+        return "錯誤: max_events 必須介於 1 到 500"
+    if deploy_test:
+        if not ALLOW_WRITES:
+            return "錯誤: deploy_test=True 會寫入 manager 的規則目錄，需在伺服器設定 MCP_ALLOW_WRITES=true"
+        if scoped_agents() is not None:
+            return "錯誤: 部署規則會影響整個環境，租戶範圍受限的呼叫者無法使用"
+    try:
+        window = int(parse_duration(timeframe).total_seconds()) if frequency else None
+    except ValueError as e:
+        return f"錯誤: {str(e)}"
+    events, source, error = fetch_finding_events(hypothesis_id, refs, kql, time_range, max_events)
+    if error:
+        return error
+    try:
+        conditions = chosen_fields(events, fields) if fields else {f: [v] for f, v in common_fields(events).items()}
+    except ValueError as e:
+        return f"錯誤: {str(e)}"
+    parent_rules = sorted({str(get_field(e, "rule.id")) for e in events if get_field(e, "rule.id")}, key=int)
+
+    needed = 2 if frequency else 1
+    candidates = [rule_id + i for i in range(needed)] if rule_id else range(DRAFT_ID_RANGE[0], DRAFT_ID_RANGE[1] + 1)
+    loaded, error = api_get("/rules", {"rule_ids": ",".join(str(i) for i in candidates), "limit": len(candidates)})
+    if error:
+        return error
+    used = {str(r["id"]) for r in loaded.get("affected_items", [])
+            if not r.get("filename", "").startswith(RULE_REVIEW_PREFIX)}
+    if rule_id is None:
+        rule_id = free_rule_id(used, needed)
+        if rule_id is None:
+            return f"錯誤: {DRAFT_ID_RANGE[0]}-{DRAFT_ID_RANGE[1]} 已沒有可用的規則 id，請指定 rule_id"
+    elif used:
+        return f"錯誤: 規則 id {', '.join(sorted(used))} 已被 manager 載入的規則使用，請換一個 rule_id"
+
+    description = description or source.get("statement") or f"MCP 草擬規則: {get_field(events[0], 'rule.description')}"
+    xml, alert_rule = draft_rules(rule_id, level, description, parent_rules, conditions, frequency, window,
+                                  same_field, mitre)
+    rule_ids, errors, warnings = validate_rules({"draft.xml": xml})
+    cases = test_cases(events, rule_id, alert_rule, level, frequency)
+    report = {
+        "source": {**source, "events": len(events)},
+        "parent_rules": parent_rules,
+        "conditions": conditions,
+        "rules": rule_ids,
+        "alert_rule": str(alert_rule),
+        "rule_xml": xml,
+        "tests_ini": to_ini(cases) if cases else None,
+        "validation": {"valid": not errors, "errors": errors, "warnings": warnings},
+    }
+    if not conditions:
+        report["validation"]["warnings"].append({"warning": "沒有共同的解碼欄位，規則只依父規則觸發，可能過於寬鬆；請以 fields 指定條件"})
+    if not cases:
+        report["note"] = "來源事件沒有 full_log (例如 Windows eventchannel)，無法以 logtest 驗證，請自行補上原始日誌"
+        return json.dumps(report, indent=2, ensure_ascii=False)
+    if errors:
+        report["note"] = "XML 驗證失敗，未執行 logtest"
+        return json.dumps(report, indent=2, ensure_ascii=False)
+
+    deployed = None
+    try:
+        if deploy_test:
+            deployed = f"{RULE_REVIEW_PREFIX}draft_{rule_id}.xml"
+            _, error = api_request("PUT", f"/rules/files/{deployed}", params={"overwrite": "false"}, data=xml)
+            if error:
+                deployed = None
+                return f"上傳草稿規則失敗: {error}"
+            logger.warning("已暫時部署草稿規則 %s (principal=%s)", deployed, current_principal())
+        else:
+            # 草稿規則尚未載入，只確認原始日誌的解碼與父規則
+            cases = [{**case, "rule": None, "alert": None} for case in cases]
+        results, error = run_logtest(cases)
+        if error:
+            return error
+    finally:
+        if deployed:
+            _, error = api_request("DELETE", f"/rules/files/{deployed}")
+            if error:
+                logger.error("無法刪除暫時部署的草稿規則 %s: %s", deployed, error)
+
+    fired_parent = sum(r["fired_rule"] in parent_rules for r in results)
+    report["logtest"] = {
+        "ruleset": "candidate (暫時部署)" if deploy_test else "manager 目前載入的規則",
+        "total": len(results),
+        "passed": sum(r["passed"] for r in results),
+        "results": results,
+    }
+    if not deploy_test:
+        report["logtest"]["parent_rule_fired"] = fired_parent
+        if fired_parent < len(results):
+            report["logtest"]["note"] = "部分原始日誌沒有觸發父規則 (可能缺少 syslog 標頭或 location 不同)，草稿規則也不會觸發"
+        report["next_step"] = ("以 deploy_test=True 確認草稿規則會觸發，或把 rule_xml / tests_ini 存成檔案交給 "
+                               "review_detection_rules；部署前可用 simulate_rule_noise 回測會觸發幾次")
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+@feature_tool("detection_engineering")
+def simulate_rule_noise(rule_xml: str | None = None, path: str | None = None, rule_id: str | None = None,
+                        days: int = 7, source: str = "alerts", max_daily_alerts: float = 5,
+                        max_events: int = 10000) -> str:
+    """噪音預算模擬: 部署前以過去 days 天的資料回測候選規則，估計會觸發幾次、每天幾次、落在哪些 agent，
+    避免吵雜的偵測規則上線。
+    規則來源二擇一: rule_xml (例如 draft_detection_rule 回傳的 rule_xml) 或 path (伺服器主機上
+    DETECTION_RULES_ROOT 底下的規則包，同 review_detection_rules)；rule_id 只看其中一條規則。
+    source: alerts (預設，只含達到告警等級的事件) 或 archives (所有事件，需 manager 啟用 logall_json；
+    父規則等級低於告警門檻時必須使用)。
+    在伺服器端重現父規則、欄位條件與 frequency / timeframe (見 noisebudget.py)，無法重現的條件列在 unsupported。
+    每天超過 max_daily_alerts 次的規則判定為 noisy，沒有觸發的為 silent。每條規則最多比對最近 max_events 筆
+    候選事件，超過時依比例推估。
+    當使用者說「這條規則上線會不會很吵」「先回測一下再部署」時使用。
+    """
+    if (rule_xml is None) == (path is None):
+        return "錯誤: rule_xml 與 path 必須剛好指定一個"
+    if source not in ("alerts", "archives"):
+        return "錯誤: source 必須是 alerts 或 archives"
+    if not 1 <= days <= 90:
+        return "錯誤: days 必須介於 1 到 90"
+    if not 1 <= max_events <= 10000:
+        return "錯誤: max_events 必須介於 1 到 10000"
+    if path is not None:
+        error = outside_rules_root(path)
+        if error:
+            return error
+        try:
+            rules, _ = load_bundle(path)
+        except (OSError, ValueError, tarfile.TarError, zipfile.BadZipFile, UnicodeDecodeError) as e:
+            return f"錯誤: 無法讀取規則包 {path}: {str(e)}"
+    else:
+        rules = {"candidate.xml": rule_xml}
+    try:
+        parsed = parse_rules(rules)
+    except ValueError as e:
+        return f"錯誤: {str(e)}"
+    if not parsed:
+        return "錯誤: 找不到任何 <rule>"
+    if rule_id is not None and rule_id not in {rule["id"] for rule in parsed}:
+        return f"錯誤: 規則包中沒有規則 {rule_id}"
+
+    index = ALERTS_INDEX if source == "alerts" else ARCHIVES_INDEX
+    time_filter = QUERIES.render("common.time_range", gte=f"now-{days}d")
+    internal = {rule["id"] for rule in parsed}
+    fired_by, results = {}, []
+    for rule in parsed:
+        query = parent_query(rule, internal)
+        sids = rule["if_matched_sid"] if rule["frequency"] else rule["if_sid"]
+        # 父規則在這包規則內: 以父規則模擬觸發的事件為候選
+        events = [event for sid in sids if sid in internal for event in fired_by.get(sid, [])]
+        candidates = scanned = len(events)
+        if query is None or "internal_only" not in query:
+            filters = [time_filter] + ([query] if query else [])
+            body = {"size": max_events, "track_total_hits": True, "_source": NOISE_SOURCE_FIELDS,
+                    "sort": [{"timestamp": {"order": "desc"}}], "query": {"bool": {"filter": filters}}}
+            result, error = search_indexer(body, index=index)
+            if error:
+                return error
+            hits = result.get("hits", {})
+            events += [{**hit["_source"], "_ref": event_ref(hit)} for hit in hits.get("hits", [])]
+            candidates += hits.get("total", {}).get("value", 0)
+            scanned += len(hits.get("hits", []))
+        fired = simulate_frequency(rule, events) if rule["frequency"] else [e for e in events if matches(rule, e)]
+        fired_by[rule["id"]] = fired
+        if rule_id is None or rule["id"] == rule_id:
+            results.append(summarize_noise(rule, fired, candidates, scanned, days, max_daily_alerts))
+
+    noisy = [r["rule"] for r in results if r["verdict"] == "noisy"]
+    report = {
+        "source": source,
+        "window": f"now-{days}d",
+        "max_daily_alerts": max_daily_alerts,
+        "verdict": "noisy" if noisy else "ok",
+        "noisy_rules": noisy,
+        "rules": results,
+    }
+    if source == "alerts" and any(r["verdict"] == "silent" for r in results):
+        report["note"] = "告警索引只有達到告警等級的事件；父規則等級較低 (不產生告警) 時請改用 source=archives 回測"
+    if noisy:
+        report["next_step"] = "加上更具體的條件 (例如排除已知的 agent / 帳號)、提高 frequency，或降低 level 後重新模擬"
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+def load_ruleset(source):
+    """source: "live" (manager 目前載入的規則) 或規則包路徑，回傳 ({rule id: 規則}, 錯誤訊息)"""
+    if source == "live":
+        try:
+            return catalog_ruleset(RULES.rules()), None
+        except RuntimeError as e:
+            return None, str(e)
+    error = outside_rules_root(source)
+    if error:
+        return None, error
+    try:
+        rules, _ = load_bundle(source)
+        return parse_ruleset(rules), None
+    except (OSError, ValueError, tarfile.TarError, zipfile.BadZipFile, UnicodeDecodeError) as e:
+        return None, f"錯誤: 無法讀取規則包 {source}: {str(e)}"
+
+@feature_tool("detection_engineering")
+def compare_rulesets(candidate: str, baseline: str = "live", limit: int = 200) -> str:
+    """比較兩份規則庫，列出新增 / 移除 / 修改的規則與 MITRE 對應的變化，用於升級或規則調整前的影響評估。
+    baseline / candidate: "live" (manager 目前載入的規則) 或伺服器主機上 DETECTION_RULES_ROOT 底下的規則包
+    (目錄或 .zip / .tar.gz，例如新版 Wazuh 的 ruleset 匯出或自訂規則目錄)。預設以 live 為基準比較 candidate。
+    modified 列出等級、描述、群組、MITRE 技術的差異；兩邊都是規則包時也比對規則的比對條件。
+    mitre_coverage 是整份規則庫新增 / 失去覆蓋的 MITRE 技術；各清單最多 limit 筆。
+    當使用者說「升級到新版規則會有什麼影響」或「這批規則改了什麼」時使用。
+    """
+    if limit < 1:
+        return "錯誤: limit 必須大於 0"
+    sides = {}
+    for name, source in (("baseline", baseline), ("candidate", candidate)):
+        rules, error = load_ruleset(source)
+        if error:
+            return error
+        sides[name] = {"source": source, "rules": rules}
+    report = {name: {"source": side["source"], "rules": len(side["rules"])} for name, side in sides.items()}
+    report.update(compare_rules(sides["baseline"]["rules"], sides["candidate"]["rules"], limit))
+    if "live" in (baseline, candidate):
+        report["note"] = "live 規則由 Manager API 取得，無法比對比對條件，只比較等級、描述、群組與 MITRE"
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+@feature_tool("detection_engineering")
+def get_manager_file(kind: str, name: str, group: str = None, offset: int = 0,
+                     chunk_size: int = DEFAULT_CHUNK_BYTES) -> str:
+    """從 Wazuh Manager 取回告警引用的設定檔內容 (需設定 MCP_ALLOW_FILE_RETRIEVAL=true)。
+    kind: rule (規則檔，例如 0095-sshd_rules.xml)、decoder、list (CDB 清單，例如 audit-keys)、
+    group (group 共用檔，需指定 group，例如 agent.conf)。
+    內容以 base64 分段回傳 (chunk_size 最大 65536 bytes)；next_offset 不是 null 時以 offset=next_offset 取下一段，
+    sha256 為整個檔案的雜湊，可用來確認拼回的內容完整。
+    當使用者說「看一下這條規則的規則檔」或「這個 CDB 清單裡有什麼」時使用。
+    """
+    if not ALLOW_FILE_RETRIEVAL:
+        return "錯誤: 檔案取回已停用，需在伺服器設定 MCP_ALLOW_FILE_RETRIEVAL=true"
+    if scoped_agents() is not None:
+        return "錯誤: manager 的設定檔屬於整個環境，租戶範圍受限的呼叫者無法使用"
+    try:
+        path = artifact_path(kind, name, group)
+    except ValueError as e:
+        return f"錯誤: {str(e)}"
+    content, error = api_request("GET", path, params={"raw": "true"}, raw=True)
+    if error:
+        return error
+    if len(content) > FILE_RETRIEVAL_MAX_BYTES:
+        return (f"錯誤: 檔案大小 {len(content)} bytes 超過上限 {FILE_RETRIEVAL_MAX_BYTES} "
+                f"(MCP_FILE_RETRIEVAL_MAX_BYTES)")
+    try:
+        report = artifact_chunk(content, offset, chunk_size)
+    except ValueError as e:
+        return f"錯誤: {str(e)}"
+    return json.dumps({"kind": kind, "name": name, **({"group": group} if group else {}), **report},
+                      indent=2, ensure_ascii=False)
+
+@feature_tool("hunting")
+def numeric_field_stats(field: str, kql: str = "", time_range: str = "now-24h", interval: str = "1h",
+                        percentiles: list[float] | None = None, metric: str = "sum", moving_window: int = 3,
+                        group_by: str | None = None, limit: int = 10, apply_global_filters: bool = True) -> str:
+    """數值欄位 (傳輸量 bytes、持續時間、次數等) 的統計摘要，回答「多少」而不只是「幾筆」的量化問題。
+    overall: 筆數、最小 / 最大 / 平均 / 總和、標準差與百分位數 (percentiles，預設 50/90/95/99)。
+    series: 依 interval 切段的 metric (sum / avg / max / min) 值，附上前 moving_window 段的移動平均、
+    與上一段相比的變化量與百分比，metric=sum 時另附每秒速率；largest_changes 列出變化最大的時段。
+    group_by (例如 agent.name、data.srcip) 會依總和排序列出前 limit 組，各組有自己的統計與百分位數。
+    欄位必須是 Indexer 中的數值型別。
+    當使用者問「哪台主機的外送流量 p99 特別高」「連線時間是不是突然變長」時使用。
+    """
+    if metric not in NUMERIC_METRICS:
+        return f"錯誤: metric 只支援 {' / '.join(NUMERIC_METRICS)}"
+    if moving_window < 1:
+        return "錯誤: moving_window 必須大於 0"
+    try:
+        parse_duration(interval)
+        aggs = QUERIES.render("stats.numeric_aggregations", field=field, interval=interval, time_range=time_range,
+                              percents=list(percentiles or DEFAULT_PERCENTS), group_by=group_by, limit=limit)
+        query = build_query(kql)
+    except KQLSyntaxError as e:
+        return f"查詢語法錯誤: {str(e)}"
+    except ValueError as e:
+        return f"錯誤: {str(e)}"
+    body = {
+        "size": 0,
+        "query": {"bool": {"filter": [query, QUERIES.render("common.time_range", gte=time_range)]}},
+        "aggs": aggs,
+    }
+    result, error = search_indexer(body, global_filters=apply_global_filters)
+    if error:
+        if "is not supported for aggregation" in error or "Text fields are not optimised" in error:
+            return f"錯誤: {field} 在 Indexer 不是數值型別，無法計算統計 ({error[:300]})"
+        return error
+    report = {"field": field, "time_range": time_range, "interval": interval, "metric": metric,
+              **summarize_numeric(result.get("aggregations", {}), metric, moving_window, interval)}
+    if report["overall"]["count"] == 0:
+        report["note"] = f"時間範圍內沒有帶數值 {field} 的告警；請確認欄位名稱與型別"
+    if apply_global_filters and global_filters_summary():
+        report["global_filters"] = global_filters_summary()
+    if "_incomplete" in result:
+        report = {"incomplete": result["_incomplete"], **report}
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+# wazuh_join 可用的資料來源: 索引與時間欄位 (None = 狀態資料，不套用時間範圍)
+JOIN_SOURCES = {
+    "alerts": (ALERTS_INDEX, "timestamp"),
+    "archives": (ARCHIVES_INDEX, "timestamp"),
+    "vulnerabilities": (VULNERABILITY_INDEX, None),
+}
+JOIN_MAX_SIDE = 5000
+
+def fetch_join_side(source, kql, key, fields, time_range, limit, global_filters=True):
+    """取回 join 一側的資料，回傳 (文件清單, 符合總數, 錯誤訊息)"""
+    if source not in JOIN_SOURCES:
+        return None, 0, f"錯誤: 不支援的資料來源 '{source}'，可用: {', '.join(JOIN_SOURCES)}"
+    if source == "vulnerabilities" and not QUERIES.at_least("4.8"):
+        return None, 0, "錯誤: Wazuh 4.8 以前弱點資料不在 Indexer，無法 join (請改用 wazuh://agents/{agent_id}/vulnerabilities)"
+    index, time_field = JOIN_SOURCES[source]
+    try:
+        filters = [build_query(kql), {"exists": {"field": key}}]
+    except KQLSyntaxError as e:
+        return None, 0, f"查詢語法錯誤: {str(e)}"
+    if time_field:
+        filters.append(QUERIES.render("common.time_range", gte=time_range))
+    body = {"size": limit, "query": {"bool": {"filter": filters}}}
+    if fields:
+        body["_source"] = sorted(set(fields) | {key} | ({time_field} if time_field else set()))
+    if time_field:
+        body["sort"] = [{time_field: {"order": "desc"}}]
+    result, error = search_indexer(body, index=index, global_filters=global_filters)
+    if error:
+        return None, 0, error
+    total = result.get("hits", {}).get("total", {})
+    docs = [hit.get("_source", {}) for hit in result.get("hits", {}).get("hits", [])]
+    return docs, total.get("value", 0) if isinstance(total, dict) else total, None
+
+@feature_tool("hunting")
+def wazuh_join(left_kql: str, right_kql: str, left_key: str, right_key: str | None = None,
+               left_source: str = "alerts", right_source: str = "alerts", time_range: str = "now-24h",
+               how: str = "inner", within: str | None = None, left_fields: list[str] | None = None,
+               right_fields: list[str] | None = None, limit: int = 1000, max_rows: int = 200,
+               apply_global_filters: bool = True) -> str:
+    """執行兩個查詢並依共同欄位合併結果 (在伺服器端完成，不必自己比對兩份資料)。
+    left_source / right_source: alerts (告警)、archives (所有事件，需啟用 logall_json)、
+    vulnerabilities (弱點狀態，Wazuh 4.8 起，不套用 time_range)。
+    left_key / right_key 是要比對的欄位 (right_key 省略時與 left_key 相同)，例如:
+    - 告警 join 弱點: left_key="agent.id", right_source="vulnerabilities"
+    - DNS 查詢 join 程序事件: left_key="data.win.eventdata.processId", within="1m"
+    within: 只合併時間相差在此範圍內的事件 (例如 pid 會重複使用時)。
+    how: inner (只列出有對應的) 或 left (左側全部列出，沒有對應時 matches=0)。
+    left_fields / right_fields 只取指定欄位 (建議設定，避免結果過大)。
+    每側最多取最新的 limit 筆 (上限 5000)，結果最多 max_rows 列，每列最多附 20 筆右側資料。
+    當使用者問「觸發這條規則的主機有哪些高風險弱點」或「這個 DNS 查詢是哪個程序發出的」時使用。
+    """
+    if how not in ("inner", "left"):
+        return "錯誤: how 只支援 inner 或 left"
+    if not 1 <= limit <= JOIN_MAX_SIDE:
+        return f"錯誤: limit 必須介於 1 到 {JOIN_MAX_SIDE}"
+    try:
+        window = parse_duration(within) if within else None
+    except ValueError as e:
+        return f"錯誤: {str(e)}"
+    right_key = right_key or left_key
+    left, left_total, error = fetch_join_side(left_source, left_kql, left_key, left_fields, time_range, limit,
+                                              apply_global_filters)
+    if error:
+        return error
+    right, right_total, error = fetch_join_side(right_source, right_kql, right_key, right_fields, time_range, limit,
+                                                apply_global_filters)
+    if error:
+        return error
+    rows, stats = join_rows(left, right, left_key, right_key, how, window, left_fields, right_fields, max_rows)
+    report = {
+        "left": {"source": left_source, "kql": left_kql, "key": left_key, "matched": left_total, "fetched": len(left)},
+        "right": {"source": right_source, "kql": right_kql, "key": right_key, "matched": right_total,
+                  "fetched": len(right)},
+        "how": how,
+        "stats": stats,
+        "rows": rows,
+    }
+    notes = [f"{side} 只取了最新的 {report[side]['fetched']} 筆 (共 {report[side]['matched']} 筆)，部分對應可能遺漏"
+             for side in ("left", "right") if report[side]["matched"] > report[side]["fetched"]]
+    if len(rows) >= max_rows:
+        notes.append(f"結果只列出前 {max_rows} 列")
+    if notes:
+        report["notes"] = notes
+    if apply_global_filters and global_filters_summary():
+        report["global_filters"] = global_filters_summary()
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+@feature_tool("fleet")
+def list_agent_groups() -> str:
+    """列出 Wazuh 的 agent group 與成員數，供 search_alerts / hunt_sequence 的 agent_groups 參數使用。
+    當使用者說「在所有網域控制站上找…」而不確定 group 名稱時先用這個工具。
+    """
+    data, error = api_get("/groups", {"limit": 500, "select": "name,count"})
+    if error:
+        return error
+    groups = [{"name": g.get("name"), "agents": g.get("count", 0)} for g in data.get("affected_items", [])]
+    visible = SCOPES.groups_for(current_principal())
+    if visible is not None and "*" not in visible:
+        groups = [g for g in groups if g["name"] in visible]
+    return json.dumps({"groups": groups}, indent=2, ensure_ascii=False)
+
+@feature_tool("fleet")
+def plan_config_rollout(group: str, config: str, waves: list[int] | None = None) -> str:
+    """規劃 agent group 共用設定 (agent.conf) 的變更: 模擬哪些 agent 會受影響，並排出分批上線的計畫。
+    config 是提議的完整 agent.conf 內容 (一或多個 <agent_config>，os / name 屬性會用來篩選 agent)。
+    回傳受影響的 agent 數與平台 / 作業系統 / agent 版本 / 連線狀態的分布、與目前 agent.conf 的差異，
+    以及各批次的 agent: waves 為累計百分比 (預設 [10, 50, 100])，第一批會涵蓋每種「平台 + agent 版本」組合的
+    canary，未連線的 agent 放在最後一批。計畫存起來後以 apply_config_rollout 逐步執行 (計畫本身不會改動任何設定)。
+    當使用者說「這個 agent.conf 改動會影響哪些主機」「幫我分批推這個設定」時使用。
+    """
+    if scoped_agents() is not None:
+        return "錯誤: group 設定影響整個 group 的 agent，租戶範圍受限的呼叫者無法規劃"
+    waves = list(waves or DEFAULT_WAVES)
+    if any(not 1 <= p <= 100 for p in waves) or waves != sorted(set(waves)) or waves[-1] != 100:
+        return "錯誤: waves 必須是遞增的累計百分比 (1-100)，最後一批為 100，例如 [10, 50, 100]"
+    try:
+        blocks = parse_agent_config(config)
+    except ValueError as e:
+        return f"錯誤: {str(e)}"
+    data, error = api_get("/agents", {"group": group, "limit": 100000, "select": ROLLOUT_AGENT_FIELDS})
+    if error:
+        return error
+    agents = data.get("affected_items", [])
+    if not agents:
+        return f"錯誤: agent group '{group}' 不存在或沒有任何 agent"
+    affected = [agent for agent in agents if matching_blocks(blocks, agent)]
+    current, error = api_request("GET", f"/groups/{group}/files/agent.conf", params={"raw": "true"}, raw=True)
+    names = {agent["id"]: agent.get("name") for agent in agents}
+    affected_ids = {agent["id"] for agent in affected}
+    summary = {
+        "group_agents": len(agents),
+        "affected": len(affected),
+        "unaffected": sorted(names[a["id"]] for a in agents if a["id"] not in affected_ids)[:50],
+        "config_blocks": [block or {"applies_to": "all"} for block in blocks],
+        "breakdown": breakdown(affected),
+    }
+    if not affected:
+        return json.dumps({**summary, "note": "提議的設定不會套用到這個 group 的任何 agent (檢查 os / name 屬性)"},
+                          indent=2, ensure_ascii=False)
+    planned = plan_waves(affected, waves)
+    record = ROLLOUTS.create(group, config, planned, current_principal(), summary)
+    SUBSCRIPTIONS.notify_document(f"{ROLLOUTS_URI}/{record['id']}")
+    report = {
+        "plan": record["id"],
+        "group": group,
+        "staging_group": record["staging_group"],
+        **summary,
+        "changes": config_diff(current.decode("utf-8", "replace") if current else None, config),
+        "waves": [{"wave": i + 1, "agents": [f"{names[a]} ({a})" for a in wave]} for i, wave in enumerate(planned)],
+        "next_step": f"apply_config_rollout(plan_id=\"{record['id']}\", action=\"stage\") 建立暫存 group，"
+                     "再以 action=\"wave\" 逐批加入 agent，確認沒問題後 action=\"promote\"",
+    }
+    if error:
+        report["changes"]["note"] = f"無法取得目前的 agent.conf，差異以空白設定比較: {error}"
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+@feature_tool("fleet")
+def apply_config_rollout(plan_id: str, action: str = "status") -> str:
+    """執行 plan_config_rollout 建立的上線計畫的一個步驟 (status 以外需設定 MCP_ALLOW_WRITES=true)。
+    action:
+    - status: 查看計畫進度 (預設，不會改動任何設定)
+    - stage: 建立暫存 group 並上傳新的 agent.conf
+    - wave: 把下一批 agent 加入暫存 group，這些 agent 開始套用新設定
+    - promote: 全部批次完成後把新設定寫入原本的 group，並刪除暫存 group
+    - rollback: 刪除暫存 group，已加入的 agent 回到原本的設定
+    每一批之後先確認 agent 狀態與告警正常 (例如 search_alerts 查 agent 的錯誤) 再進行下一批。
+    """
+    record = ROLLOUTS.get(plan_id)
+    if record is None:
+        return f"錯誤: 找不到上線計畫 {plan_id}"
+    if action == "status":
+        return json.dumps(progress(record), indent=2, ensure_ascii=False)
+    if action not in ("stage", "wave", "promote", "rollback"):
+        return "錯誤: action 必須是 status、stage、wave、promote 或 rollback"
+    if not ALLOW_WRITES:
+        return "錯誤: 上線步驟會變更 manager 的 group 設定，需在伺服器設定 MCP_ALLOW_WRITES=true"
+    if scoped_agents() is not None:
+        return "錯誤: group 設定影響整個 group 的 agent，租戶範圍受限的呼叫者無法執行"
+    staging, status = record["staging_group"], record["status"]
+    principal = current_principal()
+    wave = next_wave(record)
+    allowed = {"stage": ("planned",), "wave": ("staged", "in_progress"), "promote": ("in_progress",),
+               "rollback": ("staged", "in_progress")}[action]
+    if status not in allowed:
+        return f"錯誤: 計畫 {plan_id} 目前為 {status}，無法執行 {action}"
+    if action == "stage":
+        _, error = api_request("POST", "/groups", body={"group_id": staging})
+        if error:
+            return f"建立暫存 group 失敗: {error}"
+        _, error = api_request("PUT", f"/groups/{staging}/configuration", data=record["config"],
+                               content_type="application/xml")
+        if error:
+            api_request("DELETE", "/groups", params={"groups_list": staging})
+            return f"上傳設定到暫存 group 失敗 (已刪除暫存 group): {error}"
+        record = ROLLOUTS.record_step(plan_id, principal, action, "staged")
+    elif action == "wave":
+        if wave is None:
+            return f"錯誤: 計畫 {plan_id} 的批次都已套用，請執行 promote"
+        for batch in batches(record["waves"][wave]["agents"]):
+            _, error = api_request("PUT", "/agents/group", params={"group_id": staging, "agents_list": ",".join(batch)})
+            if error:
+                return f"第 {wave + 1} 批加入暫存 group 失敗 (部分 agent 可能已加入，可重新執行): {error}"
+        record = ROLLOUTS.record_step(plan_id, principal, action, "in_progress", wave)
+    elif action == "promote":
+        if wave is not None:
+            return f"錯誤: 還有批次尚未套用 (第 {wave + 1} 批)，全部完成後才能 promote"
+        _, error = api_request("PUT", f"/groups/{record['group']}/configuration", data=record["config"],
+                               content_type="application/xml")
+        if error:
+            return f"寫入 {record['group']} 的 agent.conf 失敗: {error}"
+        _, error = api_request("DELETE", "/groups", params={"groups_list": staging})
+        if error:
+            logger.error("上線計畫 %s 已寫入新設定，但刪除暫存 group %s 失敗: %s", plan_id, staging, error)
+        record = ROLLOUTS.record_step(plan_id, principal, action, "completed")
+    else:
+        _, error = api_request("DELETE", "/groups", params={"groups_list": staging})
+        if error:
+            return f"刪除暫存 group 失敗: {error}"
+        record = ROLLOUTS.record_step(plan_id, principal, action, "rolled_back")
+    logger.warning("設定上線計畫 %s 執行 %s (principal=%s)", plan_id, action, principal)
+    SUBSCRIPTIONS.notify_document(f"{ROLLOUTS_URI}/{plan_id}")
+    report = progress(record)
+    following = next_wave(record)
+    if record["status"] in ("staged", "in_progress"):
+        report["next_step"] = (f"確認後以 action=\"wave\" 套用第 {following + 1} 批" if following is not None
+                               else "全部批次已套用，確認沒問題後以 action=\"promote\" 寫入原本的 group")
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+def daily_alert_counts(time_range, time_to="now"):
+    """time_range ~ time_to 每天的告警統計 (規則 / agent / 等級)，回傳 ({date: 統計}, 來源說明, 錯誤訊息)。
+    整天的部分讀預先彙總 (rollups.py)，其餘 (頭尾不滿一天、還沒彙總的日子) 查 Indexer；
+    有租戶範圍的呼叫者一律查 Indexer (彙總是全環境的數字)。"""
+    start, end = parse_time_bound(time_range), parse_time_bound(time_to)
+    if start >= end:
+        raise ValueError("time_range 必須早於 time_to")
+    if scoped_agents() is None:
+        stored, gaps = ROLLUPS.plan(start, end)
+    else:
+        stored, gaps = {}, [(start, end)]
+    days = dict(stored)
+    aggs = QUERIES.render("rollups.daily_aggregations", terms_size=ROLLUP_TERMS)
+    incomplete = None
+    for lower, upper in gaps:
+        body = {"size": 0, "query": QUERIES.render("common.time_range", gte=lower.isoformat(), lt=upper.isoformat()),
+                "aggs": aggs}
+        result, error = search_indexer(body)
+        if error:
+            return None, None, error
+        incomplete = incomplete or result.get("_incomplete")
+        # 片段的邊界都在午夜 (頭尾除外)，不會與讀自彙總的日子重疊
+        days.update(split_days(result))
+    sources = {
+        "rollup_days": len(stored),
+        "live_ranges": [{"from": lower.isoformat(), "to": upper.isoformat()} for lower, upper in gaps],
+    }
+    if incomplete:
+        sources["incomplete"] = incomplete
+    return days, sources, None
+
+@feature_tool("reporting")
+def alert_trends(time_range: str = "now-30d", group_by: str = "rule", limit: int = 10) -> str:
+    """長時間範圍 (數週到數月) 的每日告警趨勢: 每天的告警總數，以及整段期間前 limit 名的
+    規則 (group_by=rule) / agent (agent) / 等級 (level) 與它們每天的數量；group_by=none 只列每天總數。
+    有啟用每日彙總 (MCP_ROLLUP_INTERVAL) 時整天的部分直接讀彙總，90 天的趨勢也能立即回答；
+    sources 說明哪些日子讀彙總、哪些時段即時查詢。
+    當使用者問「過去三個月告警量的趨勢」「這個月最常觸發的規則」「哪台主機的告警一直在增加」時使用。
+    """
+    dimensions = {"rule": "rules", "agent": "agents", "level": "levels", "none": None}
+    if group_by not in dimensions:
+        return f"錯誤: group_by 只支援 {' / '.join(dimensions)}"
+    if not 1 <= limit <= 100:
+        return "錯誤: limit 必須介於 1 到 100"
+    try:
+        days, sources, error = daily_alert_counts(time_range)
+    except ValueError as e:
+        return f"錯誤: {str(e)}"
+    if error:
+        return error
+    report = {"time_range": time_range, "group_by": group_by,
+              **merge_rollups(days, dimensions[group_by], limit), "sources": sources}
+    if dimensions[group_by] and any(d.get("other", {}).get(dimensions[group_by]) for d in days.values()):
+        report["note"] = (f"部分日子的 {group_by} 種類超過 {ROLLUP_TERMS} 個 (MCP_ROLLUP_TERMS)，"
+                          "排名尾端的數量可能偏低")
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+@feature_tool("reporting")
+def wazuh_environment_brief(time_range: str = "now-7d", top_rules: int = 10,
+                            output_format: str = "markdown") -> str:
+    """產生受監控環境的精簡簡報: agent 數量 (依作業系統 / group / 狀態)、最常觸發的規則、
+    啟用的 Wazuh 模組、告警資料保留範圍與索引名稱。
+    建議在對話 (或獵捕任務) 一開始先呼叫一次，之後就不必猜測環境裡有什麼。
+    output_format: markdown (預設，適合放進 prompt) 或 json。
+    有租戶範圍的呼叫者只會看到自己的主機與告警，不含全域的模組設定與索引清單。
+    """
+    if output_format not in ("markdown", "json"):
+        return "錯誤: output_format 只支援 markdown 或 json"
+    allowed = scoped_agents()
+    brief = {"generated_at": datetime.now(timezone.utc).isoformat(), "time_range": time_range}
+    errors, notes = [], []
+
+    data, error = api_get("/agents", {"limit": 100000, "select": "id,status,os.platform,group"})
+    if error:
+        errors.append(f"agent 清單: {error}")
+    else:
+        agents = [a for a in data.get("affected_items", []) if allowed is None or a.get("id") in allowed]
+        brief["agents"] = agent_breakdown(agents)
+
+    body = {"size": 0, "aggs": QUERIES.render("brief.aggregations", time_range=time_range, limit=top_rules)}
+    if ROLLUPS.enabled and allowed is None:
+        # 最常觸發的規則改由每日彙總計算，Indexer 只需要查資料保留範圍
+        del body["aggs"]["recent"]
+        try:
+            days, _, error = daily_alert_counts(time_range)
+        except ValueError as e:
+            days, error = None, str(e)
+        if error:
+            errors.append(f"規則統計: {error}")
+        else:
+            brief["top_rules"] = [{"rule_id": r["key"], "description": r.get("description"),
+                                   "level": r.get("level") or 0, "alerts": r["alerts"]}
+                                  for r in merge_rollups(days, "rules", top_rules)["top"]]
+    result, error = search_indexer(body)
+    if error:
+        errors.append(f"告警統計: {error}")
+    else:
+        aggs = result.get("aggregations", {})
+        if "recent" in aggs:
+            brief["top_rules"] = [{
+                "rule_id": b.get("key"),
+                "description": next((d.get("key") for d in b.get("description", {}).get("buckets", [])), None),
+                "level": int(b.get("level", {}).get("value") or 0),
+                "alerts": b.get("doc_count", 0),
+            } for b in aggs.get("recent", {}).get("rules", {}).get("buckets", [])]
+        oldest = aggs.get("oldest", {}).get("value_as_string")
+        newest = aggs.get("newest", {}).get("value_as_string")
+        brief["retention"] = retention(parse_timestamp(oldest) if oldest else None,
+                                       parse_timestamp(newest) if newest else None,
+                                       datetime.now(timezone.utc))
+        if "_incomplete" in result:
+            notes.append("Indexer 本次只回傳部分結果，規則統計可能偏低")
+        if global_filters_summary():
+            brief["global_filters"] = global_filters_summary()
+
+    if allowed is None:
+        info, error = api_get("/manager/info")
+        if error:
+            errors.append(f"Manager 版本: {error}")
+        else:
+            brief["wazuh_version"] = next(iter(info.get("affected_items", [])), {}).get("version")
+        config, error = api_get("/manager/configuration")
+        if error:
+            errors.append(f"Manager 設定: {error}")
+        else:
+            config = next(iter(config.get("affected_items", [])), {})
+            brief["modules"] = enabled_modules(config)
+            brief["log_sources"] = log_sources(config)
+        indices, error = indexer_get("_cat/indices/wazuh-*?format=json&h=index,docs.count")
+        if error:
+            errors.append(f"索引清單: {error}")
+        else:
+            brief["indices"] = index_families(indices)
+    else:
+        notes.append("租戶範圍內只統計可存取的主機；模組設定與索引清單屬於全域資訊，不列出")
+    brief["features"] = sorted(FEATURES)
+    if notes:
+        brief["notes"] = notes
+    if errors:
+        brief["errors"] = errors
+    if output_format == "markdown":
+        return format_brief(brief)
+    return json.dumps(brief, indent=2, ensure_ascii=False)
+
+# --- 規則庫資源 (Resources) ---
+
+def fetch_rules():
+    data, error = api_get("/rules", {"limit": 100000, "select": "id,level,description,groups,mitre,filename,"
+                                                                "relative_dirname,status,details"})
+    if error:
+        raise RuntimeError(error)
+    return data.get("affected_items", [])
+
+RULES = RuleCatalog(fetch_rules)
+
+def read_catalog(lookup, *args):
+    try:
+        return json.dumps(lookup(*args), indent=2, ensure_ascii=False)
+    except (KeyError, RuntimeError) as e:
+        raise ResourceError(str(e.args[0] if isinstance(e, KeyError) else e)) from None
+
+@mcp.resource("wazuh://rules", name="wazuh_ruleset", mime_type="application/json")
+def ruleset_overview() -> str:
+    """Wazuh 規則庫總覽: 規則總數、各等級與各群組的規則數，以及讀取單一規則 / 群組 / 等級的 URI"""
+    return read_catalog(RULES.overview)
+
+@mcp.resource("wazuh://rules/{rule_id}", name="wazuh_rule", mime_type="application/json")
+def rule_resource(rule_id: str) -> str:
+    """單一 Wazuh 規則: 等級、描述、群組、MITRE ATT&CK、比對條件 (details) 與規則檔"""
+    return read_catalog(RULES.get, rule_id)
+
+@mcp.resource("wazuh://rules/groups/{group}", name="wazuh_rule_group", mime_type="application/json")
+def rule_group_resource(group: str) -> str:
+    """某個規則群組 (rule.groups) 的所有規則，依等級由高到低排序"""
+    return read_catalog(RULES.in_group, group)
+
+@mcp.resource("wazuh://rules/levels/{level}", name="wazuh_rule_level", mime_type="application/json")
+def rule_level_resource(level: str) -> str:
+    """某個規則等級 (0-15) 的所有規則"""
+    return read_catalog(RULES.at_level, level)
+
+mcp.add_middleware(RuleResourceList(RULES, lambda rule_id: read_catalog(RULES.get, rule_id)))
+
+# --- Agent / 告警資源範本 (Resource templates) ---
+VULNERABILITY_LIMIT = 500
+
+def resource_json(data):
+    return json.dumps(data, indent=2, ensure_ascii=False)
+
+def require_agent(agent_id):
+    """驗證 agent id 格式與租戶範圍，回傳 Manager API 的 agent 資料"""
+    if not (agent_id.isdigit() and len(agent_id) >= 3):
+        raise ResourceError(f"agent id 格式錯誤: '{agent_id}' (應為數字，例如 001)")
+    allowed = scoped_agents()
+    if allowed is not None and agent_id not in allowed:
+        raise ResourceError(f"找不到 agent {agent_id} (或不在可存取的範圍內)")
+    data, error = api_get("/agents", {"agents_list": agent_id})
+    if error:
+        raise ResourceError(error)
+    items = data.get("affected_items", [])
+    if not items:
+        raise ResourceError(f"找不到 agent {agent_id} (或不在可存取的範圍內)")
+    return items[0]
+
+def fetch_agents():
+    data, error = api_get("/agents", {"limit": 100000, "select": "id,name,ip,status,version,lastKeepAlive"})
+    return (data or {}).get("affected_items", []), error
+
+@mcp.resource(AGENTS_URI, name="wazuh_agents", mime_type="application/json")
+def agents_resource() -> str:
+    """所有 agent 的連線狀態清單。可用 resources/subscribe 訂閱，agent 連線、斷線或新增時會收到更新通知"""
+    agents, error = fetch_agents()
+    if error:
+        raise ResourceError(error)
+    allowed = scoped_agents()
+    if allowed is not None:
+        agents = [a for a in agents if a.get("id") in allowed]
+    by_status = {}
+    for agent in agents:
+        by_status[agent.get("status")] = by_status.get(agent.get("status"), 0) + 1
+    return resource_json({"total": len(agents), "by_status": by_status, "agents": agents})
+
+# integrator 推送的告警 (ingest.py)
+INGEST = AlertBuffer(int(os.getenv("MCP_INGEST_BUFFER", "10000")))
+
+@mcp.resource(ALERTS_LIVE_URI, name="wazuh_live_alerts", mime_type="application/json")
+def live_alerts_resource() -> str:
+    """integrator 即時推送的最新 20 筆告警與推送統計。可用 resources/subscribe 訂閱，有新告警時會收到更新通知"""
+    if not INGEST_TOKEN:
+        raise ResourceError("告警推送未啟用 (MCP_INGEST_TOKEN)")
+    stats = INGEST.stats()
+    alerts, _, _ = INGEST.since(max(0, stats["cursor"] - 1000), alert_filter(scoped_agents()), limit=1000)
+    return resource_json({**stats, "alerts": alerts[-20:]})
+
+# --- 伺服器文件資源: 管理工具保存的獵捕假說與設定上線計畫，可直接讀取並訂閱變動 ---
+def require_document(record, kind, document_id):
+    if record is None:
+        raise ResourceError(f"找不到{kind} {document_id} (或不在可存取的範圍內)")
+    return record
+
+@mcp.resource(HYPOTHESES_URI, name="wazuh_hypotheses", mime_type="application/json")
+def hypotheses_resource() -> str:
+    """所有獵捕假說的摘要 (狀態、信心分數、佐證數)。可用 resources/subscribe 訂閱，假說建立或更新時會收到通知；
+    新增與修改請用 record_hypothesis / attach_evidence"""
+    records = HYPOTHESES.list(visible=visible_hypothesis)
+    return resource_json({"total": len(records), "hypotheses": [
+        {**brief_hypothesis(record), "uri": f"{HYPOTHESES_URI}/{record['id']}"} for record in records]})
+
+@mcp.resource(HYPOTHESES_URI + "/{hypothesis_id}", name="wazuh_hypothesis", mime_type="application/json")
+def hypothesis_resource(hypothesis_id: str) -> str:
+    """單一獵捕假說的完整內容: 陳述、狀態與信心的歷程、所有佐證 (含 _ref 與可重跑的工具呼叫)"""
+    return resource_json(require_document(find_hypothesis(hypothesis_id), "假說", hypothesis_id))
+
+@mcp.resource(ROLLOUTS_URI, name="wazuh_rollouts", mime_type="application/json")
+def rollouts_resource() -> str:
+    """所有 agent.conf 設定上線計畫的進度。可用 resources/subscribe 訂閱，計畫建立或執行步驟時會收到通知；
+    建立與執行請用 plan_config_rollout / apply_config_rollout"""
+    if scoped_agents() is not None:
+        raise ResourceError("設定上線計畫影響整個 group，租戶範圍受限的呼叫者無法讀取")
+    records = ROLLOUTS.list()
+    return resource_json({"total": len(records), "rollouts": [
+        {**progress(record), "uri": f"{ROLLOUTS_URI}/{record['id']}"} for record in records]})
+
+@mcp.resource(ROLLOUTS_URI + "/{plan_id}", name="wazuh_rollout", mime_type="application/json")
+def rollout_resource(plan_id: str) -> str:
+    """單一設定上線計畫: 提議的 agent.conf、受影響 agent 的分布、各批次的 agent 與執行歷程"""
+    if scoped_agents() is not None:
+        raise ResourceError("設定上線計畫影響整個 group，租戶範圍受限的呼叫者無法讀取")
+    return resource_json(require_document(ROLLOUTS.get(plan_id), "上線計畫", plan_id))
+
+@feature_tool("hunting")
+def tail_live_alerts(cursor: int = 0, min_level: int = 0, agent: str | None = None, limit: int = 50) -> str:
+    """讀取 Wazuh integrator 即時推送的告警 (live tail)，沒有 Indexer 匯入延遲。
+    第一次以 cursor=0 呼叫，之後帶上回傳的 next_cursor 只取新的告警；missed > 0 表示期間有告警
+    被擠出緩衝區 (MCP_INGEST_BUFFER)，需要改用 search_alerts 補查。只包含 integrator 設定要推送的告警
+    (通常是高等級或特定群組)，完整的歷史告警仍請用 search_alerts。
+    當使用者要求「盯著新進來的告警」「有新的高風險告警就告訴我」時使用。
+    """
+    if not INGEST_TOKEN:
+        return "錯誤: 告警推送未啟用，請設定 MCP_INGEST_TOKEN 並在 Manager 加上 custom-wazuh-mcp 整合"
+    if not 1 <= limit <= 500:
+        return "錯誤: limit 必須介於 1 到 500"
+    alerts, next_cursor, missed = INGEST.since(max(0, cursor), alert_filter(scoped_agents(), min_level, agent),
+                                               limit)
+    report = {"next_cursor": next_cursor, "count": len(alerts), "missed": missed,
+              "last_push": INGEST.stats()["last_push"], "alerts": alerts}
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+def after_ingest(alerts):
+    """推送後的背景工作: 清除晚到告警所在時段的查詢快取、通知 wazuh://alerts/live 的訂閱者"""
+    windows = late_windows(alerts, datetime.now(timezone.utc) - QUERY_CACHE.ingest_delay)
+    if windows:
+        removed = QUERY_CACHE.invalidate(windows)
+        logger.info("推送的告警中有 %d 個晚到的時間點，已清除 %d 筆查詢快取", len(windows), removed)
+    SUBSCRIPTIONS.notify_alerts({get_field(a, "agent.id") for a in alerts} - {None})
+
+# 訂閱 (resources/subscribe): FastMCP 沒有高階 API，直接註冊在底層 MCP server 上
+SUBSCRIPTIONS = SubscriptionRegistry()
+AGENT_WATCHER = AgentWatcher(fetch_agents, SUBSCRIPTIONS,
+                             parse_duration(os.getenv("MCP_AGENT_WATCH_INTERVAL", "30s")).total_seconds())
+
+@mcp._mcp_server.subscribe_resource()
+async def subscribe_resource(uri):
+    uri = str(uri)
+    if document_collection(uri) is not None:
+        if document_collection(uri) == ROLLOUTS_URI and scoped_agents() is not None:
+            raise ValueError("設定上線計畫影響整個 group，租戶範圍受限的呼叫者無法訂閱")
+        SUBSCRIPTIONS.subscribe(mcp._mcp_server.request_context.session, uri, scoped_agents(), current_principal())
+        remember_subscription(uri, True)
+        return
+    if uri == ALERTS_LIVE_URI:
+        if not INGEST_TOKEN:
+            raise ValueError("告警推送未啟用 (MCP_INGEST_TOKEN)，無法訂閱")
+        SUBSCRIPTIONS.subscribe(mcp._mcp_server.request_context.session, uri, scoped_agents())
+        remember_subscription(uri, True)
+        return
+    if uri != AGENTS_URI and not (uri.startswith(AGENTS_URI + "/") and uri[len(AGENTS_URI) + 1:].isdigit()):
+        raise ValueError(f"不支援訂閱 {uri}，目前只能訂閱 {AGENTS_URI}、{AGENTS_URI}/{{agent_id}}、{ALERTS_LIVE_URI}、"
+                         f"{HYPOTHESES_URI} 與 {ROLLOUTS_URI} (及其下的單一文件)")
+    if AGENT_WATCHER.interval <= 0:
+        raise ValueError("agent 狀態輪詢已停用 (MCP_AGENT_WATCH_INTERVAL=0)，無法訂閱")
+    SUBSCRIPTIONS.subscribe(mcp._mcp_server.request_context.session, uri, scoped_agents())
+    remember_subscription(uri, True)
+
+@mcp._mcp_server.unsubscribe_resource()
+async def unsubscribe_resource(uri):
+    SUBSCRIPTIONS.unsubscribe(mcp._mcp_server.request_context.session, str(uri))
+    remember_subscription(str(uri), False)
+
+def remember_subscription(uri, subscribed):
+    """MCP_PERSIST_SESSIONS 時記錄 session 的訂閱，重啟後接續時重新訂閱"""
+    if SESSIONS is None:
+        return
+    session_id = request_session_id()
+    uris = set(SESSIONS.context(session_id).get("subscriptions", []))
+    SESSIONS.set_context(session_id, "subscriptions", sorted(uris | {uri} if subscribed else uris - {uri}))
+
+_base_capabilities = mcp._mcp_server.get_capabilities
+
+def _capabilities_with_subscribe(*args, **kwargs):
+    """MCP SDK 固定回報 resources.subscribe=false；伺服器文件 (假說、上線計畫) 一律可訂閱，所以回報 true"""
+    capabilities = _base_capabilities(*args, **kwargs)
+    if capabilities.resources is not None:
+        capabilities.resources.subscribe = True
+    return capabilities
+
+mcp._mcp_server.get_capabilities = _capabilities_with_subscribe
+
+# MCP logging: 註冊後 capabilities 會帶 logging；client 設定的等級只過濾轉送內容，
+# 伺服器實際產生的 log 仍以 MCP_LOG_LEVEL (或執行中調整的等級) 為準
+@mcp._mcp_server.set_logging_level()
+async def set_logging_level(level):
+    if not unscoped_caller():
+        raise ValueError("伺服器 log 可能包含其他租戶的資訊，受租戶範圍限制的呼叫者無法訂閱")
+    CLIENT_LOG.set_level(mcp._mcp_server.request_context.session, level)
+    if SESSIONS is not None:
+        SESSIONS.set_context(request_session_id(), "log_level", level)
+
+@mcp.resource("wazuh://agents/{agent_id}", name="wazuh_agent", mime_type="application/json")
+def agent_resource(agent_id: str) -> str:
+    """單一 agent 的資訊: 名稱、IP、作業系統、版本、group、連線狀態與最後心跳時間 (可訂閱狀態變動)"""
+    agent = require_agent(agent_id)
+    return resource_json({**agent, "resources": {"vulnerabilities": f"wazuh://agents/{agent_id}/vulnerabilities"}})
+
+@mcp.resource("wazuh://agents/{agent_id}/vulnerabilities", name="wazuh_agent_vulnerabilities",
+              mime_type="application/json")
+def agent_vulnerabilities_resource(agent_id: str) -> str:
+    """agent 的弱點掃描結果 (CVE、嚴重度、CVSS 分數、套件)，依分數由高到低排序"""
+    require_agent(agent_id)
+    request = QUERIES.render("vulnerabilities.agent", agent_id=agent_id, limit=VULNERABILITY_LIMIT)
+    if request["source"] == "api":
+        data, error = api_get(request["path"], request["params"])
+        if error:
+            raise ResourceError(error)
+        findings = [from_api(item) for item in data.get("affected_items", [])]
+        total = data.get("total_affected_items", len(findings))
+    else:
+        result, error = search_indexer(request["body"], index=request["index"], global_filters=False)
+        if error:
+            raise ResourceError(error)
+        hits = result.get("hits", {})
+        findings = [from_index(hit.get("_source", {})) for hit in hits.get("hits", [])]
+        total = hits.get("total", {}).get("value", len(findings))
+    return resource_json(summarize_vulnerabilities(agent_id, findings, truncated=total > len(findings)))
+
+@mcp.resource("wazuh://alerts/{alert_id}", name="wazuh_alert", mime_type="application/json")
+def alert_resource(alert_id: str) -> str:
+    """單筆告警的完整內容 (以 Indexer 的 _id 或告警的 id 欄位查詢)，附上規則與 agent 的資源 URI"""
+    result, error = search_indexer({"size": 1, "query": QUERIES.render("prompts.alert", alert_id=alert_id)})
+    if error:
+        raise ResourceError(error)
+    hits = result.get("hits", {}).get("hits", [])
+    if not hits:
+        raise ResourceError(f"找不到告警 {alert_id} (或不在可存取的範圍內)")
+    alert = SEVERITY.annotate(hits[0].get("_source", {}))
+    links = {"rule": f"wazuh://rules/{get_field(alert, 'rule.id')}"}
+    if get_field(alert, "agent.id"):
+        links["agent"] = f"wazuh://agents/{get_field(alert, 'agent.id')}"
+    return resource_json({**alert, "_ref": event_ref(hits[0]), "resources": links})
+
+@mcp.tool()
+def wazuh_backend_latency(window: str = "1h") -> str:
+    """查詢背景金絲雀 (canary) 探測記錄的後端延遲: 每個後端 (Manager API、Indexer 搜尋、Indexer 叢集)
+    在 window 內的 p50 / p95 / 最大延遲、成功率與最近的錯誤，以及是否正在變慢 (degrading)。
+    當使用者抱怨「查詢很慢」、或查詢逾時想確認是不是後端的問題時使用。
+    """
+    try:
+        seconds = parse_duration(window).total_seconds()
+    except ValueError as e:
+        return f"錯誤: {str(e)}"
+    if CANARY.interval <= 0:
+        return "錯誤: 後端延遲探測已停用 (MCP_CANARY_INTERVAL=0)"
+    report = {"window": window, "interval_seconds": CANARY.interval, "backends": CANARY.stats(seconds)}
+    slow = [name for name, stats in report["backends"].items() if stats["status"] in ("degrading", "failing")]
+    if slow:
+        report["warning"] = f"以下後端正在變慢或失敗: {', '.join(slow)}"
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+# --- 獵捕 Prompt 範本 (Prompts) ---
+
+@mcp.prompt()
+def triage_alert(alert_id: str) -> str:
+    """分流 (triage) 單一告警: 附上告警內容、規則定義、同主機前後一小時的活動與這條規則的歷史。"""
+    sections, errors = [], []
+    result, error = search_indexer({"size": 1, "query": QUERIES.render("prompts.alert", alert_id=alert_id)})
+    hits = result.get("hits", {}).get("hits", []) if result else []
+    if error:
+        errors.append(f"告警內容: {error}")
+    elif not hits:
+        errors.append(f"告警內容: 找不到告警 {alert_id} (或不在可存取的範圍內)")
+    else:
+        alert = SEVERITY.annotate(hits[0].get("_source", {}))
+        sections.append(context_block("告警", {**alert, "_ref": event_ref(hits[0])}))
+        rule_id = get_field(alert, "rule.id")
+        try:
+            sections.append(context_block("規則定義", RULES.get(rule_id)))
+        except (KeyError, RuntimeError) as e:
+            errors.append(f"規則 {rule_id} 的定義: {e.args[0] if isinstance(e, KeyError) else e}")
+        agent, ts = get_field(alert, "agent.name"), alert.get("timestamp")
+        if agent and ts:
+            body = {"size": 0, "query": {"bool": {"filter": [
+                QUERIES.render("common.agent", name=agent),
+                QUERIES.render("common.time_range", gte=f"{ts}||-1h", lte=f"{ts}||+1h"),
+            ]}}, "aggs": {"rules": {"terms": {"field": "rule.description", "size": 10}}}}
+            nearby, error = search_indexer(body)
+            if error:
+                errors.append(f"同主機前後一小時的告警: {error}")
+            else:
+                sections.append(context_block(f"{agent} 在告警前後一小時的其他告警", {
+                    b["key"]: b["doc_count"] for b in nearby["aggregations"]["rules"]["buckets"]}))
+            body = {"size": 0, "query": {"bool": {"filter": [{"term": {"rule.id": rule_id}},
+                                                             QUERIES.render("common.agent", name=agent)]}},
+                    "aggs": {"first": {"min": {"field": "timestamp"}}, "last": {"max": {"field": "timestamp"}}}}
+            history, error = search_indexer(body)
+            if error:
+                errors.append(f"規則歷史: {error}")
+            else:
+                sections.append(context_block(f"規則 {rule_id} 在 {agent} 的歷史", {
+                    "alerts": history["hits"]["total"]["value"],
+                    "first_seen": history["aggregations"]["first"].get("value_as_string"),
+                    "last_seen": history["aggregations"]["last"].get("value_as_string"),
+                }))
+    return compose(
+        f"請對 Wazuh 告警 {alert_id} 進行初步分流 (triage)，判斷是否需要升級處理。",
+        sections,
+        ["根據規則定義說明這條告警代表什麼行為，以及可能的 MITRE ATT&CK 技術",
+         "檢查告警中的主機、帳號、IP、程序等關鍵欄位，指出可疑或異常之處",
+         "參考同主機前後一小時的活動與規則歷史，判斷是偶發、例行 (誤報可能) 還是攻擊鏈的一部分",
+         "給出結論: 誤報 / 需要調查 / 需要立即處置，附上信心程度與理由",
+         "列出下一步要查的項目 (可用 search_alerts、hunt_sequence 等工具)"],
+        errors)
+
+@mcp.prompt()
+def hunt_lateral_movement(agent: str, time_range: str = "now-24h") -> str:
+    """在指定 agent 上獵捕橫向移動: 附上 agent 資訊、登入相關告警的來源 IP / 帳號統計，以及這些來源還碰過哪些主機。"""
+    sections, errors = [], []
+    data, error = api_get("/agents", {"name": agent, "select": "id,name,ip,os.name,status,group,lastKeepAlive"})
+    allowed = scoped_agents()
+    if error:
+        errors.append(f"agent 資訊: {error}")
+    else:
+        items = [a for a in data.get("affected_items", []) if allowed is None or a.get("id") in allowed]
+        if items:
+            sections.append(context_block("Agent", items[0]))
+        else:
+            errors.append(f"agent 資訊: 找不到 agent {agent} (或不在可存取的範圍內)")
+    body = {"size": 0, "query": {"bool": {"filter": [
+        QUERIES.render("prompts.lateral_movement", agent=agent),
+        QUERIES.render("common.time_range", gte=time_range),
+    ]}}, "aggs": QUERIES.render("prompts.lateral_aggregations")}
+    result, error = search_indexer(body)
+    if error:
+        errors.append(f"登入相關告警: {error}")
+    else:
+        aggs = result["aggregations"]
+        sources = terms(aggs, "src:")
+        sections.append(context_block(f"{agent} 的登入 / 橫向移動相關告警 ({time_range} 起)", {
+            "alerts": result["hits"]["total"]["value"],
+            "outcome": {k: v["doc_count"] for k, v in aggs["outcome"]["buckets"].items()},
+            "source_ips": sources,
+            "accounts": terms(aggs, "user:"),
+            "rules": {b["key"]: b["doc_count"] for b in aggs["rules"]["buckets"]},
+        }))
+        if sources:
+            body = {"size": 0, "query": {"bool": {
+                "filter": [{"bool": {"should": [{"terms": {f: list(sources)}} for f in SOURCE_IP_FIELDS],
+                                     "minimum_should_match": 1}},
+                           QUERIES.render("common.time_range", gte=time_range)],
+                "must_not": [QUERIES.render("common.agent", name=agent)],
+            }}, "aggs": QUERIES.render("prompts.source_reach")}
+            reach, error = search_indexer(body)
+            if error:
+                errors.append(f"來源 IP 的擴散範圍: {error}")
+            else:
+                sections.append(context_block("相同來源 IP 也出現在這些主機", {
+                    b["key"]: {"alerts": b["doc_count"], "rules": [r["key"] for r in b["rules"]["buckets"]]}
+                    for b in reach["aggregations"]["agents"]["buckets"]}))
+    return compose(
+        f"請在 agent {agent} 上獵捕橫向移動 (lateral movement) 的跡象，時間範圍 {time_range} 起。",
+        sections,
+        ["找出不尋常的來源 IP: 內部主機之間的登入、非上班時間、從未出現過的來源",
+         "比對登入成功與失敗: 連續失敗後成功、同一帳號從多個來源登入、服務帳號的互動式登入",
+         "檢查相同來源 IP 是否也出現在其他主機上，推測擴散路徑",
+         "用 hunt_sequence 驗證可疑的序列 (例如 登入成功 -> 新增服務 / 排程工作)",
+         "整理可疑的主機、帳號與 IP，並建議圍堵步驟"],
+        errors)
+
+@mcp.prompt()
+def summarize_critical_alerts(time_range: str = "now-24h", min_severity: str = "critical") -> str:
+    """摘要一段時間內的重大告警: 附上總數、最常觸發的規則、受影響主機與 ATT&CK 戰術分布。"""
+    sections, errors = [], []
+    try:
+        query = build_query("", min_severity)
+    except ValueError as e:
+        query, errors = None, [f"min_severity: {e}"]
+    if query is not None:
+        body = {"size": 0, "track_total_hits": True,
+                "query": {"bool": {"filter": [query, QUERIES.render("common.time_range", gte=time_range)]}},
+                "aggs": QUERIES.render("prompts.critical_summary")}
+        result, error = search_indexer(body)
+        if error:
+            errors.append(f"告警統計: {error}")
+        else:
+            aggs = result["aggregations"]
+            sections.append(context_block(f"{min_severity} 以上的告警 ({time_range} 起)", {
+                "alerts": result["hits"]["total"]["value"],
+                "rules": [{"rule_id": b["key"],
+                           "description": next((d["key"] for d in b["description"]["buckets"]), None),
+                           "alerts": b["doc_count"],
+                           "agents": [a["key"] for a in b["agents"]["buckets"]],
+                           "last_seen": b["last_seen"].get("value_as_string")} for b in aggs["rules"]["buckets"]],
+                "agents": {b["key"]: b["doc_count"] for b in aggs["agents"]["buckets"]},
+                "tactics": {b["key"]: b["doc_count"] for b in aggs["tactics"]["buckets"]},
+            }))
+            if "_incomplete" in result:
+                errors.append("Indexer 只回傳部分結果，數量可能偏低")
+    return compose(
+        f"請摘要 {time_range} 起嚴重度 {min_severity} 以上的 Wazuh 告警，給值班分析師一份交接報告。",
+        sections,
+        ["用三到五句話說明整體狀況 (數量、集中在哪些主機 / 規則、與平常相比是否異常)",
+         "依風險排序列出需要優先處理的事件，說明原因",
+         "指出可能相關聯的告警 (同主機、同戰術、時間接近)",
+         "標示可能是誤報或例行雜訊的規則，建議調整方式",
+         "列出建議的下一步調查"],
+        errors)
+
+# --- 參數自動完成 (completion/complete) ---
+def fetch_agents_or_raise():
+    agents, error = fetch_agents()
+    if error:
+        raise RuntimeError(error)
+    return agents
+
+COMPLETER = Completer(CachedList(fetch_agents_or_raise, AGENT_CACHE_TTL), RULES, scoped_agents)
+
+@mcp._mcp_server.completion()
+async def complete_argument(ref, argument, context):
+    """prompt 與資源範本參數的候選值 (agent ID / 名稱、規則 ID、規則群組、等級、嚴重度、時間範圍)"""
+    values, total = await asyncio.to_thread(COMPLETER.complete, argument.name, argument.value)
+    return Completion(values=values, total=total, hasMore=total > len(values))
+
+# --- 4. 管理端點 (HTTP 模式) ---
+@mcp.custom_route("/healthz", methods=["GET"])
+async def healthz(request: Request) -> JSONResponse:
+    """存活檢查 (liveness): 行程有在處理請求就回 200，不檢查後端"""
+    return JSONResponse({"status": "ok"})
+
+@mcp.custom_route("/readyz", methods=["GET"])
+async def readyz(request: Request) -> JSONResponse:
+    """就緒檢查 (readiness): 後端都連得上回 200，否則 503；帶管理權杖時附上錯誤細節"""
+    report = await asyncio.to_thread(PREFLIGHT.run)
+    body = report if admin_authorized(request) else public_view(report)
+    return JSONResponse(body, status_code=200 if report["ready"] else 503)
+
+@mcp.custom_route("/metrics", methods=["GET"])
+async def metrics(request: Request) -> PlainTextResponse:
+    """Prometheus 指標: 最近 15 分鐘的後端金絲雀延遲與成功率"""
+    return PlainTextResponse(prometheus_text(CANARY.stats(METRICS_WINDOW)),
+                             media_type="text/plain; version=0.0.4")
+
+def admin_authorized(request):
+    return bool(ADMIN_TOKEN) and request.headers.get("Authorization") == f"Bearer {ADMIN_TOKEN}"
+
+@mcp.custom_route("/ingest/alerts", methods=["POST"])
+async def ingest_alerts(request: Request) -> JSONResponse:
+    """Wazuh integrator 推送告警 (單一 JSON、JSON 陣列或 NDJSON)，需帶 MCP_INGEST_TOKEN"""
+    if not INGEST_TOKEN:
+        return JSONResponse({"error": "ingestion disabled"}, status_code=404)
+    token = request.headers.get("Authorization", "").removeprefix("Bearer ").strip()
+    if not hmac.compare_digest(token.encode(), INGEST_TOKEN.encode()):
+        return JSONResponse({"error": "unauthorized"}, status_code=401)
+    body = await request.body()
+    if len(body) > INGEST_MAX_BYTES:
+        return JSONResponse({"error": f"內容超過 {INGEST_MAX_BYTES} bytes"}, status_code=413)
+    try:
+        alerts = parse_payload(body)
+    except (ValueError, UnicodeDecodeError) as e:
+        return JSONResponse({"error": str(e)}, status_code=400)
+    cursor = INGEST.push(alerts)
+    # 快取清除與訂閱通知會等待事件迴圈，在背景執行緒處理，不拖慢 integrator
+    threading.Thread(target=after_ingest, args=(alerts,), daemon=True).start()
+    return JSONResponse({"accepted": len(alerts), "cursor": cursor}, status_code=202)
+
+@mcp.custom_route("/admin/diagnostics", methods=["GET"])
+async def admin_diagnostics(request: Request) -> JSONResponse:
+    """診斷快照: 開啟中的 session、進行中的呼叫、連線池狀態"""
+    if not admin_authorized(request):
+        return JSONResponse({"error": "unauthorized"}, status_code=401)
+    return JSONResponse({**build_snapshot(TRACKER, HTTP), "backend": SUPERVISOR.status()})
+
+@mcp.custom_route("/admin/sessions", methods=["GET"])
+async def admin_sessions(request: Request) -> JSONResponse:
+    """有往來紀錄的 session 清單 (最近活動的在前)；websocket 模式另列目前的 WebSocket 連線"""
+    if not admin_authorized(request):
+        return JSONResponse({"error": "unauthorized"}, status_code=401)
+    return JSONResponse({"history_size": HISTORY.size, "sessions": HISTORY.sessions_summary(),
+                         "websocket_connections": WS_SESSIONS.sessions(),
+                         "sse_replay": SSE_EVENTS.stats() if SSE_EVENTS is not None else None,
+                         "expiry": SESSION_LIFETIMES.stats() if SESSION_LIFETIMES.enabled else None})
+
+@mcp.custom_route("/admin/sessions/{session_id}/history", methods=["GET"])
+async def admin_session_history(request: Request) -> JSONResponse:
+    """某個 session 最近的 JSON-RPC 往來，?limit=N 只取最後 N 筆"""
+    if not admin_authorized(request):
+        return JSONResponse({"error": "unauthorized"}, status_code=401)
+    try:
+        limit = int(request.query_params.get("limit", 0)) or None
+    except ValueError:
+        return JSONResponse({"error": "limit 必須是整數"}, status_code=400)
+    session_id = request.path_params["session_id"]
+    entries = HISTORY.history(session_id, limit)
+    if entries is None:
+        return JSONResponse({"error": f"沒有 session {session_id} 的紀錄"}, status_code=404)
+    return JSONResponse({"session": session_id, "entries": entries})
+
+@mcp.custom_route("/admin/log-level", methods=["POST"])
+async def admin_log_level(request: Request) -> JSONResponse:
+    """調整 log 等級，body: {"level": "debug"}"""
+    if not admin_authorized(request):
+        return JSONResponse({"error": "unauthorized"}, status_code=401)
+    try:
+        body = await request.json()
+        level = set_log_level(body.get("level", ""))
+    except ValueError as e:
+        return JSONResponse({"error": str(e)}, status_code=400)
+    return JSONResponse({"log_level": level})
+
+# --- 5. 啟動區 ---
+# 啟動前檢查: lazy 立即啟動 (預設)，fail-fast 等後端就緒才開始接受連線
+STARTUP_MODE = os.getenv("MCP_STARTUP_MODE", "lazy").lower()
+if STARTUP_MODE not in STARTUP_MODES:
+    raise ValueError(f"MCP_STARTUP_MODE 必須是 {' 或 '.join(STARTUP_MODES)}")
+PREFLIGHT_TIMEOUT = parse_duration(os.getenv("MCP_PREFLIGHT_TIMEOUT", "60s")).total_seconds()
+PREFLIGHT = Preflight([
+    Check("manager_api", check_manager_api),
+    Check("indexer", check_indexer),
+    Check("alerts_index", check_alerts_index, required=False),
+])
+
+# 背景定期檢查後端連線，異常時自動重新驗證 / 重新連線 (0s = 停用)
+SUPERVISOR = Supervisor(PREFLIGHT, parse_duration(os.getenv("MCP_SUPERVISOR_INTERVAL", "30s")).total_seconds(),
+                        on_failure=reset_backend_connections)
+mcp.add_middleware(DegradedNotice(SUPERVISOR))
+
+# 每個工具宣告 outputSchema 並附上 structuredContent；最後註冊 (最內層)，拿到未經標註的原始回應
+mcp.add_middleware(StructuredOutput())
+
+# 背景定期對每個後端執行便宜的已知查詢並記錄延遲 (0s = 停用)
+CANARY = Canary([
+    Probe("manager_api", probe_manager_api, "Wazuh Manager API (GET /)"),
+    Probe("indexer_search", probe_indexer_search, f"Indexer 搜尋 ({ALERTS_INDEX} 最近 5 分鐘的告警數)"),
+    Probe("indexer_cluster", probe_indexer_cluster, "Indexer 叢集狀態 (_cluster/health)"),
+], parse_duration(os.getenv("MCP_CANARY_INTERVAL", "60s")).total_seconds())
+METRICS_WINDOW = 15 * 60
+
+CACHE_TAIL = CacheTail(lambda body: search_indexer(body, global_filters=False, as_system=True),
+                       QUERY_CACHE, CACHE_TAIL_INTERVAL, CACHE_TAIL_LOOKBACK)
+
+ROLLUPS = Rollups(lambda body: search_indexer(body, as_system=True), STORE,
+                  lambda: QUERIES.render("rollups.daily_aggregations", terms_size=ROLLUP_TERMS),
+                  ROLLUP_INTERVAL, ROLLUP_DAYS, ROLLUP_REFRESH_DAYS)
+
+def log_preflight():
+    """lazy 模式: 在背景跑一次檢查，讓操作人員從 log 就能發現設定錯誤"""
+    report = PREFLIGHT.run(use_cache=False)
+    if report["ready"]:
+        logger.info("後端連線檢查通過:\n%s", format_report(report))
+    else:
+        logger.warning("後端目前無法就緒 (lazy 模式仍會接受連線，工具呼叫時才會回報錯誤):\n%s",
+                       format_report(report))
+
+_background_started = threading.Event()
+
+def start_background():
+    """啟動背景工作 (連線監督、快取尾端檢查、延遲探測、agent 狀態輪詢、告警彙總)，重複呼叫只會啟動一次"""
+    if _background_started.is_set():
+        return
+    _background_started.set()
+    if SUPERVISOR.interval > 0:
+        SUPERVISOR.start()
+    if CACHE_TAIL.interval > 0:
+        CACHE_TAIL.start()
+    if CANARY.interval > 0:
+        CANARY.start()
+    if AGENT_WATCHER.interval > 0:
+        AGENT_WATCHER.start()
+    if ROLLUPS.enabled:
+        ROLLUPS.start()
+
+async def serve(transport, server=None, background=True):
+    """嵌入其他程式時的進入點: 在呼叫端提供的 transport 上執行 MCP Server (見 embedding.py)。
+    不會安裝 signal handler，也不做 fail-fast 檢查；行程的生命週期由宿主程式管理。"""
+    if background:
+        threading.Thread(target=log_preflight, daemon=True).start()
+        start_background()
+    await serve_transport(server or mcp, transport)
+
+# websocket 模式: 每條連線一個 MCP session (0 = 不限連線數)
+WS_SESSIONS = WebSocketSessions(mcp, int(os.getenv("MCP_WS_MAX_SESSIONS", "100")))
+
+# Streamable HTTP 的 SSE 事件保留給 Last-Event-ID 重送 (每個 stream 的事件數，0 = 不保留)
+SSE_REPLAY_EVENTS = int(os.getenv("MCP_SSE_REPLAY_EVENTS", "200"))
+SSE_EVENTS = None
+if SSE_REPLAY_EVENTS > 0:
+    try:
+        SSE_EVENTS = ReplayBuffer(SSE_REPLAY_EVENTS,
+                                  parse_duration(os.getenv("MCP_SSE_REPLAY_TTL", "10m")).total_seconds())
+    except ValueError as e:
+        sys.exit(f"MCP_SSE_REPLAY_TTL 設定錯誤: {e}")
+
+# Streamable HTTP 一個 POST 最多可批次送出的 JSON-RPC 訊息數 (0 = 不接受批次)
+BATCH_MAX_MESSAGES = int(os.getenv("MCP_BATCH_MAX_MESSAGES", "20"))
+
+def end_session(session_id, session):
+    """HTTP session 過期或被 client 刪除: 釋放往來紀錄、SSE 事件、持久化紀錄、資源訂閱與 log 轉送"""
+    HISTORY.forget(session_id)
+    TRACKER.forget(session_id)
+    if SSE_EVENTS is not None:
+        SSE_EVENTS.forget(session_id)
+    if SESSIONS is not None:
+        SESSIONS.forget(session_id)
+    if session is not None:
+        SUBSCRIPTIONS.drop(session)
+        CLIENT_LOG.drop(session)
+
+def http_middleware():
+    """Streamable HTTP app 內額外的 starlette middleware (先拆開批次請求，再檢查過期、接續 session，
+    最後綁定 session 的 event store)"""
+    middleware = []
+    if BATCH_MAX_MESSAGES > 0:
+        middleware.append(StarletteMiddleware(BatchRequests, max_messages=BATCH_MAX_MESSAGES))
+    if SESSION_LIFETIMES.enabled:
+        middleware.append(StarletteMiddleware(ExpireSessions, lifetimes=SESSION_LIFETIMES))
+    if SESSIONS is not None:
+        middleware.append(StarletteMiddleware(ResumableSessions, sessions=SESSIONS))
+    if SSE_EVENTS is not None:
+        middleware.append(StarletteMiddleware(ReplayableStreams, events=SSE_EVENTS))
+    return middleware
+
+def origin_check(app, prefix):
+    """MCP 端點的 Origin 檢查 (MCP_ALLOWED_ORIGINS，預設只允許 loopback) 與 CORS (MCP_CORS)"""
+    try:
+        policy = OriginPolicy(os.getenv("MCP_ALLOWED_ORIGINS", ""),
+                              os.getenv("MCP_CORS", "false").lower() in ("1", "true", "yes"))
+    except ValueError as e:
+        sys.exit(f"MCP_ALLOWED_ORIGINS 設定錯誤: {e}")
+    return OriginCheck(app, f"{prefix}/mcp", policy)
+
+def run_server(transport="stdio", host=None, port=None, socket_path=None, tls_cert=None, tls_key=None,
+               tls_client_ca=None):
+    """啟動 MCP Server；http / websocket 模式的位址預設讀取 MCP_SERVER_HOST / MCP_SERVER_PORT
+    (有憑證時改為 HTTPS，預設讀取 MCP_TLS_CERT / MCP_TLS_KEY / MCP_TLS_CLIENT_CA)，
+    unix 模式的 socket 檔預設讀取 MCP_SOCKET_PATH"""
+    if transport == "unix":
+        prefix = path_prefix(os.getenv("MCP_HTTP_PATH_PREFIX"))
+        app = origin_check(build_http_app(mcp, prefix, TrustedProxies(os.getenv("MCP_TRUSTED_PROXIES", "127.0.0.1")),
+                                          http_middleware()), prefix)
+        app = LimitRequestBody(app, MAX_REQUEST_BYTES)
+        socket_path = socket_path or os.getenv("MCP_SOCKET_PATH", DEFAULT_SOCKET_PATH)
+        try:
+            sock = bind_unix(socket_path, parse_socket_mode(os.getenv("MCP_SOCKET_MODE", "660")),
+                             os.getenv("MCP_SOCKET_GROUP"))
+        except (RuntimeError, ValueError, OSError) as e:
+            sys.exit(f"無法建立 socket {socket_path}: {e}")
+        try:
+            logger.info("MCP Server 監聽 unix socket %s", socket_path)
+            uvicorn.Server(uvicorn.Config(app, proxy_headers=False, lifespan="on")).run(sockets=[sock])
+        finally:
+            close_unix(sock, socket_path)
+    elif transport in ("http", "websocket"):
+        prefix = path_prefix(os.getenv("MCP_HTTP_PATH_PREFIX"))
+        trusted = TrustedProxies(os.getenv("MCP_TRUSTED_PROXIES", "127.0.0.1"))
+        client_ca = tls_client_ca or os.getenv("MCP_TLS_CLIENT_CA")
+        try:
+            options = tls_options(tls_cert or os.getenv("MCP_TLS_CERT"), tls_key or os.getenv("MCP_TLS_KEY"),
+                                  os.getenv("MCP_TLS_KEY_PASSWORD"), client_ca)
+        except ValueError as e:
+            sys.exit(f"TLS 設定錯誤: {e}")
+        # X-Forwarded-* 由 proxy.ForwardedHeaders 依 MCP_TRUSTED_PROXIES 處理，不使用 uvicorn 內建的
+        if transport == "http":
+            app = build_http_app(mcp, prefix, trusted, http_middleware())
+        else:
+            app = behind_proxy(build_websocket_app(mcp, WS_SESSIONS), prefix, trusted)
+            options.update({
+                "ws_ping_interval": parse_duration(os.getenv("MCP_WS_PING_INTERVAL", "20s")).total_seconds(),
+                "ws_ping_timeout": parse_duration(os.getenv("MCP_WS_PING_TIMEOUT", "20s")).total_seconds(),
+            })
+            if MAX_REQUEST_BYTES:
+                options["ws_max_size"] = MAX_REQUEST_BYTES
+        authenticators = []
+        try:
+            keys = APIKeys(os.getenv("MCP_API_KEYS"), os.getenv("MCP_API_KEYS_FILE"))
+            if keys.enabled:
+                authenticators.append(keys)
+            if os.getenv("MCP_OIDC_ISSUER"):
+                authenticators.append(OIDCValidator(
+                    os.getenv("MCP_OIDC_ISSUER"), os.getenv("MCP_OIDC_AUDIENCE"), os.getenv("MCP_OIDC_JWKS_URL"),
+                    [a.strip() for a in os.getenv("MCP_OIDC_ALGORITHMS", ",".join(OIDC_ALGORITHMS)).split(",")
+                     if a.strip()],
+                    os.getenv("MCP_OIDC_PRINCIPAL_CLAIM", "sub")))
+        except ValueError as e:
+            sys.exit(f"驗證設定錯誤: {e}")
+        if authenticators:
+            app = BearerAuth(app, f"{prefix}/mcp", *authenticators)
+        # 放在驗證外層: 先擋掉不允許的 Origin，CORS 預檢不帶 Authorization 也能通過
+        app = origin_check(app, prefix)
+        app = LimitRequestBody(app, MAX_REQUEST_BYTES)
+        if client_ca:
+            # mutual TLS: MCP 端點必須出示 client 憑證，監控端點不需要
+            app = RequireClientCert(app, f"{prefix}/mcp")
+            options.update(http=ClientCertH11Protocol, ws=ClientCertWebSocketProtocol)
+        uvicorn.run(app, host=host or os.getenv("MCP_SERVER_HOST", "127.0.0.1"),
+                    port=port or int(os.getenv("MCP_SERVER_PORT", "8000")),
+                    proxy_headers=False, lifespan="on", **options)
+    else:
+        mcp.run()
+
+def main():
+    parser = argparse.ArgumentParser(description="Wazuh MCP Threat Hunter")
+    parser.add_argument("--transport", choices=["stdio", "http", "websocket", "unix"], default="stdio",
+                        help="MCP 傳輸方式，預設 stdio (給 Claude Desktop 使用)；websocket 給不支援 Streamable HTTP 的 client；"
+                             "unix 以 Unix domain socket 提供 Streamable HTTP")
+    parser.add_argument("--host", help="http / websocket 模式的監聽位址")
+    parser.add_argument("--port", type=int, help="http / websocket 模式的監聽埠")
+    parser.add_argument("--socket-path", help=f"unix 模式的 socket 檔路徑，預設 {DEFAULT_SOCKET_PATH}")
+    parser.add_argument("--tls-cert", help="http / websocket 模式改用 HTTPS 的憑證檔 (PEM，可含中繼憑證鏈)")
+    parser.add_argument("--tls-key", help="--tls-cert 對應的私鑰檔 (PEM)")
+    parser.add_argument("--tls-client-ca", help="要求 client 憑證 (mutual TLS) 時用來驗證的 CA 憑證檔 (PEM)")
+    parser.add_argument("--read-only", action="store_true",
+                        help="唯讀模式: 隱藏並拒絕所有會寫入 Wazuh 的工具 (同 MCP_READ_ONLY=true)")
+    parser.add_argument("--install-service", action="store_true",
+                        help="註冊為 Windows 服務 (以 http 模式開機自動啟動)")
+    parser.add_argument("--uninstall-service", action="store_true", help="移除 Windows 服務")
+    sub = parser.add_subparsers(dest="command")
+    exp = sub.add_parser("export", help="匯出伺服器狀態 (案件、註記、抑制規則、排程) 為 JSON")
+    exp.add_argument("--output", "-o", default="-", help="輸出檔案路徑，預設輸出到 stdout")
+    imp = sub.add_parser("import", help="從 JSON 匯入伺服器狀態")
+    imp.add_argument("--input", "-i", required=True, help="匯出檔路徑")
+    imp.add_argument("--replace", action="store_true", help="先清空匯出檔內出現的資料分類再匯入")
+    rul = sub.add_parser("rules", help="產生監控本伺服器的 Wazuh 規則 (稽核 / 運作 log)")
+    rul.add_argument("--base-id", type=int, default=DEFAULT_BASE_ID, help=f"規則起始 ID，預設 {DEFAULT_BASE_ID}")
+    rul.add_argument("--decoders", action="store_true", help="改為輸出 decoder (syslog 來源需要)")
+    rul.add_argument("--output", "-o", default="-", help="輸出檔案路徑，預設輸出到 stdout")
+    args = parser.parse_args()
+
+    if args.command == "export":
+        data = json.dumps(export_state(STORE), indent=2, ensure_ascii=False)
+        if args.output == "-":
+            print(data)
+        else:
+            with open(args.output, "w", encoding="utf-8") as f:
+                f.write(data)
+            print(f"已匯出至 {args.output}", file=sys.stderr)
+    elif args.command == "rules":
+        try:
+            xml = decoders_xml() if args.decoders else rules_xml(args.base_id)
+        except ValueError as e:
+            sys.exit(str(e))
+        if args.output == "-":
+            print(xml, end="")
+        else:
+            with open(args.output, "w", encoding="utf-8") as f:
+                f.write(xml)
+            print(f"已輸出至 {args.output}", file=sys.stderr)
+    elif args.command == "import":
+        try:
+            with open(args.input, encoding="utf-8") as f:
+                bundle = json.load(f)
+        except (OSError, json.JSONDecodeError) as e:
+            sys.exit(f"無法讀取匯出檔 {args.input}: {e}")
+        try:
+            counts = import_state(STORE, bundle, replace=args.replace)
+        except ValueError as e:
+            sys.exit(f"匯入失敗: {e}")
+        print(f"匯入完成: {json.dumps(counts, ensure_ascii=False)}", file=sys.stderr)
+    elif args.install_service or args.uninstall_service:
+        try:
+            if args.install_service:
+                install_service()
+            else:
+                uninstall_service()
+        except RuntimeError as e:
+            sys.exit(str(e))
+    else:
+        if STARTUP_MODE == "fail-fast":
+            report = PREFLIGHT.wait(PREFLIGHT_TIMEOUT)
+            if not report["ready"]:
+                sys.exit(f"啟動前檢查失敗 (MCP_STARTUP_MODE=fail-fast)，後端在 "
+                         f"{int(PREFLIGHT_TIMEOUT)} 秒內無法就緒:\n{format_report(report)}")
+            logger.info("啟動前檢查通過:\n%s", format_report(report))
+        else:
+            threading.Thread(target=log_preflight, daemon=True).start()
+        if args.read_only:
+            READ_ONLY.enabled = True
+        start_background()
+        install_signal_handlers(TRACKER, HTTP)
+        run_server(args.transport, args.host, args.port, args.socket_path, args.tls_cert, args.tls_key,
+                   args.tls_client_ca)
+
 if __name__ == "__main__":
-    mcp.run()
\ No newline at end of file
+    main()
\ No newline at end of file
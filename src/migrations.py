@@ -0,0 +1,58 @@
+"""伺服器端狀態的版本化遷移 (migrations)。
+
+升級伺服器後，啟動時會依序套用尚未執行過的遷移，確保既有的案件、註記、
+排程獵捕等資料格式跟得上新版程式。每個遷移只會執行一次，執行紀錄存放在
+_migrations namespace，目前版本存放在 _meta/schema_version。
+
+新增遷移的方式: 在 MIGRATIONS 尾端加上 (版本號, 說明, 函式)，版本號必須遞增，
+已發佈的遷移不可再修改。匯入舊版備份時會補跑遷移，所以遷移函式必須可重複執行。
+"""
+
+from datetime import datetime, timezone
+
+META_NAMESPACE = "_meta"
+MIGRATIONS_NAMESPACE = "_migrations"
+
+
+def _baseline(store):
+    """初始版本: 目前的資料格式，不需轉換"""
+
+
+MIGRATIONS = [
+    (1, "baseline", _baseline),
+]
+
+LATEST_VERSION = MIGRATIONS[-1][0]
+
+
+class MigrationError(RuntimeError):
+    pass
+
+
+def current_version(store):
+    meta = store.get(META_NAMESPACE, "schema_version")
+    return meta["version"] if meta else 0
+
+
+def run_migrations(store):
+    """套用所有尚未執行的遷移，回傳這次套用的版本清單"""
+    version = current_version(store)
+    if version > LATEST_VERSION:
+        # 資料是較新版本的伺服器寫入的，繼續執行可能會把資料寫壞
+        raise MigrationError(
+            f"狀態資料版本為 {version}，但此版本伺服器只支援到 {LATEST_VERSION}，請升級伺服器"
+        )
+
+    applied = []
+    for number, name, migrate in MIGRATIONS:
+        if number <= version:
+            continue
+        try:
+            migrate(store)
+        except Exception as e:
+            raise MigrationError(f"遷移 {number} ({name}) 執行失敗: {e}") from e
+        now = datetime.now(timezone.utc).isoformat()
+        store.put(MIGRATIONS_NAMESPACE, f"{number:04d}", {"version": number, "name": name, "applied_at": now})
+        store.put(META_NAMESPACE, "schema_version", {"version": number, "updated_at": now})
+        applied.append(number)
+    return applied
@@ -0,0 +1,81 @@
+"""HTTP / WebSocket 傳輸的 mutual TLS: 只有出示 MCP_TLS_CLIENT_CA 簽發之憑證的 client 才能使用 MCP 端點。
+
+TLS 層以 CERT_OPTIONAL 驗證 (有出示憑證就必須有效)，所以 /healthz、/metrics 等監控端點不需要 client 憑證；
+MCP 端點 (/mcp) 由 RequireClientCert 把關，沒有憑證的請求回 401 (WebSocket 則拒絕握手)。
+uvicorn 不會把 client 憑證放進 ASGI scope，這裡以自訂的 protocol class 在連線建立時取出，
+放在 scope["extensions"]["tls"]；憑證主體 (subject) 會記在每筆工具呼叫的稽核事件 (client_cert)。
+"""
+
+import json
+
+from uvicorn.protocols.http.h11_impl import H11Protocol
+from uvicorn.protocols.websockets.websockets_impl import WebSocketProtocol
+
+# 常見的屬性名稱縮寫 (RFC 4514)
+_SHORT_NAMES = {
+    "commonName": "CN", "organizationName": "O", "organizationalUnitName": "OU", "countryName": "C",
+    "stateOrProvinceName": "ST", "localityName": "L", "emailAddress": "emailAddress",
+}
+# 驗證失敗時的 WebSocket 關閉代碼 (1008 = Policy Violation)
+CLOSE_POLICY_VIOLATION = 1008
+
+
+def subject_of(cert):
+    """ssl.getpeercert() 的 subject -> "CN=soc-bot,O=Example" """
+    return ",".join(f"{_SHORT_NAMES.get(name, name)}={value}"
+                    for rdn in cert.get("subject", ()) for name, value in rdn)
+
+
+def _attach_client_cert(protocol, transport):
+    ssl_object = transport.get_extra_info("ssl_object")
+    cert = ssl_object.getpeercert() if ssl_object is not None else None
+    if not cert:
+        return
+    tls = {"client_cert_subject": subject_of(cert), "client_cert_serial": cert.get("serialNumber")}
+    app = protocol.app
+
+    async def with_client_cert(scope, receive, send):
+        scope["extensions"] = {**scope.get("extensions", {}), "tls": tls}
+        await app(scope, receive, send)
+
+    protocol.app = with_client_cert
+
+
+class ClientCertH11Protocol(H11Protocol):
+    def connection_made(self, transport):
+        super().connection_made(transport)
+        _attach_client_cert(self, transport)
+
+
+class ClientCertWebSocketProtocol(WebSocketProtocol):
+    def connection_made(self, transport):
+        super().connection_made(transport)
+        _attach_client_cert(self, transport)
+
+
+def client_cert(scope):
+    return scope.get("extensions", {}).get("tls")
+
+
+class RequireClientCert:
+    """ASGI middleware: path (MCP 端點) 底下的請求必須帶有已驗證的 client 憑證"""
+
+    def __init__(self, app, path):
+        self.app = app
+        self.path = path.rstrip("/")
+
+    def _protected(self, path):
+        return path == self.path or path.startswith(self.path + "/")
+
+    async def __call__(self, scope, receive, send):
+        if scope["type"] in ("http", "websocket") and self._protected(scope["path"]) and not client_cert(scope):
+            if scope["type"] == "websocket":
+                await send({"type": "websocket.close", "code": CLOSE_POLICY_VIOLATION})
+                return
+            body = json.dumps({"error": "client certificate required"}).encode()
+            await send({"type": "http.response.start", "status": 401,
+                        "headers": [(b"content-type", b"application/json"),
+                                    (b"content-length", str(len(body)).encode())]})
+            await send({"type": "http.response.body", "body": body})
+            return
+        await self.app(scope, receive, send)
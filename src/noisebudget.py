@@ -0,0 +1,248 @@
+"""候選規則的噪音預算模擬: 部署前以過去 N 天的告警 / archives 回測規則邏輯，估計會觸發幾次、落在哪些 agent。
+
+不經過 manager 的 analysisd，而是在伺服器端重現規則的比對條件:
+- 父規則 (<if_sid> / <if_group> / <decoded_as>) 轉成 Indexer 查詢，撈出候選事件
+- 欄位條件 (<field>、<srcip>、<user>、<program_name>、<match>、<regex> 等，含 negate="yes") 逐筆比對；
+  type="pcre2" 用 Python re，osregex / osmatch 轉換成等效的 Python 正規表示式 (不分大小寫)
+- frequency / timeframe (<if_matched_sid> / <if_matched_group> 與 same_* / same_field) 依時間順序以滑動視窗模擬，
+  觸發後清空該鍵的視窗
+- 同一包內的規則可以互為父規則 (例如 ruledraft 產生的單筆規則 + 關聯規則)
+無法重現的條件 (CDB 清單、時間、if_fts 等) 列在 unsupported，視為一律符合，因此估計值偏向高估。
+"""
+
+import ipaddress
+import re
+from collections import Counter, deque
+from datetime import datetime
+import xml.etree.ElementTree as ET
+
+from alert_utils import get_field, parse_timestamp
+
+# 靜態欄位標籤 -> 事件欄位 (多個時任一個符合即可)
+STATIC_TAGS = {
+    "srcip": ["data.srcip"], "dstip": ["data.dstip"], "srcport": ["data.srcport"], "dstport": ["data.dstport"],
+    "protocol": ["data.protocol"], "action": ["data.action"], "id": ["data.id"], "url": ["data.url"],
+    "status": ["data.status"], "system_name": ["data.system_name"], "extra_data": ["data.extra_data"],
+    "user": ["data.srcuser", "data.dstuser"], "srcuser": ["data.srcuser"], "dstuser": ["data.dstuser"],
+    "program_name": ["predecoder.program_name"], "hostname": ["predecoder.hostname"], "location": ["location"],
+    "match": ["full_log"], "regex": ["full_log"],
+}
+IP_TAGS = ("srcip", "dstip")
+# 預設為 osregex 的標籤，其他靜態欄位預設 osmatch
+OSREGEX_TAGS = ("regex", "field")
+SAME_TAGS = {"same_srcip": "data.srcip", "same_dstip": "data.dstip", "same_srcport": "data.srcport",
+             "same_dstport": "data.dstport", "same_srcuser": "data.srcuser", "same_user": "data.dstuser",
+             "same_id": "data.id", "same_url": "data.url", "same_location": "agent.id",
+             "same_protocol": "data.protocol", "same_action": "data.action", "same_system_name": "data.system_name"}
+# 只是說明或已另外處理的標籤
+DESCRIPTIVE_TAGS = {"description", "group", "mitre", "options", "info", "pci_dss", "gdpr", "hipaa", "nist_800_53",
+                    "tsc", "gpg13", "if_sid", "if_group", "if_matched_sid", "if_matched_group", "decoded_as",
+                    "same_field", *SAME_TAGS}
+SOURCE_FIELDS = ["timestamp", "agent.id", "agent.name", "rule.id", "rule.groups", "decoder", "predecoder",
+                 "location", "full_log", "data"]
+TOP_AGENTS = 10
+EXAMPLES = 3
+
+_OSREGEX_CLASSES = {
+    "w": r"[A-Za-z0-9@_\-]", "W": r"[^A-Za-z0-9@_\-]", "d": r"\d", "D": r"\D", "s": r"\s", "S": r"\S",
+    "p": r"[()*+,\-.:;<=>?\[\]!\"'#$%&|{}]", ".": ".", "t": r"\t",
+}
+
+
+def _ids(text):
+    return [part for part in re.split(r"[,\s]+", text or "") if part]
+
+
+def osregex(pattern):
+    """OS_Regex (Wazuh 預設的 regex 語法) -> Python 正規表示式"""
+    out, i = [], 0
+    while i < len(pattern):
+        char = pattern[i]
+        if char == "\\" and i + 1 < len(pattern):
+            nxt = pattern[i + 1]
+            out.append(_OSREGEX_CLASSES.get(nxt, re.escape(nxt)))
+            i += 2
+            continue
+        out.append(char if char in "()|^$+*" else re.escape(char))
+        i += 1
+    return "".join(out)
+
+
+def osmatch(pattern):
+    """OS_Match (簡單字串比對: | 分隔多個選項，^ / $ 表示開頭 / 結尾) -> Python 正規表示式"""
+    options = []
+    for option in pattern.split("|"):
+        start, end = option.startswith("^"), option.endswith("$") and not option.endswith("\\$")
+        core = option[1 if start else 0:len(option) - 1 if end else len(option)]
+        options.append(("^" if start else "") + re.escape(core) + ("$" if end else ""))
+    return "|".join(options)
+
+
+def compile_matcher(tag, element):
+    """條件元素 -> fn(值) -> bool"""
+    text = (element.text or "").strip()
+    kind = element.get("type") or ("osregex" if tag in OSREGEX_TAGS else "osmatch")
+    if tag in IP_TAGS and not element.get("type"):
+        negated = text.startswith("!")
+        networks = [ipaddress.ip_network(part.strip(), strict=False) for part in text.lstrip("!").split(",")]
+
+        def match_ip(value):
+            try:
+                inside = any(ipaddress.ip_address(value) in network for network in networks)
+            except ValueError:
+                return False
+            return inside != negated
+        return match_ip
+    if kind == "pcre2":
+        compiled = re.compile(text)
+    elif kind == "osmatch":
+        compiled = re.compile(osmatch(text), re.I)
+    else:
+        compiled = re.compile(osregex(text), re.I)
+    return lambda value: compiled.search(value) is not None
+
+
+def parse_rules(rules):
+    """規則包 {檔名: XML} -> 依檔案順序的規則清單 (模擬用的條件)；XML 錯誤拋出 ValueError"""
+    parsed = []
+    for filename, text in sorted(rules.items()):
+        try:
+            root = ET.fromstring(f"<mcp_root>{text}</mcp_root>")
+        except ET.ParseError as e:
+            raise ValueError(f"{filename}: XML 格式錯誤: {e}") from e
+        for group in root.iter("group"):
+            group_names = [g.strip() for g in (group.get("name") or "").split(",") if g.strip()]
+            for rule in group.findall("rule"):
+                parsed.append(_parse_rule(rule, filename, group_names))
+    return parsed
+
+
+def _parse_rule(rule, filename, group_names):
+    frequency = rule.get("frequency")
+    item = {
+        "id": rule.get("id"),
+        "file": filename,
+        "level": int(rule.get("level")) if (rule.get("level") or "").isdigit() else None,
+        "description": " ".join((rule.findtext("description") or "").split()),
+        "groups": sorted(set(group_names + [g.strip() for e in rule.findall("group")
+                                            for g in (e.text or "").split(",") if g.strip()])),
+        "if_sid": [i for e in rule.findall("if_sid") for i in _ids(e.text)],
+        "if_group": [g for e in rule.findall("if_group") for g in _ids(e.text)],
+        "if_matched_sid": [i for e in rule.findall("if_matched_sid") for i in _ids(e.text)],
+        "if_matched_group": [g for e in rule.findall("if_matched_group") for g in _ids(e.text)],
+        "decoded_as": (rule.findtext("decoded_as") or "").strip() or None,
+        "frequency": int(frequency) if frequency and frequency.isdigit() else None,
+        "timeframe": int(rule.get("timeframe") or 0) or None,
+        "same": [SAME_TAGS[e.tag] for e in rule if e.tag in SAME_TAGS]
+                + [_data_field((e.text or "").strip()) for e in rule.findall("same_field")],
+        "conditions": [],
+        "unsupported": [],
+    }
+    for element in rule:
+        tag = element.tag
+        if tag in DESCRIPTIVE_TAGS:
+            continue
+        if tag == "field" and element.get("name"):
+            fields = [_data_field(element.get("name")), element.get("name")]
+        elif tag in STATIC_TAGS:
+            fields = STATIC_TAGS[tag]
+        else:
+            item["unsupported"].append(tag)
+            continue
+        try:
+            matcher = compile_matcher(tag, element)
+        except (re.error, ValueError) as e:
+            item["unsupported"].append(f"{tag} ({e})")
+            continue
+        item["conditions"].append({"tag": tag if tag != "field" else f"field:{element.get('name')}",
+                                   "fields": fields, "match": matcher, "negate": element.get("negate") == "yes"})
+    return item
+
+
+def _data_field(name):
+    return name if name.startswith("data.") else f"data.{name}"
+
+
+def matches(rule, event):
+    """事件是否符合規則的欄位條件 (不含父規則)"""
+    if rule["decoded_as"] and rule["decoded_as"] not in (get_field(event, "decoder.name"),
+                                                          get_field(event, "decoder.parent")):
+        return False
+    for condition in rule["conditions"]:
+        values = [str(v) for f in condition["fields"] if (v := get_field(event, f)) not in (None, "")]
+        hit = any(condition["match"](value) for value in values)
+        if hit == condition["negate"]:
+            return False
+    return True
+
+
+def parent_query(rule, internal):
+    """規則的候選事件查詢 (父規則不在這包規則內的部分)；沒有任何限制時回傳 None (= 所有事件)"""
+    sids = rule["if_matched_sid"] if rule["frequency"] else rule["if_sid"]
+    groups = rule["if_matched_group"] if rule["frequency"] else rule["if_group"]
+    should = []
+    external = [sid for sid in sids if sid not in internal]
+    if external:
+        should.append({"terms": {"rule.id": external}})
+    if groups:
+        should.append({"terms": {"rule.groups": groups}})
+    filters = []
+    if should:
+        filters.append({"bool": {"should": should, "minimum_should_match": 1}})
+    elif sids:
+        # 父規則全在這包規則內: 不需要另外查詢
+        return {"internal_only": True}
+    if rule["decoded_as"]:
+        filters.append({"bool": {"should": [{"term": {"decoder.name": rule["decoded_as"]}},
+                                            {"term": {"decoder.parent": rule["decoded_as"]}}],
+                                 "minimum_should_match": 1}})
+    return {"bool": {"filter": filters}} if filters else None
+
+
+def _time(event):
+    parsed = parse_timestamp(get_field(event, "timestamp"))
+    return parsed.timestamp() if parsed else 0
+
+
+def simulate_frequency(rule, events):
+    """依時間順序以滑動視窗模擬 frequency / timeframe，回傳觸發關聯規則的事件 (最後一筆)"""
+    windows, fired = {}, []
+    for event in sorted(events, key=_time):
+        key = tuple(str(get_field(event, field)) for field in rule["same"])
+        window = windows.setdefault(key, deque())
+        now = _time(event)
+        window.append(now)
+        while window and now - window[0] > (rule["timeframe"] or 0):
+            window.popleft()
+        if len(window) >= rule["frequency"] and matches(rule, event):
+            fired.append(event)
+            window.clear()
+    return fired
+
+
+def summarize(rule, fired, candidates, scanned, days, budget):
+    """單條規則的模擬結果與噪音判定"""
+    ratio = candidates / scanned if scanned and candidates > scanned else 1
+    estimated = round(len(fired) * ratio)
+    daily = Counter((parse_timestamp(get_field(e, "timestamp")) or datetime.min).date().isoformat() for e in fired)
+    agents = Counter(get_field(e, "agent.name") or get_field(e, "agent.id") or "unknown" for e in fired)
+    per_day = round(estimated / days, 2)
+    result = {
+        "rule": rule["id"],
+        "level": rule["level"],
+        "description": rule["description"],
+        "candidates": {"total": candidates, "scanned": scanned},
+        "would_fire": len(fired),
+        "per_day": per_day,
+        "agents": [{"agent": agent, "count": count} for agent, count in agents.most_common(TOP_AGENTS)],
+        "distinct_agents": len(agents),
+        "daily": dict(sorted(daily.items())),
+        "examples": [e["_ref"] for e in fired[:EXAMPLES] if e.get("_ref")],
+        "verdict": "silent" if not fired else "noisy" if per_day > budget else "ok",
+    }
+    if ratio > 1:
+        result["estimated_total"] = estimated
+        result["note"] = f"候選事件超過上限，只比對最近的 {scanned} 筆，per_day 依比例推估"
+    if rule["unsupported"]:
+        result["unsupported"] = sorted(set(rule["unsupported"]))
+    return result
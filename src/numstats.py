@@ -0,0 +1,94 @@
+"""數值欄位的統計摘要: 百分位數、標準差、時間序列的移動平均與變化率。
+
+適合傳輸量 (bytes)、持續時間、次數等欄位的量化獵捕，例如「哪台主機的外送流量 p99 特別高」
+「登入耗時是不是突然變長」。欄位在 Indexer 必須是數值型別 (long / float)，keyword 型別的數字無法計算。
+"""
+
+from alert_utils import parse_duration
+
+DEFAULT_PERCENTS = (50, 90, 95, 99)
+METRICS = ("sum", "avg", "max", "min")
+MAX_GROUPS = 50
+
+
+def stats_aggregations(field, interval, time_range, percents=DEFAULT_PERCENTS, group_by=None, limit=10):
+    """整體統計 + 百分位數 + 依 interval 切段的時間序列；有 group_by 時每個分組也各算一份"""
+    if not percents or any(not 0 < p < 100 for p in percents):
+        raise ValueError("percentiles 必須介於 0 與 100 之間 (不含)")
+    if not 1 <= limit <= MAX_GROUPS:
+        raise ValueError(f"limit 必須介於 1 到 {MAX_GROUPS}")
+    metrics = {
+        "stats": {"extended_stats": {"field": field}},
+        "percentiles": {"percentiles": {"field": field, "percents": list(percents)}},
+    }
+    aggs = {
+        **metrics,
+        "over_time": {
+            "date_histogram": {"field": "timestamp", "fixed_interval": interval, "min_doc_count": 0,
+                               "extended_bounds": {"min": time_range, "max": "now"}},
+            "aggs": {"stats": {"stats": {"field": field}}},
+        },
+    }
+    if group_by:
+        aggs["groups"] = {"terms": {"field": group_by, "size": limit, "order": {"stats.sum": "desc"}},
+                          "aggs": metrics}
+    return aggs
+
+
+def _summary(aggs):
+    stats = aggs.get("stats", {})
+    values = aggs.get("percentiles", {}).get("values", {})
+    return {
+        "count": stats.get("count", 0),
+        "min": stats.get("min"),
+        "max": stats.get("max"),
+        "avg": stats.get("avg"),
+        "sum": stats.get("sum"),
+        "std_deviation": stats.get("std_deviation"),
+        # Indexer 回傳 "95.0" 這種鍵，改成 p95
+        "percentiles": {f"p{float(k):g}": v for k, v in values.items()},
+    }
+
+
+def series(buckets, metric, window, interval):
+    """時間序列: 每段的 metric 值、前 window 段的移動平均、與上一段相比的變化 (絕對值 / 百分比) 與每秒速率"""
+    seconds = parse_duration(interval).total_seconds()
+    points, history = [], []
+    previous = None
+    for b in buckets:
+        stats = b.get("stats", {})
+        value = stats.get(metric) if stats.get("count") else None
+        if value is None and metric == "sum":
+            value = 0
+        history.append(value)
+        recent = [v for v in history[-window:] if v is not None]
+        point = {
+            "time": b.get("key_as_string"),
+            "count": b.get("doc_count", 0),
+            metric: value,
+            "moving_avg": round(sum(recent) / len(recent), 3) if recent else None,
+            "change": None,
+            "change_pct": None,
+            "rate_per_second": round(value / seconds, 3) if value is not None and metric == "sum" else None,
+        }
+        if value is not None and previous is not None:
+            point["change"] = round(value - previous, 3)
+            point["change_pct"] = round((value - previous) / previous * 100, 1) if previous else None
+        if value is not None:
+            previous = value
+        points.append(point)
+    return points
+
+
+def summarize(aggregations, metric, window, interval):
+    report = {
+        "overall": _summary(aggregations),
+        "series": series(aggregations.get("over_time", {}).get("buckets", []), metric, window, interval),
+    }
+    if "groups" in aggregations:
+        report["groups"] = [{"key": b.get("key"), "events": b.get("doc_count", 0), **_summary(b)}
+                            for b in aggregations["groups"].get("buckets", [])]
+    # 變化最大的時段 (依絕對值)，方便直接看到突增 / 驟降
+    moves = [p for p in report["series"] if p["change"] is not None]
+    report["largest_changes"] = sorted(moves, key=lambda p: abs(p["change"]), reverse=True)[:5]
+    return report
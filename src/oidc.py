@@ -0,0 +1,84 @@
+"""OAuth2 / OIDC 資源伺服器: 驗證 IdP 簽發的 JWT access token (Authorization: Bearer <JWT>)。
+
+企業部署時 client 向 IdP (Keycloak、Entra ID、Okta...) 取得 token 再呼叫 MCP 端點，
+這裡依 MCP_OIDC_ISSUER 的 discovery 文件 (或直接指定 MCP_OIDC_JWKS_URL) 取得簽章公鑰，
+檢查簽章、有效期限、issuer 與 audience (MCP_OIDC_AUDIENCE)。
+通過驗證後以 MCP_OIDC_PRINCIPAL_CLAIM (預設 sub，常見的還有 preferred_username、email) 的值作為 principal，
+租戶範圍、配額與稽核都以這個身分計算；與 API key (apikeys.py) 可以同時啟用。
+"""
+
+import json
+import logging
+import threading
+import urllib.request
+
+import jwt
+
+from scoping import RESERVED_PRINCIPALS
+
+logger = logging.getLogger("wazuh_mcp.auth")
+
+DEFAULT_ALGORITHMS = ("RS256", "ES256")
+JWKS_CACHE_SECONDS = 300
+
+
+def discover_jwks_url(issuer, timeout=10):
+    """從 {issuer}/.well-known/openid-configuration 取得 jwks_uri"""
+    url = issuer.rstrip("/") + "/.well-known/openid-configuration"
+    with urllib.request.urlopen(url, timeout=timeout) as response:
+        config = json.load(response)
+    if config.get("issuer", "").rstrip("/") != issuer.rstrip("/"):
+        raise ValueError(f"discovery 文件的 issuer ({config.get('issuer')}) 與 MCP_OIDC_ISSUER 不符")
+    if not config.get("jwks_uri"):
+        raise ValueError(f"{url} 沒有 jwks_uri")
+    return config["jwks_uri"]
+
+
+class OIDCValidator:
+    def __init__(self, issuer, audience, jwks_url=None, algorithms=DEFAULT_ALGORITHMS,
+                 principal_claim="sub", leeway=30):
+        if not issuer or not audience:
+            raise ValueError("MCP_OIDC_ISSUER 與 MCP_OIDC_AUDIENCE 必須同時設定")
+        self.issuer = issuer
+        self.audience = audience
+        self.jwks_url = jwks_url
+        self.algorithms = list(algorithms)
+        self.principal_claim = principal_claim
+        self.leeway = leeway
+        self._client = None
+        self._lock = threading.Lock()
+
+    def _jwks(self):
+        # 第一次驗證時才連 IdP，IdP 暫時連不上不會讓伺服器無法啟動
+        with self._lock:
+            if self._client is None:
+                url = self.jwks_url or discover_jwks_url(self.issuer)
+                self._client = jwt.PyJWKClient(url, cache_keys=True, lifespan=JWKS_CACHE_SECONDS)
+            return self._client
+
+    def validate(self, token):
+        """驗證 token 並回傳 claims；任何問題都拋出 jwt.PyJWTError 或 ValueError"""
+        key = self._jwks().get_signing_key_from_jwt(token).key
+        return jwt.decode(token, key, algorithms=self.algorithms, audience=self.audience, issuer=self.issuer,
+                          leeway=self.leeway, options={"require": ["exp", "iss", "aud"]})
+
+    def authenticate(self, token):
+        """token 有效時回傳身分 {"principal", "method": "oidc", "subject", "expires_at"}，否則 None"""
+        # API key 不是 JWT 格式 (header.payload.signature)，直接略過，不必連 IdP
+        if token.count(".") != 2:
+            return None
+        try:
+            claims = self.validate(token)
+        except (jwt.PyJWTError, ValueError, OSError) as e:
+            logger.info("JWT 驗證失敗: %s", e)
+            return None
+        principal = claims.get(self.principal_claim)
+        if not principal or not isinstance(principal, str):
+            logger.info("JWT 缺少 %s claim，無法對應身分 (sub=%s)", self.principal_claim, claims.get("sub"))
+            return None
+        if principal.lower() in RESERVED_PRINCIPALS:
+            logger.warning("拒絕 JWT: %s claim 為保留的身分 '%s' (sub=%s)", self.principal_claim, principal,
+                           claims.get("sub"))
+            return None
+        return {"principal": principal, "method": "oidc", "subject": claims.get("sub"),
+                "expires_at": claims.get("exp")}
@@ -0,0 +1,132 @@
+"""MCP 端點 (/mcp) 的 Origin 檢查與 CORS，防止 DNS rebinding 與跨站 WebSocket 劫持。
+
+瀏覽器中的惡意網頁可以把自己的網域重新解析到 MCP 伺服器的位址 (DNS rebinding)，
+再以使用者的網路位置呼叫工具；這類請求一定帶著攻擊者網頁的 Origin。因此 (依 MCP 規範):
+- 帶 Origin 的請求必須在允許清單中，否則在進入 MCP 處理前回 403 (WebSocket 則拒絕握手)
+- 預設只允許 loopback 的 Origin (http(s)://localhost、127.0.0.1、[::1]，任何埠)，
+  綁定到其他位址時瀏覽器 client 的網址要另外加進 MCP_ALLOWED_ORIGINS
+- 沒有 Origin 的請求 (一般的 MCP client、curl) 不受影響
+允許清單的寫法: https://console.example.com、https://*.example.com (子網域)、http://10.0.0.5:*、
+null (file:// 等不透明來源)；"*" 關閉檢查 (不建議)。
+啟用 CORS (MCP_CORS=true) 時，允許的 Origin 會收到 Access-Control-Allow-* 標頭並可通過預檢 (OPTIONS)，
+瀏覽器中的 client 才能讀到 Mcp-Session-Id 等回應標頭。
+"""
+
+import json
+import logging
+from urllib.parse import urlsplit
+
+from apikeys import CLOSE_POLICY_VIOLATION, _header
+
+logger = logging.getLogger("wazuh_mcp.auth")
+
+LOOPBACK_HOSTS = ("localhost", "127.0.0.1", "::1")
+DEFAULT_PORTS = {"http": 80, "https": 443}
+CORS_METHODS = "GET, POST, DELETE, OPTIONS"
+CORS_HEADERS = "Accept, Authorization, Content-Type, Last-Event-ID, Mcp-Session-Id, MCP-Protocol-Version"
+CORS_EXPOSE = "Mcp-Session-Id, WWW-Authenticate"
+CORS_MAX_AGE = "600"
+
+
+def _split(origin):
+    """Origin -> (scheme, host, port)；格式錯誤時拋出 ValueError"""
+    parts = urlsplit(origin.strip().lower())
+    if parts.scheme not in DEFAULT_PORTS or not parts.hostname or parts.path not in ("", "/"):
+        raise ValueError(f"無效的 Origin '{origin}'，請使用 scheme://host[:port] 的格式")
+    return parts.scheme, parts.hostname, parts.port or DEFAULT_PORTS[parts.scheme]
+
+
+def _pattern(entry):
+    """允許清單的一項 -> (scheme, host 或 *.網域, port 或 None = 任何埠)"""
+    wildcard_port = entry.endswith(":*")
+    base = entry[:-2] if wildcard_port else entry
+    scheme, host, port = _split(base.replace("://*.", "://wildcard."))
+    if "://*." in base:
+        host = "*." + host[len("wildcard."):]
+    return scheme, host, None if wildcard_port else port
+
+
+class OriginPolicy:
+    def __init__(self, allowed=None, cors=False):
+        """allowed: MCP_ALLOWED_ORIGINS 的內容 (逗號分隔字串或清單)；格式錯誤時拋出 ValueError"""
+        if isinstance(allowed, str):
+            allowed = allowed.split(",")
+        entries = [entry.strip() for entry in allowed or [] if entry.strip()]
+        self.allow_all = "*" in entries
+        self.allow_null = "null" in entries
+        self.patterns = [_pattern(entry) for entry in entries if entry not in ("*", "null")]
+        self.patterns += [(scheme, host, None) for scheme in DEFAULT_PORTS for host in LOOPBACK_HOSTS]
+        self.cors = cors
+
+    def allows(self, origin):
+        if self.allow_all:
+            return True
+        if origin.strip().lower() == "null":
+            return self.allow_null
+        try:
+            scheme, host, port = _split(origin)
+        except ValueError:
+            return False
+        for allowed_scheme, allowed_host, allowed_port in self.patterns:
+            if scheme != allowed_scheme or (allowed_port is not None and port != allowed_port):
+                continue
+            if host == allowed_host or (allowed_host.startswith("*.") and host.endswith(allowed_host[1:])):
+                return True
+        return False
+
+    def cors_headers(self, origin):
+        return [(b"access-control-allow-origin", origin.encode("latin-1")), (b"vary", b"Origin"),
+                (b"access-control-expose-headers", CORS_EXPOSE.encode())]
+
+
+class OriginCheck:
+    """ASGI middleware: path (MCP 端點) 底下帶 Origin 的請求必須符合 policy；放在 BearerAuth 外層，
+    CORS 預檢 (不帶 Authorization) 才不會被擋"""
+
+    def __init__(self, app, path, policy):
+        self.app = app
+        self.path = path.rstrip("/")
+        self.policy = policy
+
+    def _protected(self, path):
+        return path == self.path or path.startswith(self.path + "/")
+
+    async def _reject(self, scope, send, origin):
+        logger.warning("拒絕來自不允許的 Origin 的請求: %s %s (Origin %s, client %s)", scope.get("method", "WEBSOCKET"),
+                       scope["path"], origin, (scope.get("client") or ("?",))[0])
+        if scope["type"] == "websocket":
+            await send({"type": "websocket.close", "code": CLOSE_POLICY_VIOLATION})
+            return
+        body = json.dumps({"error": "origin not allowed"}).encode()
+        await send({"type": "http.response.start", "status": 403,
+                    "headers": [(b"content-type", b"application/json"),
+                                (b"content-length", str(len(body)).encode())]})
+        await send({"type": "http.response.body", "body": body})
+
+    async def _preflight(self, send, origin):
+        await send({"type": "http.response.start", "status": 204,
+                    "headers": self.policy.cors_headers(origin) + [
+                        (b"access-control-allow-methods", CORS_METHODS.encode()),
+                        (b"access-control-allow-headers", CORS_HEADERS.encode()),
+                        (b"access-control-max-age", CORS_MAX_AGE.encode())]})
+        await send({"type": "http.response.body", "body": b""})
+
+    async def __call__(self, scope, receive, send):
+        if scope["type"] not in ("http", "websocket") or not self._protected(scope["path"]):
+            return await self.app(scope, receive, send)
+        origin = _header(scope.get("headers", []), b"origin")
+        if origin is None:
+            return await self.app(scope, receive, send)
+        if not self.policy.allows(origin):
+            return await self._reject(scope, send, origin)
+        if scope["type"] == "websocket" or not self.policy.cors:
+            return await self.app(scope, receive, send)
+        if scope["method"] == "OPTIONS" and _header(scope["headers"], b"access-control-request-method"):
+            return await self._preflight(send, origin)
+
+        async def add_cors(message):
+            if message["type"] == "http.response.start":
+                message = {**message, "headers": list(message.get("headers", [])) + self.policy.cors_headers(origin)}
+            await send(message)
+
+        await self.app(scope, receive, add_cors)
@@ -0,0 +1,87 @@
+"""osquery wodle 的排程查詢結果。
+
+Wazuh 的 osquery wodle 讀取 osqueryd 的結果日誌，每一列結果成為一筆 osquery 群組的告警:
+- data.osquery.name: 查詢名稱；pack 裡的查詢為 pack_<pack 名稱>_<查詢名稱>
+- data.osquery.pack: pack 名稱 (wodle 啟用 add_labels / 新版解碼器才有，舊資料只能從 name 判斷)
+- data.osquery.action: added / removed (差異模式) 或 snapshot (快照模式)
+- data.osquery.columns: 查詢結果的欄位
+已經部署 osquery 的環境可以直接拿這些結果來獵捕 (例如 listening_ports、crontab、authorized_keys)，
+不必另外在主機上執行查詢。
+"""
+
+from alert_utils import get_field, first_field
+from provenance import event_ref
+
+OSQUERY_FILTER = {"bool": {"should": [
+    {"terms": {"rule.groups": ["osquery"]}},
+    {"exists": {"field": "data.osquery.name"}},
+], "minimum_should_match": 1}}
+ACTIONS = ("added", "removed", "snapshot")
+SOURCE_FIELDS = ["timestamp", "agent.id", "agent.name", "rule.id", "data.osquery"]
+
+
+def results_query(query_name=None, pack=None, action=None):
+    """依查詢名稱、pack 與 action 篩選 osquery 結果；名稱同時比對單獨的查詢與 pack 裡的查詢"""
+    filters = [OSQUERY_FILTER]
+    if query_name:
+        filters.append({"bool": {"should": [
+            {"term": {"data.osquery.name": query_name}},
+            {"wildcard": {"data.osquery.name": {"value": f"pack_*_{query_name}"}}},
+        ], "minimum_should_match": 1}})
+    if pack:
+        filters.append({"bool": {"should": [
+            {"term": {"data.osquery.pack": pack}},
+            {"prefix": {"data.osquery.name": f"pack_{pack}_"}},
+        ], "minimum_should_match": 1}})
+    if action:
+        if action not in ACTIONS:
+            raise ValueError(f"未知的 action '{action}'，可用: {', '.join(ACTIONS)}")
+        filters.append({"term": {"data.osquery.action": action}})
+    return {"bool": {"filter": filters}}
+
+
+def summary_aggregations(limit):
+    """各查詢的結果筆數、涉及的 agent 數與最新一次結果的時間"""
+    return {"queries": {"terms": {"field": "data.osquery.name", "size": limit}, "aggs": {
+        "agents": {"cardinality": {"field": "agent.id"}},
+        "actions": {"terms": {"field": "data.osquery.action", "size": len(ACTIONS)}},
+        "last_seen": {"max": {"field": "timestamp"}},
+    }}}
+
+
+def split_name(name, pack=None):
+    """pack_<pack>_<query> -> (pack, query)；pack 名稱本身含底線時以已知的 pack 切開"""
+    if pack and name and name.startswith(f"pack_{pack}_"):
+        return pack, name[len(f"pack_{pack}_"):]
+    if name and name.startswith("pack_") and "_" in name[5:]:
+        return tuple(name[5:].split("_", 1))
+    return pack, name
+
+
+def summarize_queries(aggregations):
+    rows = []
+    for bucket in aggregations.get("queries", {}).get("buckets", []):
+        pack, query = split_name(bucket["key"])
+        rows.append({
+            "name": bucket["key"], "pack": pack, "query": query, "results": bucket["doc_count"],
+            "agents": bucket.get("agents", {}).get("value"),
+            "actions": {b["key"]: b["doc_count"] for b in bucket.get("actions", {}).get("buckets", [])},
+            "last_seen": bucket.get("last_seen", {}).get("value_as_string"),
+        })
+    return rows
+
+
+def result_row(hit):
+    """單筆 osquery 結果: 查詢、agent、action 與結果欄位"""
+    alert = hit.get("_source", {})
+    name = get_field(alert, "data.osquery.name")
+    pack, query = split_name(name, get_field(alert, "data.osquery.pack"))
+    return {
+        "timestamp": alert.get("timestamp"),
+        "agent": first_field(alert, "agent.name", "agent.id"),
+        "pack": pack,
+        "query": query,
+        "action": get_field(alert, "data.osquery.action"),
+        "columns": get_field(alert, "data.osquery.columns") or {},
+        "_ref": event_ref(hit),
+    }
@@ -0,0 +1,100 @@
+"""工具輸出格式轉換 (JSON / Arrow IPC) 與 middleware 對工具回應的附加標註。
+
+Arrow IPC 讓資料科學使用者可以直接用 pandas / polars 載入獵捕結果:
+    pyarrow.ipc.open_stream(base64.b64decode(data)).read_pandas()
+"""
+
+import base64
+import json
+import os
+from datetime import datetime
+
+from fastmcp.tools.tool import ToolResult
+from mcp.types import TextContent
+
+try:
+    import pyarrow as pa
+except ImportError:  # pyarrow 為選用套件，沒安裝時只停用 arrow 輸出
+    pa = None
+
+OUTPUT_FORMATS = ("json", "arrow")
+
+
+def flatten(doc, prefix=""):
+    """將巢狀告警攤平成 {"rule.level": 10, "agent.name": ...} 的表格列"""
+    row = {}
+    for key, value in doc.items():
+        name = f"{prefix}{key}"
+        if isinstance(value, dict):
+            row.update(flatten(value, f"{name}."))
+        else:
+            row[name] = value
+    return row
+
+
+def _column(values):
+    """建立單一欄位；型別不一致時 (例如同欄位有數字也有字串) 改以 JSON 字串保存，確保不遺失資料"""
+    try:
+        return pa.array(values)
+    except (pa.ArrowInvalid, pa.ArrowTypeError):
+        return pa.array([
+            None if v is None else (v if isinstance(v, str) else json.dumps(v, ensure_ascii=False))
+            for v in values
+        ], type=pa.string())
+
+
+def to_arrow_ipc(rows):
+    """將多筆 dict 轉成 Arrow IPC stream 的位元組"""
+    if pa is None:
+        raise RuntimeError("未安裝 pyarrow，無法輸出 Arrow 格式 (pip install pyarrow)")
+    flat = [flatten(r) for r in rows]
+    columns = sorted({key for r in flat for key in r})
+    table = pa.table({c: _column([r.get(c) for r in flat]) for c in columns})
+    sink = pa.BufferOutputStream()
+    with pa.ipc.new_stream(sink, table.schema) as writer:
+        writer.write_table(table)
+    return sink.getvalue().to_pybytes(), table.num_rows, columns
+
+
+def render_rows(rows, output_format="json", output_dir=None, extra=None):
+    """依 output_format 輸出結果。arrow 模式下若有 output_dir 則寫成 .arrow 檔，否則回傳 base64"""
+    extra = extra or {}
+    if output_format == "json":
+        return json.dumps({**extra, "alerts": rows}, indent=2, ensure_ascii=False)
+    if output_format != "arrow":
+        raise ValueError(f"不支援的輸出格式 '{output_format}'，可用: {', '.join(OUTPUT_FORMATS)}")
+
+    data, num_rows, columns = to_arrow_ipc(rows)
+    report = {**extra, "format": "arrow-ipc-stream", "rows": num_rows, "columns": columns}
+    if output_dir:
+        os.makedirs(output_dir, exist_ok=True)
+        path = os.path.join(output_dir, f"hunt-{datetime.now().strftime('%Y%m%d-%H%M%S-%f')}.arrow")
+        with open(path, "wb") as f:
+            f.write(data)
+        report["path"] = os.path.abspath(path)
+    else:
+        report["encoding"] = "base64"
+        report["data"] = base64.b64encode(data).decode("ascii")
+    return json.dumps(report, indent=2, ensure_ascii=False)
+
+
+def annotate_result(result, key, value, label, first=True):
+    """在工具回應加上標註: JSON 物件加 key 欄位，其他文字則在前面加一行 [label]；
+    first=False 時放在最後，非 JSON 物件的回應改附在獨立的內容區塊 (原本的文字保持不變，
+    JSON 陣列仍可解析、以「錯誤:」開頭的訊息也不受影響)"""
+    content = list(result.content)
+    if content and isinstance(content[0], TextContent):
+        text = content[0].text
+        try:
+            parsed = json.loads(text)
+        except ValueError:
+            parsed = None
+        line = f"[{label}] {json.dumps(value, ensure_ascii=False)}"
+        if isinstance(parsed, dict):
+            merged = {key: value, **parsed} if first else {**parsed, key: value}
+            content[0] = TextContent(type="text", text=json.dumps(merged, indent=2, ensure_ascii=False))
+        elif first:
+            content[0] = TextContent(type="text", text=f"{line}\n{text}")
+        else:
+            content.append(TextContent(type="text", text=line))
+    return ToolResult(content=content, structured_content=result.structured_content)
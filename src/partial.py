@@ -0,0 +1,45 @@
+"""Indexer 回傳不完整結果 (分片失敗、逾時) 的偵測與縮小查詢重試。
+
+OpenSearch 在部分分片失敗或查詢逾時時仍會回傳 200，結果看起來和完整的一樣；
+這裡把這種情況明確標示出來，避免 AI 把「少掉的資料」當成「沒有發生」。
+"""
+
+from alert_utils import parse_duration
+
+
+def incomplete_info(result):
+    """結果不完整時回傳說明 dict，完整時回傳 None"""
+    shards = result.get("_shards", {})
+    failed = shards.get("failed", 0)
+    timed_out = result.get("timed_out", False)
+    if not failed and not timed_out:
+        return None
+    reasons = []
+    for failure in shards.get("failures", [])[:5]:
+        reason = failure.get("reason", {})
+        reasons.append({
+            "index": failure.get("index"),
+            "shard": failure.get("shard"),
+            "reason": reason.get("reason") if isinstance(reason, dict) else str(reason),
+        })
+    return {
+        "timed_out": timed_out,
+        "shards_total": shards.get("total"),
+        "shards_failed": failed,
+        "failures": reasons,
+        "warning": "Indexer 只回傳了部分結果，計數與清單可能偏低，不可據此判定「沒有發生」",
+    }
+
+
+def narrow(body):
+    """縮小查詢以便重試: 回傳筆數減半、逾時時間加倍"""
+    narrowed = dict(body)
+    if narrowed.get("size"):
+        narrowed["size"] = max(1, narrowed["size"] // 2)
+    if narrowed.get("timeout"):
+        try:
+            seconds = parse_duration(narrowed["timeout"]).total_seconds()
+            narrowed["timeout"] = f"{int(seconds * 2)}s"
+        except ValueError:
+            pass
+    return narrowed
@@ -0,0 +1,121 @@
+"""持久化機制 (persistence) 的綜合獵捕。
+
+攻擊者站穩腳步後幾乎一定會留下持久化: 排程工作、服務、登錄檔 Run 機碼、cron、systemd。
+這些線索分散在 Windows 事件記錄、Sysmon、auditd 與 FIM (syscheck) 之中，
+這裡以一次查詢把它們全部撈出來 (每種機制是一個具名查詢，靠 matched_queries 分類)，
+再整理成每台主機的「持久化候選清單」，並附上建立者、建立程序與原始規則等脈絡。
+"""
+
+from alert_utils import get_field, first_field
+from provenance import event_ref
+
+
+def _wildcard(field, pattern):
+    return {"wildcard": {field: {"value": pattern, "case_insensitive": True}}}
+
+
+def _event_ids(*ids):
+    return {"terms": {"data.win.system.eventID": list(ids)}}
+
+
+def _process(image, command=None):
+    """Sysmon Event 1 / Security 4688 的程序建立事件"""
+    clauses = [_wildcard("data.win.eventdata.image", f"*\\\\{image}")]
+    if command:
+        clauses.append(_wildcard("data.win.eventdata.commandLine", command))
+    return {"bool": {"filter": clauses}}
+
+
+def _any(*clauses):
+    return {"bool": {"should": list(clauses), "minimum_should_match": 1}}
+
+
+_RUN_KEY = "*\\\\CurrentVersion\\\\Run*"
+
+MECHANISMS = {
+    "scheduled_task": _any(
+        _event_ids("4698", "4702", "106"),
+        _process("schtasks.exe", "*/create*"),
+        _process("at.exe"),
+        _wildcard("syscheck.path", "*\\\\System32\\\\Tasks\\\\*"),
+    ),
+    "service": _any(
+        _event_ids("7045", "4697"),
+        _process("sc.exe", "*create*"),
+        _wildcard("syscheck.path", "*\\\\CurrentControlSet\\\\Services\\\\*"),
+    ),
+    "registry_run_key": _any(
+        _wildcard("syscheck.path", _RUN_KEY),
+        {"bool": {"filter": [_event_ids("13"), _wildcard("data.win.eventdata.targetObject", _RUN_KEY)]}},
+    ),
+    "cron": _any(
+        _wildcard("syscheck.path", "/etc/cron*"),
+        _wildcard("syscheck.path", "/var/spool/cron/*"),
+        _wildcard("data.audit.exe", "*/crontab"),
+    ),
+    "systemd": _any(
+        _wildcard("syscheck.path", "/etc/systemd/system/*"),
+        _wildcard("syscheck.path", "/usr/lib/systemd/system/*"),
+        _wildcard("syscheck.path", "*/.config/systemd/user/*"),
+        {"bool": {"filter": [_wildcard("data.audit.exe", "*/systemctl"),
+                             _wildcard("data.audit.command", "*enable*")]}},
+    ),
+}
+
+
+def persistence_query(mechanisms):
+    unknown = [m for m in mechanisms if m not in MECHANISMS]
+    if unknown:
+        raise ValueError(f"未知的持久化機制 {unknown}，可用: {', '.join(MECHANISMS)}")
+    should = [{"bool": {**MECHANISMS[m]["bool"], "_name": m}} for m in mechanisms]
+    return {"bool": {"should": should, "minimum_should_match": 1}}
+
+
+def normalize(hit):
+    """將單筆事件整理成持久化候選: 機制、名稱、會被執行的內容與建立脈絡"""
+    alert = hit.get("_source", {})
+    matched = hit.get("matched_queries") or ["unknown"]
+    path = get_field(alert, "syscheck.path")
+    value_name = get_field(alert, "syscheck.value_name")
+    return {
+        "mechanism": matched[0],
+        "timestamp": alert.get("timestamp"),
+        "name": first_field(alert, "data.win.eventdata.taskName", "data.win.eventdata.serviceName",
+                           "data.win.eventdata.targetObject")
+                or (f"{path}\\{value_name}" if path and value_name else path),
+        "executes": first_field(alert, "data.win.eventdata.imagePath", "data.win.eventdata.serviceFileName",
+                                "data.win.eventdata.details", "data.win.eventdata.taskContent",
+                                "syscheck.value_data", "data.audit.command"),
+        "change": get_field(alert, "syscheck.event"),
+        "context": {
+            "user": first_field(alert, "data.win.eventdata.subjectUserName", "data.win.eventdata.user",
+                                "syscheck.uname_after", "data.audit.auid"),
+            "process": first_field(alert, "data.win.eventdata.image", "syscheck.audit.process.name", "data.audit.exe"),
+            "command_line": first_field(alert, "data.win.eventdata.commandLine", "data.audit.command"),
+            "parent": first_field(alert, "data.win.eventdata.parentImage", "syscheck.audit.process.parent_name"),
+            "rule": {"id": get_field(alert, "rule.id"), "description": get_field(alert, "rule.description")},
+        },
+        "_ref": event_ref(hit),
+    }
+
+
+def group_by_agent(hits):
+    """{主機: {"candidates": [...], "mechanisms": {機制: 數量}}}，同一名稱只保留最新的一筆並計數"""
+    agents = {}
+    for hit in hits:
+        alert = hit.get("_source", {})
+        agent = get_field(alert, "agent.name") or get_field(alert, "agent.id")
+        entry = agents.setdefault(agent, {"candidates": {}, "mechanisms": {}})
+        candidate = normalize(hit)
+        key = (candidate["mechanism"], candidate["name"], candidate["executes"])
+        existing = entry["candidates"].get(key)
+        if existing:
+            existing["events"] += 1
+            continue
+        candidate["events"] = 1
+        entry["candidates"][key] = candidate
+        entry["mechanisms"][candidate["mechanism"]] = entry["mechanisms"].get(candidate["mechanism"], 0) + 1
+    return {
+        agent: {"mechanisms": e["mechanisms"], "candidates": list(e["candidates"].values())}
+        for agent, e in agents.items()
+    }
@@ -0,0 +1,87 @@
+"""啟動前檢查 (preflight) 與就緒狀態 (readiness)。
+
+MCP_STARTUP_MODE 決定啟動行為:
+- lazy (預設): 立即開始接受 MCP 連線，後端在第一次使用時才連線；檢查結果在背景記錄到 log，
+  HTTP 模式可由 /readyz 查詢
+- fail-fast: 先確認後端都連得上才開始接受連線；在 MCP_PREFLIGHT_TIMEOUT 內一直失敗則印出
+  每一項檢查的診斷並以非 0 結束，讓 systemd / Kubernetes 明確看到啟動失敗
+"""
+
+import threading
+import time
+from datetime import datetime, timezone
+
+STARTUP_MODES = ("lazy", "fail-fast")
+
+
+class Check:
+    """fn() 成功時回傳 None，失敗時回傳錯誤訊息字串；required=False 的檢查失敗只警告，不影響就緒狀態"""
+
+    def __init__(self, name, fn, required=True):
+        self.name = name
+        self.fn = fn
+        self.required = required
+
+
+class Preflight:
+    def __init__(self, checks, cache_seconds=5):
+        self.checks = checks
+        self.cache_seconds = cache_seconds
+        self.lock = threading.Lock()
+        self._last = None
+        self._last_at = 0
+
+    def _run_one(self, check):
+        started = time.monotonic()
+        try:
+            error = check.fn()
+        except Exception as e:  # 檢查本身出錯也視為失敗，不讓例外中斷啟動流程
+            error = f"{type(e).__name__}: {e}"
+        return {
+            "name": check.name,
+            "ok": error is None,
+            "required": check.required,
+            "latency_ms": round((time.monotonic() - started) * 1000),
+            "detail": error,
+        }
+
+    def run(self, use_cache=True):
+        """執行所有檢查；cache_seconds 內重複呼叫 (例如 readiness probe) 直接回傳上次結果"""
+        with self.lock:
+            if use_cache and self._last and time.monotonic() - self._last_at < self.cache_seconds:
+                return self._last
+            results = [self._run_one(check) for check in self.checks]
+            self._last = {
+                "ready": all(r["ok"] for r in results if r["required"]),
+                "checked_at": datetime.now(timezone.utc).isoformat(),
+                "checks": results,
+            }
+            self._last_at = time.monotonic()
+            return self._last
+
+    def wait(self, timeout, interval=5):
+        """重試直到全部必要檢查通過或逾時，回傳最後一次的結果"""
+        deadline = time.monotonic() + timeout
+        while True:
+            report = self.run(use_cache=False)
+            if report["ready"] or time.monotonic() + interval > deadline:
+                return report
+            time.sleep(interval)
+
+
+def public_view(report):
+    """給未驗證的 probe 看的版本: 不含錯誤細節 (可能包含內部主機名稱)"""
+    return {
+        "ready": report["ready"],
+        "checked_at": report["checked_at"],
+        "checks": [{"name": r["name"], "ok": r["ok"]} for r in report["checks"]],
+    }
+
+
+def format_report(report):
+    """啟動失敗時輸出到 stderr 的文字版診斷"""
+    lines = []
+    for r in report["checks"]:
+        status = "OK  " if r["ok"] else ("FAIL" if r["required"] else "WARN")
+        lines.append(f"  [{status}] {r['name']} ({r['latency_ms']} ms)" + (f": {r['detail']}" if r["detail"] else ""))
+    return "\n".join(lines)
@@ -0,0 +1,54 @@
+"""呼叫者身分 (principal) 的判斷。
+
+stdio 模式只有本機使用者，一律視為 "local" (不受租戶範圍限制)；HTTP 模式 (含 unix socket) 下由前端可信任的
+反向代理 / 閘道在 MCP_PRINCIPAL_HEADER 指定的標頭帶入租戶或使用者名稱，只採用來自 MCP_TRUSTED_PROXIES 的連線帶的標頭
+(直接連到 server 的 client 自行帶入的標頭會被忽略，否則任何人都能冒用其他租戶)；
+啟用 Bearer 驗證 (API key / OIDC，見 apikeys.py) 時，通過驗證的身分優先於標頭。
+"local" 只保留給 stdio: client 在標頭自行帶入保留的名稱時忽略。沒有帶身分的 HTTP 請求為 "anonymous"；
+MCP_PRINCIPAL_FALLBACK=client-ip 時改以 client 位址 ("ip:203.0.113.5"，在受信任的代理後方為
+X-Forwarded-For 的位址) 計算用量與配額。這兩種都是未識別的呼叫者，設定租戶範圍時看不到任何主機。
+"""
+
+import os
+
+from fastmcp.server.dependencies import get_http_headers, get_http_request
+
+from apikeys import authenticated_principal
+from proxy import via_trusted_proxy
+from scoping import ANONYMOUS_PRINCIPAL, LOCAL_PRINCIPAL
+
+PRINCIPAL_HEADER = os.getenv("MCP_PRINCIPAL_HEADER", "x-mcp-principal").lower()
+FALLBACK = os.getenv("MCP_PRINCIPAL_FALLBACK", "anonymous").lower()
+if FALLBACK not in ("anonymous", "client-ip"):
+    raise ValueError("MCP_PRINCIPAL_FALLBACK 必須是 anonymous 或 client-ip")
+
+
+def current_principal():
+    """回傳目前請求的 principal；不在 HTTP 請求中 (stdio) 時回傳 "local" """
+    try:
+        request = get_http_request()
+    except Exception:
+        return LOCAL_PRINCIPAL
+    authenticated = authenticated_principal(request.scope)
+    if authenticated:
+        return authenticated
+    claimed = get_http_headers().get(PRINCIPAL_HEADER) if via_trusted_proxy(request.scope) else None
+    if claimed and not unidentified(claimed) and claimed.lower() != LOCAL_PRINCIPAL:
+        return claimed
+    if FALLBACK == "client-ip" and request.client:
+        return f"ip:{request.client.host}"
+    return ANONYMOUS_PRINCIPAL
+
+
+def unidentified(principal):
+    """沒有帶身分的 HTTP 呼叫者 ("anonymous" 或 client-ip 的 "ip:<位址>")"""
+    return principal.lower() == ANONYMOUS_PRINCIPAL or principal.startswith("ip:")
+
+
+def client_certificate():
+    """mutual TLS 時目前請求的 client 憑證主體 (例如 "CN=soc-bot,O=Example")；沒有時回傳 None"""
+    try:
+        scope = get_http_request().scope
+    except Exception:
+        return None
+    return (scope.get("extensions", {}).get("tls") or {}).get("client_cert_subject")
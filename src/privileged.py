@@ -0,0 +1,135 @@
+"""特權指令 (sudo / su / pkexec) 使用摘要。
+
+來源包含 Wazuh 內建 sudo / su 解碼器產生的告警與 auditd 的 EXECVE 事件。
+平常只有固定幾個人、固定幾個指令會用到 sudo，所以「第一次用 sudo 的帳號」
+與「不在基準清單裡的指令」都很值得注意。
+基準清單由 PRIVILEGED_COMMAND_BASELINE 指定，可以是檔案路徑或以逗號分隔的清單，
+每一項為指令的 glob 樣式，例如: /usr/bin/systemctl *,/usr/bin/apt*,/usr/bin/journalctl
+"""
+
+import fnmatch
+import os
+
+from alert_utils import get_field
+
+PRIVILEGED_TOOLS = ("sudo", "su", "pkexec")
+PRIVILEGED_QUERY = {"bool": {"should": [
+    {"terms": {"rule.groups": ["sudo", "su"]}},
+    {"terms": {"predecoder.program_name": list(PRIVILEGED_TOOLS)}},
+    {"terms": {"data.audit.exe": ["/usr/bin/sudo", "/usr/bin/su", "/bin/su", "/usr/bin/pkexec"]}},
+], "minimum_should_match": 1}}
+
+# 執行特權指令的使用者 (auditd 的 auid 是登入時的原始帳號，換過身分也不會變)
+USER_FIELDS = ["data.srcuser", "data.audit.auid", "data.audit.acct"]
+TARGET_FIELDS = ["data.dstuser", "data.audit.euid"]
+SOURCE_FIELDS = [
+    "timestamp", "agent.name", "rule.groups", "rule.description", "predecoder.program_name",
+    "data.srcuser", "data.dstuser", "data.command", "data.tty", "data.pwd",
+    "data.audit.auid", "data.audit.acct", "data.audit.euid", "data.audit.exe",
+    "data.audit.command", "data.audit.execve", "data.audit.success",
+]
+
+
+def parse_command_baseline(text):
+    """回傳 glob 樣式清單；text 若是存在的檔案路徑，則逐行讀取 (# 開頭為註解)"""
+    text = (text or "").strip()
+    if text and os.path.isfile(text):
+        with open(text, encoding="utf-8") as f:
+            items = [line.split("#", 1)[0] for line in f]
+    else:
+        items = text.split(",")
+    return [item.strip() for item in items if item.strip()]
+
+
+def _first(alert, fields):
+    for field in fields:
+        value = get_field(alert, field)
+        if value not in (None, ""):
+            return str(value)
+    return None
+
+
+def privileged_tool(alert):
+    program = get_field(alert, "predecoder.program_name")
+    if program in PRIVILEGED_TOOLS:
+        return program
+    exe = get_field(alert, "data.audit.exe") or ""
+    for tool in PRIVILEGED_TOOLS:
+        if exe.endswith("/" + tool):
+            return tool
+    groups = get_field(alert, "rule.groups") or []
+    return next((g for g in groups if g in PRIVILEGED_TOOLS), None)
+
+
+def command_line(alert):
+    """sudo 解碼器的 data.command，或把 auditd EXECVE 的 a0, a1 … 組回完整指令"""
+    command = get_field(alert, "data.command")
+    if command:
+        return str(command)
+    execve = get_field(alert, "data.audit.execve")
+    if isinstance(execve, dict):
+        keys = sorted((k for k in execve if k.startswith("a") and k[1:].isdigit()), key=lambda k: int(k[1:]))
+        args = [execve[k] for k in keys]
+        # execve 的 a0 是 sudo 本身，真正被執行的指令從 a1 開始
+        if args and os.path.basename(str(args[0])) in PRIVILEGED_TOOLS:
+            args = args[1:]
+        if args:
+            return " ".join(str(a) for a in args)
+    return get_field(alert, "data.audit.command")
+
+
+def in_baseline(command, baseline):
+    return any(fnmatch.fnmatch(command, pattern) for pattern in baseline)
+
+
+def users_aggregation():
+    return {f"users__{i}": {"terms": {"field": field, "size": 10000}} for i, field in enumerate(USER_FIELDS)}
+
+
+def collect_users(aggregations):
+    return {str(b["key"]) for i in range(len(USER_FIELDS))
+            for b in aggregations.get(f"users__{i}", {}).get("buckets", [])}
+
+
+def summarize(alerts, known_users, baseline, limit):
+    """依 (使用者, 主機) 彙整；標出基準期間沒出現過的使用者與不在基準清單的指令"""
+    summary = {}
+    for alert in alerts:
+        user = _first(alert, USER_FIELDS) or "unknown"
+        host = get_field(alert, "agent.name")
+        entry = summary.setdefault((user, host), {
+            "user": user, "agent": host, "count": 0, "tools": {}, "targets": set(),
+            "commands": {}, "first_seen": alert.get("timestamp"), "last_seen": alert.get("timestamp"),
+        })
+        entry["count"] += 1
+        tool = privileged_tool(alert) or "unknown"
+        entry["tools"][tool] = entry["tools"].get(tool, 0) + 1
+        target = _first(alert, TARGET_FIELDS)
+        if target:
+            entry["targets"].add(target)
+        command = command_line(alert)
+        if command:
+            entry["commands"][command] = entry["commands"].get(command, 0) + 1
+        ts = alert.get("timestamp")
+        if ts:
+            entry["first_seen"] = min(entry["first_seen"] or ts, ts)
+            entry["last_seen"] = max(entry["last_seen"] or ts, ts)
+
+    rows = []
+    for entry in summary.values():
+        flags = []
+        if entry["user"] != "unknown" and entry["user"] not in known_users:
+            flags.append("first_time_user")
+        unusual = [c for c in entry["commands"] if baseline and not in_baseline(c, baseline)]
+        if unusual:
+            flags.append("commands_outside_baseline")
+        top = sorted(entry["commands"].items(), key=lambda kv: kv[1], reverse=True)
+        rows.append({
+            **entry,
+            "targets": sorted(entry["targets"]),
+            "commands": [{"command": c, "count": n} for c, n in top[:limit]],
+            "outside_baseline": unusual[:limit],
+            "flags": flags,
+        })
+    rows.sort(key=lambda r: (len(r["flags"]), r["count"]), reverse=True)
+    return rows
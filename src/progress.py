@@ -0,0 +1,74 @@
+"""長時間查詢的進度回報 (MCP notifications/progress)。
+
+client 在 tools/call 帶了 progressToken 時，ProgressMiddleware 為該次呼叫建立 Reporter 放在
+contextvar 中 (切段查詢的執行緒也會帶著同一個 context)。每完成一次 Indexer 查詢呼叫 step()，
+progress 為已完成的查詢數；切段查詢事先以 expect() 告知預計的查詢數，client 就能顯示 total。
+工具多半是同步函式，通知透過 event loop 排程送出，不會等待送達。
+"""
+
+import asyncio
+import contextvars
+import logging
+import threading
+
+from fastmcp.server.middleware import Middleware
+
+logger = logging.getLogger("wazuh_mcp")
+
+_current = contextvars.ContextVar("tool_progress", default=None)
+
+
+class Reporter:
+    def __init__(self, context, loop):
+        self.context = context
+        self.loop = loop
+        self.lock = threading.Lock()
+        self.done = 0
+        self.total = None
+
+    def expect(self, count):
+        with self.lock:
+            self.total = self.done + count
+
+    def step(self, message):
+        with self.lock:
+            self.done += 1
+            progress, total = self.done, max(self.total, self.done) if self.total else None
+        try:
+            asyncio.run_coroutine_threadsafe(self.context.report_progress(progress, total, message), self.loop)
+        except RuntimeError as e:  # event loop 已關閉 (呼叫已結束)
+            logger.debug("進度通知未送出: %s", e)
+
+
+def expect(count):
+    """告知接下來預計執行的查詢數 (例如切段數)"""
+    reporter = _current.get()
+    if reporter is not None:
+        reporter.expect(count)
+
+
+def step(message):
+    """完成一個查詢步驟；不在帶 progressToken 的工具呼叫中時不做任何事"""
+    reporter = _current.get()
+    if reporter is not None:
+        reporter.step(message)
+
+
+def _progress_token(context):
+    try:
+        meta = context.request_context.meta
+    except (AttributeError, LookupError, ValueError):
+        return None
+    return getattr(meta, "progressToken", None) if meta else None
+
+
+class ProgressMiddleware(Middleware):
+    async def on_call_tool(self, context, call_next):
+        ctx = context.fastmcp_context
+        if ctx is None or _progress_token(ctx) is None:
+            return await call_next(context)
+        token = _current.set(Reporter(ctx, asyncio.get_running_loop()))
+        try:
+            return await call_next(context)
+        finally:
+            _current.reset(token)
@@ -0,0 +1,41 @@
+"""分析型工具 (關聯、序列、異常偵測) 的來源追溯與信心分數。
+
+機器推導出的結論必須能被分析師稽核: 每個發現都要附上
+用了哪些原始事件、哪些門檻、哪一版的判斷邏輯，以及信心分數與扣分理由。
+"""
+
+# 判斷邏輯有調整時遞增，讓舊報告可以對照當時的規則
+HEURISTICS_VERSION = {
+    "sequence": "1.0",
+}
+
+
+def event_ref(hit):
+    """Indexer 原始事件的參照 (index + _id)，可用來回查完整事件"""
+    return {"index": hit.get("_index"), "id": hit.get("_id")}
+
+
+def provenance(analytic, parameters, events):
+    return {
+        "analytic": analytic,
+        "heuristics_version": HEURISTICS_VERSION[analytic],
+        "parameters": parameters,
+        "source_events": [e["_ref"] for e in events if "_ref" in e],
+    }
+
+
+def confidence(score, factors):
+    """score 限制在 0~1；factors 是影響分數的理由清單"""
+    return {"score": round(max(0.0, min(1.0, score)), 2), "factors": factors}
+
+
+def sequence_confidence(span_seconds, maxspan_seconds, truncated):
+    """時間越緊密的序列越可能是同一串行為；查詢被截斷時可能有更佳配對被漏掉"""
+    factors = []
+    ratio = span_seconds / maxspan_seconds if maxspan_seconds else 1.0
+    score = 1.0 - 0.5 * ratio
+    factors.append(f"序列耗時佔 maxspan 的 {ratio:.0%}")
+    if truncated:
+        score -= 0.15
+        factors.append("部分步驟結果被截斷或不完整，配對可能不是最接近的一組")
+    return confidence(score, factors)
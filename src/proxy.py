@@ -0,0 +1,85 @@
+"""HTTP 模式在反向代理 (nginx / Traefik) 後方執行: 路徑前綴與 X-Forwarded-* 標頭。
+
+- MCP_HTTP_PATH_PREFIX: 代理原封不動轉送 /wazuh-mcp/... 時，整個 app (MCP 端點、/healthz、
+  /metrics、/admin) 掛在這個前綴下
+- MCP_TRUSTED_PROXIES: 只有來自這些位址 (IP 或 CIDR，逗號分隔，"*" 為全部) 的連線才採用
+  X-Forwarded-For (log 與用量計算看到的是真正的 client 位址)、X-Forwarded-Proto (https)、
+  X-Forwarded-Prefix (代理會去掉前綴時，讓產生的絕對網址仍帶有前綴)，也只有這些連線帶的
+  MCP_PRINCIPAL_HEADER 才會被當成呼叫者身分 (見 principal.py)；unix socket 沒有 IP，需設為 "*" 才會採用
+"""
+
+import ipaddress
+
+from starlette.applications import Starlette
+from starlette.routing import Mount
+
+
+def path_prefix(value):
+    value = (value or "").strip().strip("/")
+    return f"/{value}" if value else ""
+
+
+class TrustedProxies:
+    def __init__(self, text):
+        entries = [e.strip() for e in (text or "").split(",") if e.strip()]
+        self.any = "*" in entries
+        self.networks = [ipaddress.ip_network(e, strict=False) for e in entries if e != "*"]
+
+    def __contains__(self, host):
+        if self.any:
+            return True
+        try:
+            address = ipaddress.ip_address(host)
+        except ValueError:  # unix socket 等沒有 IP 的連線
+            return False
+        return any(address in network for network in self.networks)
+
+
+def forwarded_client(header, trusted):
+    """X-Forwarded-For 由右往左第一個不是受信任代理的位址"""
+    hops = [h.strip() for h in header.split(",") if h.strip()]
+    for hop in reversed(hops):
+        if hop not in trusted:
+            return hop
+    return hops[0] if hops else None
+
+
+class ForwardedHeaders:
+    """ASGI middleware: 直接修改 scope，uvicorn 的 access log 也會看到改寫後的 client 位址"""
+
+    def __init__(self, app, trusted):
+        self.app = app
+        self.trusted = trusted
+
+    async def __call__(self, scope, receive, send):
+        peer = (scope.get("client") or (None,))[0]
+        if scope["type"] in ("http", "websocket") and peer in self.trusted:
+            scope.setdefault("extensions", {})["proxy"] = {"peer": peer}
+            headers = {k.decode("latin-1").lower(): v.decode("latin-1") for k, v in scope["headers"]}
+            client = forwarded_client(headers.get("x-forwarded-for", ""), self.trusted)
+            if client:
+                scope["client"] = (client, 0)
+            proto = headers.get("x-forwarded-proto", "").split(",")[0].strip().lower()
+            if proto in ("http", "https"):
+                scope["scheme"] = proto if scope["type"] == "http" else {"http": "ws", "https": "wss"}[proto]
+            prefix = path_prefix(headers.get("x-forwarded-prefix"))
+            if prefix:
+                scope["root_path"] = prefix + scope.get("root_path", "")
+        await self.app(scope, receive, send)
+
+
+def via_trusted_proxy(scope):
+    """這個連線是否來自 MCP_TRUSTED_PROXIES 中的位址 (由 ForwardedHeaders 標記)"""
+    return "proxy" in scope.get("extensions", {})
+
+
+def behind_proxy(app, prefix, trusted):
+    """依設定把 app 掛在前綴下並套用 X-Forwarded-* 處理"""
+    if prefix:
+        app = Starlette(routes=[Mount(prefix, app=app)], lifespan=app.router.lifespan_context)
+    return ForwardedHeaders(app, trusted)
+
+
+def build_http_app(server, prefix, trusted, middleware=None):
+    """FastMCP 的 HTTP app，依設定掛在前綴下並套用 X-Forwarded-* 處理；middleware 為 app 內額外的 starlette middleware"""
+    return behind_proxy(server.http_app(middleware=middleware), prefix, trusted)
@@ -0,0 +1,125 @@
+"""歷史時間範圍的查詢結果快取。
+
+時間範圍完全落在「現在 - 匯入延遲」之前的資料不會再變動，同一個查詢的結果
+可以放心長時間快取 (存放在狀態儲存，重啟後仍有效)。反覆在同一段時間窗內
+獵捕時，第二次之後幾乎不需要再打 Indexer。
+
+只有使用絕對時間 (例如 2024-05-01T00:00:00Z) 作為上限的查詢才會被快取；
+now-1d 這類相對時間每次執行的範圍都不同，不能快取。
+
+例外是晚到的告警 (agent 離線後補送、filebeat 積壓)：CacheTail 發現某段歷史時間的
+告警數有變動時，會清除時間範圍涵蓋那段時間的快取，下一次查詢就會重新取得結果。
+"""
+
+import hashlib
+import json
+from datetime import datetime, timedelta, timezone
+
+from alert_utils import parse_timestamp
+
+CACHE_NAMESPACE = "query_cache"
+TIME_FIELDS = ("timestamp", "@timestamp")
+
+
+def _range_bounds(node, keys=("lte", "lt")):
+    """找出查詢中「必須成立」的時間範圍邊界 (只看 filter / must，不看 should / must_not)；
+    keys 預設取上限，("gte", "gt") 取下限"""
+    if not isinstance(node, dict):
+        return []
+    bounds_found = []
+    if "range" in node:
+        for field, bounds in node["range"].items():
+            if field in TIME_FIELDS and isinstance(bounds, dict):
+                value = next((bounds[k] for k in keys if k in bounds), None)
+                if value is not None:
+                    bounds_found.append(value)
+    if "bool" in node:
+        for clause in ("filter", "must"):
+            children = node["bool"].get(clause, [])
+            for child in children if isinstance(children, list) else [children]:
+                bounds_found.extend(_range_bounds(child, keys))
+    if "function_score" in node:
+        bounds_found.extend(_range_bounds(node["function_score"].get("query"), keys))
+    return bounds_found
+
+
+def _absolute(values):
+    parsed = []
+    for value in values:
+        if not isinstance(value, str) or "now" in value:
+            continue
+        ts = parse_timestamp(value)
+        if ts is not None and ts.tzinfo is None:
+            ts = ts.replace(tzinfo=timezone.utc)
+        if ts is not None:
+            parsed.append(ts)
+    return parsed
+
+
+def immutable_end(query):
+    """回傳查詢時間上限 (絕對時間)；沒有上限或使用相對時間時回傳 None"""
+    parsed = _absolute(_range_bounds(query))
+    return min(parsed) if parsed else None
+
+
+def query_window(query):
+    """查詢涵蓋的時間範圍 (下限, 上限)；下限不明 (沒有下限或使用相對時間) 時為 None"""
+    starts = _absolute(_range_bounds(query, ("gte", "gt")))
+    return (max(starts) if starts else None), immutable_end(query)
+
+
+class QueryCache:
+    def __init__(self, store, ttl, ingest_delay):
+        self.store = store
+        self.ttl = ttl
+        self.ingest_delay = ingest_delay
+        self.hits = 0
+        self.misses = 0
+
+    def cacheable(self, body):
+        end = immutable_end(body.get("query", {}))
+        return end is not None and end < datetime.now(timezone.utc) - self.ingest_delay
+
+    @staticmethod
+    def key(index, body):
+        raw = json.dumps({"index": index, "body": body}, sort_keys=True, ensure_ascii=False)
+        return hashlib.sha256(raw.encode("utf-8")).hexdigest()
+
+    def get(self, index, body):
+        entry = self.store.get(CACHE_NAMESPACE, self.key(index, body))
+        if entry and entry["expires_at"] > datetime.now(timezone.utc).isoformat():
+            self.hits += 1
+            return entry["result"]
+        self.misses += 1
+        return None
+
+    def put(self, index, body, result):
+        expires = datetime.now(timezone.utc) + self.ttl
+        start, end = query_window(body.get("query", {}))
+        self.store.put(CACHE_NAMESPACE, self.key(index, body),
+                       {"expires_at": expires.isoformat(), "result": result,
+                        "window": [start.isoformat() if start else None, end.isoformat()]})
+
+    def invalidate(self, windows=None):
+        """清除時間範圍與 windows ([(start, end), ...] datetime) 重疊的快取；windows=None 清除全部。
+        沒有記錄時間範圍的舊快取一律清除"""
+        removed = 0
+        for key, entry in self.store.list(CACHE_NAMESPACE):
+            if windows is not None and entry.get("window"):
+                start, end = entry["window"]
+                start = parse_timestamp(start) if start else None
+                end = parse_timestamp(end)
+                if not any((start is None or start <= w_end) and w_start <= end for w_start, w_end in windows):
+                    continue
+            self.store.delete(CACHE_NAMESPACE, key)
+            removed += 1
+        return removed
+
+    def purge_expired(self):
+        now = datetime.now(timezone.utc).isoformat()
+        removed = 0
+        for key, entry in self.store.list(CACHE_NAMESPACE):
+            if entry.get("expires_at", "") <= now:
+                self.store.delete(CACHE_NAMESPACE, key)
+                removed += 1
+        return removed
@@ -0,0 +1,324 @@
+"""Indexer 查詢範本庫: 所有工具使用的 DSL 片段都透過這裡取得，並依 Wazuh 版本選擇對應的版本。
+
+每個範本以名稱註冊 (例如 "firewall.events")，可以有多個版本:
+    @template("firewall.events", since="4.8")   # 4.8 起欄位改名時只新增這個版本
+其餘版本維持不變。WAZUH_VERSION (例如 4.7.3) 決定使用哪個版本，未設定時使用 DEFAULT_VERSION。
+
+每個範本都附上 example 參數，tests/golden 會以 example 對 SUPPORTED_VERSIONS 逐一產生查詢
+並與存檔比對 (golden file)；重構查詢程式碼時，序列化結果有任何變動都會被測試抓到。
+"""
+
+from commandoutput import output_query as command_output, summary_aggregations as command_summary
+from dnsanalytics import dns_query, window_aggregations, baseline_aggregations
+from firewall import firewall_query, summary_aggregations
+from numstats import stats_aggregations
+from osquery import results_query as osquery_results, summary_aggregations as osquery_summary
+from huntprompts import (alert_query, lateral_movement_query, lateral_movement_aggregations,
+                         source_reach_aggregations, critical_summary_aggregations)
+from persistence import persistence_query
+from privileged import PRIVILEGED_QUERY, users_aggregation
+from registry import registry_query
+from removable import ATTACH_QUERY, FILE_QUERY
+from rollups import daily_aggregations
+from saasaudit import actor_aggregations as saas_actor_aggregations, audit_query
+from shaping import group_aggregation
+from suggestions import pivot_aggregation
+from tlsfingerprint import fingerprint_query, fingerprint_aggregations
+from vulnerabilities import api_request, index_request
+
+SUPPORTED_VERSIONS = ("4.7", "4.8", "4.9")
+DEFAULT_VERSION = "4.7"
+
+_TEMPLATES = {}
+
+
+def version_key(version):
+    """"4.7.3" -> (4, 7)；只比較主要與次要版本"""
+    try:
+        major, minor = version.strip().split(".")[:2]
+        return int(major), int(minor)
+    except ValueError:
+        raise ValueError(f"無法解析 Wazuh 版本 '{version}'，請使用如 4.7 或 4.7.3 的格式") from None
+
+
+def template(name, since="4.0", example=None):
+    """註冊範本；同一名稱可用不同 since 註冊多個版本"""
+    def register(fn):
+        variants = _TEMPLATES.setdefault(name, [])
+        if any(v["since"] == version_key(since) for v in variants):
+            raise ValueError(f"範本 {name} 已有 {since} 版本")
+        variants.append({"since": version_key(since), "build": fn, "example": example or {}})
+        variants.sort(key=lambda v: v["since"])
+        return fn
+    return register
+
+
+def names():
+    return sorted(_TEMPLATES)
+
+
+def example(name):
+    return _TEMPLATES[name][-1]["example"]
+
+
+class QueryLibrary:
+    """綁定特定 Wazuh 版本的範本庫"""
+
+    def __init__(self, version=None):
+        self.version = version or DEFAULT_VERSION
+        self._key = version_key(self.version)
+
+    def variant(self, name):
+        if name not in _TEMPLATES:
+            raise KeyError(f"未知的查詢範本 '{name}'")
+        usable = [v for v in _TEMPLATES[name] if v["since"] <= self._key]
+        if not usable:
+            raise ValueError(f"查詢範本 {name} 不支援 Wazuh {self.version}")
+        return usable[-1]
+
+    def at_least(self, version):
+        return self._key >= version_key(version)
+
+    def render(self, name, /, **params):
+        return self.variant(name)["build"](**params)
+
+
+# --- 共用片段 ---
+
+@template("common.time_range", example={"gte": "now-7d", "lt": "now-24h"})
+def _time_range(gte, lt=None, lte=None):
+    bounds = {"gte": gte}
+    if lt:
+        bounds["lt"] = lt
+    if lte:
+        bounds["lte"] = lte
+    return {"range": {"timestamp": bounds}}
+
+
+@template("common.min_level", example={"level": 10})
+def _min_level(level):
+    return {"range": {"rule.level": {"gte": level}}}
+
+
+@template("common.agent", example={"name": "web-01"})
+def _agent(name):
+    return {"term": {"agent.name": name}}
+
+
+@template("common.agent_ids", example={"agent_ids": ["003", "001", "002"]})
+def _agent_ids(agent_ids):
+    if not agent_ids:
+        return {"match_none": {}}
+    return {"terms": {"agent.id": sorted(agent_ids)}}
+
+
+@template("common.trend", example={"interval": "1h", "time_from": "now-24h", "time_to": "now"})
+def _trend(interval, time_from, time_to="now"):
+    """sparkline 用的時間序列 (沒有事件的時段補 0)"""
+    return {"date_histogram": {
+        "field": "timestamp", "fixed_interval": interval, "min_doc_count": 0,
+        "extended_bounds": {"min": time_from, "max": time_to},
+    }}
+
+
+@template("common.event_refs", example={"ids": ["b2Jq4pABc1", "AZq4pABc2x"]})
+def _event_refs(ids):
+    """工具結果中的 _ref (Indexer 的 _id) 回查原始事件"""
+    return {"ids": {"values": sorted(ids)}}
+
+
+@template("common.mitre", example={"technique": "T1003.001"})
+def _mitre(technique):
+    """Wazuh 告警的 rule.mitre.id 可能標在子技術或父技術"""
+    ids = sorted({technique, technique.split(".")[0]})
+    return {"terms": {"rule.mitre.id": ids}}
+
+
+# --- wazuh_environment_brief ---
+
+@template("brief.aggregations", example={"time_range": "now-7d", "limit": 10})
+def _brief_aggregations(time_range, limit):
+    """最常觸發的規則 (time_range 內) 與整體最舊 / 最新的告警時間 (資料保留範圍)"""
+    return {
+        "recent": {
+            "filter": _time_range(time_range),
+            "aggs": {"rules": {
+                "terms": {"field": "rule.id", "size": limit},
+                "aggs": {
+                    "description": {"terms": {"field": "rule.description", "size": 1}},
+                    "level": {"max": {"field": "rule.level"}},
+                },
+            }},
+        },
+        "oldest": {"min": {"field": "timestamp"}},
+        "newest": {"max": {"field": "timestamp"}},
+    }
+
+
+# --- 資源 (resources) ---
+
+@template("vulnerabilities.agent", example={"agent_id": "001", "limit": 500})
+def _vulnerabilities_api(agent_id, limit):
+    """4.7 以前: 弱點掃描結果在 Manager API"""
+    return api_request(agent_id, limit)
+
+
+@template("vulnerabilities.agent", since="4.8", example={"agent_id": "001", "limit": 500})
+def _vulnerabilities_index(agent_id, limit):
+    """4.8 起: 弱點狀態存放在 Indexer 的 wazuh-states-vulnerabilities-*"""
+    return index_request(agent_id, limit)
+
+
+# --- prompts ---
+
+@template("prompts.alert", example={"alert_id": "1715000000.123456"})
+def _prompt_alert(alert_id):
+    return alert_query(alert_id)
+
+
+@template("prompts.lateral_movement", example={"agent": "web-01"})
+def _prompt_lateral(agent):
+    return lateral_movement_query(agent)
+
+
+@template("prompts.lateral_aggregations", example={"limit": 10})
+def _prompt_lateral_aggs(limit=10):
+    return lateral_movement_aggregations(limit)
+
+
+@template("prompts.source_reach", example={"limit": 20})
+def _prompt_source_reach(limit=20):
+    return source_reach_aggregations(limit)
+
+
+@template("prompts.critical_summary", example={"limit": 10})
+def _prompt_critical(limit=10):
+    return critical_summary_aggregations(limit)
+
+
+# --- search_alerts ---
+
+@template("search.group_by", example={"group_by": ["rule.id", "agent.name"], "size": 500})
+def _group_by(group_by, size):
+    return group_aggregation(group_by, size)
+
+
+@template("search.pivots", example={"pivots": [("data.srcip", "srcip", "203.0.113.7", 3)], "time_range": "now-7d"})
+def _pivots(pivots, time_range):
+    return pivot_aggregation(pivots, time_range)
+
+
+# --- 專題獵捕 ---
+
+@template("tls.events", example={"fields": [("ja3", "data.tls.ja3"), ("ja4", "data.tls.ja4")],
+                                 "values": ["e7d705a3286e19ea42f587b344ee6865"]})
+def _tls_events(fields, values=None):
+    return fingerprint_query(fields, values)
+
+
+@template("tls.aggregations", example={"fields": [("ja3", "data.tls.ja3")], "limit": 20,
+                                       "known_bad": {"e7d705a3286e19ea42f587b344ee6865": "test"}, "rare": False})
+def _tls_aggregations(fields, limit, known_bad, rare=False):
+    return fingerprint_aggregations(fields, limit, known_bad, rare)
+
+
+@template("dns.events", example={"filters": [{"range": {"timestamp": {"gte": "now-24h"}}}]})
+def _dns_events(filters=None):
+    return dns_query(filters)
+
+
+@template("dns.window_aggregations")
+def _dns_window():
+    return window_aggregations()
+
+
+@template("dns.baseline_aggregations", example={"domains": ["example.com", "xj3k9q.top"]})
+def _dns_baseline(domains):
+    return baseline_aggregations(domains)
+
+
+@template("privileged.events")
+def _privileged_events():
+    return PRIVILEGED_QUERY
+
+
+@template("privileged.users_aggregation")
+def _privileged_users():
+    return users_aggregation()
+
+
+@template("persistence.events",
+          example={"mechanisms": ["scheduled_task", "service", "registry_run_key", "cron", "systemd"]})
+def _persistence_events(mechanisms):
+    return persistence_query(mechanisms)
+
+
+@template("registry.events", example={"presets": ["run_keys", "ifeo"], "key_pattern": None, "events": None})
+def _registry_events(presets=None, key_pattern=None, events=None):
+    return registry_query(presets, key_pattern, events)
+
+
+@template("commands.output", example={"command": "netstat listening ports"})
+def _command_output(command=None):
+    return command_output(command)
+
+
+@template("commands.summary_aggregations", example={"limit": 50})
+def _command_summary(limit):
+    return command_summary(limit)
+
+
+@template("osquery.results", example={"query_name": "listening_ports", "pack": "incident-response",
+                                      "action": "added"})
+def _osquery_results(query_name=None, pack=None, action=None):
+    return osquery_results(query_name, pack, action)
+
+
+@template("osquery.summary_aggregations", example={"limit": 50})
+def _osquery_summary(limit):
+    return osquery_summary(limit)
+
+
+@template("saas.events", example={"integration": "office365", "activities": ["mailbox_rule", "oauth_consent"]})
+def _saas_events(integration, activities):
+    return audit_query(integration, activities)
+
+
+@template("saas.actor_aggregations", example={"integration": "office365",
+                                              "activities": ["mailbox_rule", "mass_download", "oauth_consent"],
+                                              "limit": 20, "window": "1h"})
+def _saas_actors(integration, activities, limit, window):
+    return saas_actor_aggregations(integration, activities, limit, window)
+
+
+@template("removable.attach")
+def _removable_attach():
+    return ATTACH_QUERY
+
+
+@template("removable.file_copies")
+def _removable_files():
+    return FILE_QUERY
+
+
+@template("firewall.events", example={"action": "deny"})
+def _firewall_events(action=None):
+    return firewall_query(action)
+
+
+@template("firewall.summary_aggregations", example={"dimension": "src", "limit": 20, "interval": "1h",
+                                                    "time_range": "now-24h"})
+def _firewall_summary(dimension, limit, interval, time_range):
+    return summary_aggregations(dimension, limit, interval, time_range)
+
+
+@template("stats.numeric_aggregations", example={"field": "data.bytes", "interval": "1h", "time_range": "now-24h",
+                                                 "percents": [50, 90, 95, 99], "group_by": "agent.name",
+                                                 "limit": 10})
+def _numeric_stats(field, interval, time_range, percents, group_by=None, limit=10):
+    return stats_aggregations(field, interval, time_range, percents, group_by, limit)
+
+
+@template("rollups.daily_aggregations", example={"terms_size": 1000})
+def _rollup_daily(terms_size):
+    return daily_aggregations(terms_size)
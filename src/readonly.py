@@ -0,0 +1,38 @@
+"""唯讀模式 (--read-only / MCP_READ_ONLY=true)：開放給初階分析人員時，保證不會有任何呼叫改動 Wazuh。
+
+WRITE_CALLS 中不論參數都會寫入的工具 (例如 inject_test_events) 不列在工具清單中；
+只有部分參數會寫入的工具 (例如 review_detection_rules 的 deploy=True) 仍可使用，寫入的呼叫在執行前拒絕。
+與 MCP_ALLOW_WRITES 不同，唯讀模式連工具都不顯示，也不受 MCP_TOOL_POLICY_FILE 的設定影響。
+"""
+
+import logging
+
+from fastmcp.server.middleware import Middleware
+from fastmcp.tools.tool import ToolResult
+from mcp.types import TextContent
+
+logger = logging.getLogger("wazuh_mcp")
+
+
+class ReadOnlyMode(Middleware):
+    def __init__(self, write_calls, enabled=False, resolve=None):
+        """write_calls: {工具名稱: fn(arguments) -> 這次呼叫是否寫入}；resolve(name) -> 實際的工具名稱"""
+        self.write_calls = write_calls
+        self.enabled = enabled
+        self.resolve = resolve or (lambda name: name)
+        self.hidden = {name for name, is_write in write_calls.items() if is_write({})}
+
+    async def on_list_tools(self, context, call_next):
+        tools = await call_next(context)
+        if not self.enabled:
+            return tools
+        return [tool for tool in tools if self.resolve(tool.name) not in self.hidden]
+
+    async def on_call_tool(self, context, call_next):
+        tool = self.resolve(context.message.name)
+        is_write = self.write_calls.get(tool)
+        if self.enabled and is_write and is_write(context.message.arguments or {}):
+            logger.info("唯讀模式拒絕寫入呼叫 %s", tool)
+            return ToolResult(content=[TextContent(
+                type="text", text=f"錯誤: 伺服器以唯讀模式執行，不允許 {tool} 的寫入操作 (MCP_READ_ONLY)")])
+        return await call_next(context)
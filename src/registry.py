@@ -0,0 +1,97 @@
+"""Windows 登錄檔 FIM (syscheck registry monitoring) 的獵捕預設組合。
+
+攻擊者最常動的登錄檔位置就那幾個: Run 機碼、IFEO (映像檔劫持 / 偵錯器)、
+LSA 驗證套件、服務設定與 Winlogon。預設組合把這些位置整理好，不必自己記路徑。
+Wazuh 的登錄檔 FIM 不一定有值的內容: 有設定 report_changes 時會有 syscheck.diff，
+否則只能比對變更前後的雜湊與大小。
+"""
+
+import fnmatch
+
+from alert_utils import get_field
+from provenance import event_ref
+
+REGISTRY_PRESETS = {
+    "run_keys": {
+        "description": "開機 / 登入自動執行 (Run、RunOnce、Policies\\Explorer\\Run)",
+        "patterns": ["*\\CurrentVersion\\Run*", "*\\Policies\\Explorer\\Run*"],
+    },
+    "ifeo": {
+        "description": "Image File Execution Options (偵錯器劫持、SilentProcessExit)",
+        "patterns": ["*\\Image File Execution Options\\*", "*\\SilentProcessExit\\*"],
+    },
+    "lsa": {
+        "description": "LSA 驗證 / 安全 / 通知套件 (竊取密碼的 DLL 常掛在這裡)",
+        "patterns": ["*\\Control\\Lsa", "*\\Control\\Lsa\\*", "*\\Control\\SecurityProviders\\*"],
+    },
+    "services": {
+        "description": "服務設定 (ImagePath、ServiceDll、啟動類型)",
+        "patterns": ["*\\CurrentControlSet\\Services\\*"],
+    },
+    "winlogon": {
+        "description": "Winlogon (Userinit、Shell、Notify) 與 AppInit_DLLs",
+        "patterns": ["*\\Windows NT\\CurrentVersion\\Winlogon*", "*\\Windows NT\\CurrentVersion\\Windows"],
+    },
+}
+
+SOURCE_FIELDS = ["timestamp", "agent.name", "agent.id", "rule.id", "rule.description", "rule.level", "syscheck"]
+
+
+def _wildcard(pattern):
+    # Lucene wildcard 中反斜線是跳脫字元，路徑中的反斜線要重複一次
+    return {"wildcard": {"syscheck.path": {"value": pattern.replace("\\", "\\\\"), "case_insensitive": True}}}
+
+
+def registry_query(presets=None, key_pattern=None, events=None):
+    """登錄檔 FIM 事件中，符合預設組合或自訂路徑樣式 (glob) 的篩選條件"""
+    unknown = [p for p in presets or [] if p not in REGISTRY_PRESETS]
+    if unknown:
+        raise ValueError(f"未知的預設組合 {unknown}，可用: {', '.join(REGISTRY_PRESETS)}")
+    patterns = [p for name in presets or [] for p in REGISTRY_PRESETS[name]["patterns"]]
+    if key_pattern:
+        patterns.append(key_pattern)
+    clauses = [{"prefix": {"syscheck.path": "HKEY_"}}]
+    if patterns:
+        clauses.append({"bool": {"should": [_wildcard(p) for p in patterns], "minimum_should_match": 1}})
+    if events:
+        clauses.append({"terms": {"syscheck.event": list(events)}})
+    return {"bool": {"filter": clauses}}
+
+
+def _preset_of(path):
+    for name, preset in REGISTRY_PRESETS.items():
+        if any(fnmatch.fnmatch(path.lower(), p.lower()) for p in preset["patterns"]):
+            return name
+    return None
+
+
+def render_change(hit):
+    """一筆登錄檔 FIM 事件: 機碼 / 值、變更類型與前後差異"""
+    alert = hit.get("_source", {})
+    syscheck = alert.get("syscheck", {})
+    path = syscheck.get("path", "")
+    change = {
+        "timestamp": alert.get("timestamp"),
+        "agent": get_field(alert, "agent.name"),
+        "key": path,
+        "value_name": syscheck.get("value_name"),
+        "value_type": syscheck.get("value_type"),
+        "arch": syscheck.get("arch"),
+        "event": syscheck.get("event"),
+        "preset": _preset_of(path),
+        "rule": {"id": get_field(alert, "rule.id"), "description": get_field(alert, "rule.description")},
+        "_ref": event_ref(hit),
+    }
+    if syscheck.get("diff"):
+        change["diff"] = syscheck["diff"]
+    else:
+        # 沒有 report_changes 時只能比對雜湊與大小
+        before = {k: syscheck.get(f"{k}_before") for k in ("sha256", "size") if syscheck.get(f"{k}_before")}
+        after = {k: syscheck.get(f"{k}_after") for k in ("sha256", "size") if syscheck.get(f"{k}_after")}
+        if before:
+            change["before"] = before
+        if after:
+            change["after"] = after
+    if syscheck.get("changed_attributes"):
+        change["changed_attributes"] = syscheck["changed_attributes"]
+    return change
@@ -0,0 +1,107 @@
+"""USB / 卸除式媒體的內部威脅獵捕。
+
+先找出「插入卸除式裝置」的事件，再把同一台主機在插入後一段時間內、
+落在卸除式媒體路徑上的檔案寫入 (Sysmon 11、FIM) 關聯到那次插入，
+最後依主機與使用者彙整，回答「誰在什麼時候把什麼東西複製到隨身碟」。
+"""
+
+from alert_utils import get_field, first_field, parse_timestamp
+from provenance import event_ref
+
+ATTACH_QUERY = {"bool": {"should": [
+    # Windows: 6416 偵測到新的外接裝置、Kernel-PnP 400 / DriverFrameworks 2003 裝置設定
+    {"terms": {"data.win.system.eventID": ["6416", "2003", "2100"]}},
+    {"bool": {"filter": [
+        {"term": {"data.win.system.eventID": "400"}},
+        {"wildcard": {"data.win.system.providerName": {"value": "*Kernel-PnP*", "case_insensitive": True}}},
+    ]}},
+    # Linux: kernel 的 usb-storage / 新 USB 裝置訊息
+    {"match_phrase": {"full_log": "usb-storage"}},
+    {"match_phrase": {"full_log": "New USB device found"}},
+    {"terms": {"rule.groups": ["usb"]}},
+], "minimum_should_match": 1}}
+
+# 卸除式媒體的掛載路徑 (Windows 非 C: 的磁碟機代號、Linux 的自動掛載目錄)
+_REMOVABLE_PATH = "([d-z]:\\\\.*|/media/.*|/run/media/.*|/mnt/.*)"
+FILE_QUERY = {"bool": {"should": [
+    {"bool": {"filter": [
+        {"term": {"data.win.system.eventID": "11"}},
+        {"regexp": {"data.win.eventdata.targetFilename": {"value": _REMOVABLE_PATH, "case_insensitive": True}}},
+    ]}},
+    {"regexp": {"syscheck.path": {"value": _REMOVABLE_PATH, "case_insensitive": True}}},
+], "minimum_should_match": 1}}
+
+ATTACH_SOURCE = ["timestamp", "agent.name", "rule.description", "full_log",
+                 "data.win.system.eventID", "data.win.eventdata.deviceDescription",
+                 "data.win.eventdata.deviceId", "data.win.eventdata.classId",
+                 "data.win.eventdata.subjectUserName"]
+FILE_SOURCE = ["timestamp", "agent.name", "data.win.eventdata.targetFilename", "data.win.eventdata.image",
+               "data.win.eventdata.user", "syscheck.path", "syscheck.event", "syscheck.size_after",
+               "syscheck.uname_after", "syscheck.audit.login_user.name", "syscheck.audit.process.name"]
+
+
+def attach_event(hit):
+    alert = hit.get("_source", {})
+    return {
+        "timestamp": alert.get("timestamp"),
+        "agent": get_field(alert, "agent.name"),
+        "device": first_field(alert, "data.win.eventdata.deviceDescription", "data.win.eventdata.deviceId")
+                  or get_field(alert, "rule.description"),
+        "user": get_field(alert, "data.win.eventdata.subjectUserName"),
+        "_ref": event_ref(hit),
+    }
+
+
+def file_event(hit):
+    alert = hit.get("_source", {})
+    return {
+        "timestamp": alert.get("timestamp"),
+        "agent": get_field(alert, "agent.name"),
+        "path": first_field(alert, "data.win.eventdata.targetFilename", "syscheck.path"),
+        "event": get_field(alert, "syscheck.event") or "created",
+        "size": get_field(alert, "syscheck.size_after"),
+        "process": first_field(alert, "data.win.eventdata.image", "syscheck.audit.process.name"),
+        "user": first_field(alert, "data.win.eventdata.user", "syscheck.audit.login_user.name", "syscheck.uname_after"),
+        "_ref": event_ref(hit),
+    }
+
+
+def correlate(attaches, files, follow_window):
+    """把每筆檔案活動歸到同一主機上、follow_window 內最近一次的插入事件；
+    回傳 (插入工作階段清單, 找不到對應插入事件的檔案活動)"""
+    sessions = sorted(({**a, "files": []} for a in attaches), key=lambda a: a["timestamp"] or "")
+    by_agent = {}
+    for session in sessions:
+        by_agent.setdefault(session["agent"], []).append(session)
+    orphans = []
+    for f in sorted(files, key=lambda f: f["timestamp"] or ""):
+        ts = parse_timestamp(f["timestamp"])
+        match = None
+        for session in by_agent.get(f["agent"], []):
+            start = parse_timestamp(session["timestamp"])
+            if ts and start and start <= ts <= start + follow_window:
+                match = session
+        if match:
+            match["files"].append(f)
+        else:
+            orphans.append(f)
+    return sessions, orphans
+
+
+def summarize(sessions, orphans):
+    """依主機與使用者彙整插入次數與寫入卸除式媒體的檔案數"""
+    per_agent = {}
+    per_user = {}
+    for session in sessions:
+        agent = per_agent.setdefault(session["agent"], {"attaches": 0, "files": 0, "devices": set()})
+        agent["attaches"] += 1
+        agent["files"] += len(session["files"])
+        agent["devices"].add(session["device"])
+    for f in [f for s in sessions for f in s["files"]] + orphans:
+        user = per_user.setdefault(f["user"] or "unknown", {"files": 0, "agents": set()})
+        user["files"] += 1
+        user["agents"].add(f["agent"])
+    return (
+        {k: {**v, "devices": sorted(d for d in v["devices"] if d)} for k, v in per_agent.items()},
+        {k: {**v, "agents": sorted(a for a in v["agents"] if a)} for k, v in per_user.items()},
+    )
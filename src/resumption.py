@@ -0,0 +1,214 @@
+"""HTTP session 的持久化與重啟後的接續 (MCP_PERSIST_SESSIONS=true)。
+
+Streamable HTTP 的 session 原本只存在記憶體，伺服器重啟後 client 帶舊的 Mcp-Session-Id 會收到
+session 不存在，必須重新 initialize，訂閱與 log 等級也要重新設定。啟用後:
+- 每個 session 第一次發出請求時，把 initialize 的內容 (client 資訊與能力) 與建立者身分存進狀態儲存
+  (sessions namespace，key 為 session id 的 SHA-256，資料庫外洩也拿不到可用的 session id)
+- session 內的設定 (logging/setLevel 的等級、資源訂閱) 變更時一併記錄
+- 重啟後收到不認得但有紀錄的 Mcp-Session-Id 時，以同一個 id 重新建立已初始化的 session，
+  client 不必重新 initialize；第一個 JSON-RPC 請求時還原 client 能力、log 等級與訂閱
+- 有 Bearer 驗證時，只有建立 session 的身分能接續；超過 MCP_SESSION_RESUME_TTL 沒有活動的紀錄會被清除
+- client 以 DELETE 結束 session 時刪除紀錄
+"""
+
+import hashlib
+import logging
+import threading
+import time
+from datetime import datetime, timezone
+
+import anyio
+from fastmcp.server.dependencies import get_http_request
+from fastmcp.server.middleware import Middleware
+from mcp.server.streamable_http import StreamableHTTPServerTransport
+from mcp.types import InitializeRequestParams
+
+from apikeys import authenticated_principal
+
+logger = logging.getLogger("wazuh_mcp")
+
+SESSION_NAMESPACE = "sessions"
+SESSION_HEADER = "mcp-session-id"
+# last_seen 最多每分鐘寫一次，避免每個請求都寫入資料庫
+TOUCH_INTERVAL = 60
+
+
+def _key(session_id):
+    return hashlib.sha256(session_id.encode()).hexdigest()
+
+
+def _now():
+    return datetime.now(timezone.utc)
+
+
+def request_session_id():
+    """目前 HTTP 請求帶的 Mcp-Session-Id；stdio / 不在請求中時為 None"""
+    try:
+        return get_http_request().headers.get(SESSION_HEADER)
+    except Exception:
+        return None
+
+
+class PersistentSessions:
+    def __init__(self, store, ttl, restore=None):
+        """restore(session, context): session 接續後重新套用 session 內的設定 (log 等級、訂閱)"""
+        self.store = store
+        self.ttl = ttl
+        self.restore = restore
+        self.lock = threading.Lock()
+        self.touched = {}  # session id -> 上次寫入 last_seen 的時間 (monotonic)
+
+    def load(self, session_id):
+        record = self.store.get(SESSION_NAMESPACE, _key(session_id))
+        if record is None:
+            return None
+        if datetime.fromisoformat(record["last_seen"]) < _now() - self.ttl:
+            self.store.delete(SESSION_NAMESPACE, _key(session_id))
+            return None
+        return record
+
+    def purge(self):
+        """刪除超過 TTL 沒有活動的紀錄，回傳刪除筆數"""
+        cutoff = _now() - self.ttl
+        expired = [key for key, record in self.store.list(SESSION_NAMESPACE)
+                   if datetime.fromisoformat(record["last_seen"]) < cutoff]
+        for key in expired:
+            self.store.delete(SESSION_NAMESPACE, key)
+        return len(expired)
+
+    def forget(self, session_id):
+        with self.lock:
+            self.touched.pop(session_id, None)
+            self.store.delete(SESSION_NAMESPACE, _key(session_id))
+
+    def seen(self, session_id, session, principal):
+        """session 的每個請求: 第一次看到時記錄 (或從紀錄還原)，之後定期更新 last_seen"""
+        with self.lock:
+            last = self.touched.get(session_id)
+            if last is not None and time.monotonic() - last < TOUCH_INTERVAL:
+                return
+            self.touched[session_id] = time.monotonic()
+            record = self.load(session_id)
+            restored = None
+            if record is None:
+                if session.client_params is None:
+                    return
+                record = {
+                    "principal": principal,
+                    "client_params": session.client_params.model_dump(mode="json", exclude_none=True),
+                    "created_at": _now().isoformat(),
+                    "context": {},
+                }
+            elif last is None and session.client_params is None:
+                # 重啟後接續的 session: 還原 initialize 時 client 宣告的資訊與能力
+                session._client_params = InitializeRequestParams.model_validate(record["client_params"])
+                restored = record["context"]
+            record["last_seen"] = _now().isoformat()
+            self.store.put(SESSION_NAMESPACE, _key(session_id), record)
+        if restored is not None:
+            logger.info("已接續重啟前的 MCP session (%s, client %s)", record["principal"],
+                        record["client_params"].get("clientInfo", {}).get("name"))
+            if self.restore:
+                self.restore(session, restored)
+
+    def context(self, session_id):
+        record = self.load(session_id) if session_id else None
+        return dict(record["context"]) if record else {}
+
+    def set_context(self, session_id, key, value):
+        """記錄 session 內的設定；session 還沒有紀錄 (例如尚未發出任何請求) 時略過"""
+        if not session_id:
+            return
+        with self.lock:
+            record = self.load(session_id)
+            if record is None:
+                return
+            if value in (None, [], {}):
+                record["context"].pop(key, None)
+            else:
+                record["context"][key] = value
+            self.store.put(SESSION_NAMESPACE, _key(session_id), record)
+
+
+class SessionPersistence(Middleware):
+    """記錄 / 還原發出請求的 session"""
+
+    def __init__(self, sessions, principal):
+        """sessions() -> PersistentSessions (狀態儲存比 middleware 晚建立)；principal() -> 目前呼叫者"""
+        self.sessions = sessions
+        self.principal = principal
+
+    async def on_request(self, context, call_next):
+        ctx = context.fastmcp_context
+        session_id = request_session_id()
+        if session_id and ctx is not None:
+            try:
+                self.sessions().seen(session_id, ctx.session, self.principal())
+            except (AttributeError, RuntimeError):
+                pass
+        return await call_next(context)
+
+
+def find_session_manager(app):
+    """FastMCP HTTP app 中負責 /mcp 的 StreamableHTTPSessionManager"""
+    for route in getattr(app, "routes", []):
+        manager = getattr(getattr(route, "endpoint", None), "session_manager", None)
+        if manager is not None:
+            return manager
+    raise RuntimeError("找不到 Streamable HTTP 的 session manager")
+
+
+async def resume(manager, session_id):
+    """以原本的 session id 建立已初始化的 session (對應 MCP SDK 建立新 session 的流程，但不需要 initialize)"""
+    async with manager._session_creation_lock:
+        if session_id in manager._server_instances:
+            return
+        options = {"mcp_session_id": session_id, "is_json_response_enabled": manager.json_response,
+                   "event_store": manager.event_store, "security_settings": manager.security_settings}
+        if hasattr(manager, "retry_interval"):
+            options["retry_interval"] = manager.retry_interval
+        transport = StreamableHTTPServerTransport(**options)
+        manager._server_instances[session_id] = transport
+
+        async def run_server(*, task_status=anyio.TASK_STATUS_IGNORED):
+            async with transport.connect() as (read_stream, write_stream):
+                task_status.started()
+                try:
+                    # stateless=True 讓 ServerSession 一開始就處於已初始化狀態
+                    await manager.app.run(read_stream, write_stream, manager.app.create_initialization_options(),
+                                          stateless=True)
+                except Exception:
+                    logger.exception("接續的 MCP session 發生錯誤")
+                finally:
+                    if manager._server_instances.get(session_id) is transport and not transport.is_terminated:
+                        del manager._server_instances[session_id]
+
+        await manager._task_group.start(run_server)
+
+
+class ResumableSessions:
+    """starlette middleware (放在 FastMCP 的 HTTP app 內): 重啟後接續有紀錄的 session，DELETE 時刪除紀錄"""
+
+    def __init__(self, app, sessions):
+        self.app = app
+        self.sessions = sessions
+        self.manager = None
+
+    async def __call__(self, scope, receive, send):
+        if scope["type"] != "http":
+            return await self.app(scope, receive, send)
+        if self.manager is None:
+            self.manager = find_session_manager(scope["app"])
+        session_id = next((value.decode("latin-1") for name, value in scope["headers"]
+                           if name.lower() == SESSION_HEADER.encode()), None)
+        if session_id and session_id not in self.manager._server_instances:
+            record = await anyio.to_thread.run_sync(self.sessions.load, session_id)
+            caller = authenticated_principal(scope)
+            if record is not None and caller is not None and caller != record["principal"]:
+                # 不是建立者: 當作 session 不存在，不透露這個 id 曾經有效
+                logger.warning("%s 嘗試接續 %s 建立的 MCP session，已忽略", caller, record["principal"])
+            elif record is not None:
+                await resume(self.manager, session_id)
+        await self.app(scope, receive, send)
+        if session_id and scope["method"] == "DELETE":
+            await anyio.to_thread.run_sync(self.sessions.forget, session_id)
@@ -0,0 +1,194 @@
+"""agent group 共用設定 (agent.conf) 變更的影響模擬與分批上線。
+
+直接改 group 的 agent.conf 會讓整個 group 的 agent 同時收到新設定，設定有誤時全部一起出問題。
+規劃 (plan) 時:
+- 解析提議的 agent.conf，依 <agent_config> 的 os / name 屬性篩選，列出會受影響的 agent 與
+  平台、作業系統、agent 版本、連線狀態的分布，並與 group 目前的 agent.conf 比較差異
+- 把受影響的 agent 分成數批 (waves，累計百分比)，第一批是 canary: 每種「平台 + agent 版本」組合各挑一台連線中的 agent，
+  盡早發現相容性問題；未連線的 agent 放在最後一批 (重新連線時才會收到設定)
+上線 (apply) 時以 group 指派分批進行，每一步都需要 MCP_ALLOW_WRITES=true:
+- stage: 建立暫存 group (<group>-rollout-<計畫 id>) 並上傳新設定
+- wave: 把下一批 agent 加入暫存 group (多重 group 時後面的 group 設定優先，等同套用新設定)
+- promote: 全部批次完成後把新設定寫入原本的 group，並刪除暫存 group
+- rollback: 刪除暫存 group，已加入的 agent 回到原本的設定
+計畫存放在狀態儲存的 rollouts namespace，記錄每一步的執行者與時間。
+"""
+
+import difflib
+import math
+import re
+import threading
+import xml.etree.ElementTree as ET
+from collections import Counter
+from datetime import datetime, timezone
+
+ROLLOUT_NAMESPACE = "rollouts"
+DEFAULT_WAVES = (10, 50, 100)
+FILTER_ATTRIBUTES = ("os", "name", "profile")
+AGENT_FIELDS = "id,name,status,version,os.platform,os.name,os.version,os.uname,group"
+# Manager API 一次指派的 agent 數
+ASSIGN_BATCH = 100
+MAX_DIFF_LINES = 200
+_ID = re.compile(r"R(\d+)$")
+
+
+def _now():
+    return datetime.now(timezone.utc).isoformat()
+
+
+def parse_agent_config(text):
+    """agent.conf 內容 -> 各 <agent_config> 區塊的篩選屬性清單；格式錯誤拋出 ValueError"""
+    try:
+        root = ET.fromstring(f"<mcp_root>{text}</mcp_root>")
+    except ET.ParseError as e:
+        raise ValueError(f"agent.conf 的 XML 格式錯誤: {e}") from e
+    blocks = []
+    for element in root:
+        if element.tag != "agent_config":
+            raise ValueError(f"agent.conf 的頂層只能是 <agent_config>，不是 <{element.tag}>")
+        blocks.append({k: v for k, v in element.attrib.items() if k in FILTER_ATTRIBUTES})
+    if not blocks:
+        raise ValueError("agent.conf 沒有任何 <agent_config> 區塊")
+    return blocks
+
+
+def _os_string(agent):
+    os_info = agent.get("os") or {}
+    return " ".join(str(os_info.get(k) or "") for k in ("uname", "platform", "name", "version"))
+
+
+def matching_blocks(blocks, agent):
+    """會套用到這個 agent 的區塊 (profile 依 agent 端設定，無法從 manager 判斷，一律視為符合)"""
+    matched = []
+    for i, block in enumerate(blocks):
+        if "os" in block and block["os"].lower() not in _os_string(agent).lower():
+            continue
+        if "name" in block and block["name"] != agent.get("name"):
+            continue
+        matched.append(i)
+    return matched
+
+
+def _platform(agent):
+    return (agent.get("os") or {}).get("platform") or "unknown"
+
+
+def breakdown(agents):
+    """平台 / 作業系統 / agent 版本 / 連線狀態的分布"""
+    def count(key):
+        return dict(Counter(key(a) for a in agents).most_common())
+    return {
+        "platform": count(_platform),
+        "os": count(lambda a: " ".join(filter(None, [(a.get("os") or {}).get("name"),
+                                                      (a.get("os") or {}).get("version")])) or "unknown"),
+        "agent_version": count(lambda a: a.get("version") or "unknown"),
+        "status": count(lambda a: a.get("status") or "unknown"),
+    }
+
+
+def plan_waves(agents, percentages):
+    """受影響的 agent -> 各批的 agent id (累計百分比；第一批含各「平台 + 版本」組合的 canary)"""
+    connected = sorted((a for a in agents if a.get("status") == "active"), key=lambda a: a["id"])
+    offline = sorted((a for a in agents if a.get("status") != "active"), key=lambda a: a["id"])
+    canaries, seen = [], set()
+    for agent in connected:
+        combo = (_platform(agent), agent.get("version"))
+        if combo not in seen:
+            seen.add(combo)
+            canaries.append(agent)
+    chosen = {a["id"] for a in canaries}
+    ordered = canaries + [a for a in connected if a["id"] not in chosen]
+    waves, placed = [], 0
+    for i, percent in enumerate(percentages):
+        target = len(ordered) if percent >= 100 else max(math.ceil(len(ordered) * percent / 100), 1)
+        if i == 0:
+            target = max(target, len(canaries))
+        target = min(target, len(ordered))
+        waves.append([a["id"] for a in ordered[placed:target]])
+        placed = max(placed, target)
+    if offline:
+        waves.append([a["id"] for a in offline])
+    return [wave for wave in waves if wave]
+
+
+def config_diff(current, proposed):
+    """目前與提議的 agent.conf 的 unified diff (最多 MAX_DIFF_LINES 行)"""
+    lines = list(difflib.unified_diff((current or "").splitlines(), proposed.splitlines(),
+                                      "agent.conf (current)", "agent.conf (proposed)", lineterm=""))
+    return {"added": sum(1 for line in lines if line.startswith("+") and not line.startswith("+++")),
+            "removed": sum(1 for line in lines if line.startswith("-") and not line.startswith("---")),
+            "diff": lines[:MAX_DIFF_LINES], "truncated": len(lines) > MAX_DIFF_LINES}
+
+
+def staging_group(group, plan_id):
+    return f"{group}-rollout-{plan_id.lower()}"
+
+
+def batches(agent_ids):
+    for start in range(0, len(agent_ids), ASSIGN_BATCH):
+        yield agent_ids[start:start + ASSIGN_BATCH]
+
+
+class Rollouts:
+    def __init__(self, store):
+        self.store = store
+        self.lock = threading.Lock()
+
+    def get(self, plan_id):
+        return self.store.get(ROLLOUT_NAMESPACE, plan_id)
+
+    def list(self):
+        """所有計畫，依建立順序排列"""
+        records = [record for _, record in self.store.list(ROLLOUT_NAMESPACE)]
+        return sorted(records, key=lambda r: int(_ID.match(r["id"]).group(1)) if _ID.match(r["id"]) else 0)
+
+    def create(self, group, config, waves, principal, summary):
+        with self.lock:
+            numbers = [int(m.group(1)) for key, _ in self.store.list(ROLLOUT_NAMESPACE) if (m := _ID.match(key))]
+            plan_id = f"R{max(numbers, default=0) + 1}"
+            record = {
+                "id": plan_id,
+                "group": group,
+                "staging_group": staging_group(group, plan_id),
+                "config": config,
+                "summary": summary,
+                "waves": [{"agents": agents, "applied_at": None, "applied_by": None} for agents in waves],
+                "status": "planned",
+                "created_by": principal,
+                "created_at": _now(),
+                "steps": [],
+            }
+            self.store.put(ROLLOUT_NAMESPACE, plan_id, record)
+            return record
+
+    def record_step(self, plan_id, principal, action, status, wave=None):
+        with self.lock:
+            record = self.get(plan_id)
+            step = {"at": _now(), "by": principal, "action": action}
+            if wave is not None:
+                step["wave"] = wave + 1
+                record["waves"][wave].update(applied_at=step["at"], applied_by=principal)
+            record["steps"].append(step)
+            record["status"] = status
+            self.store.put(ROLLOUT_NAMESPACE, plan_id, record)
+            return record
+
+
+def next_wave(record):
+    """下一個尚未套用的批次 index；全部套用時為 None"""
+    return next((i for i, wave in enumerate(record["waves"]) if wave["applied_at"] is None), None)
+
+
+def progress(record):
+    applied = [wave for wave in record["waves"] if wave["applied_at"]]
+    return {
+        "plan": record["id"],
+        "group": record["group"],
+        "staging_group": record["staging_group"],
+        "status": record["status"],
+        "waves": [{"wave": i + 1, "agents": len(wave["agents"]), "applied_at": wave["applied_at"]}
+                  for i, wave in enumerate(record["waves"])],
+        "agents_on_new_config": sum(len(wave["agents"]) for wave in applied),
+        "agents_total": sum(len(wave["agents"]) for wave in record["waves"]),
+        "steps": record["steps"],
+    }
@@ -0,0 +1,223 @@
+"""常用統計的預先彙總 (materialized rollups): 每天的告警數，依規則 / agent / 等級分類。
+
+背景工作每隔 MCP_ROLLUP_INTERVAL 把已結束的 UTC 日子彙總一次存進狀態儲存 (namespace "rollups")，
+最近 MCP_ROLLUP_REFRESH_DAYS 天會重算以收進晚到的告警，MCP_ROLLUP_DAYS 之前的資料會刪除。
+長時間範圍的統計 (例如 90 天趨勢) 整天的部分直接讀彙總，只有頭尾不滿一天、或還沒彙總的日子才查 Indexer，
+從掃描數千萬筆告警變成讀幾十筆小文件。彙總以系統身分計算 (套用全域篩選)，租戶範圍的呼叫者不使用。
+"""
+
+import logging
+import threading
+from datetime import datetime, time, timedelta, timezone
+
+logger = logging.getLogger("wazuh_mcp")
+
+ROLLUP_NAMESPACE = "rollups"
+DIMENSIONS = ("rules", "agents", "levels")
+DAY = timedelta(days=1)
+
+
+def daily_aggregations(terms_size):
+    """每天一個 bucket，各自統計規則 (含等級與描述)、agent 與等級的告警數"""
+    return {"days": {
+        "date_histogram": {"field": "timestamp", "calendar_interval": "1d", "time_zone": "UTC",
+                           "min_doc_count": 1},
+        "aggs": {
+            "rules": {
+                "terms": {"field": "rule.id", "size": terms_size},
+                "aggs": {
+                    "level": {"max": {"field": "rule.level"}},
+                    "description": {"terms": {"field": "rule.description", "size": 1}},
+                },
+            },
+            "agents": {"terms": {"field": "agent.name", "size": terms_size}},
+            "levels": {"terms": {"field": "rule.level", "size": 20}},
+        },
+    }}
+
+
+def day_of(value):
+    return value.astimezone(timezone.utc).date()
+
+
+def day_start(day):
+    return datetime.combine(day, time.min, tzinfo=timezone.utc)
+
+
+def summarize_day(bucket):
+    """date_histogram 的一個 bucket -> 彙總文件"""
+    rules = {}
+    for b in bucket.get("rules", {}).get("buckets", []):
+        rules[str(b["key"])] = {
+            "alerts": b.get("doc_count", 0),
+            "level": int(b.get("level", {}).get("value") or 0),
+            "description": next((d.get("key") for d in b.get("description", {}).get("buckets", [])), None),
+        }
+    return {
+        "total": bucket.get("doc_count", 0),
+        "rules": rules,
+        "agents": {str(b["key"]): b.get("doc_count", 0) for b in bucket.get("agents", {}).get("buckets", [])},
+        "levels": {str(b["key"]): b.get("doc_count", 0) for b in bucket.get("levels", {}).get("buckets", [])},
+        # terms 只保留前 N 個，其餘的數量 (非 0 時 top 排名在尾端可能不準)
+        "other": {dim: bucket.get(dim, {}).get("sum_other_doc_count", 0) for dim in DIMENSIONS},
+    }
+
+
+def split_days(result):
+    """查詢結果 -> {date: 彙總文件}"""
+    days = {}
+    for bucket in result.get("aggregations", {}).get("days", {}).get("buckets", []):
+        stamp = datetime.fromtimestamp(bucket["key"] / 1000, tz=timezone.utc)
+        days[stamp.date()] = summarize_day(bucket)
+    return days
+
+
+def merge(days, dimension, limit):
+    """合併多天的彙總: 每天總數 + 整段期間前 limit 名 (以及它們每天的數量)"""
+    totals, series = {}, []
+    meta = {}
+    for day in sorted(days):
+        doc = days[day]
+        counts = doc.get(dimension, {}) if dimension else {}
+        point = {"day": day.isoformat(), "alerts": doc.get("total", 0)}
+        if dimension:
+            point["by_key"] = {}
+            for key, value in counts.items():
+                alerts = value["alerts"] if isinstance(value, dict) else value
+                totals[key] = totals.get(key, 0) + alerts
+                point["by_key"][key] = alerts
+                if isinstance(value, dict):
+                    meta[key] = {"level": value.get("level"), "description": value.get("description")}
+        series.append(point)
+    top = sorted(totals.items(), key=lambda kv: kv[1], reverse=True)[:limit]
+    keys = [key for key, _ in top]
+    for point in series:
+        by_key = point.pop("by_key", None)
+        if by_key is not None:
+            point["top"] = {key: by_key.get(key, 0) for key in keys}
+    return {
+        "total": sum(p["alerts"] for p in series),
+        "top": [{"key": key, "alerts": alerts, **meta.get(key, {})} for key, alerts in top],
+        "series": series,
+    }
+
+
+class Rollups:
+    def __init__(self, search, store, aggregations, interval, keep_days, refresh_days):
+        """search(body) -> (結果, 錯誤訊息)，應以系統身分查詢；aggregations() 回傳 daily_aggregations 的查詢片段"""
+        self.search = search
+        self.store = store
+        self.aggregations = aggregations
+        self.interval = interval
+        self.keep_days = keep_days
+        self.refresh_days = refresh_days
+        self.last_run = None
+        self._stop = threading.Event()
+
+    @property
+    def enabled(self):
+        return self.interval > 0
+
+    def body(self, start, end):
+        return {
+            "size": 0,
+            "query": {"range": {"timestamp": {"gte": start.isoformat(), "lt": end.isoformat()}}},
+            "aggs": self.aggregations(),
+        }
+
+    def stored_days(self):
+        return {datetime.fromisoformat(key).date() for key, _ in self.store.list(ROLLUP_NAMESPACE)}
+
+    def load(self, days):
+        """讀取指定日子的彙總，回傳 {date: 文件}；沒有彙總的日子不在結果中"""
+        loaded = {}
+        for day in days:
+            doc = self.store.get(ROLLUP_NAMESPACE, day.isoformat())
+            if doc is not None:
+                loaded[day] = doc
+        return loaded
+
+    def tick(self, now=None):
+        """彙總還沒算過的已結束日子與最近 refresh_days 天，刪除過期的彙總；回傳本次寫入的日子"""
+        today = day_of(now or datetime.now(timezone.utc))
+        wanted = [today - timedelta(days=n) for n in range(1, self.keep_days + 1)]
+        stored = self.stored_days()
+        pending = sorted(day for day in wanted if day not in stored or (today - day).days <= self.refresh_days)
+        written = []
+        # 連續的日子合併成一次查詢 (每段最多 7 天，避免初次回填時單一查詢過大)
+        for start, end in self._spans(pending, 7):
+            result, error = self.search(self.body(day_start(start), day_start(end) + DAY))
+            if error or "_incomplete" in result:
+                logger.warning("告警彙總 %s ~ %s 失敗，下次重試: %s", start, end, error or result["_incomplete"])
+                continue
+            found = split_days(result)
+            day = start
+            while day <= end:
+                doc = found.get(day) or summarize_day({})
+                self.store.put(ROLLUP_NAMESPACE, day.isoformat(),
+                               {**doc, "computed_at": datetime.now(timezone.utc).isoformat()})
+                written.append(day)
+                day += DAY
+        for day in stored:
+            if (today - day).days > self.keep_days:
+                self.store.delete(ROLLUP_NAMESPACE, day.isoformat())
+        self.last_run = datetime.now(timezone.utc)
+        if written:
+            logger.info("已更新 %d 天的告警彙總 (%s ~ %s)", len(written), written[0], written[-1])
+        return written
+
+    @staticmethod
+    def _spans(days, max_len):
+        spans = []
+        for day in days:
+            if spans and spans[-1][1] + DAY == day and (day - spans[-1][0]).days < max_len:
+                spans[-1] = (spans[-1][0], day)
+            else:
+                spans.append((day, day))
+        return spans
+
+    def plan(self, start, end):
+        """[start, end) 拆成可讀彙總的整天與需要查 Indexer 的片段: (彙總 {date: 文件}, [(起, 迄)])"""
+        first_full = day_of(start) if start == day_start(day_of(start)) else day_of(start) + DAY
+        last_full = day_of(end) - DAY  # end 所在的那天不完整 (或 end 剛好是午夜，前一天是最後一個整天)
+        full_days = []
+        day = first_full
+        while day <= last_full:
+            full_days.append(day)
+            day += DAY
+        stored = self.load(full_days) if self.enabled else {}
+        gaps, cursor = [], start
+        for day in full_days:
+            if day in stored:
+                if cursor < day_start(day):
+                    gaps.append((cursor, day_start(day)))
+                cursor = day_start(day) + DAY
+        if cursor < end:
+            gaps.append((cursor, end))
+        return stored, gaps
+
+    def status(self):
+        days = sorted(self.stored_days())
+        return {
+            "enabled": self.enabled,
+            "days": len(days),
+            "oldest": days[0].isoformat() if days else None,
+            "newest": days[-1].isoformat() if days else None,
+            "last_run": self.last_run.isoformat() if self.last_run else None,
+        }
+
+    def _loop(self):
+        # 啟動後先跑一次 (回填)，之後每 interval 一次
+        while True:
+            try:
+                self.tick()
+            except Exception:  # 背景工作不能因為單次彙總出錯而結束
+                logger.exception("告警彙總發生例外")
+            if self._stop.wait(self.interval):
+                return
+
+    def start(self):
+        threading.Thread(target=self._loop, name="alert-rollups", daemon=True).start()
+
+    def stop(self):
+        self._stop.set()
@@ -0,0 +1,126 @@
+"""比較兩份規則庫 (manager 目前載入的規則 vs 規則包)，評估升級或規則調整的影響。
+
+規則包可以是 Wazuh 新版的 ruleset 匯出，也可以是自訂規則目錄 (格式同 review_detection_rules)。
+比較的欄位: 等級、描述、群組、MITRE 技術；兩邊都是規則包時另外比對規則的比對條件 (XML 內容)。
+"""
+
+import hashlib
+import xml.etree.ElementTree as ET
+
+COMPARED_FIELDS = ("level", "description", "groups", "mitre")
+
+
+def _split_groups(value):
+    return [g.strip() for g in (value or "").split(",") if g.strip()]
+
+
+def _conditions(rule):
+    """規則的比對條件 (去掉描述 / 群組 / MITRE 等說明性元素) 的雜湊"""
+    parts = [f"{k}={v}" for k, v in sorted(rule.attrib.items()) if k not in ("level", "id")]
+    for child in rule:
+        if child.tag in ("description", "group", "mitre"):
+            continue
+        attrs = ",".join(f"{k}={v}" for k, v in sorted(child.attrib.items()))
+        parts.append(f"<{child.tag} {attrs}>{(child.text or '').strip()}")
+    return hashlib.sha256("\n".join(parts).encode("utf-8")).hexdigest()[:16]
+
+
+def parse_ruleset(rules):
+    """規則包 {檔名: XML 內容} -> {rule id: 規則摘要}；overwrite="yes" 的規則取代先前的定義"""
+    found = {}
+    for filename, text in sorted(rules.items()):
+        try:
+            root = ET.fromstring(f"<mcp_root>{text}</mcp_root>")
+        except ET.ParseError as e:
+            raise ValueError(f"{filename}: XML 格式錯誤: {e}") from e
+        for group in root.iter("group"):
+            parent_groups = _split_groups(group.get("name"))
+            for rule in group.findall("rule"):
+                rule_id = rule.get("id")
+                if not rule_id or not rule_id.isdigit():
+                    continue
+                level = rule.get("level")
+                found[rule_id] = {
+                    "id": rule_id,
+                    "level": int(level) if level and level.isdigit() else None,
+                    "description": " ".join((rule.findtext("description") or "").split()),
+                    "groups": sorted(set(parent_groups + [g for e in rule.findall("group")
+                                                          for g in _split_groups(e.text)])),
+                    "mitre": sorted({(e.text or "").strip() for e in rule.iterfind("mitre/id") if e.text}),
+                    "file": filename,
+                    "conditions": _conditions(rule),
+                }
+    return found
+
+
+def catalog_ruleset(rules):
+    """RuleCatalog 的規則 (Manager API) -> 與 parse_ruleset 相同的格式 (沒有 conditions)"""
+    return {rule_id: {
+        "id": rule_id,
+        "level": rule["level"],
+        "description": " ".join((rule["description"] or "").split()),
+        "groups": sorted(set(rule["groups"])),
+        "mitre": sorted(set(rule["mitre"])),
+        "file": rule["file"],
+    } for rule_id, rule in rules.items()}
+
+
+def _brief(rule):
+    return {k: rule[k] for k in ("id", "level", "description", "mitre", "file")}
+
+
+def _changes(old, new):
+    changes = {}
+    for field in COMPARED_FIELDS:
+        if old[field] == new[field]:
+            continue
+        if isinstance(old[field], list):
+            changes[field] = {"added": sorted(set(new[field]) - set(old[field])),
+                              "removed": sorted(set(old[field]) - set(new[field]))}
+        else:
+            changes[field] = {"from": old[field], "to": new[field]}
+    if old.get("conditions") and new.get("conditions") and old["conditions"] != new["conditions"]:
+        changes["conditions"] = "比對條件不同"
+    return changes
+
+
+def _rule_key(rule_id):
+    return int(rule_id) if rule_id.isdigit() else 0
+
+
+def compare(baseline, candidate, limit):
+    """回傳新增 / 移除 / 修改的規則與 MITRE 覆蓋變化；各清單最多 limit 筆 (summary 仍是完整數量)"""
+    added = [_brief(candidate[i]) for i in sorted(set(candidate) - set(baseline), key=_rule_key)]
+    removed = [_brief(baseline[i]) for i in sorted(set(baseline) - set(candidate), key=_rule_key)]
+    modified, unchanged, raised, lowered = [], 0, 0, 0
+    for rule_id in sorted(set(baseline) & set(candidate), key=_rule_key):
+        changes = _changes(baseline[rule_id], candidate[rule_id])
+        if not changes:
+            unchanged += 1
+            continue
+        level = changes.get("level")
+        if level and level["from"] is not None and level["to"] is not None:
+            raised += level["to"] > level["from"]
+            lowered += level["to"] < level["from"]
+        modified.append({"id": rule_id, "description": candidate[rule_id]["description"], "changes": changes})
+
+    def techniques(rules):
+        return {t for rule in rules.values() for t in rule["mitre"]}
+
+    report = {
+        "summary": {
+            "added": len(added), "removed": len(removed), "modified": len(modified), "unchanged": unchanged,
+            "level_raised": raised, "level_lowered": lowered,
+        },
+        # 整份規則庫都不再 (或開始) 對應的 MITRE 技術，單一規則改對應不算
+        "mitre_coverage": {
+            "gained": sorted(techniques(candidate) - techniques(baseline)),
+            "lost": sorted(techniques(baseline) - techniques(candidate)),
+        },
+        "added": added[:limit],
+        "removed": removed[:limit],
+        "modified": modified[:limit],
+    }
+    if max(len(added), len(removed), len(modified)) > limit:
+        report["truncated"] = f"各清單只列出前 {limit} 筆，完整數量見 summary"
+    return report
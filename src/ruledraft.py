@@ -0,0 +1,174 @@
+"""從已確認的發現 (一組告警) 草擬 Wazuh 候選規則，縮短「獵捕 -> 偵測規則」的交接。
+
+草稿規則以這些事件原本觸發的規則為父規則 (<if_sid>)，再加上所有事件共同的解碼欄位作為條件:
+- 靜態欄位 (srcip、dstip、srcport、dstport、protocol、action、id、url、status、system_name、srcuser / dstuser)
+  用對應的專用標籤，例如 <srcip>、<user>
+- 動態欄位 (例如 data.win.eventdata.image) 用 <field name="win.eventdata.image" type="pcre2">，值以 ^...$ 完整比對
+指定 frequency 時另外產生一條關聯規則 (frequency / timeframe / same_field)，單筆事件的規則降為低等級。
+同時產生 review_detection_rules 使用的 .ini 測試案例 (以事件的 full_log 為 pass 日誌)。
+"""
+
+import re
+from xml.sax.saxutils import escape, quoteattr
+
+# 靜態解碼欄位 -> 規則標籤
+STATIC_FIELDS = {
+    "data.srcip": "srcip", "data.dstip": "dstip", "data.srcport": "srcport", "data.dstport": "dstport",
+    "data.protocol": "protocol", "data.action": "action", "data.id": "id", "data.url": "url",
+    "data.status": "status", "data.system_name": "system_name", "data.srcuser": "user", "data.dstuser": "user",
+    "predecoder.program_name": "program_name",
+}
+SAME_TAGS = {"data.srcip": "same_srcip", "data.dstip": "same_dstip", "data.srcport": "same_srcport",
+             "data.dstport": "same_dstport", "data.srcuser": "same_srcuser", "data.dstuser": "same_user",
+             "data.id": "same_id", "data.url": "same_url", "agent.id": "same_location"}
+# 每筆事件都不同、寫進規則沒有意義的欄位
+_VOLATILE = re.compile(r"(time|guid|processid|logonid|recordid|sequence|^data\.srcport$|^data\.pid$)", re.I)
+# 沒有指定 rule_id 時從這個範圍挑第一個 (frequency 規則要連續兩個) 未使用的 id
+DRAFT_ID_RANGE = (100800, 100899)
+MAX_AUTO_CONDITIONS = 5
+MAX_VALUES = 10
+SINGLE_EVENT_LEVEL = 3
+SOURCE_FIELDS = ["timestamp", "rule.id", "rule.level", "rule.description", "decoder.name", "location",
+                 "full_log", "data", "predecoder.program_name", "agent.name"]
+
+
+def _leaves(doc, prefix=""):
+    for key, value in (doc or {}).items():
+        path = f"{prefix}.{key}" if prefix else key
+        if isinstance(value, dict):
+            yield from _leaves(value, path)
+        elif isinstance(value, (str, int, float)) and not isinstance(value, bool) and str(value) != "":
+            yield path, str(value)
+
+
+def event_fields(event):
+    """事件中可以寫進規則的欄位 {欄位: 值} (data.* 與 predecoder.program_name)"""
+    fields = dict(_leaves({"data": event.get("data") or {}}))
+    program = (event.get("predecoder") or {}).get("program_name")
+    if program:
+        fields["predecoder.program_name"] = str(program)
+    return fields
+
+
+def common_fields(events):
+    """所有事件都有且值相同、看起來不是每筆都會變的欄位 -> {欄位: 值}"""
+    per_event = [event_fields(event) for event in events]
+    if not per_event:
+        return {}
+    shared = {field: value for field, value in per_event[0].items()
+              if all(other.get(field) == value for other in per_event[1:]) and not _VOLATILE.search(field)}
+    # 靜態欄位優先 (Wazuh 比對較快，也較好讀)，再依欄位名稱排序
+    ordered = sorted(shared, key=lambda f: (f not in STATIC_FIELDS, f))
+    return {field: shared[field] for field in ordered[:MAX_AUTO_CONDITIONS]}
+
+
+def chosen_fields(events, fields):
+    """使用者指定的欄位 -> {欄位: [值]}；缺少欄位的事件會讓規則比對不到，直接回報錯誤"""
+    per_event = [event_fields(event) for event in events]
+    chosen = {}
+    for field in fields:
+        values = [fields_of.get(field) for fields_of in per_event]
+        if any(value is None for value in values):
+            raise ValueError(f"有事件沒有欄位 {field}，規則會比對不到這些事件")
+        distinct = sorted(set(values))
+        if len(distinct) > MAX_VALUES:
+            raise ValueError(f"欄位 {field} 有 {len(distinct)} 種值，不適合作為規則條件")
+        chosen[field] = distinct
+    return chosen
+
+
+def condition(field, values):
+    """欄位條件 -> 規則 XML 片段"""
+    if len(values) == 1:
+        pattern = f"^{re.escape(values[0])}$"
+    else:
+        pattern = "^(" + "|".join(re.escape(v) for v in values) + ")$"
+    tag = STATIC_FIELDS.get(field)
+    if tag:
+        return f'<{tag} type="pcre2">{escape(pattern)}</{tag}>'
+    name = field[len("data."):] if field.startswith("data.") else field
+    return f'<field name={quoteattr(name)} type="pcre2">{escape(pattern)}</field>'
+
+
+def same_condition(field):
+    tag = SAME_TAGS.get(field)
+    if tag:
+        return f"<{tag} />"
+    name = field[len("data."):] if field.startswith("data.") else field
+    return f"<same_field>{escape(name)}</same_field>"
+
+
+def draft_rules(rule_id, level, description, parent_rules, conditions, frequency=None, timeframe=None,
+                same_field=None, mitre=None, group="mcp_draft"):
+    """產生規則 XML；conditions 為 {欄位: [值]}。回傳 (XML, 會觸發告警的規則 id)"""
+    lines = [f"<group name={quoteattr(group + ',')}>"]
+    base_level = SINGLE_EVENT_LEVEL if frequency else level
+    lines.append(f'  <rule id="{rule_id}" level="{base_level}">')
+    if parent_rules:
+        lines.append(f"    <if_sid>{', '.join(parent_rules)}</if_sid>")
+    lines.extend(f"    {condition(field, values)}" for field, values in conditions.items())
+    lines.append(f"    <description>{escape(description)}</description>")
+    if mitre and not frequency:
+        lines.append("    <mitre>" + "".join(f"<id>{escape(t)}</id>" for t in mitre) + "</mitre>")
+    lines.append("  </rule>")
+    alert_rule = rule_id
+    if frequency:
+        alert_rule = rule_id + 1
+        lines.append(f'  <rule id="{alert_rule}" level="{level}" frequency="{frequency}" timeframe="{timeframe}">')
+        lines.append(f"    <if_matched_sid>{rule_id}</if_matched_sid>")
+        if same_field:
+            lines.append(f"    {same_condition(same_field)}")
+        lines.append(f"    <description>{escape(description)} ({frequency} 次 / {timeframe} 秒)</description>")
+        if mitre:
+            lines.append("    <mitre>" + "".join(f"<id>{escape(t)}</id>" for t in mitre) + "</mitre>")
+        lines.append("  </rule>")
+    lines.append("</group>")
+    return "\n".join(lines) + "\n", alert_rule
+
+
+def test_cases(events, rule_id, alert_rule, level, frequency=None):
+    """以事件的 full_log 組成 logtest 測試案例 (run_logtest 的格式)；沒有 full_log 時回傳 []。
+    frequency 規則: 前面的日誌累積次數 (應觸發單筆事件的規則)，最後一筆才觸發關聯規則"""
+    events = [event for event in events if event.get("full_log")]
+    if frequency:
+        events = (events * frequency)[:max(frequency, len(events))]
+    decoders = {(event.get("decoder") or {}).get("name") for event in events} - {None}
+    decoder = decoders.pop() if len(decoders) == 1 and not frequency else None
+    cases = []
+    for i, event in enumerate(events, 1):
+        accumulating = frequency and i < len(events)
+        cases.append({
+            "file": "draft.ini", "name": "累積次數" if accumulating else "草稿規則", "log": i,
+            "event": event["full_log"], "expect": "pass",
+            "rule": str(rule_id if accumulating else alert_rule),
+            "alert": str(SINGLE_EVENT_LEVEL if accumulating else level),
+            "decoder": decoder,
+            "log_format": "syslog", "location": event.get("location") or "mcp-rule-draft",
+        })
+    return cases
+
+
+def to_ini(cases):
+    """測試案例 -> review_detection_rules 使用的 .ini 內容 (同名的案例放在同一個區段)"""
+    sections = {}
+    for case in cases:
+        sections.setdefault(case["name"], []).append(case)
+    blocks = []
+    for name, group in sections.items():
+        lines = [f"[{name}]"]
+        lines.extend(f"log {n} pass = {case['event']}" for n, case in enumerate(group, 1))
+        first = group[0]
+        lines.append(f"rule = {first['rule']}")
+        lines.append(f"alert = {first['alert']}")
+        if first["decoder"]:
+            lines.append(f"decoder = {first['decoder']}")
+        blocks.append("\n".join(lines))
+    return "\n\n".join(blocks) + "\n"
+
+
+def free_rule_id(used, count=1):
+    """DRAFT_ID_RANGE 中第一段連續 count 個未使用的 id；沒有時回傳 None"""
+    for rule_id in range(DRAFT_ID_RANGE[0], DRAFT_ID_RANGE[1] - count + 2):
+        if not any(str(rule_id + i) in used for i in range(count)):
+            return rule_id
+    return None
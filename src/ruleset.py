@@ -0,0 +1,120 @@
+"""Wazuh 規則庫的 MCP 資源 (resources): 讓 client 直接讀取規則內容，不必再呼叫工具。
+
+URI 配置:
+    wazuh://rules                   規則庫總覽 (各等級 / 群組的規則數)
+    wazuh://rules/{rule_id}         單一規則 (等級、描述、群組、MITRE、比對條件、所在檔案)
+    wazuh://rules/groups/{group}    某個群組的所有規則
+    wazuh://rules/levels/{level}    某個等級的所有規則
+規則清單由 Manager API 查詢後快取 RULE_CACHE_TTL 秒；resources/list 會列出每一條規則。
+"""
+
+import threading
+import time
+from collections import Counter
+
+from fastmcp.resources import FunctionResource
+from fastmcp.server.middleware import Middleware
+
+RULE_CACHE_TTL = 300
+RULE_URI = "wazuh://rules"
+
+
+def rule_view(item):
+    """Manager API 的規則資料 -> 資源內容"""
+    return {
+        "id": str(item.get("id")),
+        "level": item.get("level"),
+        "description": item.get("description"),
+        "groups": item.get("groups", []),
+        "mitre": (item.get("mitre") or {}).get("id", []),
+        "file": item.get("filename"),
+        "path": item.get("relative_dirname"),
+        "status": item.get("status"),
+        "details": item.get("details", {}),
+    }
+
+
+def rule_brief(rule):
+    return {"id": rule["id"], "level": rule["level"], "description": rule["description"],
+            "uri": f"{RULE_URI}/{rule['id']}"}
+
+
+class RuleCatalog:
+    def __init__(self, fetch_rules, ttl=RULE_CACHE_TTL):
+        """fetch_rules() -> Manager API 的規則清單 (查詢失敗時拋出 RuntimeError)"""
+        self.fetch_rules = fetch_rules
+        self.ttl = ttl
+        self.lock = threading.Lock()
+        self._rules = None
+        self._loaded_at = 0
+
+    def rules(self):
+        with self.lock:
+            if self._rules is not None and time.time() - self._loaded_at < self.ttl:
+                return self._rules
+        rules = {}
+        for item in self.fetch_rules():
+            rule = rule_view(item)
+            rules[rule["id"]] = rule
+        with self.lock:
+            self._rules, self._loaded_at = rules, time.time()
+        return rules
+
+    def get(self, rule_id):
+        rule = self.rules().get(str(rule_id).strip())
+        if rule is None:
+            raise KeyError(f"找不到規則 {rule_id}")
+        return rule
+
+    def overview(self):
+        rules = self.rules().values()
+        return {
+            "rules": len(rules),
+            "by_level": {str(k): v for k, v in sorted(Counter(r["level"] for r in rules).items())},
+            "groups": dict(sorted(Counter(g for r in rules for g in r["groups"]).items())),
+            "uris": {
+                "rule": f"{RULE_URI}/{{rule_id}}",
+                "group": f"{RULE_URI}/groups/{{group}}",
+                "level": f"{RULE_URI}/levels/{{level}}",
+            },
+        }
+
+    def in_group(self, group):
+        rules = [rule_brief(r) for r in self.rules().values() if group in r["groups"]]
+        if not rules:
+            raise KeyError(f"規則庫中沒有群組 {group}")
+        return {"group": group, "rules": sorted(rules, key=lambda r: (-(r["level"] or 0), r["id"]))}
+
+    def at_level(self, level):
+        try:
+            level = int(level)
+        except ValueError:
+            raise KeyError(f"規則等級必須是 0-15 的整數，收到 '{level}'") from None
+        rules = [rule_brief(r) for r in self.rules().values() if r["level"] == level]
+        return {"level": level, "rules": sorted(rules, key=lambda r: r["id"])}
+
+
+class RuleResourceList(Middleware):
+    """resources/list 時把每一條規則列為 wazuh://rules/{id} 資源 (內容由資源範本讀取)"""
+
+    def __init__(self, catalog, read_rule):
+        self.catalog = catalog
+        self.read_rule = read_rule
+
+    async def on_list_resources(self, context, call_next):
+        resources = await call_next(context)
+        try:
+            rules = self.catalog.rules()
+        except RuntimeError:
+            # Manager API 無法連線時仍列出其他資源，規則可透過範本 URI 直接讀取
+            return resources
+        listed = list(resources)
+        for rule in sorted(rules.values(), key=lambda r: int(r["id"]) if r["id"].isdigit() else 0):
+            listed.append(FunctionResource(
+                uri=f"{RULE_URI}/{rule['id']}",
+                name=f"rule {rule['id']}",
+                description=f"[level {rule['level']}] {rule['description']}",
+                mime_type="application/json",
+                fn=lambda rule_id=rule["id"]: self.read_rule(rule_id),
+            ))
+        return listed
@@ -0,0 +1,175 @@
+"""Detection-as-code: 候選規則的 XML 驗證與 logtest 測試。
+
+輸入是一個目錄或壓縮檔 (.zip / .tar.gz)，內含:
+- *.xml: 候選規則檔 (Wazuh 規則格式，一個檔案可有多個 <group>)
+- *.ini: 測試案例，沿用 Wazuh ruleset 的測試格式:
+
+    [sshd: 暴力破解]
+    log 1 pass = Oct 15 21:07:00 host sshd[1234]: Failed password for root from 10.0.0.5 port 22 ssh2
+    log 2 fail = Oct 15 21:07:00 host sshd[1234]: Accepted password for root from 10.0.0.5 port 22 ssh2
+    rule = 100100
+    alert = 10
+    decoder = sshd
+
+  "pass" 表示這行日誌必須觸發 rule (以及 alert 等級 / decoder，有寫才檢查)；"fail" 表示不能觸發該規則。
+  可加 log_format / location 指定 logtest 的參數。
+壓縮檔直接在記憶體中讀取成員，不會解壓到磁碟；成員名稱為絕對路徑或含 .. (解壓時會跳出目標目錄) 時整包拒絕，
+目錄中經由 symlink 指到目錄外的檔案也一樣拒絕。
+"""
+
+import configparser
+import os
+import posixpath
+import re
+import tarfile
+import zipfile
+import xml.etree.ElementTree as ET
+
+# Wazuh 建議自訂規則使用的 id 範圍
+CUSTOM_RULE_ID_RANGE = (100000, 120000)
+MAX_LEVEL = 16
+LOG_KEY = re.compile(r"^log\s+(\d+)\s+(pass|fail)$")
+
+
+def _wanted(name):
+    return name.lower().endswith((".xml", ".ini")) and not os.path.basename(name).startswith(".")
+
+
+def _member_name(name):
+    """壓縮檔成員 -> 正規化的相對路徑；會跳出解壓目錄的名稱拋出 ValueError"""
+    normalized = posixpath.normpath(name.replace("\\", "/"))
+    if normalized.startswith("/") or normalized.split("/")[0] == ".." or re.match(r"^[a-zA-Z]:", normalized):
+        raise ValueError(f"壓縮檔成員 '{name}' 會跳出解壓目錄，拒絕讀取這個規則包")
+    return normalized
+
+
+def load_bundle(path):
+    """讀取目錄或壓縮檔內的規則 (*.xml) 與測試 (*.ini)，回傳 (rules, tests)，皆為 {檔名: 內容}"""
+    files = {}
+    if os.path.isdir(path):
+        top = os.path.realpath(path)
+        for root, _, names in os.walk(path):
+            for name in sorted(names):
+                full = os.path.join(root, name)
+                if _wanted(name):
+                    if os.path.commonpath([top, os.path.realpath(full)]) != top:
+                        raise ValueError(f"'{os.path.relpath(full, path)}' 是指到規則包以外的 symlink，拒絕讀取")
+                    with open(full, encoding="utf-8") as f:
+                        files[os.path.relpath(full, path)] = f.read()
+    elif zipfile.is_zipfile(path):
+        with zipfile.ZipFile(path) as archive:
+            for name in archive.namelist():
+                member = _member_name(name)
+                if _wanted(member):
+                    files[member] = archive.read(name).decode("utf-8")
+    elif tarfile.is_tarfile(path):
+        with tarfile.open(path) as archive:
+            for member in archive.getmembers():
+                name = _member_name(member.name)
+                if member.issym():
+                    _member_name(posixpath.join(posixpath.dirname(name), member.linkname))
+                elif member.islnk():
+                    _member_name(member.linkname)
+                if member.isfile() and _wanted(name):
+                    files[name] = archive.extractfile(member).read().decode("utf-8")
+    else:
+        raise ValueError(f"'{path}' 不是目錄，也不是 zip / tar 壓縮檔")
+    rules = {name: text for name, text in files.items() if name.lower().endswith(".xml")}
+    tests = {name: text for name, text in files.items() if name.lower().endswith(".ini")}
+    if not rules:
+        raise ValueError(f"'{path}' 內沒有任何 .xml 規則檔")
+    return rules, tests
+
+
+def validate_rules(rules):
+    """檢查 XML 結構與規則屬性，回傳 (規則清單 {id: 資訊}, errors, warnings)"""
+    found, errors, warnings = {}, [], []
+    for filename, text in sorted(rules.items()):
+        try:
+            # 規則檔通常有多個頂層 <group>，不是單一根節點的 XML，包一層再解析
+            root = ET.fromstring(f"<mcp_root>{text}</mcp_root>")
+        except ET.ParseError as e:
+            errors.append({"file": filename, "error": f"XML 格式錯誤: {e}"})
+            continue
+        for rule in root.iter("rule"):
+            rule_id, level = rule.get("id"), rule.get("level")
+            where = {"file": filename, "rule": rule_id}
+            if not rule_id or not rule_id.isdigit():
+                errors.append({**where, "error": "缺少 id 或 id 不是數字"})
+                continue
+            if level is None or not level.isdigit() or int(level) > MAX_LEVEL:
+                errors.append({**where, "error": f"level 必須是 0-{MAX_LEVEL} 的整數"})
+            if rule_id in found:
+                errors.append({**where, "error": f"id 與 {found[rule_id]['file']} 重複"})
+                continue
+            if not CUSTOM_RULE_ID_RANGE[0] <= int(rule_id) <= CUSTOM_RULE_ID_RANGE[1]:
+                warnings.append({**where, "warning": "id 不在自訂規則建議範圍 100000-120000，可能與內建規則衝突"})
+            if rule.find("description") is None:
+                warnings.append({**where, "warning": "缺少 <description>"})
+            found[rule_id] = {
+                "file": filename,
+                "level": int(level) if level and level.isdigit() else None,
+                "description": (rule.findtext("description") or "").strip(),
+            }
+    return found, errors, warnings
+
+
+def parse_tests(filename, text):
+    """解析 Wazuh ruleset 格式的測試檔，回傳測試案例清單 (每行日誌一筆)"""
+    parser = configparser.ConfigParser(interpolation=None, delimiters=("=",))
+    try:
+        parser.read_string(text, source=filename)
+    except configparser.Error as e:
+        raise ValueError(f"{filename}: 測試檔格式錯誤: {e}") from e
+    cases = []
+    for section in parser.sections():
+        options = parser[section]
+        logs = sorted(
+            (int(m.group(1)), m.group(2), value)
+            for key, value in options.items() if (m := LOG_KEY.match(key))
+        )
+        for number, expect, log in logs:
+            cases.append({
+                "file": filename,
+                "name": section,
+                "log": number,
+                "event": log,
+                "expect": expect,
+                "rule": options.get("rule"),
+                "alert": options.get("alert"),
+                "decoder": options.get("decoder"),
+                "log_format": options.get("log_format", "syslog"),
+                "location": options.get("location", "mcp-rule-review"),
+            })
+    return cases
+
+
+def evaluate(case, output):
+    """比對 logtest 的輸出與測試預期，回傳 (是否通過, 失敗原因清單)"""
+    rule = output.get("rule", {})
+    fired = str(rule.get("id")) if rule.get("id") is not None else None
+    if case["expect"] == "fail":
+        if case["rule"] and fired == case["rule"]:
+            return False, [f"不應觸發規則 {case['rule']}"]
+        return True, []
+    problems = []
+    if case["rule"] and fired != case["rule"]:
+        problems.append(f"預期觸發規則 {case['rule']}，實際為 {fired or '無'}")
+    if case["alert"] and str(rule.get("level")) != case["alert"]:
+        problems.append(f"預期等級 {case['alert']}，實際為 {rule.get('level')}")
+    decoder = output.get("decoder", {}).get("name")
+    if case["decoder"] and decoder != case["decoder"]:
+        problems.append(f"預期 decoder {case['decoder']}，實際為 {decoder or '無'}")
+    return not problems, problems
+
+
+def coverage(rule_ids, results):
+    """每條候選規則被哪些測試觸發；沒有任何測試觸發的規則列為 untested"""
+    fired = {}
+    for result in results:
+        if result.get("fired_rule") in rule_ids:
+            fired.setdefault(result["fired_rule"], []).append(f"{result['name']} (log {result['log']})")
+    return {
+        "fired": {rule_id: fired[rule_id] for rule_id in sorted(fired)},
+        "untested": sorted(set(rule_ids) - set(fired)),
+    }
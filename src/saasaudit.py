@@ -0,0 +1,164 @@
+"""雲端服務稽核日誌的獵捕: Wazuh office365 與 github 模組收進來的稽核事件。
+
+帳號被盜後攻擊者常在 SaaS 端留下痕跡，而不是在端點上:
+- office365: 建立信箱規則 (轉寄、刪除或搬走特定信件以隱藏 BEC 往來)、大量下載 SharePoint / OneDrive 檔案、
+  同意 (consent) 第三方 OAuth 應用程式存取信箱或檔案
+- github: 刪除 repository、建立 personal access token / OAuth token / SSH 或 deploy key (持續存取的憑證)
+查詢時依 actor (office365 的 UserId、github 的 actor) 聚合各類活動的次數、來源 IP 與時間範圍；
+大量下載只在某個 actor 於 download_window 內的下載數達到門檻時才標記，其他活動每一筆都值得檢視。
+"""
+
+from alert_utils import get_field, first_field
+from provenance import event_ref
+
+INTEGRATIONS = {
+    "office365": {
+        "field": "data.office365.Operation",
+        "actor": "data.office365.UserId",
+        "source_ip": "data.office365.ClientIP",
+        "target": "data.office365.ObjectId",
+        "activities": {
+            "mailbox_rule": ("建立或修改信箱規則 (轉寄、刪除、搬移信件)",
+                             ["New-InboxRule", "Set-InboxRule", "UpdateInboxRules"]),
+            "mass_download": ("SharePoint / OneDrive 檔案下載 (依 actor 與時間窗判斷是否大量)",
+                              ["FileDownloaded", "FileSyncDownloadedFull", "FileSyncDownloadedPartial"]),
+            "oauth_consent": ("同意 OAuth 應用程式的權限",
+                              ["Consent to application.", "Add OAuth2PermissionGrant.",
+                               "Add delegated permission grant.", "Add app role assignment grant to user."]),
+        },
+    },
+    "github": {
+        "field": "data.github.action",
+        "actor": "data.github.actor",
+        "source_ip": "data.github.actor_ip",
+        "target": "data.github.repo",
+        "activities": {
+            "repo_deletion": ("刪除 repository", ["repo.destroy"]),
+            "token_creation": ("建立存取憑證 (personal access token、OAuth token、SSH / deploy key)",
+                               ["personal_access_token.request_created", "personal_access_token.access_granted",
+                                "oauth_authorization.create", "oauth_access.create", "public_key.create",
+                                "deploy_key.create"]),
+        },
+    },
+}
+# 只在超過門檻時才算可疑的活動，個別事件不列在 events 中
+VOLUME_ACTIVITIES = ("mass_download",)
+SOURCE_FIELDS = ["timestamp", "agent.name", "rule.id", "rule.description", "data.integration",
+                 "data.office365.Operation", "data.office365.UserId", "data.office365.ClientIP",
+                 "data.office365.ObjectId", "data.office365.Workload", "data.office365.Parameters",
+                 "data.office365.ModifiedProperties", "data.office365.Target",
+                 "data.github.action", "data.github.actor", "data.github.actor_ip", "data.github.repo",
+                 "data.github.org", "data.github.actor_location.country_code", "data.github.programmatic_access_type",
+                 "data.github.user_agent"]
+
+
+def check_activities(integration, activities):
+    """activities 為 None 時回傳全部；有未知的活動時拋出 ValueError"""
+    known = INTEGRATIONS[integration]["activities"]
+    if not activities:
+        return list(known)
+    unknown = [a for a in activities if a not in known]
+    if unknown:
+        raise ValueError(f"未知的活動: {', '.join(unknown)} (可用: {', '.join(known)})")
+    return list(activities)
+
+
+def activity_filters(integration, activities):
+    spec = INTEGRATIONS[integration]
+    return {name: {"terms": {spec["field"]: spec["activities"][name][1]}} for name in activities}
+
+
+def audit_query(integration, activities):
+    """這個模組的事件中屬於 activities 的部分"""
+    return {"bool": {"filter": [{"term": {"data.integration": integration}}],
+                     "should": list(activity_filters(integration, activities).values()),
+                     "minimum_should_match": 1}}
+
+
+def actor_aggregations(integration, activities, limit, window):
+    """依 actor 聚合: 各活動次數、來源 IP、目標數、時間範圍，以及下載量最大的時間窗"""
+    spec = INTEGRATIONS[integration]
+    filters = activity_filters(integration, activities)
+    per_actor = {
+        "activities": {"filters": {"filters": filters}},
+        "source_ips": {"terms": {"field": spec["source_ip"], "size": 5}},
+        "targets": {"cardinality": {"field": spec["target"]}},
+        "first_seen": {"min": {"field": "timestamp"}},
+        "last_seen": {"max": {"field": "timestamp"}},
+    }
+    for name in activities:
+        if name in VOLUME_ACTIVITIES:
+            per_actor[f"{name}__windows"] = {"filter": filters[name], "aggs": {"windows": {
+                "date_histogram": {"field": "timestamp", "fixed_interval": window, "min_doc_count": 1}}}}
+    return {
+        "activities": {"filters": {"filters": filters}},
+        "actors": {"terms": {"field": spec["actor"], "size": limit}, "aggs": per_actor},
+    }
+
+
+def _peak(bucket, name):
+    windows = bucket.get(f"{name}__windows", {}).get("windows", {}).get("buckets", [])
+    top = max(windows, key=lambda w: w["doc_count"], default=None)
+    return {"count": top["doc_count"], "window_start": top.get("key_as_string", top["key"])} if top else None
+
+
+def summarize_actors(integration, aggregations, activities, threshold):
+    """聚合結果 -> 每個 actor 的活動統計與標記；有標記的排在前面"""
+    rows = []
+    for bucket in aggregations.get("actors", {}).get("buckets", []):
+        counts = {name: b["doc_count"] for name, b in bucket["activities"]["buckets"].items() if b["doc_count"]}
+        flags = [name for name in counts if name not in VOLUME_ACTIVITIES]
+        row = {
+            "actor": bucket["key"],
+            "events": bucket["doc_count"],
+            "activities": counts,
+            "source_ips": [b["key"] for b in bucket.get("source_ips", {}).get("buckets", [])],
+            "distinct_targets": bucket.get("targets", {}).get("value"),
+            "first_seen": bucket.get("first_seen", {}).get("value_as_string"),
+            "last_seen": bucket.get("last_seen", {}).get("value_as_string"),
+        }
+        for name in activities:
+            if name in VOLUME_ACTIVITIES and (peak := _peak(bucket, name)):
+                row[f"{name}_peak"] = peak
+                if peak["count"] >= threshold:
+                    flags.append(name)
+        row["flags"] = flags
+        rows.append(row)
+    rows.sort(key=lambda r: (len(r["flags"]), r["events"]), reverse=True)
+    totals = {name: b["doc_count"] for name, b in aggregations.get("activities", {}).get("buckets", {}).items()}
+    return totals, rows
+
+
+def activity_of(integration, alert):
+    spec = INTEGRATIONS[integration]
+    value = get_field(alert, spec["field"])
+    return next((name for name, (_, values) in spec["activities"].items() if value in values), None)
+
+
+def audit_event(integration, hit):
+    """單筆稽核事件的重點欄位"""
+    alert = hit.get("_source", {})
+    event = {"timestamp": alert.get("timestamp"), "activity": activity_of(integration, alert)}
+    if integration == "office365":
+        event.update({
+            "operation": get_field(alert, "data.office365.Operation"),
+            "actor": get_field(alert, "data.office365.UserId"),
+            "source_ip": get_field(alert, "data.office365.ClientIP"),
+            "workload": get_field(alert, "data.office365.Workload"),
+            "target": first_field(alert, "data.office365.ObjectId", "data.office365.Target"),
+            # 信箱規則的條件與動作 (ForwardTo、DeleteMessage、MoveToFolder…) 或同意授權的應用程式與權限
+            "details": first_field(alert, "data.office365.Parameters", "data.office365.ModifiedProperties"),
+        })
+    else:
+        event.update({
+            "action": get_field(alert, "data.github.action"),
+            "actor": get_field(alert, "data.github.actor"),
+            "source_ip": get_field(alert, "data.github.actor_ip"),
+            "country": get_field(alert, "data.github.actor_location.country_code"),
+            "org": get_field(alert, "data.github.org"),
+            "target": get_field(alert, "data.github.repo"),
+            "access": get_field(alert, "data.github.programmatic_access_type"),
+        })
+    event["rule"] = get_field(alert, "rule.description")
+    event["_ref"] = event_ref(hit)
+    return event
@@ -0,0 +1,63 @@
+"""租戶隔離: principal -> Wazuh agent group 的範圍對照。
+
+設定 MCP_PRINCIPAL_SCOPES 後，每個查詢都會在查詢層自動加上 agent.id 篩選，
+不依賴 LLM 自己記得加條件。格式:
+    {"tenant-a": ["customer-a"], "soc-lead": ["*"]}
+- "*" 代表可以看到全部主機
+- 對照表中沒有列出的 principal 一律看不到任何主機 (fail closed)，本機 stdio 的 "local" 除外
+- 沒有帶身分的 HTTP 呼叫者 ("anonymous"、"ip:<位址>") 視為沒有列出；保留的名稱不能出現在對照表中
+"""
+
+import json
+
+# 伺服器自己指定的身分: local 只給 stdio 的本機使用者 (不受租戶範圍限制)，anonymous 是沒有帶身分的 HTTP 請求；
+# client 帶入的身分不能使用這些名稱
+LOCAL_PRINCIPAL = "local"
+ANONYMOUS_PRINCIPAL = "anonymous"
+RESERVED_PRINCIPALS = (LOCAL_PRINCIPAL, ANONYMOUS_PRINCIPAL)
+
+
+def parse_scopes(text):
+    scopes = json.loads(text) if text else {}
+    if not isinstance(scopes, dict) or not all(isinstance(v, list) for v in scopes.values()):
+        raise ValueError("MCP_PRINCIPAL_SCOPES 必須是 {principal: [agent group, ...]} 格式的 JSON")
+    reserved = [p for p in scopes if p.lower() in RESERVED_PRINCIPALS or p.startswith("ip:")]
+    if reserved:
+        raise ValueError(f"MCP_PRINCIPAL_SCOPES 不能設定保留的身分: {', '.join(reserved)}")
+    return scopes
+
+
+class ScopeResolver:
+    def __init__(self, scopes, fetch_group_agents):
+        """fetch_group_agents(group) -> agent id 集合 (查詢失敗時拋出例外；快取由 GroupMembership 負責)"""
+        self.scopes = scopes
+        self.fetch_group_agents = fetch_group_agents
+
+    @property
+    def enabled(self):
+        return bool(self.scopes)
+
+    def groups_for(self, principal):
+        if principal == LOCAL_PRINCIPAL or not self.enabled:
+            return None
+        # 保留的身分 (anonymous、ip:<位址>) 不會出現在對照表中，一律看不到任何主機
+        return self.scopes.get(principal, [])
+
+    def allowed_agents(self, principal):
+        """回傳可存取的 agent id 集合；None 代表不限制"""
+        groups = self.groups_for(principal)
+        if groups is None or "*" in groups:
+            return None
+        allowed = set()
+        for group in groups:
+            allowed.update(self.fetch_group_agents(group))
+        return allowed
+
+    def scope_query(self, query, principal):
+        """在 Indexer 查詢外層加上 agent.id 限制"""
+        allowed = self.allowed_agents(principal)
+        if allowed is None:
+            return query
+        if not allowed:
+            return {"match_none": {}}
+        return {"bool": {"filter": [query, {"terms": {"agent.id": sorted(allowed)}}]}}
@@ -0,0 +1,83 @@
+"""產生監控 MCP Server 本身的 Wazuh decoder 與規則 (python src/main.py rules)。
+
+對應 audit.py 的稽核事件與 syslogsink.py 的 JSON log 格式，部署後工具被濫用
+(例如同一個 principal 反覆嘗試被拒絕的寫入操作) 會直接在 Wazuh 裡產生告警。
+規則 ID 從 base_id 起連續配置 (預設 100900)，請選一段不會和既有自訂規則衝突的範圍。
+"""
+
+from xml.sax.saxutils import escape
+
+DEFAULT_BASE_ID = 100900
+GROUP = "wazuh_mcp"
+
+# (相對 ID, 等級, 父規則相對 ID, 條件, 描述, 額外群組)
+# 條件為 [(欄位, regex)]，全部符合才觸發
+RULES = [
+    (0, 0, None, [("logger", "^wazuh_mcp")], "Wazuh MCP Server: 事件", []),
+    (1, 3, 0, [("mcp.event", "^tool_call$"), ("mcp.outcome", "^ok$")],
+     "Wazuh MCP Server: $(mcp.principal) 呼叫工具 $(mcp.tool)", ["audit"]),
+    (2, 5, 0, [("mcp.event", "^tool_call$"), ("mcp.outcome", "^error$")],
+     "Wazuh MCP Server: 工具 $(mcp.tool) 執行失敗: $(mcp.reason)", []),
+    (3, 8, 0, [("mcp.event", "^tool_call$"), ("mcp.outcome", "^denied$")],
+     "Wazuh MCP Server: $(mcp.principal) 呼叫 $(mcp.tool) 被拒絕: $(mcp.reason)", ["audit", "access_denied"]),
+    (4, 10, 3, [("mcp.write", "^true$")],
+     "Wazuh MCP Server: $(mcp.principal) 嘗試被拒絕的寫入操作 $(mcp.tool)", ["audit", "access_denied"]),
+    (5, 8, 1, [("mcp.write", "^true$")],
+     "Wazuh MCP Server: $(mcp.principal) 執行寫入操作 $(mcp.tool)", ["audit"]),
+    (6, 5, 0, [("level", "^warning$")], "Wazuh MCP Server 警告: $(message)", []),
+    (7, 8, 0, [("level", "^error$|^critical$")], "Wazuh MCP Server 錯誤: $(message)", []),
+    (8, 10, 6, [("message", "degraded")], "Wazuh MCP Server 與 Wazuh 後端的連線中斷", []),
+]
+
+# 重複事件: (相對 ID, 等級, 比對的規則相對 ID, 次數, 秒數, 是否限同一 principal, 描述)
+FREQUENCY_RULES = [
+    (10, 12, 3, 5, 300, True, "Wazuh MCP Server: $(mcp.principal) 5 分鐘內多次被拒絕 (可能在試探權限)"),
+    (11, 13, 4, 3, 600, True, "Wazuh MCP Server: $(mcp.principal) 10 分鐘內多次嘗試被拒絕的寫入操作"),
+    (12, 10, 2, 20, 300, False, "Wazuh MCP Server: 5 分鐘內大量工具執行失敗 (後端異常或濫用)"),
+]
+
+
+def decoders_xml():
+    """syslog 送來的 "wazuh-mcp: {JSON}" 以 JSON decoder 解析；檔案收集 (log_format json) 不需要 decoder"""
+    return "\n".join([
+        "<!-- Wazuh MCP Server 的 decoder: 複製到 /var/ossec/etc/decoders/ -->",
+        '<decoder name="wazuh-mcp">',
+        "  <program_name>^wazuh-mcp$</program_name>",
+        "  <plugin_decoder>JSON_Decoder</plugin_decoder>",
+        "</decoder>",
+        "",
+    ])
+
+
+def rules_xml(base_id=DEFAULT_BASE_ID):
+    if not 100000 <= base_id <= 120000 - 20:
+        raise ValueError("自訂規則 ID 必須落在 100000-120000 之間")
+    lines = [
+        "<!--",
+        "  Wazuh MCP Server 自身的監控規則 (由 python src/main.py rules 產生)。",
+        "  複製到 /var/ossec/etc/rules/ 後重新啟動 manager；log 來源擇一:",
+        "    - MCP_LOG_SYSLOG=wazuh (或 udp://manager:514)，並在 ossec.conf 開啟 syslog 接收",
+        "    - MCP_AUDIT_LOG=/var/log/wazuh-mcp/audit.log，並在 agent 加上",
+        "      <localfile><log_format>json</log_format><location>/var/log/wazuh-mcp/audit.log</location></localfile>",
+        "-->",
+        f'<group name="{GROUP},">',
+        "",
+    ]
+    for rel, level, parent, fields, description, groups in RULES:
+        lines.append(f'  <rule id="{base_id + rel}" level="{level}">')
+        if parent is not None:
+            lines.append(f"    <if_sid>{base_id + parent}</if_sid>")
+        lines.extend(f'    <field name="{name}">{escape(regex)}</field>' for name, regex in fields)
+        lines.append(f"    <description>{escape(description)}</description>")
+        if groups:
+            lines.append(f"    <group>{','.join(groups)},</group>")
+        lines.extend(["  </rule>", ""])
+    for rel, level, matched, frequency, timeframe, same_principal, description in FREQUENCY_RULES:
+        lines.append(f'  <rule id="{base_id + rel}" level="{level}" frequency="{frequency}" timeframe="{timeframe}">')
+        lines.append(f"    <if_matched_sid>{base_id + matched}</if_matched_sid>")
+        if same_principal:
+            lines.append("    <same_field>mcp.principal</same_field>")
+        lines.append(f"    <description>{escape(description)}</description>")
+        lines.extend(["    <group>audit,</group>", "  </rule>", ""])
+    lines.extend(["</group>", ""])
+    return "\n".join(lines)
@@ -0,0 +1,59 @@
+"""EQL 風格的事件序列比對 (client-side join)。
+
+Wazuh Indexer (OpenSearch) 沒有 EQL，因此每個步驟各自查詢，
+再於本地依「同一台 Agent + 相同關聯欄位」串起依序發生、且總時間不超過 maxspan 的事件。
+"""
+
+from alert_utils import get_field, parse_timestamp
+
+
+def _join_key(alert, join_by):
+    value = get_field(alert, join_by)
+    if value is None:
+        return None
+    return (get_field(alert, "agent.id"), str(value))
+
+
+def match_sequences(step_events, join_by, maxspan, limit):
+    """step_events: 每個步驟的告警清單 (依時間遞增)；回傳符合順序的事件串列"""
+    # 後續步驟先依關聯鍵分組，方便快速查找
+    indexed = []
+    for events in step_events[1:]:
+        groups = {}
+        for alert in events:
+            key = _join_key(alert, join_by)
+            ts = parse_timestamp(alert.get("timestamp"))
+            if key is not None and ts is not None:
+                groups.setdefault(key, []).append((ts, alert))
+        indexed.append(groups)
+
+    sequences = []
+    for first in step_events[0]:
+        key = _join_key(first, join_by)
+        start = parse_timestamp(first.get("timestamp"))
+        if key is None or start is None:
+            continue
+
+        chain = [first]
+        previous = start
+        for groups in indexed:
+            nxt = next(
+                (alert for ts, alert in groups.get(key, [])
+                 if ts >= previous and ts - start <= maxspan and alert not in chain),
+                None
+            )
+            if nxt is None:
+                chain = None
+                break
+            chain.append(nxt)
+            previous = parse_timestamp(nxt.get("timestamp"))
+
+        if chain:
+            sequences.append({
+                "join_key": {"agent.id": key[0], join_by: key[1]},
+                "span_seconds": (previous - start).total_seconds(),
+                "events": chain
+            })
+            if len(sequences) >= limit:
+                break
+    return sequences
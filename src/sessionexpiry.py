@@ -0,0 +1,204 @@
+"""Streamable HTTP session 的存活期限與閒置逐出 (MCP_SESSION_IDLE_TIMEOUT / MCP_SESSION_MAX_LIFETIME)。
+
+MCP SDK 的 session 只在 client 送 DELETE 時結束，client 直接離開 (關掉視窗、當機) 的 session
+連同 transport、對話紀錄、SSE 事件與訂閱會一直留在記憶體。啟用後:
+- 超過閒置時間沒有任何請求 (開著 GET stream 的 session 不算閒置)，或建立超過存活期限的 session 視為過期
+- 背景清理工作定期結束過期的 session 並呼叫 on_end 釋放相關狀態；client 送 DELETE 結束的 session 同樣釋放
+- 之後帶著過期 Mcp-Session-Id 的請求回 404 與 JSON-RPC 錯誤，說明原因並提示重新 initialize
+  (依 MCP 規範，client 收到 404 就應建立新的 session)
+"""
+
+import json
+import logging
+import time
+from collections import OrderedDict
+
+import anyio
+from fastmcp.server.middleware import Middleware
+
+from resumption import SESSION_HEADER, find_session_manager, request_session_id
+
+logger = logging.getLogger("wazuh_mcp")
+
+# 記得最近過期的 session 數量，超過的過期 session 再被使用時只會收到 SDK 一般的 404
+MAX_EXPIRED = 10000
+MAX_SWEEP_INTERVAL = 30
+# 過期 session 的 JSON-RPC 錯誤碼 (伺服器自訂範圍)
+SESSION_EXPIRED = -32001
+
+
+def span(seconds):
+    """秒數 -> 30s / 15m / 1h / 1d 的簡短寫法"""
+    for unit, size in (("d", 86400), ("h", 3600), ("m", 60)):
+        if seconds >= size and seconds % size == 0:
+            return f"{int(seconds // size)}{unit}"
+    return f"{seconds:g}s"
+
+
+class SessionLifetimes:
+    def __init__(self, idle_timeout=0, max_lifetime=0, on_end=None):
+        """時間以秒計 (0 = 不限制)；on_end(session_id, session) 在 session 過期或被刪除時釋放相關狀態"""
+        self.idle_timeout = idle_timeout
+        self.max_lifetime = max_lifetime
+        self.on_end = on_end
+        self.active = {}  # session id -> {"created", "last", "open" (進行中的請求數), "session"}
+        self.expired = OrderedDict()  # session id -> 過期原因
+
+    @property
+    def enabled(self):
+        return bool(self.idle_timeout or self.max_lifetime)
+
+    def sweep_interval(self):
+        return max(1, min([t for t in (self.idle_timeout, self.max_lifetime) if t] + [MAX_SWEEP_INTERVAL * 4]) / 4)
+
+    def _track(self, session_id):
+        now = time.monotonic()
+        return self.active.setdefault(session_id, {"created": now, "last": now, "open": 0, "session": None})
+
+    def attach(self, session_id, session):
+        """記下 session id 對應的 ServerSession (訂閱、log 轉送以 ServerSession 為 key)"""
+        self._track(session_id)["session"] = session
+
+    def reason(self, session_id, now=None):
+        """session 過期的原因；未過期時為 None"""
+        if session_id in self.expired:
+            return self.expired[session_id]
+        tracked = self.active.get(session_id)
+        if tracked is None:
+            return None
+        now = now or time.monotonic()
+        if self.max_lifetime and now - tracked["created"] > self.max_lifetime:
+            return f"session exceeded its maximum lifetime of {span(self.max_lifetime)}"
+        if self.idle_timeout and not tracked["open"] and now - tracked["last"] > self.idle_timeout:
+            return f"session was idle for more than {span(self.idle_timeout)}"
+        return None
+
+    def begin(self, session_id):
+        tracked = self._track(session_id)
+        tracked["open"] += 1
+        tracked["last"] = time.monotonic()
+
+    def finish(self, session_id):
+        tracked = self.active.get(session_id)
+        if tracked is not None:
+            tracked["open"] = max(0, tracked["open"] - 1)
+            tracked["last"] = time.monotonic()
+
+    def untrack(self, session_id):
+        self.active.pop(session_id, None)
+
+    def due(self):
+        now = time.monotonic()
+        return [(session_id, reason) for session_id in list(self.active)
+                if (reason := self.reason(session_id, now)) is not None]
+
+    def end(self, session_id, reason=None):
+        """session 結束: 停止追蹤並釋放狀態；reason 不是 None 時 (過期) 記住原因"""
+        tracked = self.active.pop(session_id, None)
+        if reason is not None:
+            self.expired[session_id] = reason
+            while len(self.expired) > MAX_EXPIRED:
+                self.expired.popitem(last=False)
+        if self.on_end:
+            try:
+                self.on_end(session_id, tracked["session"] if tracked else None)
+            except Exception:
+                logger.exception("釋放 session %s 的狀態時發生錯誤", session_id)
+
+    def stats(self):
+        return {"idle_timeout": span(self.idle_timeout) if self.idle_timeout else None,
+                "max_lifetime": span(self.max_lifetime) if self.max_lifetime else None,
+                "tracked": len(self.active), "recently_expired": len(self.expired)}
+
+
+class SessionTracking(Middleware):
+    """記下發出請求的 session 物件，過期時用來移除訂閱與 log 轉送"""
+
+    def __init__(self, lifetimes):
+        self.lifetimes = lifetimes
+
+    async def on_request(self, context, call_next):
+        ctx = context.fastmcp_context
+        session_id = request_session_id()
+        if session_id and ctx is not None and session_id in self.lifetimes.active:
+            try:
+                self.lifetimes.attach(session_id, ctx.session)
+            except (AttributeError, RuntimeError):
+                pass
+        return await call_next(context)
+
+
+class ExpireSessions:
+    """starlette middleware (放在 FastMCP 的 HTTP app 內): 追蹤 session 活動、拒絕過期 session 的請求，
+    第一個請求時啟動背景清理"""
+
+    def __init__(self, app, lifetimes):
+        self.app = app
+        self.lifetimes = lifetimes
+        self.manager = None
+
+    async def terminate(self, session_id, reason):
+        transport = self.manager._server_instances.pop(session_id, None) if self.manager else None
+        if transport is not None:
+            await transport.terminate()
+        self.lifetimes.end(session_id, reason)
+        logger.info("MCP session 已過期並結束: %s", reason)
+
+    async def sweep(self):
+        for session_id, reason in self.lifetimes.due():
+            await self.terminate(session_id, reason)
+
+    async def _sweep_forever(self):
+        while True:
+            await anyio.sleep(self.lifetimes.sweep_interval())
+            try:
+                await self.sweep()
+            except Exception:
+                logger.exception("清理過期 MCP session 時發生錯誤")
+
+    async def _reject(self, send, reason):
+        body = json.dumps({"jsonrpc": "2.0", "id": None, "error": {
+            "code": SESSION_EXPIRED,
+            "message": f"Session expired: {reason}. Start a new session by sending initialize without Mcp-Session-Id.",
+        }}).encode()
+        await send({"type": "http.response.start", "status": 404,
+                    "headers": [(b"content-type", b"application/json"),
+                                (b"content-length", str(len(body)).encode())]})
+        await send({"type": "http.response.body", "body": body})
+
+    async def __call__(self, scope, receive, send):
+        if scope["type"] != "http":
+            return await self.app(scope, receive, send)
+        if self.manager is None:
+            self.manager = find_session_manager(scope["app"])
+            # 清理工作跟著 session manager 的 task group (app 的 lifespan) 執行，app 停止時一起結束
+            self.manager._task_group.start_soon(self._sweep_forever)
+        session_id = next((value.decode("latin-1") for name, value in scope["headers"]
+                           if name.lower() == SESSION_HEADER.encode()), None)
+        if session_id:
+            reason = self.lifetimes.reason(session_id)
+            if reason is not None:
+                if session_id in self.lifetimes.active:
+                    await self.terminate(session_id, reason)
+                return await self._reject(send, reason)
+            self.lifetimes.begin(session_id)
+
+        async def track_new_session(message):
+            if message["type"] == "http.response.start" and not session_id:
+                created = next((value.decode("latin-1") for name, value in message.get("headers", [])
+                                if name.lower() == SESSION_HEADER.encode()), None)
+                if created:
+                    self.lifetimes.begin(created)
+                    self.lifetimes.finish(created)
+            await send(message)
+
+        try:
+            await self.app(scope, receive, track_new_session)
+        finally:
+            if session_id:
+                self.lifetimes.finish(session_id)
+                if scope["method"] == "DELETE":
+                    self.lifetimes.end(session_id)
+                elif session_id not in self.manager._server_instances:
+                    # 不存在的 session (SDK 已回 404)，不追蹤也不動任何狀態
+                    self.lifetimes.untrack(session_id)
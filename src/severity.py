@@ -0,0 +1,67 @@
+"""Wazuh 規則等級 (0~15) 與標準化嚴重度 (info/low/medium/high/critical) 的對照。
+
+對照表可用環境變數 WAZUH_SEVERITY_MAP 覆寫，格式為 "名稱=起-迄" 並以逗號分隔，
+例如: info=0-2,low=3-6,medium=7-11,high=12-14,critical=15
+"""
+
+SEVERITY_ORDER = ("info", "low", "medium", "high", "critical")
+
+DEFAULT_SEVERITY_MAP = "info=0-2,low=3-6,medium=7-11,high=12-14,critical=15"
+
+
+def parse_severity_map(text):
+    """解析對照表字串，回傳依等級排序的 [(名稱, 最低等級, 最高等級)]"""
+    ranges = []
+    for item in (text or "").split(","):
+        item = item.strip()
+        if not item:
+            continue
+        name, _, span = item.partition("=")
+        name = name.strip().lower()
+        if name not in SEVERITY_ORDER:
+            raise ValueError(f"未知的嚴重度 '{name}'，可用: {', '.join(SEVERITY_ORDER)}")
+        low, _, high = span.partition("-")
+        try:
+            low = int(low)
+            high = int(high) if high else low
+        except ValueError:
+            raise ValueError(f"嚴重度 '{name}' 的等級範圍 '{span}' 格式錯誤")
+        ranges.append((name, low, high))
+    if not ranges:
+        raise ValueError("嚴重度對照表是空的")
+    ranges.sort(key=lambda r: r[1])
+    return ranges
+
+
+class SeverityMapper:
+    def __init__(self, text=DEFAULT_SEVERITY_MAP):
+        self.ranges = parse_severity_map(text)
+
+    def severity(self, level):
+        """規則等級 -> 嚴重度名稱"""
+        try:
+            level = int(level)
+        except (TypeError, ValueError):
+            return None
+        for name, low, high in self.ranges:
+            if low <= level <= high:
+                return name
+        return None
+
+    def min_level(self, severity):
+        """min_severity 對應的最低規則等級，用於組出 rule.level 範圍查詢"""
+        severity = severity.strip().lower()
+        if severity not in SEVERITY_ORDER:
+            raise ValueError(f"未知的嚴重度 '{severity}'，可用: {', '.join(SEVERITY_ORDER)}")
+        rank = SEVERITY_ORDER.index(severity)
+        levels = [low for name, low, _ in self.ranges if SEVERITY_ORDER.index(name) >= rank]
+        if not levels:
+            raise ValueError(f"對照表中沒有 '{severity}' 以上的嚴重度")
+        return min(levels)
+
+    def annotate(self, alert):
+        """在告警的 rule 區塊加上 severity 欄位，讓輸出不再只有數字等級"""
+        rule = alert.get("rule")
+        if isinstance(rule, dict) and "level" in rule:
+            rule["severity"] = self.severity(rule["level"])
+        return alert
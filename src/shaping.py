@@ -0,0 +1,126 @@
+"""告警結果的壓縮整理 (去重 / 分組)，把大量重複告警收斂成帶計數的代表列。"""
+
+from alert_utils import get_field
+from logtokens import pattern
+from sparkline import sparkline
+
+# 去重時一定會納入的欄位: 同一條規則、同一台主機
+DEDUPE_BASE_FIELDS = ["rule.id", "agent.id"]
+# 欄位名稱加上這個後綴時，比較的是正規化後的訊息模式 (例如 "full_log:pattern")，而不是原始內容
+PATTERN_SUFFIX = ":pattern"
+
+
+def _dedupe_key(alert, field):
+    if field.endswith(PATTERN_SUFFIX):
+        return pattern(get_field(alert, field[:-len(PATTERN_SUFFIX)]))
+    return str(get_field(alert, field))
+
+
+def dedupe_alerts(alerts, dedupe_by):
+    """在已取回的告警中，將 rule + agent + dedupe_by 欄位都相同的告警合併成一列。
+    代表列保留第一次出現的告警 (查詢依時間遞減排序，所以是最新的一筆)，
+    並加上 _count / _first_seen / _last_seen；以訊息模式去重時另加 _patterns。
+    """
+    fields = DEDUPE_BASE_FIELDS + [f for f in dedupe_by if f not in DEDUPE_BASE_FIELDS]
+    pattern_fields = [f for f in fields if f.endswith(PATTERN_SUFFIX)]
+    rows = {}
+    for alert in alerts:
+        key = tuple(_dedupe_key(alert, f) for f in fields)
+        ts = alert.get("timestamp")
+        row = rows.get(key)
+        if row is None:
+            rows[key] = {**alert, "_count": 1, "_first_seen": ts, "_last_seen": ts}
+            if pattern_fields:
+                rows[key]["_patterns"] = {f[:-len(PATTERN_SUFFIX)]: k for f, k in zip(fields, key)
+                                          if f in pattern_fields}
+            continue
+        row["_count"] += 1
+        if ts and (row["_first_seen"] is None or ts < row["_first_seen"]):
+            row["_first_seen"] = ts
+        if ts and (row["_last_seen"] is None or ts > row["_last_seen"]):
+            row["_last_seen"] = ts
+    return list(rows.values())
+
+
+def group_aggregation(group_by, size):
+    """產生 composite aggregation：在 Indexer 端對所有符合的告警分組計數 (不受 limit 影響)"""
+    return {
+        "groups": {
+            "composite": {
+                "size": size,
+                "sources": [{f: {"terms": {"field": f, "missing_bucket": True}}} for f in group_by]
+            },
+            "aggs": {
+                "first_seen": {"min": {"field": "timestamp"}},
+                "last_seen": {"max": {"field": "timestamp"}},
+                "sample": {"top_hits": {"size": 1, "sort": [{"timestamp": {"order": "desc"}}]}}
+            }
+        }
+    }
+
+
+def parse_groups(result, trend_interval=None):
+    """將 composite aggregation 的結果轉成依數量排序的分組列表；有 trend 子聚合時附上 sparkline"""
+    buckets = result.get("aggregations", {}).get("groups", {}).get("buckets", [])
+    groups = []
+    for b in buckets:
+        sample_hits = b.get("sample", {}).get("hits", {}).get("hits", [])
+        groups.append({
+            "key": b.get("key"),
+            "count": b.get("doc_count", 0),
+            "first_seen": b.get("first_seen", {}).get("value_as_string"),
+            "last_seen": b.get("last_seen", {}).get("value_as_string"),
+            "sample": sample_hits[0].get("_source") if sample_hits else None
+        })
+        if "trend" in b:
+            groups[-1]["sparkline"] = sparkline(b["trend"].get("buckets", []), trend_interval)
+    groups.sort(key=lambda g: g["count"], reverse=True)
+    return groups
+
+
+# --- 抽樣 (Sampling) ---
+
+SAMPLE_METHODS = ("first", "last", "random", "stratified:agent", "stratified:rule")
+_STRATA_FIELDS = {"stratified:agent": "agent.id", "stratified:rule": "rule.id"}
+MAX_STRATA = 50
+
+
+def apply_sampling(body, method, limit, seed):
+    """依抽樣方式調整查詢。回傳修改後的 body；分層抽樣改用 terms + top_hits 聚合"""
+    if method not in SAMPLE_METHODS:
+        raise ValueError(f"不支援的抽樣方式 '{method}'，可用: {', '.join(SAMPLE_METHODS)}")
+    if method == "first":
+        body["sort"] = [{"timestamp": {"order": "asc"}}]
+        return body
+    if method == "last":
+        return body
+
+    # random 與分層抽樣都以固定 seed 的 random_score 打亂，方便重現同一份樣本
+    body["query"] = {"function_score": {
+        "query": body["query"],
+        "random_score": {"seed": seed, "field": "_seq_no"},
+        "boost_mode": "replace"
+    }}
+    body["sort"] = ["_score"]
+    if method == "random":
+        return body
+
+    body["size"] = 0
+    body["aggs"] = {"strata": {
+        "terms": {"field": _STRATA_FIELDS[method], "size": MAX_STRATA},
+        "aggs": {"sample": {"top_hits": {"size": min(limit, 100), "sort": ["_score"]}}}
+    }}
+    return body
+
+
+def collect_stratified(result, limit, total):
+    """依各層在母體中的比例分配名額 (每層至少 1 筆)，回傳 (樣本, 各層統計)"""
+    buckets = result.get("aggregations", {}).get("strata", {}).get("buckets", [])
+    samples, strata = [], []
+    for b in buckets:
+        count = b.get("doc_count", 0)
+        quota = max(1, round(limit * count / total)) if total else 0
+        hits = b.get("sample", {}).get("hits", {}).get("hits", [])[:quota]
+        samples.extend(hit.get("_source", {}) for hit in hits)
+        strata.append({"key": b.get("key"), "population": count, "sampled": len(hits)})
+    return samples[:limit], strata
@@ -0,0 +1,47 @@
+"""聚合結果的小型時間序列 (sparkline)，讓 LLM 不必再查一次就能描述趨勢 (例如「03:00 突然暴增」)。
+
+每個分組附上固定點數的 date_histogram 計數陣列、峰值時段與一行文字圖 (▁▂▃▄▅▆▇█)。
+"""
+
+import math
+
+from splitting import parse_time_bound
+
+SPARK_POINTS = 24
+BARS = "▁▂▃▄▅▆▇█"
+
+
+def interval_for(time_from, time_to="now", points=SPARK_POINTS):
+    """依時間範圍挑選間隔，讓序列大約 points 個點 (最小 1 分鐘)"""
+    span = (parse_time_bound(time_to) - parse_time_bound(time_from)).total_seconds()
+    if span <= 0:
+        raise ValueError("sparkline 的時間範圍起點必須早於終點")
+    minutes = max(1, math.ceil(span / points / 60))
+    if minutes % 1440 == 0:
+        return f"{minutes // 1440}d"
+    if minutes % 60 == 0:
+        return f"{minutes // 60}h"
+    return f"{minutes}m"
+
+
+def chart(counts):
+    peak = max(counts, default=0)
+    if not peak:
+        return BARS[0] * len(counts)
+    return "".join(BARS[min(len(BARS) - 1, round(c / peak * (len(BARS) - 1)))] for c in counts)
+
+
+def sparkline(buckets, interval):
+    """date_histogram 的 buckets -> {interval, start, counts, chart, peak}"""
+    counts = [b.get("doc_count", 0) for b in buckets]
+    if not counts:
+        return {"interval": interval, "counts": []}
+    peak = max(range(len(counts)), key=counts.__getitem__)
+    return {
+        "interval": interval,
+        "start": buckets[0].get("key_as_string"),
+        "counts": counts,
+        "chart": chart(counts),
+        "peak": {"time": buckets[peak].get("key_as_string"), "count": counts[peak]},
+    }
+
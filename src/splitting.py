@@ -0,0 +1,141 @@
+"""長時間範圍查詢的自動切割與合併。
+
+90 天這類大範圍查詢在大型叢集上容易逾時；切成每週一段平行查詢再合併，
+通常比單一查詢快很多，而且某一段失敗時其他段的結果仍然可用。
+較舊的片段上限是絕對時間，還會順便被歷史查詢快取 (querycache) 收下。
+"""
+
+import contextvars
+import re
+from concurrent.futures import ThreadPoolExecutor
+from datetime import datetime, timezone
+
+from alert_utils import parse_duration, parse_timestamp
+
+_NOW_RE = re.compile(r"^now(?:-(\d+\s*(?:ms|s|m|h|d|w)))?$")
+
+
+def parse_time_bound(expr):
+    """解析 "now"、"now-90d" 或絕對時間，回傳 UTC datetime；無法解析時拋出 ValueError"""
+    expr = (expr or "").strip()
+    m = _NOW_RE.match(expr)
+    if m:
+        now = datetime.now(timezone.utc)
+        return now - parse_duration(m.group(1)) if m.group(1) else now
+    ts = parse_timestamp(expr)
+    if ts is None:
+        raise ValueError(f"無法解析時間 '{expr}'，請使用 now-7d 或 2024-01-01T00:00:00Z 這類格式")
+    return ts if ts.tzinfo else ts.replace(tzinfo=timezone.utc)
+
+
+def slice_range(start, end, size):
+    """將 [start, end) 切成每段 size 的片段 (最新的片段在前)，回傳 [(起, 迄)] 的 ISO 字串"""
+    slices = []
+    cursor = end
+    while cursor > start:
+        lower = max(start, cursor - size)
+        slices.append((lower.isoformat(), cursor.isoformat()))
+        cursor = lower
+    return slices
+
+
+def with_time_slice(body, lower, upper):
+    sliced = dict(body)
+    sliced["query"] = {"bool": {"filter": [
+        body.get("query", {"match_all": {}}),
+        {"range": {"timestamp": {"gte": lower, "lt": upper}}}
+    ]}}
+    return sliced
+
+
+def run_slices(search, body, slices, workers, on_done=None):
+    """平行執行每個片段，回傳 [(片段, 結果, 錯誤)]，順序與 slices 相同"""
+    def task(piece):
+        result, error = search(with_time_slice(body, *piece))
+        if on_done:
+            on_done(piece, error)
+        return result, error
+
+    with ThreadPoolExecutor(max_workers=workers) as pool:
+        # 每個片段帶著目前的 context 執行，principal 等請求資訊才不會在執行緒中遺失
+        futures = [pool.submit(contextvars.copy_context().run, task, piece) for piece in slices]
+        return [(piece, *future.result()) for piece, future in zip(slices, futures)]
+
+
+def _merge_trends(current, other):
+    """同一分組的 sparkline 序列 (date_histogram) 依時間點相加"""
+    by_key = {b["key"]: dict(b) for b in current.get("buckets", [])}
+    for b in other.get("buckets", []):
+        if b["key"] in by_key:
+            by_key[b["key"]]["doc_count"] += b.get("doc_count", 0)
+        else:
+            by_key[b["key"]] = dict(b)
+    return {"buckets": [by_key[k] for k in sorted(by_key)]}
+
+
+def _merge_groups(aggregations_list):
+    merged = {}
+    after_key = None
+    for aggregations in aggregations_list:
+        groups = aggregations.get("groups", {})
+        after_key = after_key or groups.get("after_key")
+        for b in groups.get("buckets", []):
+            key = tuple(sorted(b["key"].items()))
+            current = merged.get(key)
+            if current is None:
+                merged[key] = b
+                continue
+            current["doc_count"] += b.get("doc_count", 0)
+            for name, pick in (("first_seen", min), ("last_seen", max)):
+                values = [v for v in (current[name].get("value_as_string"), b[name].get("value_as_string")) if v]
+                if values:
+                    current[name] = {"value_as_string": pick(values)}
+            if b["last_seen"].get("value_as_string") == current["last_seen"].get("value_as_string"):
+                current["sample"] = b["sample"]
+            if "trend" in b:
+                current["trend"] = _merge_trends(current.get("trend", {}), b["trend"])
+    groups = {"buckets": list(merged.values())}
+    if after_key:
+        groups["after_key"] = after_key
+    return {"groups": groups}
+
+
+def merge_results(outcomes, sort_order, size):
+    """將各片段結果合併成與單一查詢相同結構的結果，並附上每個片段的狀態"""
+    ok = [result for _, result, error in outcomes if not error]
+    hits = [hit for result in ok for hit in result.get("hits", {}).get("hits", [])]
+    hits.sort(key=lambda h: h.get("_source", {}).get("timestamp", ""), reverse=(sort_order == "desc"))
+    merged = {
+        "hits": {
+            "total": {"value": sum(r.get("hits", {}).get("total", {}).get("value", 0) for r in ok)},
+            "hits": hits[:size],
+        },
+    }
+    aggregations = [r.get("aggregations", {}) for r in ok if "aggregations" in r]
+    if any("by_environment" in a for a in aggregations):
+        # 依環境分開統計的聚合，每個環境各自合併
+        envs = {}
+        for a in aggregations:
+            for env, bucket in a["by_environment"].get("buckets", {}).items():
+                envs.setdefault(env, []).append(bucket)
+        merged["aggregations"] = {"by_environment": {"buckets": {
+            env: {"doc_count": sum(b.get("doc_count", 0) for b in parts), **_merge_groups(parts)}
+            for env, parts in envs.items()
+        }}}
+    elif aggregations:
+        merged["aggregations"] = _merge_groups(aggregations)
+
+    slices = []
+    for (lower, upper), result, error in outcomes:
+        status = "error" if error else ("incomplete" if result and "_incomplete" in result else "ok")
+        entry = {"from": lower, "to": upper, "status": status}
+        if error:
+            entry["error"] = error
+        slices.append(entry)
+    merged["_slices"] = slices
+    if any(s["status"] != "ok" for s in slices):
+        merged["_incomplete"] = {
+            "failed_slices": [s for s in slices if s["status"] != "ok"],
+            "warning": "部分時間片段查詢失敗或不完整，合併後的計數與清單可能偏低",
+        }
+    return merged
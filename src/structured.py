@@ -0,0 +1,92 @@
+"""結構化工具輸出: 每個工具都宣告 outputSchema，回應同時附上 structuredContent。
+
+工具本身仍回傳文字 (JSON 或 markdown 等)，由 StructuredOutput middleware 統一包成:
+    {"ok": true,  "data": <解析後的 JSON>}                  JSON 回應
+    {"ok": true,  "title": "【資安態勢報告】", "data": ...}   第一行是標題、其餘為 JSON 的回應
+    {"ok": true,  "text": "..."}                          markdown / 純文字回應
+    {"ok": false, "error": "錯誤: ..."}                     錯誤訊息
+TOOL_DATA_SCHEMAS 定義常用工具 data 的格式 (只列出穩定的欄位，允許額外欄位)，其餘工具 data 不限格式。
+middleware 要最後註冊 (最內層)，才會拿到其他 middleware 加上標註之前的原始回應。
+"""
+
+import json
+
+from fastmcp.server.middleware import Middleware
+from fastmcp.tools.tool import ToolResult
+
+from audit import ERROR_PREFIXES
+
+# 告警內容來自 Indexer (也可能是注入的測試事件)，葉節點只描述不限型別，避免驗證失敗
+_ALERT = {"type": "object", "properties": {
+    "timestamp": {"description": "ISO 8601 時間"},
+    "rule": {"type": "object", "properties": {
+        "id": {"description": "規則 ID"}, "level": {"description": "規則等級 0-15"},
+        "severity": {"description": "標準化嚴重度 info/low/medium/high/critical"},
+        "description": {}, "groups": {}}},
+    "agent": {"type": "object", "properties": {"id": {}, "name": {}, "ip": {}}},
+}}
+
+_AGENT = {"type": "object", "properties": {
+    "id": {"type": "string"}, "name": {"type": "string"}, "ip": {"type": "string"},
+    "status": {"type": "string"}, "version": {"type": "string"}, "lastKeepAlive": {"type": "string"},
+    "group": {"type": "array", "items": {"type": "string"}},
+}, "required": ["id", "name"]}
+
+TOOL_DATA_SCHEMAS = {
+    "list_agents": {"type": "array", "items": _AGENT},
+    "search_alerts": {"type": "object", "properties": {
+        "total": {"type": "integer"},
+        "alerts": {"type": "array", "items": _ALERT},
+        "group_by": {"type": "array", "items": {"type": "string"}},
+        "group_count": {"type": "integer"},
+        "groups": {"type": "array", "items": {"type": "object"}},
+    }},
+    "list_agent_groups": {"type": "object", "properties": {
+        "groups": {"type": "array", "items": {"type": "object", "properties": {"name": {"type": "string"}}}},
+    }, "required": ["groups"]},
+    "get_infrastructure_status": {"type": "object"},
+    "wazuh_backend_latency": {"type": "object", "properties": {
+        "window": {"type": "string"}, "backends": {"type": "object"},
+    }, "required": ["backends"]},
+}
+
+
+def output_schema(tool):
+    return {
+        "type": "object",
+        "properties": {
+            "ok": {"type": "boolean"},
+            "data": TOOL_DATA_SCHEMAS.get(tool, {}),
+            "title": {"type": "string"},
+            "text": {"type": "string"},
+            "error": {"type": "string"},
+        },
+        "required": ["ok"],
+    }
+
+
+def structure(text):
+    if text.startswith(ERROR_PREFIXES):
+        return {"ok": False, "error": text}
+    try:
+        return {"ok": True, "data": json.loads(text)}
+    except ValueError:
+        pass
+    title, _, rest = text.partition("\n")
+    if rest.lstrip().startswith(("{", "[")):
+        try:
+            return {"ok": True, "title": title.strip(), "data": json.loads(rest)}
+        except ValueError:
+            pass
+    return {"ok": True, "text": text}
+
+
+class StructuredOutput(Middleware):
+    async def on_list_tools(self, context, call_next):
+        tools = await call_next(context)
+        return [tool.model_copy(update={"output_schema": output_schema(tool.name)}) for tool in tools]
+
+    async def on_call_tool(self, context, call_next):
+        result = await call_next(context)
+        text = result.content[0].text if result.content and hasattr(result.content[0], "text") else ""
+        return ToolResult(content=result.content, structured_content=structure(text))
@@ -0,0 +1,183 @@
+"""資源訂閱 (resources/subscribe): agent 連線、斷線或新增時送出 notifications/resources/updated。
+
+Wazuh 沒有推播機制，所以由 AgentWatcher 在背景定期查詢 Manager API 的 agent 清單，
+與上一次的快照比較 (狀態、名稱、新增 / 移除)。只有在有人訂閱時才會查詢；
+訂閱者可訂閱 wazuh://agents (任何 agent 變動) 或 wazuh://agents/{agent_id} (單一 agent)。
+wazuh://alerts/live 則在 integrator 推送新告警 (ingest.py) 時通知，只算訂閱者可見的 agent。
+伺服器保存的文件 (獵捕假說 wazuh://hypotheses、設定上線計畫 wazuh://rollouts) 在管理工具建立或更新時通知，
+訂閱清單 URI 的收到任何一份文件的變動，訂閱單一文件的只收到該文件的變動。
+訂閱狀態依 MCP session 分開保存，session 結束 (送出通知失敗) 時自動清除。
+"""
+
+import asyncio
+import logging
+import threading
+
+logger = logging.getLogger("wazuh_mcp")
+
+AGENTS_URI = "wazuh://agents"
+ALERTS_LIVE_URI = "wazuh://alerts/live"
+HYPOTHESES_URI = "wazuh://hypotheses"
+ROLLOUTS_URI = "wazuh://rollouts"
+DOCUMENT_URIS = (HYPOTHESES_URI, ROLLOUTS_URI)
+
+
+def agent_uri(agent_id):
+    return f"{AGENTS_URI}/{agent_id}"
+
+
+def document_collection(uri):
+    """wazuh://hypotheses/H3 -> wazuh://hypotheses；不是伺服器文件的 URI 回傳 None"""
+    return next((c for c in DOCUMENT_URIS if uri == c or (uri.startswith(c + "/") and "/" not in uri[len(c) + 1:])),
+                None)
+
+
+class Subscription:
+    def __init__(self, loop, allowed, principal=None):
+        """allowed: 訂閱時呼叫者可見的 agent id 集合 (None = 不限)；principal: 訂閱的呼叫者"""
+        self.loop = loop
+        self.allowed = allowed
+        self.principal = principal
+        self.uris = set()
+
+    def targets(self, changed):
+        """changed agent id -> 此 session 應收到通知的 URI"""
+        visible = [a for a in changed if self.allowed is None or a in self.allowed]
+        uris = {agent_uri(a) for a in visible} & self.uris
+        if visible and AGENTS_URI in self.uris:
+            uris.add(AGENTS_URI)
+        return sorted(uris)
+
+    def alert_targets(self, agent_ids):
+        """推送的告警 (agent id) -> 此 session 應收到通知的 URI"""
+        if ALERTS_LIVE_URI not in self.uris:
+            return []
+        if self.allowed is not None and not any(a in self.allowed for a in agent_ids):
+            return []
+        return [ALERTS_LIVE_URI]
+
+    def document_targets(self, uri, owner):
+        """變動的文件 (uri，owner 為建立者) -> 此 session 應收到通知的 URI；租戶範圍受限的只收到自己的文件"""
+        if self.allowed is not None and self.principal != owner:
+            return []
+        return sorted({uri, document_collection(uri)} & self.uris)
+
+
+class SubscriptionRegistry:
+    def __init__(self):
+        self.sessions = {}
+        self.lock = threading.Lock()
+
+    def subscribe(self, session, uri, allowed, principal=None):
+        with self.lock:
+            sub = self.sessions.get(session)
+            if sub is None:
+                sub = self.sessions[session] = Subscription(asyncio.get_running_loop(), allowed, principal)
+            sub.uris.add(uri)
+
+    def unsubscribe(self, session, uri):
+        with self.lock:
+            sub = self.sessions.get(session)
+            if sub is not None:
+                sub.uris.discard(uri)
+                if not sub.uris:
+                    del self.sessions[session]
+
+    def drop(self, session):
+        with self.lock:
+            self.sessions.pop(session, None)
+
+    def active(self, prefix=""):
+        """是否有人訂閱 prefix 開頭的 URI"""
+        with self.lock:
+            return any(uri.startswith(prefix) for sub in self.sessions.values() for uri in sub.uris)
+
+    def notify(self, changed, timeout=10):
+        """從背景執行緒送出 agent 變動通知"""
+        return self._send(lambda sub: sub.targets(changed), timeout)
+
+    def notify_alerts(self, agent_ids, timeout=10):
+        """送出新告警通知 (agent_ids: 這批告警的 agent id)"""
+        return self._send(lambda sub: sub.alert_targets(agent_ids), timeout)
+
+    def notify_document(self, uri, owner=None):
+        """文件建立或更新後送出通知；不等待送出結果，可以在工具內 (包含 event loop 的執行緒) 直接呼叫"""
+        with self.lock:
+            pending = [(session, sub, sub.document_targets(uri, owner)) for session, sub in self.sessions.items()]
+        for session, sub, uris in pending:
+            for target in uris:
+                future = asyncio.run_coroutine_threadsafe(session.send_resource_updated(target), sub.loop)
+                future.add_done_callback(lambda f, session=session: self._forget_failed(session, f))
+        return sum(len(uris) for _, _, uris in pending)
+
+    def _forget_failed(self, session, future):
+        if future.cancelled() or future.exception() is None:
+            return
+        logger.info("資源更新通知送出失敗，移除該 session 的訂閱: %s", future.exception())
+        with self.lock:
+            self.sessions.pop(session, None)
+
+    def _send(self, targets, timeout):
+        """送出失敗的 session 視為已結束並移除"""
+        with self.lock:
+            pending = [(session, sub, targets(sub)) for session, sub in self.sessions.items()]
+        sent = 0
+        for session, sub, uris in pending:
+            for uri in uris:
+                try:
+                    asyncio.run_coroutine_threadsafe(session.send_resource_updated(uri), sub.loop).result(timeout)
+                    sent += 1
+                except Exception as e:
+                    logger.info("資源更新通知送出失敗，移除該 session 的訂閱: %s", e)
+                    with self.lock:
+                        self.sessions.pop(session, None)
+                    break
+        return sent
+
+
+def snapshot(agents):
+    return {a.get("id"): (a.get("status"), a.get("name")) for a in agents if a.get("id")}
+
+
+def changed_agents(before, after):
+    """新增、移除、狀態或名稱改變的 agent id"""
+    return sorted(a for a in before.keys() | after.keys() if before.get(a) != after.get(a))
+
+
+class AgentWatcher:
+    def __init__(self, fetch, registry, interval):
+        """fetch() -> (agents, error)"""
+        self.fetch = fetch
+        self.registry = registry
+        self.interval = interval
+        self.previous = None
+        self._stop = threading.Event()
+
+    def tick(self):
+        if not self.registry.active(AGENTS_URI):
+            self.previous = None  # 沒有訂閱者時不查詢，下次有人訂閱時重新建立基準
+            return []
+        agents, error = self.fetch()
+        if error:
+            logger.info("agent 狀態查詢失敗 (訂閱通知暫停): %s", error)
+            return []
+        current = snapshot(agents)
+        changed = changed_agents(self.previous, current) if self.previous is not None else []
+        self.previous = current
+        if changed:
+            logger.info("agent 狀態變動: %s", ", ".join(changed))
+            self.registry.notify(changed)
+        return changed
+
+    def _loop(self):
+        while not self._stop.wait(self.interval):
+            try:
+                self.tick()
+            except Exception:
+                logger.exception("agent 狀態輪詢發生例外")
+
+    def start(self):
+        threading.Thread(target=self._loop, name="agent-watcher", daemon=True).start()
+
+    def stop(self):
+        self._stop.set()
@@ -0,0 +1,84 @@
+"""根據查詢結果提出下一步的獵捕建議 (pivot suggestions)。
+
+只使用一次便宜的聚合查詢: 針對結果中最常見的 IOC (IP、雜湊、使用者)，
+統計它們在整個環境中出現在幾台主機上，據此建議下一個要呼叫的工具。
+"""
+
+from collections import Counter
+
+from alert_utils import get_field
+
+# (欄位, 顯示名稱)
+PIVOT_FIELDS = [
+    ("data.srcip", "來源 IP"),
+    ("syscheck.sha256_after", "檔案雜湊"),
+    ("data.win.eventdata.hashes", "程序雜湊"),
+    ("data.dstuser", "目標帳號"),
+]
+PIVOTS_PER_FIELD = 2
+
+
+def collect_pivots(alerts):
+    """從結果挑出每個欄位最常出現的值，回傳 [(欄位, 顯示名稱, 值, 本次出現的主機)]"""
+    pivots = []
+    for field, label in PIVOT_FIELDS:
+        counter = Counter()
+        agents = {}
+        for alert in alerts:
+            value = get_field(alert, field)
+            if value in (None, ""):
+                continue
+            value = str(value)
+            counter[value] += 1
+            agents.setdefault(value, set()).add(get_field(alert, "agent.id"))
+        for value, _ in counter.most_common(PIVOTS_PER_FIELD):
+            pivots.append((field, label, value, agents[value]))
+    return pivots
+
+
+def pivot_aggregation(pivots, time_range):
+    """一次查詢取得每個 pivot 值在環境中出現的主機數"""
+    return {
+        "size": 0,
+        "query": {"range": {"timestamp": {"gte": time_range}}},
+        "aggs": {
+            f"p{i}": {
+                "filter": {"term": {field: value}},
+                "aggs": {"agents": {"cardinality": {"field": "agent.id"}}}
+            }
+            for i, (field, _, value, _) in enumerate(pivots)
+        }
+    }
+
+
+def build_suggestions(kql, alerts, total, pivots, agg_result, time_range):
+    """組出建議清單，每筆包含說明文字與可直接呼叫的工具參數"""
+    suggestions = []
+    base = f"({kql}) and " if kql.strip() else ""
+    aggs = (agg_result or {}).get("aggregations", {})
+    for i, (field, label, value, seen_agents) in enumerate(pivots):
+        env_agents = aggs.get(f"p{i}", {}).get("agents", {}).get("value", 0)
+        others = env_agents - len(seen_agents)
+        if others > 0:
+            suggestions.append({
+                "reason": f"另有 {others} 台主機在 {time_range} 內也出現{label} {value}",
+                "tool": "search_alerts",
+                "arguments": {"kql": f'{field}:"{value}"', "group_by": ["agent.name"]}
+            })
+
+    rules = Counter(get_field(a, "rule.id") for a in alerts)
+    if len(alerts) >= 10 and rules and rules.most_common(1)[0][1] >= len(alerts) * 0.8:
+        rule_id = rules.most_common(1)[0][0]
+        suggestions.append({
+            "reason": f"結果有八成以上來自規則 {rule_id}，建議排除後看清楚其他活動",
+            "tool": "search_alerts",
+            "arguments": {"kql": f"{base}not rule.id:{rule_id}"}
+        })
+
+    if total > len(alerts) * 10:
+        suggestions.append({
+            "reason": f"共有 {total} 筆符合，目前只看到 {len(alerts)} 筆，建議先分組了解分布",
+            "tool": "search_alerts",
+            "arguments": {"kql": kql, "group_by": ["rule.id", "agent.name"]}
+        })
+    return suggestions
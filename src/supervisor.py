@@ -0,0 +1,95 @@
+"""後端連線監督: 定期檢查 Wazuh API / Indexer，短暫斷線後自動恢復。
+
+背景執行緒每 MCP_SUPERVISOR_INTERVAL 跑一次 preflight 檢查:
+- 必要檢查失敗 -> 進入 degraded 狀態，並執行 on_failure (清除 API token 快取、重建連線池)，
+  讓下一次請求重新驗證、重新連線，而不是一直重用失效的 token / 斷掉的連線
+- 恢復正常 -> 清除 degraded 狀態並記錄中斷時間
+degraded 期間所有工具回應都會附上 "_backend_degraded"，讓使用者知道結果可能不完整。
+"""
+
+import logging
+import threading
+from datetime import datetime, timezone
+
+from fastmcp.server.middleware import Middleware
+
+from output import annotate_result
+
+logger = logging.getLogger("wazuh_mcp")
+
+
+class Supervisor:
+    def __init__(self, preflight, interval, on_failure):
+        self.preflight = preflight
+        self.interval = interval
+        self.on_failure = on_failure
+        self.lock = threading.Lock()
+        self.degraded_since = None
+        self.failing = []
+        self.last_check = None
+        self.recoveries = 0
+        self._stop = threading.Event()
+
+    @property
+    def degraded(self):
+        return self.degraded_since is not None
+
+    def tick(self):
+        report = self.preflight.run(use_cache=False)
+        failing = [{"check": r["name"], "detail": r["detail"]}
+                   for r in report["checks"] if r["required"] and not r["ok"]]
+        with self.lock:
+            self.last_check = report["checked_at"]
+            self.failing = failing
+            if failing and not self.degraded:
+                self.degraded_since = datetime.now(timezone.utc)
+                logger.warning("後端連線異常，進入 degraded 狀態: %s", failing)
+            elif not failing and self.degraded:
+                outage = datetime.now(timezone.utc) - self.degraded_since
+                self.degraded_since = None
+                self.recoveries += 1
+                logger.warning("後端連線已恢復 (中斷約 %d 秒)", outage.total_seconds())
+        if failing:
+            self.on_failure()
+
+    def _loop(self):
+        while not self._stop.wait(self.interval):
+            try:
+                self.tick()
+            except Exception:  # 監督執行緒不能因為單次檢查出錯而結束
+                logger.exception("後端連線檢查發生例外")
+
+    def start(self):
+        threading.Thread(target=self._loop, name="backend-supervisor", daemon=True).start()
+
+    def stop(self):
+        self._stop.set()
+
+    def status(self):
+        with self.lock:
+            return {
+                "degraded": self.degraded,
+                "degraded_since": self.degraded_since.isoformat() if self.degraded else None,
+                "failing": self.failing,
+                "last_check": self.last_check,
+                "recoveries": self.recoveries,
+            }
+
+
+class DegradedNotice(Middleware):
+    """degraded 期間在每個工具回應加上後端狀態"""
+
+    def __init__(self, supervisor):
+        self.supervisor = supervisor
+
+    async def on_call_tool(self, context, call_next):
+        result = await call_next(context)
+        if not self.supervisor.degraded:
+            return result
+        status = self.supervisor.status()
+        notice = {
+            "since": status["degraded_since"],
+            "failing": [f["check"] for f in status["failing"]],
+            "note": "後端連線異常，結果可能不完整或失敗；伺服器會自動重新連線",
+        }
+        return annotate_result(result, "_backend_degraded", notice, "後端異常")
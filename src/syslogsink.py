@@ -0,0 +1,97 @@
+"""把伺服器自己的運作 log 送到 syslog / journald (或直接送給 Wazuh Manager)，
+讓 MCP Server 本身也被它所查詢的 SIEM 監控。
+
+MCP_LOG_SYSLOG 設定目的地 (未設定則只輸出到 stderr):
+    local               本機 syslog socket (/dev/log；journald 也會收這個 socket)
+    wazuh               Wazuh Manager 的 syslog 接收埠 (udp://WAZUH_API_HOST:514，需在 ossec.conf
+                        開啟 <remote><connection>syslog</connection>，並把本機加入 allowed-ips)
+    udp://host:514      指定的 syslog 伺服器 (也可用 tcp://)
+每筆 log 以 "wazuh-mcp: {JSON}" 送出；對應的 Wazuh decoder 與規則可用 python src/main.py rules 產生。
+"""
+
+import json
+import logging
+import logging.handlers
+import os
+import socket
+from datetime import datetime, timezone
+
+APP_NAME = "wazuh-mcp"
+LOCAL_SOCKETS = ("/dev/log", "/var/run/syslog")
+DEFAULT_PORT = 514
+
+
+class JsonLogFormatter(logging.Formatter):
+    """一筆 log 一行 JSON；送到 syslog 時前面加上程式名稱 (tag)，TCP syslog 以換行分隔訊息"""
+
+    def __init__(self, tag=APP_NAME, terminator=""):
+        super().__init__()
+        self.tag = tag
+        self.terminator = terminator
+
+    def format(self, record):
+        event = {
+            "timestamp": datetime.fromtimestamp(record.created, timezone.utc).isoformat(),
+            "level": record.levelname.lower(),
+            "logger": record.name,
+            "message": record.getMessage(),
+            "host": socket.gethostname(),
+            "pid": record.process,
+        }
+        if getattr(record, "mcp", None):
+            event["mcp"] = record.mcp
+        if record.exc_info:
+            event["exception"] = self.formatException(record.exc_info)
+        line = json.dumps(event, ensure_ascii=False)
+        return f"{self.tag}: {line}{self.terminator}" if self.tag else line + self.terminator
+
+
+def parse_target(value, wazuh_host=None):
+    """MCP_LOG_SYSLOG -> (address, socktype)；address 為 unix socket 路徑或 (host, port)"""
+    value = value.strip()
+    if value == "local":
+        path = next((p for p in LOCAL_SOCKETS if os.path.exists(p)), None)
+        if path is None:
+            raise ValueError(f"找不到本機 syslog socket ({', '.join(LOCAL_SOCKETS)})")
+        return path, None
+    if value == "wazuh":
+        if not wazuh_host:
+            raise ValueError("MCP_LOG_SYSLOG=wazuh 需要設定 WAZUH_API_HOST")
+        return (wazuh_host, DEFAULT_PORT), socket.SOCK_DGRAM
+    scheme, sep, rest = value.partition("://")
+    if not sep or scheme not in ("udp", "tcp") or not rest:
+        raise ValueError(f"MCP_LOG_SYSLOG '{value}' 格式錯誤，可用: local、wazuh、udp://host:port、tcp://host:port")
+    host, _, port = rest.partition(":")
+    try:
+        port = int(port) if port else DEFAULT_PORT
+    except ValueError:
+        raise ValueError(f"MCP_LOG_SYSLOG 的埠號 '{port}' 不是數字") from None
+    return (host, port), socket.SOCK_DGRAM if scheme == "udp" else socket.SOCK_STREAM
+
+
+def syslog_handler(value, wazuh_host=None, facility="local0", level=None):
+    """建立 syslog handler；value 為空時回傳 None"""
+    if not value or not value.strip():
+        return None
+    address, socktype = parse_target(value, wazuh_host)
+    if facility not in logging.handlers.SysLogHandler.facility_names:
+        raise ValueError(f"MCP_LOG_SYSLOG_FACILITY '{facility}' 不是有效的 syslog facility")
+    handler = logging.handlers.SysLogHandler(address=address, socktype=socktype,
+                                             facility=logging.handlers.SysLogHandler.facility_names[facility])
+    if socktype == socket.SOCK_STREAM:
+        handler.append_nul = False
+        handler.setFormatter(JsonLogFormatter(terminator="\n"))
+    else:
+        handler.setFormatter(JsonLogFormatter())
+    if level:
+        handler.setLevel(level.upper())
+    return handler
+
+
+def json_file_handler(path):
+    """JSON lines 檔案 (給 Wazuh agent 以 log_format json 收集)；支援 logrotate 換檔"""
+    if not path or not path.strip():
+        return None
+    handler = logging.handlers.WatchedFileHandler(path, encoding="utf-8")
+    handler.setFormatter(JsonLogFormatter(tag=None))
+    return handler
@@ -0,0 +1,84 @@
+"""同一個 process 內的 MCP client，用來快速測試工具行為 (不啟動子行程、不經過網路)。
+
+InProcessClient 以 FastMCP 的記憶體傳輸直接連到 server，並提供同步介面，
+可直接在 pytest 或自訂的處理流程中使用:
+
+    from testing import InProcessClient
+    with InProcessClient() as client:
+        text = client.call_tool("search_alerts", kql="rule.groups:sshd")
+        assert not is_error(text)
+
+不想連到真實 Wazuh 時，可先以 pytest 的 monkeypatch 替換 main.search_indexer / main.api_get。
+server 預設為 main.mcp (設定在 import 時讀取，環境變數要在建立 client 之前設好)。
+"""
+
+import asyncio
+import json
+import threading
+
+from fastmcp import Client
+
+from audit import ERROR_PREFIXES
+
+
+def is_error(text):
+    """工具回應是否為錯誤訊息 (工具以「錯誤: ...」等字串回報失敗，而不是拋出例外)"""
+    return text.startswith(ERROR_PREFIXES)
+
+
+def _text(contents):
+    return "\n".join(c.text for c in contents if hasattr(c, "text"))
+
+
+class InProcessClient:
+    def __init__(self, server=None, timeout=60):
+        if server is None:
+            import main
+            server = main.mcp
+        self.server = server
+        self.timeout = timeout
+        self._loop = None
+        self._client = None
+
+    def __enter__(self):
+        # client 連線需要一直開著的 event loop，放在背景執行緒，讓呼叫端維持同步寫法
+        self._loop = asyncio.new_event_loop()
+        self._thread = threading.Thread(target=self._loop.run_forever, name="inprocess-client", daemon=True)
+        self._thread.start()
+        self._client = Client(self.server)
+        self._run(self._client.__aenter__())
+        return self
+
+    def __exit__(self, *exc):
+        try:
+            self._run(self._client.__aexit__(None, None, None))
+        finally:
+            self._loop.call_soon_threadsafe(self._loop.stop)
+            self._thread.join()
+            self._loop.close()
+
+    def _run(self, coro):
+        if self._loop is None:
+            raise RuntimeError("InProcessClient 必須在 with 區塊內使用")
+        return asyncio.run_coroutine_threadsafe(coro, self._loop).result(self.timeout)
+
+    def list_tools(self):
+        return [tool.name for tool in self._run(self._client.list_tools())]
+
+    def call_tool(self, name, arguments=None, **kwargs):
+        """回傳工具的文字回應；工具拋出例外時 fastmcp 會拋出 ToolError"""
+        result = self._run(self._client.call_tool(name, {**(arguments or {}), **kwargs}))
+        return _text(result.content)
+
+    def call_tool_json(self, name, arguments=None, **kwargs):
+        text = self.call_tool(name, arguments, **kwargs)
+        if is_error(text):
+            raise AssertionError(text)
+        return json.loads(text)
+
+    def read_resource(self, uri):
+        return _text(self._run(self._client.read_resource(uri)))
+
+    def get_prompt(self, name, arguments=None, **kwargs):
+        result = self._run(self._client.get_prompt(name, {**(arguments or {}), **kwargs}))
+        return "\n\n".join(m.content.text for m in result.messages if hasattr(m.content, "text"))
@@ -0,0 +1,104 @@
+"""工具回應的時間分析 (timing 區塊)，用來判斷慢的是 MCP 伺服器還是 Wazuh。
+
+每次工具呼叫建立一個 Timing 放在 contextvar 中 (平行查詢的執行緒也會帶著同一個 context):
+- auth_ms:          向 Wazuh API 取得 token 的時間 (token 快取命中時為 0)
+- backend_ms:       等待 Wazuh API / Indexer 回應的時間 (平行查詢時為各請求加總)
+- indexer_took_ms:  Indexer 回報的查詢執行時間 (backend_ms 扣掉它約等於網路與排隊時間)
+- parse_ms:         解析後端 JSON 回應的時間
+- render_ms:        其餘伺服器端處理 (彙整、格式化輸出) 的時間
+- cache:            查詢快取 hit / miss / partial (部分命中)，沒有可快取的查詢時為 null
+"""
+
+import contextvars
+import threading
+import time
+from contextlib import contextmanager
+
+from fastmcp.server.middleware import Middleware
+
+from output import annotate_result
+
+_current = contextvars.ContextVar("tool_timing", default=None)
+
+AUTH_PATH = "/security/user/authenticate"
+
+
+class Timing:
+    def __init__(self):
+        self.started = time.perf_counter()
+        self.lock = threading.Lock()
+        self.ms = {"auth": 0.0, "backend": 0.0, "indexer_took": 0.0, "parse": 0.0}
+        self.requests = 0
+        self.cache_hits = 0
+        self.cache_misses = 0
+
+    def add(self, phase, ms):
+        with self.lock:
+            self.ms[phase] += ms
+
+    def summary(self):
+        total = (time.perf_counter() - self.started) * 1000
+        with self.lock:
+            spent = self.ms["auth"] + self.ms["backend"] + self.ms["parse"]
+            if self.cache_hits and self.cache_misses:
+                cache = "partial"
+            elif self.cache_hits or self.cache_misses:
+                cache = "hit" if self.cache_hits else "miss"
+            else:
+                cache = None
+            return {
+                "total_ms": round(total),
+                **{f"{phase}_ms": round(ms) for phase, ms in self.ms.items()},
+                "render_ms": round(max(total - spent, 0)),
+                "backend_requests": self.requests,
+                "cache": cache,
+            }
+
+
+def record(phase, ms):
+    timing = _current.get()
+    if timing is not None:
+        timing.add(phase, ms)
+
+
+@contextmanager
+def measure(phase):
+    started = time.perf_counter()
+    try:
+        yield
+    finally:
+        record(phase, (time.perf_counter() - started) * 1000)
+
+
+def record_response(resp, *args, **kwargs):
+    """requests 的 response hook: 依 URL 記到 auth 或 backend"""
+    timing = _current.get()
+    if timing is None:
+        return
+    with timing.lock:
+        timing.requests += 1
+    record("auth" if AUTH_PATH in resp.url else "backend", resp.elapsed.total_seconds() * 1000)
+
+
+def record_cache(hit):
+    timing = _current.get()
+    if timing is None:
+        return
+    with timing.lock:
+        if hit:
+            timing.cache_hits += 1
+        else:
+            timing.cache_misses += 1
+
+
+class TimingMiddleware(Middleware):
+    """在每個工具回應的最後加上 timing 區塊"""
+
+    async def on_call_tool(self, context, call_next):
+        timing = Timing()
+        token = _current.set(timing)
+        try:
+            result = await call_next(context)
+        finally:
+            _current.reset(token)
+        return annotate_result(result, "timing", timing.summary(), "timing", first=False)
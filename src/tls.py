@@ -0,0 +1,36 @@
+"""HTTP / WebSocket 傳輸直接提供 HTTPS (--tls-cert / --tls-key)，不必在前面架反向代理。
+
+憑證與私鑰都是 PEM 檔 (憑證檔可以包含中繼憑證鏈)，私鑰有密碼時設定 MCP_TLS_KEY_PASSWORD。
+啟動時先載入一次確認格式與配對正確，錯誤會在啟動時就回報，而不是等到第一個連線握手失敗。
+設定 client_ca (MCP_TLS_CLIENT_CA) 時同時要求 client 憑證 (mutual TLS，見 mtls.py)。
+"""
+
+import os
+import ssl
+
+
+def server_options(cert, key, password=None, client_ca=None):
+    """回傳 uvicorn 的 TLS 參數；cert / key 都沒有設定時回傳空 dict (維持 HTTP)"""
+    if not cert and not key:
+        if client_ca:
+            raise ValueError("MCP_TLS_CLIENT_CA 需要搭配 --tls-cert / --tls-key 使用")
+        return {}
+    if not cert or not key:
+        raise ValueError("--tls-cert 與 --tls-key (MCP_TLS_CERT / MCP_TLS_KEY) 必須同時設定")
+    for path in (cert, key) + ((client_ca,) if client_ca else ()):
+        if not os.path.isfile(path):
+            raise ValueError(f"找不到憑證或私鑰檔: {path}")
+    context = ssl.SSLContext(ssl.PROTOCOL_TLS_SERVER)
+    try:
+        context.load_cert_chain(cert, key, password)
+    except (ssl.SSLError, OSError) as e:
+        raise ValueError(f"無法載入憑證 / 私鑰 (格式錯誤、不成對或私鑰密碼錯誤): {e}") from e
+    options = {"ssl_certfile": cert, "ssl_keyfile": key, "ssl_keyfile_password": password}
+    if client_ca:
+        try:
+            context.load_verify_locations(client_ca)
+        except (ssl.SSLError, OSError) as e:
+            raise ValueError(f"無法載入 client CA {client_ca}: {e}") from e
+        # 有出示憑證就必須有效；是否一定要出示由 mtls.RequireClientCert 依路徑決定
+        options.update(ssl_ca_certs=client_ca, ssl_cert_reqs=ssl.CERT_OPTIONAL)
+    return options
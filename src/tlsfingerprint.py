@@ -0,0 +1,129 @@
+"""JA3 / JA3S / JA4 TLS 指紋獵捕。
+
+惡意程式的 TLS 用戶端指紋 (JA3 / JA4) 通常和瀏覽器不同，換了 C2 網域或 IP 也不會變。
+Suricata eve.json、Zeek ssl.log 與 ECS 格式各自把指紋放在不同欄位，這裡統一成三種類型。
+已知惡意指紋清單由 TLS_BAD_FINGERPRINTS 指定，可以是檔案路徑或以逗號分隔的清單，
+每一項為 "指紋" 或 "指紋=說明"，例如:
+    e7d705a3286e19ea42f587b344ee6865=Tofsee,t13d190900_9dc949149365_97f8aa674fd9=Sliver
+"""
+
+import os
+
+FINGERPRINT_FIELDS = {
+    "ja3": ["data.tls.ja3.hash", "data.ja3", "data.tls.client.ja3"],
+    "ja3s": ["data.tls.ja3s.hash", "data.ja3s", "data.tls.server.ja3s"],
+    "ja4": ["data.tls.ja4", "data.ja4", "data.tls.client.ja4"],
+}
+
+# 代表事件中用來判斷連線目的地的欄位
+CONTEXT_FIELDS = [
+    "agent.name", "data.srcip", "data.dstip", "data.dest_ip", "data.dstport",
+    "data.tls.sni", "data.server_name", "data.tls.client.server_name",
+]
+
+
+def normalize(value):
+    return str(value).strip().lower()
+
+
+def parse_fingerprint_list(text):
+    """回傳 {指紋: 說明}；text 若是存在的檔案路徑，則逐行讀取 (# 開頭為註解)"""
+    text = (text or "").strip()
+    if text and os.path.isfile(text):
+        with open(text, encoding="utf-8") as f:
+            items = [line.split("#", 1)[0] for line in f]
+    else:
+        items = text.split(",")
+    known = {}
+    for item in items:
+        item = item.strip()
+        if not item:
+            continue
+        fingerprint, _, label = item.partition("=")
+        known[normalize(fingerprint)] = label.strip() or "known-bad"
+    return known
+
+
+def agg_name(field):
+    return field.replace(".", "__")
+
+
+def fields_for(kind=None):
+    if kind is None:
+        return [(k, f) for k, fields in FINGERPRINT_FIELDS.items() for f in fields]
+    if kind not in FINGERPRINT_FIELDS:
+        raise ValueError(f"未知的指紋類型 '{kind}'，可用: {', '.join(FINGERPRINT_FIELDS)}")
+    return [(kind, f) for f in FINGERPRINT_FIELDS[kind]]
+
+
+def fingerprint_query(fields, values=None):
+    """事件中至少有一個指紋欄位 (或指紋符合 values) 的篩選條件"""
+    if values:
+        values = sorted({normalize(v) for v in values})
+        should = [{"terms": {field: values}} for _, field in fields]
+    else:
+        should = [{"exists": {"field": field}} for _, field in fields]
+    return {"bool": {"should": should, "minimum_should_match": 1}}
+
+
+def fingerprint_aggregations(fields, limit, known_bad, rare=False):
+    """每個指紋欄位: 最常見 (rare=True 為最少見) 的指紋，以及命中已知惡意清單的指紋 (不受 limit 影響)"""
+    stats = {
+        "agents": {"cardinality": {"field": "agent.id"}},
+        "first_seen": {"min": {"field": "timestamp"}},
+        "last_seen": {"max": {"field": "timestamp"}},
+        "sample": {"top_hits": {"size": 1, "_source": CONTEXT_FIELDS,
+                                "sort": [{"timestamp": {"order": "desc"}}]}},
+    }
+    aggs = {}
+    for _, field in fields:
+        name = agg_name(field)
+        terms = {"field": field, "size": limit}
+        if rare:
+            terms["order"] = {"_count": "asc"}
+        aggs[name] = {"terms": terms, "aggs": stats}
+        if known_bad:
+            aggs[f"bad__{name}"] = {
+                "filter": {"terms": {field: sorted(known_bad)}},
+                "aggs": {"values": {
+                    "terms": {"field": field, "size": len(known_bad)},
+                    "aggs": {**stats, "agent_names": {"terms": {"field": "agent.name", "size": 20}}}
+                }}
+            }
+    return aggs
+
+
+def _bucket_row(kind, field, bucket, known_bad):
+    sample = bucket["sample"]["hits"]["hits"]
+    row = {
+        "kind": kind,
+        "fingerprint": bucket["key"],
+        "field": field,
+        "events": bucket["doc_count"],
+        "agents": bucket["agents"]["value"],
+        "first_seen": bucket["first_seen"].get("value_as_string"),
+        "last_seen": bucket["last_seen"].get("value_as_string"),
+        "sample": sample[0].get("_source", {}) if sample else None,
+    }
+    label = known_bad.get(normalize(bucket["key"]))
+    if label:
+        row["known_bad"] = label
+    if "agent_names" in bucket:
+        row["agent_names"] = [b["key"] for b in bucket["agent_names"]["buckets"]]
+    return row
+
+
+def summarize(aggregations, fields, known_bad, limit, rare=False):
+    """回傳 (各類型最常見的 limit 個指紋, 命中已知惡意清單的指紋)"""
+    top = {}
+    matches = []
+    for kind, field in fields:
+        for b in aggregations.get(agg_name(field), {}).get("buckets", []):
+            top.setdefault(kind, []).append(_bucket_row(kind, field, b, known_bad))
+        bad = aggregations.get(f"bad__{agg_name(field)}", {}).get("values", {}).get("buckets", [])
+        matches.extend(_bucket_row(kind, field, b, known_bad) for b in bad)
+    for kind, rows in top.items():
+        rows.sort(key=lambda r: r["events"], reverse=not rare)
+        del rows[limit:]
+    matches.sort(key=lambda r: r["events"], reverse=True)
+    return top, matches
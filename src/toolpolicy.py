@@ -0,0 +1,140 @@
+"""每個身分可以呼叫哪些工具 (RBAC)，由 MCP_TOOL_POLICY_FILE 指定的 YAML (或 JSON) 政策檔決定。
+
+身分即 principal: API key 名稱、OIDC 的 principal claim 或 MCP_PRINCIPAL_HEADER 的值 (見 principal.py)。格式:
+    roles:
+      analyst:
+        allow: ["search_*", "list_*", "get_*", "alert_trends"]
+      admin:
+        allow: ["*"]
+      no-writes:
+        deny: ["inject_test_events", "review_detection_rules"]
+    identities:
+      soc-bot: analyst
+      alice: [admin, no-writes]
+    default: analyst          # 沒有列出的身分；省略時一律拒絕 (fail closed)
+- allow / deny 是 fnmatch 樣式；同一個身分的所有角色中任一 deny 符合就拒絕，否則任一 allow 符合就允許
+- 本機 stdio 的 "local" 不受限制，除非在 identities 中列出
+- 政策檔修改後下一次呼叫就會重新載入；內容有誤時沿用上一版並記錄警告
+工具清單只列出呼叫者可用的工具；呼叫不允許的工具會被拒絕 (稽核事件記為 denied)。
+"""
+
+import fnmatch
+import logging
+import os
+import threading
+
+import yaml
+from fastmcp.server.middleware import Middleware
+from fastmcp.tools.tool import ToolResult
+from mcp.types import TextContent
+
+from principal import LOCAL_PRINCIPAL, current_principal
+
+logger = logging.getLogger("wazuh_mcp")
+
+
+def _names(value, what):
+    if value is None:
+        return []
+    if isinstance(value, str):
+        return [value]
+    if isinstance(value, list) and all(isinstance(v, str) for v in value):
+        return value
+    raise ValueError(f"{what} 必須是字串或字串清單")
+
+
+class Policy:
+    def __init__(self, roles, identities, default):
+        self.roles = roles            # 角色 -> (allow 樣式, deny 樣式)
+        self.identities = identities  # principal -> [角色]
+        self.default = default        # [角色] 或 None (拒絕)
+
+    def roles_for(self, principal):
+        if principal in self.identities:
+            return self.identities[principal]
+        if principal == LOCAL_PRINCIPAL:
+            return None
+        return self.default or []
+
+    def allows(self, principal, tool):
+        roles = self.roles_for(principal)
+        if roles is None:
+            return True
+        rules = [self.roles[role] for role in roles]
+        if any(fnmatch.fnmatchcase(tool, pattern) for _, deny in rules for pattern in deny):
+            return False
+        return any(fnmatch.fnmatchcase(tool, pattern) for allow, _ in rules for pattern in allow)
+
+
+def parse_policy(data, source="MCP_TOOL_POLICY_FILE"):
+    if not isinstance(data, dict):
+        raise ValueError(f"{source} 必須是包含 roles / identities 的物件")
+    unknown = set(data) - {"roles", "identities", "default"}
+    if unknown:
+        raise ValueError(f"{source} 有不認得的欄位: {', '.join(sorted(unknown))}")
+    roles = {}
+    for name, spec in (data.get("roles") or {}).items():
+        if not isinstance(spec, dict) or set(spec) - {"allow", "deny"}:
+            raise ValueError(f"{source} 的角色 {name} 只能有 allow / deny")
+        roles[str(name)] = (_names(spec.get("allow"), f"{name}.allow"), _names(spec.get("deny"), f"{name}.deny"))
+    identities = {str(principal): _names(value, f"identities.{principal}")
+                  for principal, value in (data.get("identities") or {}).items()}
+    default = _names(data.get("default"), "default") or None
+    for role in [r for rs in identities.values() for r in rs] + (default or []):
+        if role not in roles:
+            raise ValueError(f"{source} 引用了未定義的角色 '{role}'")
+    return Policy(roles, identities, default)
+
+
+def load_policy(path):
+    with open(path, encoding="utf-8") as f:
+        try:
+            data = yaml.safe_load(f)
+        except yaml.YAMLError as e:
+            raise ValueError(f"{path} 不是有效的 YAML: {e}") from None
+    return parse_policy(data, path)
+
+
+class PolicyFile:
+    """依修改時間自動重新載入的政策檔；啟動時載入失敗直接拋出 ValueError"""
+
+    def __init__(self, path):
+        self.path = path
+        self.lock = threading.Lock()
+        self.mtime = os.stat(path).st_mtime if os.path.exists(path) else None
+        if self.mtime is None:
+            raise ValueError(f"找不到 MCP_TOOL_POLICY_FILE: {path}")
+        self.policy = load_policy(path)
+
+    def current(self):
+        with self.lock:
+            try:
+                mtime = os.stat(self.path).st_mtime
+                if mtime != self.mtime:
+                    self.policy = load_policy(self.path)
+                    self.mtime = mtime
+                    logger.info("已重新載入工具權限政策 %s", self.path)
+            except (OSError, ValueError) as e:
+                logger.warning("工具權限政策 %s 無法載入，沿用上一版: %s", self.path, e)
+            return self.policy
+
+
+class ToolPolicyMiddleware(Middleware):
+    def __init__(self, policy_file, resolve=None):
+        """resolve(name) -> 實際的工具名稱 (舊名稱的別名依目標工具判斷)"""
+        self.policy_file = policy_file
+        self.resolve = resolve or (lambda name: name)
+
+    async def on_list_tools(self, context, call_next):
+        tools = await call_next(context)
+        policy, principal = self.policy_file.current(), current_principal()
+        return [tool for tool in tools if policy.allows(principal, self.resolve(tool.name))]
+
+    async def on_call_tool(self, context, call_next):
+        tool = self.resolve(context.message.name)
+        principal = current_principal()
+        if not self.policy_file.current().allows(principal, tool):
+            logger.info("拒絕 %s 呼叫 %s (工具權限政策)", principal, tool)
+            return ToolResult(content=[TextContent(
+                type="text", text=f"錯誤: {principal} 沒有呼叫 {tool} 的權限 (MCP_TOOL_POLICY_FILE)")])
+        return await call_next(context)
@@ -0,0 +1,79 @@
+"""Unix domain socket 傳輸 (--transport unix): 本機的 agent 透過 socket 檔連線，不必開 TCP 埠。
+
+提供的是與 http 模式相同的 Streamable HTTP app (MCP 端點 /mcp、/healthz、/admin/*)，
+存取控制交給檔案權限: MCP_SOCKET_MODE (預設 660) 與 MCP_SOCKET_GROUP 決定哪些本機使用者可以連線。
+"""
+
+import contextlib
+import os
+import socket
+import stat
+
+DEFAULT_SOCKET_PATH = "/run/mcp-wazuh.sock"
+
+
+def parse_mode(text):
+    """八進位的權限字串 ("660") -> int"""
+    try:
+        mode = int(str(text), 8)
+    except ValueError:
+        raise ValueError(f"MCP_SOCKET_MODE 必須是八進位權限 (例如 660)，收到 '{text}'") from None
+    if not 0 <= mode <= 0o777:
+        raise ValueError(f"MCP_SOCKET_MODE 超出範圍: '{text}'")
+    return mode
+
+
+def _remove_stale(path):
+    """上次沒有正常結束留下的 socket 檔可以刪除；仍有程式在監聽或不是 socket 時拒絕"""
+    try:
+        info = os.stat(path)
+    except FileNotFoundError:
+        return
+    if not stat.S_ISSOCK(info.st_mode):
+        raise RuntimeError(f"{path} 已存在且不是 socket 檔，拒絕覆蓋")
+    probe = socket.socket(socket.AF_UNIX, socket.SOCK_STREAM)
+    try:
+        probe.connect(path)
+    except (ConnectionRefusedError, FileNotFoundError):
+        os.unlink(path)
+        return
+    finally:
+        probe.close()
+    raise RuntimeError(f"{path} 已有其他程式在監聽")
+
+
+def bind(path, mode, group=None):
+    """建立並監聽 socket 檔，回傳已 listen 的 socket；group 為群組名稱或 gid"""
+    if not hasattr(socket, "AF_UNIX"):
+        raise RuntimeError("此平台不支援 Unix domain socket")
+    _remove_stale(path)
+    sock = socket.socket(socket.AF_UNIX, socket.SOCK_STREAM)
+    # bind 之前先收緊 umask，chmod 之前不會有其他使用者搶先連線
+    previous = os.umask(0o177)
+    try:
+        sock.bind(path)
+    except OSError:
+        sock.close()
+        raise
+    finally:
+        os.umask(previous)
+    try:
+        if group:
+            import grp
+            gid = int(group) if str(group).isdigit() else grp.getgrnam(group).gr_gid
+            os.chown(path, -1, gid)
+        os.chmod(path, mode)
+        sock.listen(128)
+    except (OSError, KeyError) as e:
+        sock.close()
+        with contextlib.suppress(OSError):
+            os.unlink(path)
+        raise RuntimeError(f"無法設定 {path} 的權限: {e}") from e
+    return sock
+
+
+def close(sock, path):
+    """伺服器停止時關閉 socket 並刪除 socket 檔"""
+    sock.close()
+    with contextlib.suppress(OSError):
+        os.unlink(path)
@@ -0,0 +1,60 @@
+"""Agent 弱點資料 (vulnerability detector) 的版本差異處理。
+
+Wazuh 4.7 以前弱點掃描結果只能從 Manager API (/vulnerability/{agent_id}) 取得；
+4.8 起改存放在 Indexer 的 wazuh-states-vulnerabilities-* 索引。查詢方式由 querylib 的
+vulnerabilities.agent 範本依 WAZUH_VERSION 決定，這裡把兩種來源整理成相同格式。
+"""
+
+from collections import Counter
+
+VULNERABILITY_INDEX = "wazuh-states-vulnerabilities-*"
+SEVERITY_ORDER = ("critical", "high", "medium", "low", "none", "untriaged")
+
+
+def api_request(agent_id, limit):
+    return {"source": "api", "path": f"/vulnerability/{agent_id}",
+            "params": {"limit": limit, "sort": "-cvss3_score"}}
+
+
+def index_request(agent_id, limit):
+    return {"source": "indexer", "index": VULNERABILITY_INDEX, "body": {
+        "size": limit,
+        "query": {"term": {"agent.id": agent_id}},
+        "sort": [{"vulnerability.score.base": {"order": "desc", "unmapped_type": "float"}}],
+    }}
+
+
+def from_api(item):
+    return {
+        "cve": item.get("cve"),
+        "severity": (item.get("severity") or "untriaged").lower(),
+        "score": item.get("cvss3_score") or item.get("cvss2_score"),
+        "package": item.get("name"),
+        "version": item.get("version"),
+        "status": item.get("status"),
+        "detected_at": item.get("detection_time"),
+    }
+
+
+def from_index(source):
+    vuln, package = source.get("vulnerability", {}), source.get("package", {})
+    return {
+        "cve": vuln.get("id"),
+        "severity": (vuln.get("severity") or "untriaged").lower(),
+        "score": vuln.get("score", {}).get("base"),
+        "package": package.get("name"),
+        "version": package.get("version"),
+        "status": "valid",
+        "detected_at": vuln.get("detected_at"),
+    }
+
+
+def summarize(agent_id, findings, truncated):
+    counts = Counter(f["severity"] for f in findings)
+    return {
+        "agent_id": agent_id,
+        "total": len(findings),
+        "by_severity": {s: counts[s] for s in SEVERITY_ORDER if counts[s]},
+        "truncated": truncated,
+        "vulnerabilities": findings,
+    }
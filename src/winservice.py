@@ -0,0 +1,81 @@
+"""Windows 服務支援: 讓 HTTP 模式的伺服器在沒有 Docker 的 Windows SOC 工作站上常駐執行。
+
+    python src/main.py --install-service      (需以系統管理員身分執行)
+    python src/main.py --uninstall-service
+
+服務啟動後以 HTTP 模式監聽 MCP_SERVER_HOST:MCP_SERVER_PORT，設定一樣讀取專案的 .env。
+"""
+
+import os
+import sys
+import threading
+
+try:
+    import servicemanager
+    import win32event
+    import win32service
+    import win32serviceutil
+except ImportError:  # 非 Windows 或未安裝 pywin32
+    win32serviceutil = None
+
+SERVICE_NAME = "WazuhMCPThreatHunter"
+SERVICE_DISPLAY_NAME = "Wazuh MCP Threat Hunter"
+SERVICE_DESCRIPTION = "Wazuh SIEM threat hunting MCP server (HTTP transport)"
+
+
+def _require_pywin32():
+    if win32serviceutil is None:
+        raise RuntimeError("Windows 服務功能只能在 Windows 上使用，且需要安裝 pywin32")
+
+
+if win32serviceutil is not None:
+    class WazuhMCPService(win32serviceutil.ServiceFramework):
+        _svc_name_ = SERVICE_NAME
+        _svc_display_name_ = SERVICE_DISPLAY_NAME
+        _svc_description_ = SERVICE_DESCRIPTION
+
+        def __init__(self, args):
+            super().__init__(args)
+            self.stop_event = win32event.CreateEvent(None, 0, 0, None)
+
+        def SvcStop(self):
+            self.ReportServiceStatus(win32service.SERVICE_STOP_PENDING)
+            win32event.SetEvent(self.stop_event)
+
+        def SvcShutdown(self):
+            self.SvcStop()
+
+        def SvcDoRun(self):
+            # 服務由 PythonService.exe 啟動，工作目錄與 sys.path 需自行指定
+            here = os.path.dirname(os.path.abspath(__file__))
+            os.chdir(here)
+            if here not in sys.path:
+                sys.path.insert(0, here)
+            import main
+
+            servicemanager.LogMsg(
+                servicemanager.EVENTLOG_INFORMATION_TYPE,
+                servicemanager.PYS_SERVICE_STARTED,
+                (self._svc_name_, "")
+            )
+            # HTTP 伺服器跑在背景執行緒；收到停止要求後主執行緒返回，服務行程隨之結束
+            server = threading.Thread(target=main.run_server, args=("http",), daemon=True)
+            server.start()
+            win32event.WaitForSingleObject(self.stop_event, win32event.INFINITE)
+            servicemanager.LogMsg(
+                servicemanager.EVENTLOG_INFORMATION_TYPE,
+                servicemanager.PYS_SERVICE_STOPPED,
+                (self._svc_name_, "")
+            )
+
+
+def install_service():
+    _require_pywin32()
+    win32serviceutil.HandleCommandLine(
+        WazuhMCPService, argv=[sys.argv[0], "--startup", "auto", "install"]
+    )
+
+
+def uninstall_service():
+    _require_pywin32()
+    win32serviceutil.HandleCommandLine(WazuhMCPService, argv=[sys.argv[0], "remove"])
@@ -0,0 +1,77 @@
+"""WebSocket 傳輸 (--transport websocket): 給無法使用 Streamable HTTP 的 client，以 WebSocket 收送 MCP JSON-RPC。
+
+- 端點與 http 模式相同 (預設 ws://host:port/mcp，子協定 "mcp")，同樣套用 MCP_HTTP_PATH_PREFIX 與
+  MCP_TRUSTED_PROXIES；/healthz、/metrics、/admin/* 仍以一般 HTTP 提供
+- 每條連線是一個 MCP session，斷線即結束；同時連線數上限 MCP_WS_MAX_SESSIONS，超過時拒絕握手
+- keepalive: 每 MCP_WS_PING_INTERVAL 秒送出 ping，MCP_WS_PING_TIMEOUT 秒內沒收到 pong 就中斷連線
+- 握手請求的標頭 (MCP_PRINCIPAL_HEADER 等) 在整個 session 期間都視為目前的 HTTP 請求
+"""
+
+import contextlib
+import threading
+import uuid
+from datetime import datetime, timezone
+
+from fastmcp.server.http import _current_http_request
+from mcp.server.websocket import websocket_server
+from starlette.applications import Starlette
+from starlette.requests import HTTPConnection
+from starlette.routing import WebSocketRoute
+from starlette.websockets import WebSocket
+
+# 超過連線上限時的關閉代碼 (1013 = Try Again Later)
+CLOSE_TRY_AGAIN = 1013
+
+
+class WebSocketSessions:
+    """ASGI WebSocket 端點: 每條連線執行一個 MCP session，並記錄目前的連線"""
+
+    def __init__(self, server, max_sessions):
+        self.server = server
+        self.max_sessions = max_sessions
+        self.lock = threading.Lock()
+        self.active = {}  # session id -> 連線資訊
+
+    def sessions(self):
+        with self.lock:
+            return [{"session": sid, **info} for sid, info in self.active.items()]
+
+    def _open(self, scope):
+        with self.lock:
+            if self.max_sessions and len(self.active) >= self.max_sessions:
+                return None
+            session_id = uuid.uuid4().hex
+            client = scope.get("client")
+            self.active[session_id] = {
+                "client": client[0] if client else None,
+                "connected_at": datetime.now(timezone.utc).isoformat(),
+            }
+            return session_id
+
+    async def __call__(self, scope, receive, send):
+        session_id = self._open(scope)
+        if session_id is None:
+            await WebSocket(scope, receive, send).close(code=CLOSE_TRY_AGAIN, reason="too many sessions")
+            return
+        # 讓 get_http_headers() / get_http_request() 在這個 session 的所有請求中都拿到握手時的標頭
+        token = _current_http_request.set(HTTPConnection(scope))
+        try:
+            async with websocket_server(scope, receive, send) as (read_stream, write_stream):
+                low = self.server._mcp_server
+                await low.run(read_stream, write_stream, low.create_initialization_options())
+        finally:
+            _current_http_request.reset(token)
+            with self.lock:
+                self.active.pop(session_id, None)
+
+
+def build_websocket_app(server, endpoint, path="/mcp"):
+    """WebSocket 端點加上 FastMCP 的自訂 HTTP 路由 (健康檢查、管理端點)，含 server 的 lifespan"""
+
+    @contextlib.asynccontextmanager
+    async def lifespan(app):
+        async with server._lifespan_manager():
+            yield
+
+    routes = [WebSocketRoute(path, endpoint)] + server._get_additional_http_routes()
+    return Starlette(routes=routes, lifespan=lifespan)
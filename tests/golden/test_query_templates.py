@@ -0,0 +1,50 @@
+"""查詢範本的 golden file 測試: 每個範本以 example 參數對每個支援的 Wazuh 版本產生查詢，
+與 queries/<版本>/<範本>.json 比對。不需要 Wazuh 環境:
+
+    pytest tests/golden -v
+
+刻意修改查詢 (或新增範本 / 支援版本) 後，以 UPDATE_GOLDEN=1 重新產生存檔，並在 PR 中檢視差異。
+"""
+
+import json
+import os
+import sys
+
+import pytest
+
+SRC = os.path.abspath(os.path.join(os.path.dirname(__file__), "..", "..", "src"))
+GOLDEN = os.path.join(os.path.dirname(__file__), "queries")
+if SRC not in sys.path:
+    sys.path.insert(0, SRC)
+
+import querylib  # noqa: E402
+
+
+def golden_path(version, name):
+    return os.path.join(GOLDEN, version, f"{name}.json")
+
+
+def serialize(query):
+    return json.dumps(query, indent=2, sort_keys=True, ensure_ascii=False) + "\n"
+
+
+@pytest.mark.parametrize("version", querylib.SUPPORTED_VERSIONS)
+@pytest.mark.parametrize("name", querylib.names())
+def test_template_matches_golden(version, name):
+    rendered = serialize(querylib.QueryLibrary(version).render(name, **querylib.example(name)))
+    path = golden_path(version, name)
+    if os.getenv("UPDATE_GOLDEN") == "1":
+        os.makedirs(os.path.dirname(path), exist_ok=True)
+        with open(path, "w", encoding="utf-8") as f:
+            f.write(rendered)
+        return
+    assert os.path.exists(path), f"{path} 不存在，請以 UPDATE_GOLDEN=1 產生"
+    with open(path, encoding="utf-8") as f:
+        assert rendered == f.read(), f"{name} 在 Wazuh {version} 產生的查詢與 golden file 不同"
+
+
+@pytest.mark.parametrize("version", querylib.SUPPORTED_VERSIONS)
+def test_no_stale_golden_files(version):
+    directory = os.path.join(GOLDEN, version)
+    stored = {f[:-5] for f in os.listdir(directory) if f.endswith(".json")} if os.path.isdir(directory) else set()
+    assert stored <= set(querylib.names()), f"已刪除的範本仍有 golden file: {sorted(stored - set(querylib.names()))}"
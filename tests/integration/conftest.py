@@ -0,0 +1,67 @@
+"""整合測試需要 Docker 與數 GB 記憶體，預設略過；以 WAZUH_MCP_INTEGRATION=1 啟用:
+
+    WAZUH_MCP_INTEGRATION=1 pytest tests/integration -v
+"""
+
+import os
+import sys
+import tempfile
+
+import pytest
+
+from harness import WazuhStack
+
+SRC = os.path.abspath(os.path.join(os.path.dirname(__file__), "..", "..", "src"))
+
+
+def pytest_configure(config):
+    config.addinivalue_line("markers", "integration: 需要 docker 化 Wazuh 的端對端測試")
+
+
+def pytest_collection_modifyitems(config, items):
+    if os.getenv("WAZUH_MCP_INTEGRATION") == "1":
+        return
+    skip = pytest.mark.skip(reason="整合測試預設略過，設定 WAZUH_MCP_INTEGRATION=1 啟用")
+    for item in items:
+        if "integration" in item.keywords:
+            item.add_marker(skip)
+
+
+@pytest.fixture(scope="session")
+def stack():
+    stack = WazuhStack()
+    stack.up()
+    try:
+        stack.seed()
+        yield stack
+    finally:
+        stack.down()
+
+
+@pytest.fixture(scope="session")
+def server(stack):
+    """指向測試環境的 MCP server 模組 (設定在 import 時讀取，所以要先設好環境變數)"""
+    state_dir = tempfile.mkdtemp(prefix="wazuh-mcp-it-")
+    os.environ.update(stack.env())
+    os.environ["WAZUH_MCP_STORE_URL"] = "sqlite://" + os.path.join(state_dir, "state.db")
+    os.environ["MCP_ALLOW_WRITES"] = "true"
+    os.environ["MCP_ALLOW_FILE_RETRIEVAL"] = "true"
+    os.environ["MCP_INGEST_TOKEN"] = "integration-ingest"
+    os.environ["MCP_ALLOW_DEBUG"] = "true"
+    os.environ["MCP_PERSIST_SESSIONS"] = "true"
+    os.environ["MCP_TOOL_POLICY_FILE"] = os.path.join(os.path.dirname(__file__), "fixtures", "tool_policies.yaml")
+    os.environ["DETECTION_RULES_ROOT"] = os.path.join(os.path.dirname(__file__), "fixtures")
+    for name in ("MCP_GLOBAL_FILTER", "MCP_GLOBAL_EXCLUDE", "MCP_PRINCIPAL_SCOPES", "MCP_AGENT_ENVIRONMENTS"):
+        os.environ.pop(name, None)
+    if SRC not in sys.path:
+        sys.path.insert(0, SRC)
+    import main
+    return main
+
+
+@pytest.fixture(scope="session")
+def client(server):
+    """共用的同步 in-process client (src/testing.py)"""
+    from testing import InProcessClient
+    with InProcessClient(server.mcp) as client:
+        yield client
@@ -0,0 +1,124 @@
+"""整合測試環境: 以 docker compose 啟動單節點 Wazuh (manager + indexer)，並寫入範例資料。
+
+範例資料分兩部分:
+- 模擬 agent: 透過 Manager API 註冊 SIMULATED_AGENTS，讓 agent 相關工具有資料可查
+- 範例告警: fixtures/sample_alerts.json 以 Indexer bulk API 寫入今天的 wazuh-alerts 索引，
+  時間戳記改寫為「現在 - offset」，格式與 analysisd 產生、filebeat 送出的告警相同
+
+環境變數:
+- WAZUH_VERSION            Wazuh 映像檔版本 (預設 4.7.3)
+- TEST_INDEXER_PORT / TEST_API_PORT   對外的埠號 (預設 19200 / 15500)
+- WAZUH_MCP_KEEP_STACK=1   測試結束後不要清除容器 (方便除錯)
+"""
+
+import json
+import os
+import subprocess
+import time
+from datetime import datetime, timedelta, timezone
+
+import requests
+import urllib3
+
+urllib3.disable_warnings(urllib3.exceptions.InsecureRequestWarning)
+
+ROOT = os.path.abspath(os.path.join(os.path.dirname(__file__), "..", ".."))
+COMPOSE_FILE = os.path.join(ROOT, "docker", "docker-compose.integration.yml")
+FIXTURES = os.path.join(os.path.dirname(__file__), "fixtures")
+PROJECT = "wazuh-mcp-integration"
+
+INDEXER_PASSWORD = "IntegrationTest1!"
+API_USER = "wazuh-wui"
+API_PASSWORD = "IntegrationTest1!"
+SIMULATED_AGENTS = [("web-01", "10.0.0.11"), ("hr-laptop-07", "10.0.0.27"), ("dc-01", "10.0.0.5")]
+
+
+class WazuhStack:
+    def __init__(self, startup_timeout=600):
+        self.indexer_port = int(os.getenv("TEST_INDEXER_PORT", "19200"))
+        self.api_port = int(os.getenv("TEST_API_PORT", "15500"))
+        self.startup_timeout = startup_timeout
+        self.agent_ids = {}
+
+    def env(self):
+        """給 src/main.py 使用的設定 (必須在 import main 之前套用)"""
+        return {
+            "WAZUH_API_HOST": "localhost",
+            "WAZUH_API_PORT": str(self.api_port),
+            "WAZUH_API_USERNAME": API_USER,
+            "WAZUH_API_PASSWORD": API_PASSWORD,
+            "WAZUH_INDEXER_HOST": "localhost",
+            "WAZUH_INDEXER_PORT": str(self.indexer_port),
+            "WAZUH_INDEXER_USERNAME": "admin",
+            "WAZUH_INDEXER_PASSWORD": INDEXER_PASSWORD,
+        }
+
+    def _compose(self, *args):
+        subprocess.run(["docker", "compose", "-p", PROJECT, "-f", COMPOSE_FILE, *args], check=True)
+
+    def up(self):
+        self._compose("up", "-d", "--wait")
+        self._wait(self._template_loaded, "filebeat 尚未載入 wazuh 索引範本")
+
+    def down(self):
+        if os.getenv("WAZUH_MCP_KEEP_STACK") != "1":
+            self._compose("down", "-v")
+
+    def _wait(self, ready, message):
+        deadline = time.time() + self.startup_timeout
+        while time.time() < deadline:
+            try:
+                if ready():
+                    return
+            except requests.RequestException:
+                pass
+            time.sleep(5)
+        raise TimeoutError(message)
+
+    def _indexer(self, method, path, **kwargs):
+        return requests.request(method, f"https://localhost:{self.indexer_port}/{path}",
+                                auth=("admin", INDEXER_PASSWORD), verify=False, timeout=30, **kwargs)
+
+    def _template_loaded(self):
+        # 沒有範本的話欄位會被動態對應成 text，聚合查詢會失敗
+        return self._indexer("GET", "_template/wazuh").status_code == 200
+
+    def _api_token(self):
+        resp = requests.post(f"https://localhost:{self.api_port}/security/user/authenticate",
+                             auth=(API_USER, API_PASSWORD), verify=False, timeout=30)
+        resp.raise_for_status()
+        return resp.json()["data"]["token"]
+
+    def register_agents(self):
+        headers = {"Authorization": f"Bearer {self._api_token()}"}
+        for name, ip in SIMULATED_AGENTS:
+            resp = requests.post(f"https://localhost:{self.api_port}/agents", headers=headers,
+                                 json={"name": name, "ip": ip}, verify=False, timeout=30)
+            if resp.status_code == 200:
+                self.agent_ids[name] = resp.json()["data"]["id"]
+
+    def seed_alerts(self):
+        """寫入範例告警，回傳寫入筆數"""
+        with open(os.path.join(FIXTURES, "sample_alerts.json"), encoding="utf-8") as f:
+            alerts = json.load(f)
+        now = datetime.now(timezone.utc)
+        index = f"wazuh-alerts-4.x-{now:%Y.%m.%d}"
+        lines = []
+        for alert in alerts:
+            offset = timedelta(minutes=alert.pop("_offset_minutes", 0))
+            alert["timestamp"] = (now - offset).strftime("%Y-%m-%dT%H:%M:%S.000+0000")
+            name = alert.get("agent", {}).get("name")
+            if name in self.agent_ids:
+                alert["agent"]["id"] = self.agent_ids[name]
+            lines.append(json.dumps({"index": {"_index": index}}))
+            lines.append(json.dumps(alert))
+        resp = self._indexer("POST", "_bulk?refresh=true", data="\n".join(lines) + "\n",
+                             headers={"Content-Type": "application/x-ndjson"})
+        resp.raise_for_status()
+        if resp.json().get("errors"):
+            raise RuntimeError(f"範例告警寫入失敗: {resp.text[:500]}")
+        return len(alerts)
+
+    def seed(self):
+        self.register_agents()
+        return self.seed_alerts()
@@ -0,0 +1,235 @@
+"""以 main.serve() 在同一個 process 內嵌入 MCP Server (記憶體串流)，websocket 傳輸、API key 驗證、工具權限、告警推送、session 接續、SSE 事件重送、session 過期與批次請求的 app。"""
+
+import json
+import time
+
+import anyio
+import pytest
+from mcp import ClientSession
+from starlette.testclient import TestClient
+
+pytestmark = pytest.mark.integration
+
+
+def test_serve_over_memory_transport(server):
+    async def run():
+        transport, (read, write) = server.memory_transport()
+        async with anyio.create_task_group() as tg:
+            tg.start_soon(lambda: server.serve(transport, background=False))
+            async with ClientSession(read, write) as session:
+                await session.initialize()
+                tools = [t.name for t in (await session.list_tools()).tools]
+                result = await session.call_tool("list_agents", {})
+            tg.cancel_scope.cancel()
+        return tools, result.content[0].text
+    tools, text = anyio.run(run)
+    assert "search_alerts" in tools
+    assert "web-01" in text
+
+
+def test_in_process_client(client):
+    assert "list_agents" in client.list_tools()
+    agents = client.call_tool_json("list_agents")
+    assert any(a["name"] == "web-01" for a in agents)
+    assert "sshd" in client.read_resource("wazuh://rules/5710")
+
+
+def test_websocket_transport(server):
+    app = server.build_websocket_app(server.mcp, server.WS_SESSIONS)
+    with TestClient(app) as http, http.websocket_connect("/mcp", subprotocols=["mcp"]) as ws:
+        ws.send_json({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {
+            "protocolVersion": "2025-06-18", "capabilities": {},
+            "clientInfo": {"name": "integration-test", "version": "1.0"}}})
+        init = ws.receive_json()
+        ws.send_json({"jsonrpc": "2.0", "method": "notifications/initialized"})
+        assert len(server.WS_SESSIONS.sessions()) == 1
+        ws.send_json({"jsonrpc": "2.0", "id": 2, "method": "tools/call",
+                      "params": {"name": "list_agents", "arguments": {}}})
+        result = ws.receive_json()
+        assert http.get("/healthz").status_code == 200
+    assert init["result"]["serverInfo"]["name"] == "Wazuh-Threat-Hunter"
+    assert "web-01" in result["result"]["content"][0]["text"]
+    assert server.WS_SESSIONS.sessions() == []
+
+
+def test_bearer_auth(server):
+    app = server.BearerAuth(server.build_websocket_app(server.mcp, server.WS_SESSIONS), "/mcp",
+                            server.APIKeys("integration:test-key"))
+    with TestClient(app) as http:
+        assert http.get("/healthz").status_code == 200
+        denied = http.post("/mcp", json={"jsonrpc": "2.0", "id": 1, "method": "ping"})
+        assert denied.status_code == 401
+        assert denied.headers["www-authenticate"].startswith("Bearer")
+        wrong = http.post("/mcp", headers={"Authorization": "Bearer nope"}, json={})
+        assert wrong.status_code == 401
+        with http.websocket_connect("/mcp", subprotocols=["mcp"],
+                                    headers={"Authorization": "Bearer test-key"}) as ws:
+            ws.send_json({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {
+                "protocolVersion": "2025-06-18", "capabilities": {},
+                "clientInfo": {"name": "integration-test", "version": "1.0"}}})
+            assert ws.receive_json()["result"]["serverInfo"]["name"] == "Wazuh-Threat-Hunter"
+
+
+
+def test_origin_check(server):
+    app = server.OriginCheck(server.build_websocket_app(server.mcp, server.WS_SESSIONS), "/mcp",
+                             server.OriginPolicy("https://console.example.com", cors=True))
+    ping = {"jsonrpc": "2.0", "id": 1, "method": "ping"}
+    with TestClient(app) as http:
+        evil = http.post("/mcp", headers={"Origin": "http://evil.example"}, json=ping)
+        assert evil.status_code == 403 and evil.json() == {"error": "origin not allowed"}
+        assert http.get("/healthz", headers={"Origin": "http://evil.example"}).status_code == 200
+        preflight = http.options("/mcp", headers={"Origin": "https://console.example.com",
+                                                  "Access-Control-Request-Method": "POST"})
+        assert preflight.status_code == 204
+        assert preflight.headers["access-control-allow-origin"] == "https://console.example.com"
+        assert "Mcp-Session-Id" in preflight.headers["access-control-allow-headers"]
+        allowed = http.post("/mcp", headers={"Origin": "https://console.example.com"}, json=ping)
+        assert allowed.status_code != 403
+        assert allowed.headers["access-control-allow-origin"] == "https://console.example.com"
+        assert http.post("/mcp", headers={"Origin": "http://localhost:6274"}, json=ping).status_code != 403
+        assert http.post("/mcp", json=ping).status_code != 403
+        with pytest.raises(Exception):
+            with http.websocket_connect("/mcp", subprotocols=["mcp"], headers={"Origin": "http://evil.example"}):
+                pass
+
+
+def test_tool_policy(server):
+    app = server.BearerAuth(server.build_websocket_app(server.mcp, server.WS_SESSIONS), "/mcp",
+                            server.APIKeys("integration:test-key"))
+    with TestClient(app) as http, http.websocket_connect("/mcp", subprotocols=["mcp"],
+                                                        headers={"Authorization": "Bearer test-key"}) as ws:
+        ws.send_json({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {
+            "protocolVersion": "2025-06-18", "capabilities": {},
+            "clientInfo": {"name": "integration-test", "version": "1.0"}}})
+        ws.receive_json()
+        ws.send_json({"jsonrpc": "2.0", "method": "notifications/initialized"})
+        ws.send_json({"jsonrpc": "2.0", "id": 2, "method": "tools/list"})
+        tools = [t["name"] for t in ws.receive_json()["result"]["tools"]]
+        ws.send_json({"jsonrpc": "2.0", "id": 3, "method": "tools/call",
+                      "params": {"name": "inject_test_events", "arguments": {"purge": True}}})
+        denied = ws.receive_json()["result"]["content"][0]["text"]
+    assert "search_alerts" in tools and "list_agents" in tools
+    assert "inject_test_events" not in tools
+    assert "MCP_TOOL_POLICY_FILE" in denied
+
+
+def test_ingest_pushed_alerts(server, client):
+    alert = {"timestamp": "2024-01-01T00:00:00.000+0000", "rule": {"id": "100999", "level": 12},
+             "agent": {"id": "001", "name": "web-01"}}
+    with TestClient(server.mcp.http_app()) as http:
+        assert http.post("/ingest/alerts", json=alert).status_code == 401
+        headers = {"Authorization": "Bearer integration-ingest"}
+        assert http.post("/ingest/alerts", headers=headers, content=b"not json").status_code == 400
+        pushed = http.post("/ingest/alerts", headers=headers, json=[alert, alert])
+    assert pushed.status_code == 202
+    assert pushed.json()["accepted"] == 2
+    tail = client.call_tool_json("tail_live_alerts", {"cursor": pushed.json()["cursor"] - 2, "min_level": 10})
+    assert tail["count"] == 2 and tail["missed"] == 0
+    assert tail["alerts"][0]["rule"]["id"] == "100999"
+    assert client.call_tool_json("tail_live_alerts", {"cursor": tail["next_cursor"]})["count"] == 0
+
+
+def rpc(http, message, session_id=None):
+    """Streamable HTTP 的單一 JSON-RPC 請求；回傳 (response, 解析後的 JSON-RPC 回應或 None)"""
+    headers = {"Accept": "application/json, text/event-stream", "MCP-Protocol-Version": "2025-06-18"}
+    if session_id:
+        headers["Mcp-Session-Id"] = session_id
+    response = http.post("/mcp", headers=headers, json=message)
+    data = [line[5:].strip() for line in response.text.splitlines() if line.startswith("data:")]
+    return response, json.loads(data[-1]) if data else None
+
+
+def test_session_resumes_after_restart(server):
+    def restarted_app():
+        # 每次 http_app() 都是新的 session manager，等同伺服器重啟後記憶體中沒有任何 session
+        return server.mcp.http_app(middleware=server.http_middleware())
+
+    with TestClient(restarted_app()) as http:
+        init, _ = rpc(http, {"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {
+            "protocolVersion": "2025-06-18", "capabilities": {"elicitation": {}},
+            "clientInfo": {"name": "resume-test", "version": "1.0"}}})
+        session_id = init.headers["mcp-session-id"]
+        rpc(http, {"jsonrpc": "2.0", "method": "notifications/initialized"}, session_id)
+        _, before = rpc(http, {"jsonrpc": "2.0", "id": 2, "method": "tools/list"}, session_id)
+    with TestClient(restarted_app()) as http:
+        resumed, after = rpc(http, {"jsonrpc": "2.0", "id": 3, "method": "tools/list"}, session_id)
+        unknown, _ = rpc(http, {"jsonrpc": "2.0", "id": 4, "method": "tools/list"}, "0" * 32)
+        http.delete("/mcp", headers={"Mcp-Session-Id": session_id})
+    assert resumed.status_code == 200
+    assert [t["name"] for t in after["result"]["tools"]] == [t["name"] for t in before["result"]["tools"]]
+    assert unknown.status_code in (400, 404)
+    assert server.SESSIONS.load(session_id) is None
+
+
+def test_sse_events_replay_after_reconnect(server):
+    with TestClient(server.mcp.http_app(middleware=server.http_middleware())) as http:
+        init, _ = rpc(http, {"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {
+            "protocolVersion": "2025-06-18", "capabilities": {},
+            "clientInfo": {"name": "replay-test", "version": "1.0"}}})
+        session_id = init.headers["mcp-session-id"]
+        rpc(http, {"jsonrpc": "2.0", "method": "notifications/initialized"}, session_id)
+        # 帶 progressToken 的查詢: 同一個 stream 先送進度通知，最後才是結果
+        call, _ = rpc(http, {"jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": {
+            "name": "search_alerts", "arguments": {"kql": "rule.groups:sshd"}, "_meta": {"progressToken": "replay"}}},
+            session_id)
+        event_ids = [line[3:].strip() for line in call.text.splitlines() if line.startswith("id:")]
+
+        async def replay(session):
+            replayed = []
+
+            async def collect(event):
+                replayed.append((event.event_id, event.message.model_dump(by_alias=True, exclude_none=True)))
+            stream = await server.SSE_EVENTS.view(session).replay_events_after(event_ids[0], collect)
+            return stream, replayed
+
+        stream, replayed = anyio.run(replay, session_id)
+        stolen, _ = anyio.run(replay, "0" * 32)
+        http.delete("/mcp", headers={"Mcp-Session-Id": session_id})
+        forgotten, _ = anyio.run(replay, session_id)
+    assert len(event_ids) >= 2
+    assert stream is not None
+    assert [event_id for event_id, _ in replayed] == event_ids[1:]
+    assert replayed[-1][1]["id"] == 2 and "result" in replayed[-1][1]
+    assert stolen is None and forgotten is None
+
+
+def test_idle_session_expires(server, monkeypatch):
+    monkeypatch.setattr(server.SESSION_LIFETIMES, "idle_timeout", 1)
+    with TestClient(server.mcp.http_app(middleware=server.http_middleware())) as http:
+        init, _ = rpc(http, {"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {
+            "protocolVersion": "2025-06-18", "capabilities": {},
+            "clientInfo": {"name": "expiry-test", "version": "1.0"}}})
+        session_id = init.headers["mcp-session-id"]
+        rpc(http, {"jsonrpc": "2.0", "method": "notifications/initialized"}, session_id)
+        _, listed = rpc(http, {"jsonrpc": "2.0", "id": 2, "method": "tools/list"}, session_id)
+        time.sleep(1.5)
+        expired, _ = rpc(http, {"jsonrpc": "2.0", "id": 3, "method": "tools/list"}, session_id)
+    assert listed["result"]["tools"]
+    assert expired.status_code == 404
+    error = expired.json()["error"]
+    assert error["code"] == -32001 and "idle for more than 1s" in error["message"]
+    assert session_id not in server.HISTORY.sessions
+    assert server.SESSIONS.load(session_id) is None
+
+
+def test_batch_request(server):
+    initialize = {"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {
+        "protocolVersion": "2025-06-18", "capabilities": {}, "clientInfo": {"name": "batch-test", "version": "1.0"}}}
+    with TestClient(server.mcp.http_app(middleware=server.http_middleware())) as http:
+        response = http.post("/mcp", headers={"Accept": "application/json, text/event-stream"}, json=[
+            initialize,
+            {"jsonrpc": "2.0", "method": "notifications/initialized"},
+            {"jsonrpc": "2.0", "id": 2, "method": "tools/list"},
+            {**initialize, "id": 3},
+        ])
+        too_large = http.post("/mcp", headers={"Accept": "application/json, text/event-stream"},
+                              json=[{"jsonrpc": "2.0", "id": i, "method": "ping"}
+                                    for i in range(server.BATCH_MAX_MESSAGES + 1)])
+    assert response.status_code == 200 and response.headers["mcp-session-id"]
+    replies = {m["id"]: m for m in (json.loads(line[5:]) for line in response.text.splitlines()
+                                    if line.startswith("data:")) if "id" in m}
+    assert replies[1]["result"]["serverInfo"]["name"] == "Wazuh-Threat-Hunter"
+    assert replies[2]["result"]["tools"]
+    assert "initialize must be the first message" in replies[3]["error"]["message"]
+    assert too_large.status_code == 400 and too_large.json()["error"]["code"] == -32600
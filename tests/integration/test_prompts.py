@@ -0,0 +1,37 @@
+"""MCP prompts (prompts/list、prompts/get) 的端對端測試: prompt 內容必須帶入 Wazuh 的即時資料。"""
+
+import asyncio
+
+import pytest
+from fastmcp import Client
+
+pytestmark = pytest.mark.integration
+
+PROMPT_CASES = [
+    ("triage_alert", {"alert_id": "1700000000.100801"}, "dc-01"),
+    ("hunt_lateral_movement", {"agent": "web-01"}, "203.0.113.7"),
+    ("summarize_critical_alerts", {"min_severity": "high"}, "dc-01"),
+]
+
+
+def get_prompt(server, name, args):
+    async def run():
+        async with Client(server.mcp) as client:
+            result = await client.get_prompt(name, args)
+            return result.messages[0].content.text
+    return asyncio.run(run())
+
+
+def test_every_prompt_has_a_case(server):
+    async def run():
+        async with Client(server.mcp) as client:
+            return [p.name for p in await client.list_prompts()]
+    missing = sorted(set(asyncio.run(run())) - {name for name, _, _ in PROMPT_CASES})
+    assert not missing, f"以下 prompt 沒有整合測試案例: {missing}"
+
+
+@pytest.mark.parametrize("name,args,expect", PROMPT_CASES)
+def test_prompt_includes_live_context(server, name, args, expect):
+    text = get_prompt(server, name, args)
+    assert "無法取得的資料" not in text, text[:1000]
+    assert expect in text, text[:1000]
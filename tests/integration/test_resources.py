@@ -0,0 +1,96 @@
+"""MCP 資源 (resources/list、resources/read) 的端對端測試，規則內容來自 Manager 的預設規則庫。"""
+
+import asyncio
+import json
+
+import pytest
+from fastmcp import Client
+from mcp.types import PromptReference, ResourceTemplateReference
+
+pytestmark = pytest.mark.integration
+
+RESOURCE_CASES = [
+    ("wazuh://rules", "by_level"),
+    ("wazuh://rules/5710", "sshd"),
+    ("wazuh://rules/groups/sshd", "wazuh://rules/5710"),
+    ("wazuh://rules/levels/5", "5710"),
+    ("wazuh://agents", "by_status"),
+    ("wazuh://agents/000", "wazuh://agents/000/vulnerabilities"),
+    ("wazuh://agents/000/vulnerabilities", "by_severity"),
+    ("wazuh://alerts/1700000000.100801", "dc-01"),
+]
+
+
+def read(server, uri):
+    async def run():
+        async with Client(server.mcp) as client:
+            contents = await client.read_resource(uri)
+            return contents[0].text
+    return asyncio.run(run())
+
+
+def test_rules_are_listed(server):
+    async def run():
+        async with Client(server.mcp) as client:
+            return [str(r.uri) for r in await client.list_resources()]
+    uris = asyncio.run(run())
+    assert "wazuh://rules" in uris
+    assert "wazuh://rules/5710" in uris
+
+
+def test_resource_templates_are_listed(server):
+    async def run():
+        async with Client(server.mcp) as client:
+            return [t.uriTemplate for t in await client.list_resource_templates()]
+    templates = asyncio.run(run())
+    for uri in ("wazuh://agents/{agent_id}", "wazuh://agents/{agent_id}/vulnerabilities", "wazuh://alerts/{alert_id}"):
+        assert uri in templates
+
+
+def test_unknown_agent_is_an_error(server):
+    with pytest.raises(Exception):
+        read(server, "wazuh://agents/999")
+
+
+@pytest.mark.parametrize("uri,expect", RESOURCE_CASES)
+def test_read_resource(server, uri, expect):
+    assert expect in read(server, uri)
+
+
+def test_hypotheses_are_documents(server):
+    """管理工具保存的假說可以當成資源讀取，訂閱後建立或更新時收到 notifications/resources/updated"""
+    updated = []
+
+    async def on_message(message):
+        root = getattr(message, "root", None)
+        if getattr(root, "method", None) == "notifications/resources/updated":
+            updated.append(str(root.params.uri))
+
+    async def run():
+        async with Client(server.mcp, message_handler=on_message) as client:
+            await client.session.subscribe_resource("wazuh://hypotheses")
+            result = await client.call_tool("record_hypothesis", {"statement": "resource subscription check"})
+            created = json.loads(result.content[0].text)["id"]
+            await asyncio.sleep(0.5)
+            listing = (await client.read_resource("wazuh://hypotheses"))[0].text
+            document = (await client.read_resource(f"wazuh://hypotheses/{created}"))[0].text
+            return created, listing, document
+    created, listing, document = asyncio.run(run())
+    assert f"wazuh://hypotheses/{created}" in listing
+    assert "resource subscription check" in document
+    assert "wazuh://hypotheses" in updated
+
+
+@pytest.mark.parametrize("ref,argument,expect", [
+    (ResourceTemplateReference(type="ref/resource", uri="wazuh://rules/{rule_id}"), {"name": "rule_id", "value": "571"}, "5710"),
+    (ResourceTemplateReference(type="ref/resource", uri="wazuh://agents/{agent_id}"), {"name": "agent_id", "value": "web"}, None),
+    (PromptReference(type="ref/prompt", name="hunt_lateral_movement"), {"name": "agent", "value": "web"}, "web-01"),
+])
+def test_argument_completion(server, ref, argument, expect):
+    async def run():
+        async with Client(server.mcp) as client:
+            return await client.complete(ref, argument)
+    completion = asyncio.run(run())
+    assert completion.values, completion
+    if expect:
+        assert expect in completion.values
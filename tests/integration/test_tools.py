@@ -0,0 +1,282 @@
+"""對每個 MCP 工具做端對端呼叫 (in-process FastMCP client -> 真實的 Wazuh Manager API / Indexer)。
+
+新增工具時必須在 TOOL_CASES 加上至少一組參數，test_matrix_covers_every_tool 會檢查。
+expect 是結果中必須出現的字串 (來自 fixtures/sample_alerts.json 或模擬 agent)。
+"""
+
+import asyncio
+import json
+import logging
+import os
+
+import pytest
+from fastmcp import Client
+from mcp.types import ElicitResult
+
+pytestmark = pytest.mark.integration
+
+RULES_BUNDLE = os.path.join(os.path.dirname(__file__), "fixtures", "rules")
+
+ERROR_PREFIXES = ("錯誤", "查詢語法錯誤", "無法連線", "無法解析", "Indexer 回傳錯誤", "API 回傳錯誤",
+                  "查詢失敗", "發生錯誤", "發生例外錯誤", "基準期間查詢失敗", "首次出現資料庫更新失敗",
+                  "上傳候選規則")
+
+TOOL_CASES = {
+    "list_agents": [
+        ({}, "web-01"),
+        ({"status": "never_connected", "name": "web"}, "web-01"),
+        ({"ip": "10.0.0.0/24", "limit": 10}, "dc-01"),
+        ({"version": ">=4.0", "status": "active"}, "\"000\""),
+    ],
+    "get_infrastructure_status": [({}, "total")],
+    # token 每次不同，見 test_oversized_results_are_paged
+    "continue_result": [],
+    "search_alerts": [
+        ({"kql": "rule.groups:sshd"}, "203.0.113.7"),
+        ({"kql": "", "group_by": ["rule.id"]}, "5710"),
+        ({"kql": "", "sample": "stratified:agent", "limit": 5}, "agent"),
+        ({"kql": "", "dedupe_by": ["data.srcip"], "min_severity": "low"}, "_count"),
+        ({"kql": "rule.groups:sshd", "dedupe_by": ["full_log:pattern"]}, "<ip>"),
+        ({"kql": "", "time_range": "now-30d"}, "slices"),
+        ({"kql": "", "agent_groups": ["default"]}, "agent_groups"),
+        ({"kql": "", "group_by": ["rule.id"], "time_range": "now-24h", "sparkline": True}, "chart"),
+        ({"kql": "rule.groups:sshd", "debug": True}, "backend_requests"),
+    ],
+    "list_agent_groups": [({}, "default")],
+    "wazuh_join": [
+        ({"left_kql": "rule.groups:sshd", "right_kql": "", "left_key": "data.srcip",
+          "left_fields": ["rule.id", "agent.name"], "right_fields": ["rule.id"]}, "203.0.113.7"),
+        ({"left_kql": "rule.id:5710", "right_kql": "rule.id:5715", "left_key": "agent.id", "how": "left",
+          "within": "1h"}, "left_matched"),
+    ],
+    "numeric_field_stats": [({"field": "rule.level", "time_range": "now-30d", "interval": "1d"}, "p95"),
+                            ({"field": "rule.level", "group_by": "agent.name", "metric": "max"}, "web-01")],
+    "alert_trends": [({"time_range": "now-30d"}, "5710"), ({"group_by": "agent", "limit": 3}, "web-01")],
+    "wazuh_backend_latency": [({"window": "24h"}, "indexer_search")],
+    "wazuh_environment_brief": [({}, "Agents"), ({"output_format": "json"}, "retention")],
+    "hunt_sequence": [({"steps": ["rule.id:5710", "rule.id:5715"], "join_by": "data.srcip", "maxspan": "10m"},
+                       "203.0.113.7")],
+    "list_rule_groups": [({"include_unseen": True}, "sshd"), ({"sparkline": True, "time_range": "now-24h"}, "peak")],
+    "usage_report": [({}, "principals")],
+    "check_data_consistency": [({"window": "24h"}, "status")],
+    "first_seen_observables": [({"kind": "domain", "since": "7d"}, "observables")],
+    # 依序執行: record_hypothesis 建立的 H1 接著被附上佐證、結案，最後出現在報告中
+    "record_hypothesis": [
+        ({"statement": "203.0.113.7 brute-forced SSH on web-01", "case": "integration"}, "\"id\": \"H1\""),
+        ({"hypothesis_id": "H1", "status": "confirmed", "confidence": 0.8, "note": "5715 after 5710"}, "confirmed"),
+    ],
+    "attach_evidence": [({"hypothesis_id": "H1", "summary": "sshd failures then success", "supports": True,
+                          "tool": "search_alerts", "arguments": {"kql": "rule.groups:sshd"}}, "supporting")],
+    "hypothesis_report": [({"case": "integration"}, "sshd failures then success")],
+    "hunt_tls_fingerprints": [({"time_range": "now-1d"}, "e7d705a3286e19ea42f587b344ee6865")],
+    "dns_analytics": [({"min_dga_score": 0.5}, "xjw3kq9zpl2m.com")],
+    "privileged_command_summary": [({}, "/usr/bin/cat /etc/shadow")],
+    "hunt_persistence": [({}, "UpdaterSvc")],
+    "hunt_registry_changes": [({"presets": ["run_keys"]}, "Updater")],
+    "autostart_inventory": [({"kind": "startup"}, "Updater")],
+    "hunt_removable_media": [({}, "payroll_2026.xlsx")],
+    "hunt_office365_activity": [({}, "billing-update@example.net"),
+                                ({"activities": ["oauth_consent", "mass_download"]}, "MailSync Pro")],
+    "hunt_github_activity": [({}, "example-corp/payments-api"), ({"activities": ["token_creation"]}, "actors")],
+    "get_osquery_results": [({"query_name": "listening_ports"}, "/tmp/.x/nc"),
+                            ({"pack": "incident-response", "agent": "web-01", "action": "added"}, "incident-response")],
+    # 範例資料只寫入告警索引，archives 沒有資料
+    "get_command_output": [({"source": "alerts", "command": "netstat listening ports"}, "31337/nc"),
+                           ({"source": "alerts", "agent": "web-01", "latest_only": False}, "previous_output")],
+    "firewall_summary": [({"group_by": "src"}, "198.51.100.23")],
+    "entity_graph": [({"kql": "agent.name:web-01"}, "ip:198.51.100.23"),
+                     ({"output_format": "dot", "kinds": ["user", "process"]}, "executed")],
+    "wazuh_cluster_messages": [({"kql": "rule.groups:sshd"}, "password for"),
+                               ({"source": "archives"}, "templates")],
+    "scan_credential_exposure": [({}, "\"by_kind\""),
+                                 ({"kinds": ["url_basic_auth", "private_key"], "limit": 100}, "\"findings_total\"")],
+    "map_atomic_tests": [({"technique": "T1543.003"}, "Service Installation CMD")],
+    "verify_atomic_test": [({"technique": "T1543.003", "agent": "dc-01", "since": "now-2h"}, "verdict")],
+    "review_detection_rules": [
+        ({"path": RULES_BUNDLE}, "\"valid\": true"),
+        ({"path": RULES_BUNDLE, "deploy": True}, "\"failed\": 0"),
+    ],
+    "draft_detection_rule": [
+        ({"kql": "rule.groups:sshd"}, "rule_xml"),
+        ({"kql": "rule.groups:sshd", "frequency": 3, "same_field": "data.srcip"}, "<same_srcip />"),
+    ],
+    "simulate_rule_noise": [
+        ({"path": RULES_BUNDLE, "days": 30}, "would_fire"),
+        ({"rule_xml": '<group name="sshd,"><rule id="100900" level="10" frequency="2" timeframe="3600">'
+                      '<if_matched_sid>5710</if_matched_sid><same_srcip /><description>ssh noise</description>'
+                      '</rule></group>'}, "\"verdict\""),
+    ],
+    # 依序執行: 規劃 R1 -> 建立暫存 group -> 套用第一批 -> 復原 (刪除暫存 group)
+    "plan_config_rollout": [({"group": "default", "config": "<agent_config><localfile><location>"
+                                                           "/var/log/mcp-rollout.log</location><log_format>syslog"
+                                                           "</log_format></localfile></agent_config>"},
+                             "\"plan\": \"R1\"")],
+    "apply_config_rollout": [
+        ({"plan_id": "R1", "action": "stage"}, "staged"),
+        ({"plan_id": "R1", "action": "wave"}, "in_progress"),
+        ({"plan_id": "R1", "action": "rollback"}, "rolled_back"),
+    ],
+    "search_alerts_dsl": [
+        ({"body": {"query": {"term": {"rule.groups": "sshd"}}, "size": 5}}, "203.0.113.7"),
+        ({"body": '{"size": 0, "aggs": {"rules": {"terms": {"field": "rule.id", "size": 10}}}}'}, "5710"),
+    ],
+    "compare_rulesets": [
+        ({"candidate": RULES_BUNDLE}, "100100"),
+        ({"candidate": RULES_BUNDLE, "baseline": RULES_BUNDLE}, "\"unchanged\": 1"),
+    ],
+    # 已淘汰的舊名稱 (aliases.TOOL_ALIASES)
+    "get_wazuh_alert_summary": [({"limit": 5}, "_deprecation")],
+    "get_wazuh_agents": [({"status": "never_connected"}, "web-01")],
+    "get_wazuh_cluster_health": [({}, "get_infrastructure_status")],
+    "get_manager_file": [
+        ({"kind": "rule", "name": "0095-sshd_rules.xml"}, "sha256"),
+        ({"kind": "group", "group": "default", "name": "agent.conf", "chunk_size": 16}, "next_offset"),
+    ],
+    "inject_test_events": [
+        ({"mode": "index", "alerts": [{"rule": {"id": "100001", "level": 3, "description": "integration"}}]}, "batch_id"),
+        ({"mode": "manager", "events": ["Jan  1 00:00:00 web-01 sshd[1]: Invalid user mcp from 192.0.2.1"]}, "sent"),
+        ({"purge": True}, "purged"),
+    ],
+}
+
+
+def call(server, name, args):
+    async def run():
+        async with Client(server.mcp) as client:
+            result = await client.call_tool(name, args)
+            return result.content[0].text
+    return asyncio.run(run())
+
+
+def list_tool_names(server):
+    async def run():
+        async with Client(server.mcp) as client:
+            return [t.name for t in await client.list_tools()]
+    return asyncio.run(run())
+
+
+def test_matrix_covers_every_tool(server):
+    missing = sorted(set(list_tool_names(server)) - set(TOOL_CASES))
+    assert not missing, f"以下工具沒有整合測試案例: {missing}"
+
+
+@pytest.mark.parametrize("name,args,expect", [
+    (name, args, expect) for name, cases in TOOL_CASES.items() for args, expect in cases
+], ids=lambda v: v if isinstance(v, str) else None)
+def test_tool(server, name, args, expect):
+    text = call(server, name, args)
+    assert not text.startswith(ERROR_PREFIXES), text[:500]
+    assert expect in text, text[:1000]
+
+
+def test_rule_noise_refuses_paths_outside_rules_root(server, monkeypatch):
+    outside = call(server, "simulate_rule_noise", {"path": "/etc"})
+    assert outside.startswith("錯誤") and "DETECTION_RULES_ROOT" in outside
+    monkeypatch.setattr(server, "DETECTION_RULES_ROOT", None)
+    unset = call(server, "simulate_rule_noise", {"path": RULES_BUNDLE})
+    assert unset.startswith("錯誤") and "DETECTION_RULES_ROOT" in unset
+
+
+def test_oversized_results_are_paged(server, monkeypatch):
+    async def run():
+        async with Client(server.mcp) as client:
+            full = (await client.call_tool("list_agents", {})).content[0].text
+            monkeypatch.setattr(server.RESULT_PAGES, "max_bytes", 300)
+            first = await client.call_tool("list_agents", {})
+            pages, token = [first.content[0].text], first.structured_content["_truncated"]["continuation"]
+            while token:
+                page = json.loads((await client.call_tool("continue_result", {"token": token})).content[0].text)
+                pages.append(page["text"])
+                token = page["continuation"]
+            expired = (await client.call_tool("continue_result", {"token": first.structured_content["_truncated"]
+                                                                   ["continuation"]})).content[0].text
+            return full, pages, expired
+    full, pages, expired = asyncio.run(run())
+    assert len(pages) > 1 and all(len(p.encode()) <= 300 for p in pages)
+    assert json.loads("".join(pages)) == json.loads(full)
+    assert expired.startswith("錯誤")
+
+
+def test_split_query_reports_progress(server):
+    events = []
+
+    async def on_progress(progress, total, message):
+        events.append((progress, total, message))
+
+    async def run():
+        async with Client(server.mcp) as client:
+            await client.call_tool("search_alerts", {"kql": "", "time_range": "now-30d"}, progress_handler=on_progress)
+    asyncio.run(run())
+    assert events, "切段查詢應送出進度通知"
+    assert [p for p, _, _ in events] == sorted(p for p, _, _ in events)
+    assert events[-1][1] is not None
+
+
+def test_every_tool_declares_output_schema(server):
+    async def run():
+        async with Client(server.mcp) as client:
+            return await client.list_tools()
+    missing = [t.name for t in asyncio.run(run()) if not (t.outputSchema or {}).get("properties", {}).get("ok")]
+    assert not missing, f"以下工具沒有 outputSchema: {missing}"
+
+
+def test_structured_content(server):
+    async def run():
+        async with Client(server.mcp) as client:
+            agents = await client.call_tool("list_agents", {})
+            bad = await client.call_tool("search_alerts", {"kql": "rule.level:>"})
+            return agents.structured_content, bad.structured_content
+    agents, bad = asyncio.run(run())
+    assert agents["ok"] and any(a["name"] == "web-01" for a in agents["data"])
+    assert not bad["ok"] and bad["error"]
+
+
+@pytest.mark.parametrize("action,expect", [("decline", "使用者未確認"), ("accept", "purged")])
+def test_write_calls_ask_for_confirmation(server, action, expect):
+    prompts = []
+
+    async def on_elicit(message, response_type, params, context):
+        prompts.append(message)
+        return ElicitResult(action=action, content={} if action == "accept" else None)
+
+    async def run():
+        async with Client(server.mcp, elicitation_handler=on_elicit) as client:
+            result = await client.call_tool("inject_test_events", {"purge": True})
+            return result.content[0].text
+    text = asyncio.run(run())
+    assert prompts and "影響範圍" in prompts[0]
+    assert expect in text, text[:500]
+
+
+def test_read_only_mode(server, monkeypatch):
+    monkeypatch.setattr(server.READ_ONLY, "enabled", True)
+
+    async def run():
+        async with Client(server.mcp) as client:
+            tools = [tool.name for tool in await client.list_tools()]
+            deploy = await client.call_tool("review_detection_rules", {"path": RULES_BUNDLE, "deploy": True})
+            return tools, deploy.content[0].text
+    tools, deploy = asyncio.run(run())
+    assert "inject_test_events" not in tools
+    assert "review_detection_rules" in tools and "search_alerts" in tools
+    assert "MCP_READ_ONLY" in deploy
+
+
+def test_server_logs_are_forwarded_to_client(server):
+    messages = []
+
+    async def on_log(message):
+        messages.append(message)
+
+    async def run():
+        async with Client(server.mcp, log_handler=on_log) as client:
+            await client.set_logging_level("warning")
+            logging.getLogger("wazuh_mcp").warning("Indexer 慢查詢: integration-test")
+            logging.getLogger("wazuh_mcp").debug("低於訂閱等級，不應轉送")
+            for _ in range(50):
+                if messages:
+                    break
+                await asyncio.sleep(0.02)
+    asyncio.run(run())
+    assert [m.level for m in messages] == ["warning"]
+    assert "integration-test" in messages[0].data["message"]
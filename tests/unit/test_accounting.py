@@ -0,0 +1,74 @@
+"""每日用量配額 (accounting.UsageMeter): 達到上限時拒絕查詢、UTC 換日後重置、未識別的呼叫者共用 anonymous 配額。"""
+
+import os
+import sys
+
+import pytest
+
+SRC = os.path.abspath(os.path.join(os.path.dirname(__file__), "..", "..", "src"))
+if SRC not in sys.path:
+    sys.path.insert(0, SRC)
+
+import accounting  # noqa: E402
+from accounting import QuotaExceeded, UsageMeter, parse_quotas  # noqa: E402
+from storage import open_store  # noqa: E402
+
+
+@pytest.fixture
+def meter(tmp_path, monkeypatch):
+    monkeypatch.setattr(accounting, "_today", lambda: "2024-05-01")
+    store = open_store(f"sqlite:///{tmp_path / 'state.db'}")
+    yield UsageMeter(store, parse_quotas(1000, 0, '{"tenant-a": {"docs": 100}, "soc-lead": {"docs": 0}}'))
+    store.close()
+
+
+def test_daily_limit_is_enforced(meter):
+    meter.check("tenant-a")
+    meter.record("tenant-a", 60, 1024)
+    meter.check("tenant-a")
+    meter.record("tenant-a", 40, 1024)
+    with pytest.raises(QuotaExceeded):
+        meter.check("tenant-a")
+    assert meter.usage("tenant-a") == {"queries": 2, "docs": 100, "bytes": 2048}
+
+
+def test_overrides_and_unlimited_quotas(meter):
+    meter.record("tenant-b", 999, 0)
+    meter.check("tenant-b")
+    meter.record("tenant-b", 1, 0)
+    with pytest.raises(QuotaExceeded):
+        meter.check("tenant-b")
+    meter.record("soc-lead", 10 ** 9, 10 ** 9)
+    meter.check("soc-lead")
+
+
+def test_quota_resets_on_the_next_utc_day(meter, monkeypatch):
+    meter.record("tenant-a", 100, 0)
+    with pytest.raises(QuotaExceeded):
+        meter.check("tenant-a")
+    monkeypatch.setattr(accounting, "_today", lambda: "2024-05-02")
+    meter.check("tenant-a")
+    assert meter.usage("tenant-a")["docs"] == 0
+    assert meter.usage("tenant-a", "2024-05-01")["docs"] == 100
+
+
+def test_unidentified_callers_share_the_anonymous_bucket(meter):
+    # 沒有帶身分的 HTTP 請求都解析為 "anonymous"，不管從哪個位址連進來都算在同一份配額
+    meter.record("anonymous", 700, 0)
+    meter.record("anonymous", 300, 0)
+    with pytest.raises(QuotaExceeded):
+        meter.check("anonymous")
+    meter.check("tenant-a")
+    meter.check("ip:203.0.113.5")
+
+
+def test_client_ip_fallback_is_accounted_per_address(meter):
+    meter.record("ip:203.0.113.5", 1000, 0)
+    with pytest.raises(QuotaExceeded):
+        meter.check("ip:203.0.113.5")
+    meter.check("ip:203.0.113.6")
+
+
+def test_quota_overrides_must_be_an_object():
+    with pytest.raises(ValueError):
+        parse_quotas(0, 0, '["tenant-a"]')
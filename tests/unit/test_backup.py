@@ -0,0 +1,95 @@
+"""狀態的匯出 / 匯入 (backup): 匯出後以 --replace 匯入要得到相同的資料，舊版匯出檔會補跑遷移。"""
+
+import json
+import os
+import sys
+
+import pytest
+
+SRC = os.path.abspath(os.path.join(os.path.dirname(__file__), "..", "..", "src"))
+if SRC not in sys.path:
+    sys.path.insert(0, SRC)
+
+from backup import export_state, import_state  # noqa: E402
+from migrations import LATEST_VERSION, META_NAMESPACE, current_version, run_migrations  # noqa: E402
+from resumption import SESSION_NAMESPACE  # noqa: E402
+from rollups import ROLLUP_NAMESPACE  # noqa: E402
+from storage import open_store  # noqa: E402
+
+
+@pytest.fixture
+def stores(tmp_path):
+    opened = []
+
+    def make(name):
+        store = open_store(f"sqlite:///{tmp_path / name}")
+        run_migrations(store)
+        opened.append(store)
+        return store
+
+    yield make
+    for store in opened:
+        store.close()
+
+
+def seed(store):
+    store.put("cases", "c1", {"title": "可疑的 PowerShell", "status": "open", "alerts": ["a1", "a2"]})
+    store.put("cases", "c2", {"title": "beacon", "status": "closed"})
+    store.put("notes", "n1", {"text": "已通知客戶", "case": "c1"})
+    store.put(SESSION_NAMESPACE, "s1", {"principal": "tenant-a"})
+    store.put(ROLLUP_NAMESPACE, "2024-05-01", {"count": 10})
+
+
+def round_trip(bundle):
+    """與 export / import 子命令相同，經過 JSON 檔案格式"""
+    return json.loads(json.dumps(bundle, ensure_ascii=False))
+
+
+def test_export_then_replace_import_round_trips(stores):
+    source, target = stores("source.db"), stores("target.db")
+    seed(source)
+    target.put("cases", "stale", {"title": "舊資料"})
+    target.put("hunts", "h1", {"kql": "rule.level >= 10"})
+    bundle = round_trip(export_state(source))
+    assert import_state(target, bundle, replace=True) == {"cases": 2, "notes": 1}
+    assert export_state(target)["namespaces"] == {**bundle["namespaces"], "hunts": {"h1": {"kql": "rule.level >= 10"}}}
+
+
+def test_internal_namespaces_do_not_travel(stores):
+    source, target = stores("source.db"), stores("target.db")
+    seed(source)
+    bundle = round_trip(export_state(source))
+    assert set(bundle["namespaces"]) == {"cases", "notes"}
+    import_state(target, bundle)
+    assert target.list(SESSION_NAMESPACE) == [] and target.list(ROLLUP_NAMESPACE) == []
+    assert current_version(target) == LATEST_VERSION
+
+
+def test_merge_import_keeps_existing_entries(stores):
+    source, target = stores("source.db"), stores("target.db")
+    seed(source)
+    target.put("cases", "c3", {"title": "另一個案件"})
+    import_state(target, round_trip(export_state(source)))
+    assert [key for key, _ in target.list("cases")] == ["c1", "c2", "c3"]
+
+
+def test_old_schema_bundle_is_migrated(stores):
+    source, target = stores("source.db"), stores("target.db")
+    seed(source)
+    bundle = round_trip(export_state(source))
+    bundle["schema_version"] = 0
+    import_state(target, bundle, replace=True)
+    assert current_version(target) == LATEST_VERSION
+    assert target.get(META_NAMESPACE, "schema_version")["version"] == LATEST_VERSION
+    assert target.get("cases", "c1")["title"] == "可疑的 PowerShell"
+
+
+@pytest.mark.parametrize("bundle", [
+    [],
+    {"format": "something-else", "namespaces": {}},
+    {"format": "wazuh-mcp-state", "format_version": 99, "namespaces": {}},
+    {"format": "wazuh-mcp-state", "format_version": 1, "schema_version": LATEST_VERSION + 1, "namespaces": {}},
+])
+def test_foreign_or_newer_bundles_are_refused(stores, bundle):
+    with pytest.raises(ValueError):
+        import_state(stores("target.db"), bundle)
@@ -0,0 +1,81 @@
+"""search_alerts_dsl 的查詢本文防護 (dslguard.check): 會繞過租戶範圍或拖垮 Indexer 的寫法都必須被拒絕。"""
+
+import os
+import sys
+
+import pytest
+
+SRC = os.path.abspath(os.path.join(os.path.dirname(__file__), "..", "..", "src"))
+if SRC not in sys.path:
+    sys.path.insert(0, SRC)
+
+from dslguard import check  # noqa: E402
+
+LIMITS = {"max_size": 500, "max_buckets": 1000, "max_timeout": "30s"}
+
+
+def rejected(body):
+    with pytest.raises(ValueError) as excinfo:
+        check(body, **LIMITS)
+    return str(excinfo.value)
+
+
+def test_plain_query_is_accepted_and_completed():
+    body = check({"query": {"term": {"rule.id": "5710"}},
+                  "aggs": {"top": {"terms": {"field": "agent.name", "size": 50}}}}, **LIMITS)
+    assert body["size"] == 10 and body["timeout"] == "30s"
+
+
+@pytest.mark.parametrize("body", [
+    {"query": {"script": {"script": "doc['rule.level'].value > 5"}}},
+    {"query": {"function_score": {"query": {"match_all": {}}, "script_score": {"script": "1"}}}},
+    {"query": {"match_all": {}}, "script_fields": {"x": {"script": "1"}}},
+    {"aggs": {"x": {"scripted_metric": {"map_script": "state.x = 1"}}}},
+    {"sort": [{"_script": {"type": "number", "script": "1"}}]},
+    {"query": {"match_all": {}}, "runtime_mappings": {"x": {"type": "long", "script": "emit(1)"}}},
+])
+def test_scripts_are_rejected(body):
+    assert "script" in rejected(body)
+
+
+@pytest.mark.parametrize("body", [
+    {"aggs": {"all": {"global": {}, "aggs": {"agents": {"terms": {"field": "agent.id"}}}}}},
+    {"query": {"terms": {"agent.id": {"index": "other", "id": "1", "path": "ids"}}}},
+    {"query": {"more_like_this": {"like": [{"_index": "other", "_id": "1"}]}}},
+    {"query": {"percolate": {"field": "query", "index": "other", "id": "1"}}},
+])
+def test_reads_outside_the_query_scope_are_rejected(body):
+    rejected(body)
+
+
+@pytest.mark.parametrize("key", ["scroll", "pit", "stored_fields", "profile", "explain", "indices_boost",
+                                 "rescore", "suggest", "index"])
+def test_unknown_top_level_keys_are_rejected(key):
+    assert key in rejected({"query": {"match_all": {}}, key: {}})
+
+
+@pytest.mark.parametrize("body", [
+    {"aggs": {"top": {"terms": {"field": "agent.name", "size": 100000}}}},
+    {"aggs": {"top": {"terms": {"field": "agent.name", "shard_size": 5000}}}},
+    {"aggs": {"outer": {"terms": {"field": "agent.name"},
+                        "aggs": {"inner": {"terms": {"field": "rule.id", "size": 1001}}}}}},
+])
+def test_oversized_aggregations_are_rejected(body):
+    assert "超過上限" in rejected(body)
+
+
+@pytest.mark.parametrize("body", [
+    {"size": 501}, {"size": -1}, {"size": "10"}, {"from": 9900, "size": 200}, {"from": -1},
+    {"timeout": "5m"}, {"timeout": 30}, {"timeout": "soon"},
+])
+def test_paging_and_timeout_limits(body):
+    rejected({"query": {"match_all": {}}, **body})
+
+
+def test_all_problems_are_reported_together():
+    message = rejected({"query": {"script": {"script": "1"}}, "scroll": "1m", "size": 10000})
+    assert "script" in message and "scroll" in message and "size" in message
+
+
+def test_non_object_body_is_rejected():
+    rejected([{"query": {"match_all": {}}}])
@@ -0,0 +1,19 @@
+"""啟動前的冒煙測試: 以乾淨的環境變數 import main，模組層級的註冊順序錯誤 (NameError 等) 會在這裡被抓到。
+不需要 Wazuh 環境，但需要安裝 requirements.txt:
+
+    pytest tests/unit -v
+"""
+
+import os
+import subprocess
+import sys
+
+SRC = os.path.abspath(os.path.join(os.path.dirname(__file__), "..", "..", "src"))
+
+
+def test_main_imports_without_configuration(tmp_path):
+    env = {"PATH": os.environ.get("PATH", ""), "HOME": str(tmp_path),
+           "WAZUH_MCP_STORE_URL": "sqlite://" + str(tmp_path / "state.db")}
+    result = subprocess.run([sys.executable, "-B", "-c", "import main"], cwd=SRC, env=env,
+                            capture_output=True, text=True, timeout=120)
+    assert result.returncode == 0, result.stderr[-2000:]
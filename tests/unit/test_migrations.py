@@ -0,0 +1,70 @@
+"""狀態資料的版本化遷移 (migrations.run_migrations): 舊版資料會補跑遷移，較新版本的資料拒絕啟動。"""
+
+import os
+import sys
+
+import pytest
+
+SRC = os.path.abspath(os.path.join(os.path.dirname(__file__), "..", "..", "src"))
+if SRC not in sys.path:
+    sys.path.insert(0, SRC)
+
+import migrations  # noqa: E402
+from migrations import LATEST_VERSION, MigrationError, current_version, run_migrations  # noqa: E402
+from storage import open_store  # noqa: E402
+
+
+@pytest.fixture
+def store(tmp_path):
+    store = open_store(f"sqlite:///{tmp_path / 'state.db'}")
+    yield store
+    store.close()
+
+
+def test_new_store_is_migrated_to_latest(store):
+    assert current_version(store) == 0
+    assert run_migrations(store) == [m[0] for m in migrations.MIGRATIONS]
+    assert current_version(store) == LATEST_VERSION
+    assert store.get(migrations.MIGRATIONS_NAMESPACE, f"{LATEST_VERSION:04d}")["version"] == LATEST_VERSION
+
+
+def test_migrations_run_once(store):
+    run_migrations(store)
+    assert run_migrations(store) == []
+
+
+def test_newer_schema_refuses_to_start(store):
+    store.put(migrations.META_NAMESPACE, "schema_version", {"version": LATEST_VERSION + 1})
+    with pytest.raises(MigrationError):
+        run_migrations(store)
+
+
+def _rename_owner(store):
+    for key, value in store.list("cases"):
+        if "owner" in value:
+            value["assignee"] = value.pop("owner")
+            store.put("cases", key, value)
+
+
+def test_old_schema_is_converted(store, monkeypatch):
+    run_migrations(store)
+    store.put("cases", "c1", {"title": "beacon", "owner": "alice"})
+    monkeypatch.setattr(migrations, "MIGRATIONS",
+                        migrations.MIGRATIONS + [(LATEST_VERSION + 1, "rename", _rename_owner)])
+    monkeypatch.setattr(migrations, "LATEST_VERSION", LATEST_VERSION + 1)
+    assert run_migrations(store) == [LATEST_VERSION + 1]
+    assert store.get("cases", "c1") == {"title": "beacon", "assignee": "alice"}
+    assert current_version(store) == LATEST_VERSION + 1
+
+
+def test_failed_migration_keeps_the_old_version(store, monkeypatch):
+    run_migrations(store)
+
+    def broken(_):
+        raise KeyError("owner")
+
+    monkeypatch.setattr(migrations, "MIGRATIONS", migrations.MIGRATIONS + [(LATEST_VERSION + 1, "broken", broken)])
+    monkeypatch.setattr(migrations, "LATEST_VERSION", LATEST_VERSION + 1)
+    with pytest.raises(MigrationError):
+        run_migrations(store)
+    assert current_version(store) == LATEST_VERSION
@@ -0,0 +1,95 @@
+"""保留身分 (local / anonymous) 的處理: 只有 stdio 的 local 不受租戶範圍限制，其他未識別的呼叫者一律 fail closed。"""
+
+import asyncio
+import os
+import sys
+from types import SimpleNamespace
+
+import pytest
+
+SRC = os.path.abspath(os.path.join(os.path.dirname(__file__), "..", "..", "src"))
+if SRC not in sys.path:
+    sys.path.insert(0, SRC)
+
+import principal  # noqa: E402
+from apikeys import parse_keys  # noqa: E402
+from proxy import ForwardedHeaders, TrustedProxies  # noqa: E402
+from scoping import ScopeResolver, parse_scopes  # noqa: E402
+
+
+def resolver(scopes):
+    return ScopeResolver(parse_scopes(scopes), lambda group: {"001"} if group == "customer-a" else set())
+
+
+def test_local_is_unrestricted():
+    assert resolver('{"tenant-a": ["customer-a"]}').groups_for("local") is None
+
+
+@pytest.mark.parametrize("principal", ["anonymous", "ip:203.0.113.5", "tenant-b"])
+def test_unidentified_and_unlisted_callers_see_nothing(principal):
+    scopes = resolver('{"tenant-a": ["customer-a"]}')
+    assert scopes.allowed_agents(principal) == set()
+    assert scopes.scope_query({"match_all": {}}, principal) == {"match_none": {}}
+
+
+def test_listed_principal_is_scoped():
+    assert resolver('{"tenant-a": ["customer-a"]}').allowed_agents("tenant-a") == {"001"}
+
+
+@pytest.mark.parametrize("scopes", ['{"local": ["*"]}', '{"anonymous": ["*"]}', '{"ip:10.0.0.1": ["*"]}'])
+def test_reserved_principals_cannot_be_scoped(scopes):
+    with pytest.raises(ValueError):
+        parse_scopes(scopes)
+
+
+@pytest.mark.parametrize("keys", ["local:secret", "Anonymous:secret"])
+def test_reserved_principals_cannot_be_api_key_names(keys):
+    with pytest.raises(ValueError):
+        parse_keys(keys)
+
+
+def principal_for(monkeypatch, peer, headers, trusted="10.0.0.1"):
+    """讓請求經過 ForwardedHeaders 後再判斷 principal (peer 為 None 時模擬 unix socket)"""
+    seen = {}
+
+    async def app(scope, receive, send):
+        seen.update(scope)
+
+    scope = {"type": "http", "client": (peer, 50000) if peer else None,
+             "headers": [(k.encode(), v.encode()) for k, v in headers.items()]}
+    asyncio.run(ForwardedHeaders(app, TrustedProxies(trusted))(scope, None, None))
+    client = SimpleNamespace(host=seen["client"][0]) if seen["client"] else None
+    monkeypatch.setattr(principal, "get_http_request", lambda: SimpleNamespace(scope=seen, client=client))
+    monkeypatch.setattr(principal, "get_http_headers", lambda: headers)
+    return principal.current_principal()
+
+
+def test_stdio_is_local(monkeypatch):
+    def no_request():
+        raise RuntimeError("No active HTTP request found.")
+
+    monkeypatch.setattr(principal, "get_http_request", no_request)
+    assert principal.current_principal() == "local"
+
+
+def test_http_without_header_is_anonymous(monkeypatch):
+    assert principal_for(monkeypatch, "10.0.0.1", {}) == "anonymous"
+
+
+def test_header_from_trusted_proxy_is_honored(monkeypatch):
+    assert principal_for(monkeypatch, "10.0.0.1", {"x-mcp-principal": "soc-lead"}) == "soc-lead"
+
+
+@pytest.mark.parametrize("peer", ["203.0.113.5", None])
+def test_header_from_untrusted_peer_is_anonymous(monkeypatch, peer):
+    headers = {"x-mcp-principal": "soc-lead", "x-forwarded-for": "10.0.0.1"}
+    assert principal_for(monkeypatch, peer, headers) == "anonymous"
+
+
+def test_unix_socket_header_is_honored_only_with_wildcard(monkeypatch):
+    assert principal_for(monkeypatch, None, {"x-mcp-principal": "soc-lead"}, trusted="*") == "soc-lead"
+
+
+@pytest.mark.parametrize("claimed", ["local", "LOCAL", "anonymous", "ip:10.0.0.1"])
+def test_reserved_header_values_are_ignored(monkeypatch, claimed):
+    assert principal_for(monkeypatch, "10.0.0.1", {"x-mcp-principal": claimed}) == "anonymous"
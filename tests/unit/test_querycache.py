@@ -0,0 +1,72 @@
+"""歷史查詢快取 (querycache.QueryCache): 不同租戶範圍的查詢不能共用快取，使用 now 的時間範圍不能快取。"""
+
+import os
+import sys
+from datetime import timedelta
+
+import pytest
+
+SRC = os.path.abspath(os.path.join(os.path.dirname(__file__), "..", "..", "src"))
+if SRC not in sys.path:
+    sys.path.insert(0, SRC)
+
+from querycache import QueryCache  # noqa: E402
+from scoping import ScopeResolver, parse_scopes  # noqa: E402
+from storage import open_store  # noqa: E402
+
+INDEX = "wazuh-alerts-*"
+PAST = {"range": {"timestamp": {"gte": "2024-05-01T00:00:00Z", "lte": "2024-05-02T00:00:00Z"}}}
+SCOPES = ScopeResolver(parse_scopes('{"tenant-a": ["customer-a"], "tenant-b": ["customer-b"], "soc-lead": ["*"]}'),
+                       {"customer-a": {"001"}, "customer-b": {"002"}}.get)
+
+
+@pytest.fixture
+def cache(tmp_path):
+    store = open_store(f"sqlite:///{tmp_path / 'state.db'}")
+    yield QueryCache(store, ttl=timedelta(days=7), ingest_delay=timedelta(minutes=5))
+    store.close()
+
+
+def scoped(principal, query=PAST):
+    """與 _search_indexer_once 相同: 快取鍵取自套用租戶範圍之後的查詢本文"""
+    return {"size": 10, "query": SCOPES.scope_query(query, principal)}
+
+
+def test_scoped_history_queries_are_cacheable(cache):
+    assert cache.cacheable(scoped("tenant-a"))
+
+
+def test_principals_with_different_scopes_never_share_an_entry(cache):
+    cache.put(INDEX, scoped("tenant-a"), {"hits": {"total": {"value": 1}}, "tenant": "a"})
+    for principal in ("tenant-b", "soc-lead", "local", "anonymous"):
+        assert cache.get(INDEX, scoped(principal)) is None
+    cache.put(INDEX, scoped("tenant-b"), {"hits": {"total": {"value": 2}}, "tenant": "b"})
+    assert cache.get(INDEX, scoped("tenant-a"))["tenant"] == "a"
+    assert cache.get(INDEX, scoped("tenant-b"))["tenant"] == "b"
+
+
+def test_same_scope_shares_an_entry(cache):
+    cache.put(INDEX, scoped("tenant-a"), {"tenant": "a"})
+    assert cache.get(INDEX, scoped("tenant-a")) == {"tenant": "a"}
+    assert cache.get("wazuh-archives-*", scoped("tenant-a")) is None
+
+
+@pytest.mark.parametrize("bounds", [
+    {"gte": "now-24h"},
+    {"gte": "now-24h", "lte": "now"},
+    {"gte": "2024-05-01T00:00:00Z", "lte": "now-1h"},
+    {"gte": "2024-05-01T00:00:00Z", "lt": "now/d"},
+    {"gte": "2024-05-01T00:00:00Z"},
+])
+def test_open_ended_now_ranges_are_never_cached(cache, bounds):
+    body = scoped("tenant-a", {"range": {"timestamp": bounds}})
+    assert not cache.cacheable(body)
+
+
+def test_upper_bound_inside_ingest_delay_is_not_cached(cache):
+    assert not cache.cacheable({"query": {"range": {"timestamp": {"lte": "2999-01-01T00:00:00Z"}}}})
+
+
+def test_optional_clauses_do_not_make_a_query_cacheable(cache):
+    query = {"bool": {"should": [PAST], "must_not": [PAST]}}
+    assert not cache.cacheable({"query": query})
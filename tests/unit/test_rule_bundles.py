@@ -0,0 +1,65 @@
+"""規則包讀取 (ruletests.load_bundle) 不能讀到規則包以外的檔案: 跳出解壓目錄的壓縮檔成員與指到外面的 symlink。"""
+
+import io
+import os
+import sys
+import tarfile
+import zipfile
+
+import pytest
+
+SRC = os.path.abspath(os.path.join(os.path.dirname(__file__), "..", "..", "src"))
+if SRC not in sys.path:
+    sys.path.insert(0, SRC)
+
+from ruletests import load_bundle  # noqa: E402
+
+RULE = '<group name="local,"><rule id="100100" level="5"><match>x</match><description>x</description></rule></group>'
+
+
+def write_zip(path, names):
+    with zipfile.ZipFile(path, "w") as archive:
+        for name in names:
+            archive.writestr(name, RULE)
+
+
+def write_tar(path, members):
+    with tarfile.open(path, "w:gz") as archive:
+        for info in members:
+            data = RULE.encode() if info.isfile() else b""
+            info.size = len(data)
+            archive.addfile(info, io.BytesIO(data))
+
+
+def test_plain_bundles_load(tmp_path):
+    write_zip(tmp_path / "ok.zip", ["rules/local.xml"])
+    rules, _ = load_bundle(str(tmp_path / "ok.zip"))
+    assert list(rules) == ["rules/local.xml"]
+
+
+@pytest.mark.parametrize("name", ["../escape.xml", "rules/../../escape.xml", "/etc/escape.xml", "C:/escape.xml"])
+def test_zip_members_outside_bundle_are_rejected(tmp_path, name):
+    write_zip(tmp_path / "bad.zip", ["rules/local.xml", name])
+    with pytest.raises(ValueError):
+        load_bundle(str(tmp_path / "bad.zip"))
+
+
+@pytest.mark.parametrize("link", ["/etc/passwd", "../../outside.xml"])
+def test_tar_links_outside_bundle_are_rejected(tmp_path, link):
+    rule = tarfile.TarInfo("rules/local.xml")
+    symlink = tarfile.TarInfo("rules/linked.xml")
+    symlink.type, symlink.linkname = tarfile.SYMTYPE, link
+    write_tar(tmp_path / "bad.tar.gz", [rule, symlink])
+    with pytest.raises(ValueError):
+        load_bundle(str(tmp_path / "bad.tar.gz"))
+
+
+def test_directory_symlinks_outside_bundle_are_rejected(tmp_path):
+    outside = tmp_path / "secret.xml"
+    outside.write_text(RULE)
+    bundle = tmp_path / "bundle"
+    bundle.mkdir()
+    (bundle / "local.xml").write_text(RULE)
+    (bundle / "linked.xml").symlink_to(outside)
+    with pytest.raises(ValueError):
+        load_bundle(str(bundle))